@@ -0,0 +1,1360 @@
+use std::{ffi::OsString, net::SocketAddr, path::PathBuf};
+
+use clap::{
+    builder::RangedU64ValueParser, error::ErrorKind, ArgAction, CommandFactory, Parser, Subcommand,
+    ValueEnum,
+};
+use glob::Pattern;
+use log::LevelFilter;
+use regex::bytes::{Regex, RegexBuilder};
+use string_stream_processor::{
+    analyzer_from_name, DynLineAnalyzer, OnExceeded, ProcessorOptions, RateLimit, RecordSeparator,
+    SharedRateLimit, Tokenizer,
+};
+
+use crate::{
+    color::ColorChoice,
+    freq::WordFilter,
+    group::GroupBy,
+    logs::LogFormat,
+    output::{Format, Sort, SortKey, SCHEMA_VERSION},
+    sink::OutputCompression,
+    template::Template,
+    walk::WalkOptions,
+};
+
+/// Count the words of each line of files, or of the standard input.
+#[derive(Debug, Parser)]
+#[command(version, about, subcommand_precedence_over_arg = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+    #[command(flatten)]
+    args: Args,
+}
+
+#[derive(Debug, Subcommand)]
+enum CliCommand {
+    /// Number of words of each line of the sources, the default.
+    Words(Files),
+    /// Number of lines of each source.
+    Lines(Files),
+    /// Number of characters of each line of the sources, which must be valid UTF-8.
+    Chars(Files),
+    /// Number of bytes of each line of the sources, without the line ending.
+    Bytes(Files),
+    /// Number of characters of the longest word of each line of the sources, which must be
+    /// valid UTF-8, to find the giant unbroken blobs like base64 data.
+    LongestWord(Files),
+    /// Number of characters of the shortest word of each line of the sources, which must be
+    /// valid UTF-8.
+    ShortestWord(Files),
+    /// Number of occurrences of each word over all the sources, the most frequent first.
+    Freq {
+        /// Only keep the N most frequent words.
+        #[arg(long, value_name = "N")]
+        top: Option<usize>,
+        #[command(flatten)]
+        files: Files,
+    },
+    /// Statistics of the numbers of words of the lines of all the sources, keyed by `*`, then of
+    /// each source: mean, median, 95th and 99th percentiles and standard deviation.
+    Stats(Files),
+    /// Most frequent words over all the sources, keyed by `*`, then of each source.
+    TopWords {
+        /// Number of words of all the sources and of each source.
+        #[arg(
+            short = 'k',
+            long,
+            value_name = "K",
+            default_value_t = 10,
+            value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+        )]
+        top: usize,
+        #[command(flatten)]
+        files: Files,
+    },
+    /// Numbers of blank, comment and code lines of the source files by language, detected by
+    /// their extension. The current directory is processed without files.
+    CodeStats(Files),
+    /// Numbers of files, lines and words under each directory, rolled up the tree from the
+    /// files, with the depth of each directory. The table draws the tree. The current directory
+    /// is processed without files.
+    DirTotals(Files),
+    /// Number of lines of each source matching a regular expression, and of matches in them,
+    /// like `grep -c`.
+    MatchCount {
+        /// Regular expression matched against each line, without its line ending.
+        #[arg(value_name = "PATTERN")]
+        pattern: String,
+        /// Match the letters of the pattern whatever their case.
+        #[arg(short = 'i', long)]
+        ignore_case: bool,
+        #[command(flatten)]
+        files: Files,
+    },
+    /// Numbers of different and of duplicate lines of each source, compared by their hash.
+    Dedup {
+        /// Estimate the different lines in constant memory, with an error of about 1%, instead
+        /// of keeping a hash of each of them.
+        #[arg(long)]
+        approximate: bool,
+        #[command(flatten)]
+        files: Files,
+    },
+    /// Numbers of alphabetic, numeric, ASCII punctuation, whitespace and other characters of
+    /// each source, without the line endings.
+    CharClasses(Files),
+    /// Numbers of words and of different words of each source, whatever their case, and their
+    /// ratio.
+    Diversity {
+        /// Estimate the different words in constant memory, with an error of about 1%, instead
+        /// of keeping a hash of each of them.
+        #[arg(long)]
+        approximate: bool,
+        #[command(flatten)]
+        files: Files,
+    },
+    /// Flesch reading ease and Flesch-Kincaid grade level of each source, from estimates of
+    /// its sentences and of the syllables of its words.
+    Readability(Files),
+    /// Histogram of the numbers of lines of each source by number of words.
+    Hist {
+        /// Number of words of each bucket, for about ten buckets up to the longest line by
+        /// default.
+        #[arg(
+            long,
+            value_name = "N",
+            value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+        )]
+        bucket_width: Option<usize>,
+        #[command(flatten)]
+        files: Files,
+    },
+    /// Count the words of the lines of the files several times, discarding the counts, and
+    /// report the throughput and the time to read each file.
+    Bench {
+        /// Number of times the files are processed.
+        #[arg(
+            short = 'n',
+            long,
+            value_name = "N",
+            default_value_t = 5,
+            value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+        )]
+        iterations: usize,
+        #[command(flatten)]
+        files: Files,
+    },
+    /// Changes of the counts of each source between two JSON documents of the `words` results:
+    /// the lines added and removed and the difference of the numbers of words.
+    Diff {
+        /// Document of the previous results.
+        #[arg(value_name = "OLD")]
+        old: String,
+        /// Document of the new results.
+        #[arg(value_name = "NEW")]
+        new: String,
+    },
+    /// Serve an HTTP API counting the words of each line of uploaded files, local paths and
+    /// URLs.
+    Serve(Serve),
+    /// Print the JSON Schema of the results in the `--format`, to validate their parsers: the
+    /// envelope of the documents by default, with the results of each line with `--per-line`,
+    /// or a line of NDJSON.
+    Schema {
+        /// Version of the envelope of the documents.
+        #[arg(long, value_name = "N", default_value_t = SCHEMA_VERSION)]
+        schema_version: u32,
+    },
+}
+
+/// Settings of the `serve` command. The server reads the uploaded files only, unless the local
+/// paths under a directory or the URLs are allowed.
+#[derive(Debug, Clone, PartialEq, Eq, clap::Args)]
+pub struct Serve {
+    /// Address to listen on, unless the socket is passed by systemd with socket activation.
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+    pub listen: SocketAddr,
+    /// Read the local paths of the requests under this directory, the relative ones from it.
+    #[arg(long, value_name = "DIR")]
+    pub root: Option<PathBuf>,
+    /// Fetch the URLs of the requests, from the network of the server.
+    #[arg(long)]
+    pub allow_urls: bool,
+}
+
+/// Files of a subcommand, the flags are shared by all the commands.
+#[derive(Debug, clap::Args)]
+struct Files {
+    /// Files to process, `-` for the standard input. `file://` paths, `http(s)://` URLs and
+    /// `s3://bucket/prefix` URIs are read from their backend.
+    #[arg(value_name = "FILE")]
+    files: Vec<String>,
+}
+
+/// Results of a run, from the subcommand.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Command {
+    #[default]
+    Words,
+    Lines,
+    Chars,
+    Bytes,
+    LongestWord,
+    ShortestWord,
+    Freq {
+        top: Option<usize>,
+    },
+    Stats,
+    TopWords {
+        top: usize,
+    },
+    Hist {
+        bucket_width: Option<usize>,
+    },
+    CodeStats,
+    DirTotals,
+    MatchCount,
+    Dedup {
+        approximate: bool,
+    },
+    Diversity {
+        approximate: bool,
+    },
+    Readability,
+    CharClasses,
+}
+
+impl Command {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Words => "words",
+            Self::Lines => "lines",
+            Self::Chars => "chars",
+            Self::Bytes => "bytes",
+            Self::LongestWord => "longest-word",
+            Self::ShortestWord => "shortest-word",
+            Self::Freq { .. } => "freq",
+            Self::Stats => "stats",
+            Self::TopWords { .. } => "top-words",
+            Self::Hist { .. } => "hist",
+            Self::CodeStats => "code-stats",
+            Self::DirTotals => "dir-totals",
+            Self::MatchCount => "match-count",
+            Self::Dedup { .. } => "dedup",
+            Self::Diversity { .. } => "diversity",
+            Self::Readability => "readability",
+            Self::CharClasses => "char-classes",
+        }
+    }
+
+    /// Name of the number of each line of the commands with one per line, `None` for the
+    /// reports.
+    pub fn line_count(self) -> Option<&'static str> {
+        match self {
+            Self::Words => Some("word_count"),
+            Self::Chars => Some("char_count"),
+            Self::Bytes => Some("byte_count"),
+            Self::LongestWord => Some("longest_word"),
+            Self::ShortestWord => Some("shortest_word"),
+            _ => None,
+        }
+    }
+
+    /// Analyzer of the lines of the commands counting something else than their words.
+    pub fn analyzer(self) -> Option<Box<dyn DynLineAnalyzer>> {
+        match self {
+            Self::Chars | Self::Bytes | Self::LongestWord | Self::ShortestWord => {
+                analyzer_from_name(self.name())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// What to do once the counts exceed `--memory-budget`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OverBudget {
+    /// Spill the largest count vectors to a temporary file.
+    #[default]
+    Spill,
+    /// Write the number of lines of each source instead of the count of each line.
+    Lines,
+    /// Fail without writing the results.
+    Fail,
+}
+
+impl OverBudget {
+    pub fn action(self) -> OnExceeded {
+        match self {
+            Self::Spill => OnExceeded::Spill(std::env::temp_dir()),
+            Self::Lines => OnExceeded::Aggregate,
+            Self::Fail => OnExceeded::Fail,
+        }
+    }
+}
+
+/// Command line arguments.
+#[derive(Debug, Default, clap::Args)]
+pub struct Args {
+    #[arg(skip)]
+    pub command: Command,
+    /// Number of iterations of the benchmark to run instead of processing the files once, see
+    /// [`crate::bench`].
+    #[arg(skip)]
+    pub bench: Option<usize>,
+    /// Documents of the results to compare instead of processing the files, see [`crate::diff`].
+    #[arg(skip)]
+    pub diff: Option<[String; 2]>,
+    /// HTTP API to serve instead of processing the files, see [`crate::serve`].
+    #[arg(skip)]
+    pub serve: Option<Serve>,
+    /// Version of the envelope whose JSON Schema is printed instead of processing the files,
+    /// see [`crate::schema`].
+    #[arg(skip)]
+    pub schema: Option<u32>,
+    /// Regular expression of the lines counted by the `match-count` command.
+    #[arg(skip)]
+    pub pattern: Option<Regex>,
+    /// Configuration file of the defaults of the flags, instead of
+    /// `~/.config/file-processor/config.toml`.
+    #[arg(global = true, long, value_name = "PATH")]
+    pub config: Option<String>,
+    /// Files to process, `-` for the standard input. `file://` paths, `http(s)://` URLs and
+    /// `s3://bucket/prefix` URIs are read from their backend. A file named like a command is
+    /// given after `--`.
+    #[arg(value_name = "FILE")]
+    pub files: Vec<String>,
+    /// File listing the paths of more files to process, one per line, `-` for the standard
+    /// input.
+    #[arg(global = true, long, value_name = "PATH")]
+    pub files_from: Option<String>,
+    /// Separate the paths of `--files-from` by NUL bytes, like `find -print0`.
+    #[arg(global = true, short = '0', long, requires = "files_from")]
+    pub null: bool,
+    /// Number of threads of the runtime, the number of cores by default.
+    #[arg(
+        global = true,
+        long,
+        value_name = "N",
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+    )]
+    pub worker_threads: Option<usize>,
+    /// Run on the main thread only, for the small containers.
+    #[arg(global = true, long, conflicts_with = "worker_threads")]
+    pub current_thread: bool,
+    /// Capacity of the read buffer of each file, e.g. `64K` or `1MiB`.
+    #[arg(global = true, long, value_name = "SIZE", value_parser = parse_nonzero_size)]
+    pub buffer_size: Option<usize>,
+    /// Memory budget of the results, above which they are spilled to disk by default.
+    #[arg(global = true, long, value_name = "SIZE", value_parser = parse_size)]
+    pub memory_budget: Option<usize>,
+    /// What to do once the results exceed `--memory-budget`.
+    #[arg(
+        global = true,
+        long,
+        value_enum,
+        value_name = "ACTION",
+        default_value_t,
+        requires = "memory_budget"
+    )]
+    pub over_budget: OverBudget,
+    /// Save the results of the files read until their end to this file during the run, every
+    /// few seconds and at its end.
+    #[arg(
+        global = true,
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["memory_budget", "wc"]
+    )]
+    pub checkpoint: Option<String>,
+    /// Reuse the counts of the files of the previous runs cached in this directory while their
+    /// size and modification time are the same, and cache the others.
+    #[arg(
+        global = true,
+        long,
+        value_name = "DIR",
+        conflicts_with_all = ["memory_budget", "wc"]
+    )]
+    pub cache: Option<String>,
+    /// Merge the results of an earlier run saved in this JSON document with the ones of the
+    /// files read, which replace them, e.g. to maintain the cumulative results of incremental
+    /// scans.
+    #[arg(
+        global = true,
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["memory_budget", "wc"]
+    )]
+    pub merge_with: Option<String>,
+    /// Skip the files whose results are saved in the `--checkpoint` file, and add these results.
+    #[arg(global = true, long, requires = "checkpoint")]
+    pub resume: bool,
+    /// Keep reading the named pipes when their writers close them.
+    #[arg(global = true, long, conflicts_with = "wc")]
+    pub keep_open: bool,
+    /// Skip the files ignored by git in the directories, and the `.git` directories.
+    #[arg(global = true, long)]
+    pub respect_gitignore: bool,
+    /// Rules used to split the lines into words: `unicode` whitespace, the faster `ascii`
+    /// whitespace, `emoji` counting each emoji sequence as a word, `custom:CHARS` splitting on
+    /// the given characters only, written with the `\t`, `\\` and `\u{HEX}` escapes, e.g.
+    /// `custom: \t\u{a0}`, or with the `icu` feature the `icu` word segmentation of the
+    /// languages written without spaces, like Thai.
+    #[arg(global = true, long, value_name = "NAME", value_parser = parse_tokenizer)]
+    pub tokenizer: Option<Tokenizer>,
+    /// Count records ended by this string instead of lines, e.g. `\n\n` for the paragraphs,
+    /// written with the `\n`, `\r`, `\t`, `\\` and `\u{HEX}` escapes. The line endings of a
+    /// record are read as spaces.
+    #[arg(
+        global = true,
+        long,
+        value_name = "STR",
+        value_parser = parse_record_separator,
+        conflicts_with_all = [
+            "record_start",
+            "record_length",
+            "length_prefixed",
+            "cache",
+            "checkpoint"
+        ]
+    )]
+    pub record_separator: Option<RecordSeparator>,
+    /// Count records starting at each line matching this regular expression instead of lines,
+    /// e.g. `^\d{4}-\d{2}-\d{2} ` for the log entries continued on several lines.
+    #[arg(
+        global = true,
+        long,
+        value_name = "PATTERN",
+        value_parser = parse_record_start,
+        conflicts_with_all = ["record_length", "length_prefixed", "cache", "checkpoint"]
+    )]
+    pub record_start: Option<RecordSeparator>,
+    /// Count records of this number of bytes each instead of lines, for the exports without
+    /// line endings. The last record may be shorter.
+    #[arg(
+        global = true,
+        long,
+        value_name = "BYTES",
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+        conflicts_with_all = ["length_prefixed", "cache", "checkpoint"]
+    )]
+    pub record_length: Option<usize>,
+    /// Count records prefixed by their length in bytes as a big-endian 32-bit integer instead of
+    /// lines, for the binary-safe protocols. The line endings of a record are read as spaces.
+    #[arg(global = true, long, conflicts_with_all = ["cache", "checkpoint"])]
+    pub length_prefixed: bool,
+    /// Stop reading each source after N lines, or records, reporting the truncated ones, e.g.
+    /// to explore enormous logs quickly.
+    #[arg(
+        global = true,
+        long,
+        value_name = "N",
+        conflicts_with_all = ["cache", "checkpoint", "wc"]
+    )]
+    pub max_lines: Option<usize>,
+    /// Read each source at most at this number of bytes per second, e.g. `1M`, to not saturate
+    /// a shared link.
+    #[arg(
+        global = true,
+        long,
+        value_name = "SIZE",
+        value_parser = parse_nonzero_size,
+        conflicts_with = "max_line_rate"
+    )]
+    pub max_rate: Option<usize>,
+    /// Read each source at most at this number of lines per second.
+    #[arg(
+        global = true,
+        long,
+        value_name = "N",
+        value_parser = RangedU64ValueParser::<u64>::new().range(1..)
+    )]
+    pub max_line_rate: Option<u64>,
+    /// Read all the sources together at most at this number of bytes per second, e.g.
+    /// `200MB/s`, to not starve the other processes using the same disks.
+    #[arg(global = true, long, value_name = "SIZE", value_parser = parse_throughput)]
+    pub max_throughput: Option<usize>,
+    /// Maximum number of files read at the same time.
+    #[arg(
+        global = true,
+        short = 'j',
+        long = "jobs",
+        value_name = "N",
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+    )]
+    pub concurrency: Option<usize>,
+    /// Skip the files and the directories whose path or name matches the pattern, e.g. `*.gz`.
+    #[arg(global = true, long = "exclude", value_name = "PATTERN", value_parser = parse_pattern)]
+    pub excludes: Vec<Pattern>,
+    /// Read first the files whose path or name matches the pattern, e.g. `small/*`, before the
+    /// other files, in the order of the patterns when repeated. Useful with `--jobs`, the files
+    /// being read at the same time otherwise.
+    #[arg(global = true, long = "priority", value_name = "PATTERN", value_parser = parse_pattern)]
+    pub priorities: Vec<Pattern>,
+    /// Only process the files of the directories with one of these extensions, e.g. `txt,log`.
+    #[arg(global = true, long, value_name = "EXTS", value_delimiter = ',')]
+    pub include_ext: Vec<String>,
+    /// Only process the files of the directories which look like text, without NUL byte in
+    /// their first 8 KiB.
+    #[arg(global = true, long)]
+    pub text_only: bool,
+    /// Depth of the deepest files of the directories to process, 1 for their files only.
+    #[arg(global = true, long, value_name = "N")]
+    pub max_depth: Option<usize>,
+    /// Process the paths of a file after the first one, through links or given twice, merging
+    /// their counts.
+    #[arg(global = true, long)]
+    pub no_dedupe: bool,
+    /// Skip and report the files larger than this size, e.g. `500M`.
+    #[arg(global = true, long, value_name = "SIZE", value_parser = parse_size)]
+    pub max_file_size: Option<usize>,
+    /// Walk the symbolic links to directories, skipping the ones which loop.
+    #[arg(global = true, long, overrides_with = "no_follow_symlinks")]
+    pub follow_symlinks: bool,
+    /// Do not walk the symbolic links to directories, the default.
+    #[arg(global = true, long, overrides_with = "follow_symlinks")]
+    pub no_follow_symlinks: bool,
+    /// Filter of the logs, from the configuration.
+    #[arg(skip)]
+    pub log_level: Option<String>,
+    /// File of the results instead of the standard output, in the format of its extension
+    /// (`.yaml`, `.toml`, `.csv`, `.ndjson`, `.arrow` or `.parquet`, optionally followed by
+    /// `.gz` or `.zst`) and in JSON otherwise.
+    #[arg(global = true, short, long, value_name = "PATH")]
+    pub output: Option<String>,
+    /// Post the results to this HTTP endpoint instead of writing them.
+    #[arg(global = true, long, value_name = "URL", conflicts_with = "output")]
+    pub post_to: Option<String>,
+    /// Bearer token of the requests of `--post-to`, from the configuration by default.
+    #[arg(global = true, long, value_name = "TOKEN")]
+    pub post_token: Option<String>,
+    /// Number of retries of `--post-to` on the connection and server errors.
+    #[arg(global = true, long, value_name = "N", default_value_t = 3)]
+    pub post_retries: u32,
+    /// Compress the results with gzip, implied by a `.gz` output file.
+    #[arg(global = true, long, conflicts_with = "output_compression")]
+    pub gzip: bool,
+    /// Compress the results on the fly (`gzip` or `zstd`), implied by a `.gz` or `.zst` output
+    /// file.
+    #[arg(global = true, long, value_enum, value_name = "FORMAT")]
+    pub output_compression: Option<OutputCompression>,
+    /// Format of the results (`json`, `yaml`, `toml`, `ndjson`, `csv`, `arrow`, `parquet` or
+    /// `table`), defaults to the one of the output file extension.
+    #[arg(global = true, long, value_parser = Format::from_name)]
+    pub format: Option<Format>,
+    /// Template of the rows of the results, replacing the format.
+    #[arg(global = true, long, value_name = "TEMPLATE", value_parser = Template::parse)]
+    pub format_template: Option<Template>,
+    /// One record of the identifier, the line number and the count of each line instead of the
+    /// counts of each file, in the documents, the NDJSON objects and the table.
+    #[arg(global = true, long)]
+    pub per_line: bool,
+    /// Print the results like `wc -l -w -c`, instead of the format.
+    #[arg(global = true, long)]
+    pub wc: bool,
+    /// Leave the words shorter than N characters out of the frequencies.
+    #[arg(global = true, long, value_name = "N", default_value_t)]
+    pub min_length: usize,
+    /// Leave these words out of the frequencies whatever their case, e.g. `the,a,an`.
+    #[arg(global = true, long, value_name = "WORDS", value_delimiter = ',')]
+    pub stop_words: Vec<String>,
+    /// File of more words to leave out of the frequencies, one per line.
+    #[arg(global = true, long, value_name = "PATH")]
+    pub stop_words_file: Option<String>,
+    /// Order of the sources in the output.
+    #[arg(global = true, long, value_enum, value_name = "KEY")]
+    pub sort: Option<SortKey>,
+    /// Reverse the order of the sources, sorted by identifier without `--sort`.
+    #[arg(global = true, long)]
+    pub reverse: bool,
+    /// One entry per group of sources instead of one per source: by extension (`ext`), by
+    /// parent directory (`dir`) or by the first capture group of a regular expression on the
+    /// identifier (`regex:PATTERN`).
+    #[arg(
+        global = true,
+        long,
+        value_name = "KEY",
+        value_parser = GroupBy::parse,
+        conflicts_with_all = ["memory_budget", "with_meta", "checksum", "merge_with"]
+    )]
+    pub group_by: Option<GroupBy>,
+    /// Write the local files byte-identical to an earlier one, in the order of the identifiers,
+    /// as references to it instead of their counts, e.g. for archives of copied logs, in the
+    /// documents and NDJSON only. They are detected by hashing the files of the same size before
+    /// counting, and are not read again.
+    #[arg(global = true, long, conflicts_with_all = ["memory_budget", "wc"])]
+    pub dedup_sources: bool,
+    /// Add the lines, bytes read and processing duration of each source to the documents and the
+    /// NDJSON objects of the sources. The ones of the reports and of `--per-line` are written
+    /// apart: in the `sources` map of the documents and as `meta` NDJSON objects.
+    #[arg(global = true, long)]
+    pub with_meta: bool,
+    /// Add the SHA-256 checksum of the content of each source read entirely to its metadata,
+    /// computed while reading it, after its decompression: a compressed file and its
+    /// decompressed copy have the same checksum. Implies `--with-meta`.
+    #[arg(global = true, long)]
+    pub checksum: bool,
+    /// Abort the run on the first file which cannot be opened or read, instead of skipping it.
+    #[arg(global = true, long)]
+    pub fail_fast: bool,
+    /// Exit with an error code when some sources could not be processed, not only when none could.
+    #[arg(global = true, long)]
+    pub strict: bool,
+    /// Only log the errors, and do not show the progress of the run on the standard error.
+    #[arg(global = true, short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Show a table of the sources on the standard error during the run, where they can be
+    /// sorted, paused and skipped, instead of the progress bar.
+    #[arg(global = true, long, conflicts_with_all = ["quiet", "watch", "wc", "dry_run"])]
+    pub tui: bool,
+    /// Log the debug messages, and the trace ones when repeated.
+    #[arg(global = true, short, long, action = ArgAction::Count)]
+    pub verbose: u8,
+    /// Format of the log messages.
+    #[arg(
+        global = true,
+        long,
+        value_name = "FORMAT",
+        value_enum,
+        default_value_t
+    )]
+    pub log_format: LogFormat,
+    /// Print the size and the path of each file which would be processed, and their total size,
+    /// without processing them.
+    #[arg(global = true, long, conflicts_with = "watch")]
+    pub dry_run: bool,
+    /// Keep running, and process the sources again when one of the files changes.
+    #[arg(global = true, long)]
+    pub watch: bool,
+    /// Serve the counters of the runs of `--watch` and the gauges of their sources on
+    /// `http://ADDR/metrics`, in the Prometheus text format.
+    #[arg(global = true, long, value_name = "ADDR", requires = "watch")]
+    pub metrics_addr: Option<SocketAddr>,
+    /// Print a summary of the run on the standard error.
+    #[arg(global = true, long)]
+    pub summary: bool,
+    /// When to color the table and the log levels.
+    #[arg(global = true, long, value_enum, value_name = "WHEN", default_value_t)]
+    pub color: ColorChoice,
+}
+
+impl Args {
+    /// Parse the arguments of the process, exiting with the usage on invalid ones.
+    pub fn parse() -> Self {
+        Self::try_parse_from(std::env::args_os()).unwrap_or_else(|e| e.exit())
+    }
+
+    /// Parse the arguments, the program name first.
+    pub fn try_parse_from(
+        args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+    ) -> Result<Self, clap::Error> {
+        let Cli { command, mut args } = Cli::try_parse_from(args)?;
+        let (command, files) = match command {
+            None => (Command::Words, None),
+            Some(CliCommand::Words(files)) => (Command::Words, Some(files)),
+            Some(CliCommand::Lines(files)) => (Command::Lines, Some(files)),
+            Some(CliCommand::Chars(files)) => (Command::Chars, Some(files)),
+            Some(CliCommand::LongestWord(files)) => (Command::LongestWord, Some(files)),
+            Some(CliCommand::ShortestWord(files)) => (Command::ShortestWord, Some(files)),
+            Some(CliCommand::Bytes(files)) => (Command::Bytes, Some(files)),
+            Some(CliCommand::Freq { top, files }) => (Command::Freq { top }, Some(files)),
+            Some(CliCommand::Stats(files)) => (Command::Stats, Some(files)),
+            Some(CliCommand::TopWords { top, files }) => (Command::TopWords { top }, Some(files)),
+            Some(CliCommand::Hist {
+                bucket_width,
+                files,
+            }) => (Command::Hist { bucket_width }, Some(files)),
+            Some(CliCommand::MatchCount {
+                pattern,
+                ignore_case,
+                files,
+            }) => {
+                let pattern = RegexBuilder::new(&pattern)
+                    .case_insensitive(ignore_case)
+                    .build()
+                    .map_err(|e| {
+                        Cli::command().error(
+                            ErrorKind::ValueValidation,
+                            format!("invalid pattern {pattern}, {e}"),
+                        )
+                    })?;
+                args.pattern = Some(pattern);
+                (Command::MatchCount, Some(files))
+            }
+            Some(CliCommand::Dedup { approximate, files }) => {
+                (Command::Dedup { approximate }, Some(files))
+            }
+            Some(CliCommand::Diversity { approximate, files }) => {
+                (Command::Diversity { approximate }, Some(files))
+            }
+            Some(CliCommand::Readability(files)) => (Command::Readability, Some(files)),
+            Some(CliCommand::CharClasses(files)) => (Command::CharClasses, Some(files)),
+            Some(CliCommand::CodeStats(mut files)) => {
+                if files.files.is_empty() && args.files_from.is_none() {
+                    files.files.push(".".into());
+                }
+                (Command::CodeStats, Some(files))
+            }
+            Some(CliCommand::DirTotals(mut files)) => {
+                if files.files.is_empty() && args.files_from.is_none() {
+                    files.files.push(".".into());
+                }
+                (Command::DirTotals, Some(files))
+            }
+            Some(CliCommand::Bench { iterations, files }) => {
+                args.bench = Some(iterations);
+                (Command::Words, Some(files))
+            }
+            Some(CliCommand::Diff { old, new }) => {
+                args.diff = Some([old, new]);
+                (Command::Words, None)
+            }
+            Some(CliCommand::Serve(serve)) => {
+                args.serve = Some(serve);
+                (Command::Words, None)
+            }
+            Some(CliCommand::Schema { schema_version }) => {
+                args.schema = Some(schema_version);
+                (Command::Words, None)
+            }
+        };
+        args.command = command;
+        args.files
+            .extend(files.into_iter().flat_map(|files| files.files));
+        args.check_command()?;
+        let stdin_manifest = args.files_from.as_deref() == Some("-");
+        let stdin_file = args.files.iter().any(|file| file == "-")
+            || (args.files.is_empty() && args.files_from.is_none());
+        if stdin_manifest && stdin_file {
+            return Err(Cli::command().error(
+                ErrorKind::ArgumentConflict,
+                "the standard input cannot be both a file and the --files-from list",
+            ));
+        }
+        if args.watch && (stdin_manifest || stdin_file) {
+            return Err(Cli::command().error(
+                ErrorKind::ArgumentConflict,
+                "the standard input cannot be watched",
+            ));
+        }
+        if args.bench.is_some() && stdin_file {
+            return Err(Cli::command().error(
+                ErrorKind::ArgumentConflict,
+                "the standard input cannot be benchmarked",
+            ));
+        }
+        Ok(args)
+    }
+
+    /// Reject the flags which do not apply to the command.
+    fn check_command(&self) -> Result<(), clap::Error> {
+        let words = matches!(self.command, Command::Words);
+        let per_line = self.command.line_count().is_some();
+        let freq = matches!(self.command, Command::Freq { .. });
+        let languages = matches!(self.command, Command::CodeStats);
+        let top_words = matches!(self.command, Command::TopWords { .. });
+        let frequencies = freq || top_words;
+        let flags = [
+            ("--wc", self.wc && !words),
+            ("--per-line", self.per_line && !per_line),
+            (
+                "--format-template",
+                self.format_template.is_some() && !per_line,
+            ),
+            ("--memory-budget", self.memory_budget.is_some() && !words),
+            ("--group-by", self.group_by.is_some() && !per_line),
+            ("--dedup-sources", self.dedup_sources && !per_line),
+            ("--checkpoint", self.checkpoint.is_some() && !words),
+            ("--cache", self.cache.is_some() && !words),
+            ("--merge-with", self.merge_with.is_some() && !words),
+            ("--sort", self.sort.is_some() && (freq || languages)),
+            ("--reverse", self.reverse && (freq || languages)),
+            ("--min-length", self.min_length > 0 && !frequencies),
+            ("--stop-words", !self.stop_words.is_empty() && !frequencies),
+            (
+                "--stop-words-file",
+                self.stop_words_file.is_some() && !frequencies,
+            ),
+        ];
+        match flags.into_iter().find(|(_, invalid)| *invalid) {
+            Some((flag, _)) => Err(Cli::command().error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "{flag} does not apply to the {} command",
+                    self.command.name()
+                ),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Options of the processing of the sources.
+    pub fn options(&self) -> ProcessorOptions {
+        let mut options = ProcessorOptions::default();
+        if let Some(size) = self.buffer_size {
+            options = options.with_read_buffer_size(size);
+        }
+        if let Some(tokenizer) = self.tokenizer {
+            options = options.with_tokenizer(tokenizer);
+        }
+        if let Some(concurrency) = self.concurrency {
+            options = options.with_max_concurrency(concurrency);
+        }
+        options
+    }
+
+    /// Rate limit of each source of `--max-rate` or `--max-line-rate`, if any.
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        let bytes = self
+            .max_rate
+            .map(|rate| RateLimit::BytesPerSec(rate as u64));
+        bytes.or(self.max_line_rate.map(RateLimit::LinesPerSec))
+    }
+
+    /// Rate limit of all the sources together of `--max-throughput`, if any.
+    pub fn throughput_limit(&self) -> Option<SharedRateLimit> {
+        let limit = self
+            .max_throughput
+            .map(|rate| RateLimit::BytesPerSec(rate as u64));
+        limit.map(SharedRateLimit::new)
+    }
+
+    /// Compression of the results, from the flags or the extension of the output file.
+    pub fn output_compression(&self) -> Option<OutputCompression> {
+        let path = self
+            .output
+            .as_deref()
+            .and_then(OutputCompression::from_path);
+        self.output_compression
+            .or(self.gzip.then_some(OutputCompression::Gzip))
+            .or(path)
+    }
+
+    /// Separator of the records counted instead of the lines, if any.
+    pub fn records(&self) -> Option<RecordSeparator> {
+        self.record_separator
+            .clone()
+            .or_else(|| self.record_start.clone())
+            .or(self.record_length.map(RecordSeparator::Fixed))
+            .or(self
+                .length_prefixed
+                .then_some(RecordSeparator::LengthPrefixed))
+    }
+
+    /// Words left out of the frequencies, reading the `--stop-words-file`.
+    pub fn word_filter(&self) -> Result<WordFilter, String> {
+        WordFilter::new(
+            self.min_length,
+            &self.stop_words,
+            self.stop_words_file.as_deref(),
+        )
+    }
+
+    /// How the directories among the files are walked.
+    pub fn walk_options(&self) -> WalkOptions {
+        WalkOptions {
+            gitignore: self.respect_gitignore,
+            excludes: self.excludes.clone(),
+            include_exts: self.include_ext.clone(),
+            text_only: self.text_only,
+            max_depth: self.max_depth,
+            follow_symlinks: self.follow_symlinks,
+            max_file_size: self.max_file_size.map(|size| size as u64),
+            keep_duplicates: self.no_dedupe,
+            priorities: self.priorities.clone(),
+        }
+    }
+
+    /// Level of the logs of `-q` and `-v`, none without them.
+    pub fn verbosity(&self) -> Option<LevelFilter> {
+        match (self.quiet, self.verbose) {
+            (true, _) => Some(LevelFilter::Error),
+            (false, 0) => None,
+            (false, 1) => Some(LevelFilter::Debug),
+            (false, _) => Some(LevelFilter::Trace),
+        }
+    }
+
+    /// Order of the sources of `--sort` and `--reverse`, none without them.
+    pub fn sort(&self) -> Option<Sort> {
+        (self.sort.is_some() || self.reverse).then(|| Sort {
+            key: self.sort.unwrap_or_default(),
+            reverse: self.reverse,
+        })
+    }
+}
+
+/// Parse an `--exclude` pattern.
+pub fn parse_pattern(pattern: &str) -> Result<Pattern, String> {
+    Pattern::new(pattern).map_err(|e| format!("invalid pattern {pattern}, {e}"))
+}
+
+/// Parse a `--tokenizer` name, or the `custom:` prefix followed by the separators.
+pub fn parse_tokenizer(name: &str) -> Result<Tokenizer, String> {
+    if let Some(chars) = name.strip_prefix("custom:") {
+        return parse_separators(chars).map(Tokenizer::custom);
+    }
+    match name {
+        "unicode" => Ok(Tokenizer::Unicode),
+        "ascii" => Ok(Tokenizer::Ascii),
+        "emoji" => Ok(Tokenizer::Emoji),
+        #[cfg(feature = "icu")]
+        "icu" => Ok(Tokenizer::Icu),
+        _ => Err(format!("unknown tokenizer {name}")),
+    }
+}
+
+/// Parse the word separators of a custom tokenizer, see [`unescape`].
+fn parse_separators(chars: &str) -> Result<Vec<char>, String> {
+    let separators: Vec<char> = unescape(chars)?.chars().collect();
+    match separators.is_empty() {
+        true => Err(format!("no separators in {chars}")),
+        false => Ok(separators),
+    }
+}
+
+/// Parse a `--record-separator`, see [`unescape`].
+fn parse_record_separator(separator: &str) -> Result<RecordSeparator, String> {
+    match unescape(separator)? {
+        separator if separator.is_empty() => Err("empty record separator".into()),
+        separator => Ok(RecordSeparator::Str(separator.into_bytes())),
+    }
+}
+
+/// Parse a `--record-start` pattern.
+fn parse_record_start(pattern: &str) -> Result<RecordSeparator, String> {
+    Regex::new(pattern)
+        .map(RecordSeparator::LineStart)
+        .map_err(|e| format!("invalid pattern {pattern}, {e}"))
+}
+
+/// Replace the `\n`, `\r`, `\t`, `\\` and `\u{HEX}` escapes of a string by their characters.
+fn unescape(escaped: &str) -> Result<String, String> {
+    let invalid = || format!("invalid escape in {escaped}");
+    let mut unescaped = String::new();
+    let mut rest = escaped.chars();
+    while let Some(c) = rest.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        let c = match rest.next().ok_or_else(invalid)? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '\\' => '\\',
+            'u' => {
+                let escape = rest.as_str().strip_prefix('{').ok_or_else(invalid)?;
+                let (hex, after) = escape.split_once('}').ok_or_else(invalid)?;
+                rest = after.chars();
+                u32::from_str_radix(hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(invalid)?
+            }
+            _ => return Err(invalid()),
+        };
+        unescaped.push(c);
+    }
+    Ok(unescaped)
+}
+
+/// Parse a byte size with an optional unit suffix, `B` or a binary prefix (`K`, `M`, `G`)
+/// optionally followed by `iB` or `B`, e.g. `1M` or `64KiB`.
+pub fn parse_size(size: &str) -> Result<usize, String> {
+    let split = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (num, unit) = size.split_at(split);
+    let mut chars = unit.chars();
+    let (prefix, suffix) = (chars.next(), chars.as_str());
+    let shift = match (prefix, suffix) {
+        (None | Some('B'), "") => 0,
+        (Some('K' | 'k'), "" | "B" | "iB") => 10,
+        (Some('M' | 'm'), "" | "B" | "iB") => 20,
+        (Some('G' | 'g'), "" | "B" | "iB") => 30,
+        _ => return Err(format!("invalid size unit in {size}")),
+    };
+    num.parse::<usize>()
+        .ok()
+        .and_then(|num| num.checked_mul(1 << shift))
+        .ok_or_else(|| format!("invalid size {size}"))
+}
+
+/// Parse a byte size which is not null, see [`parse_size`].
+fn parse_nonzero_size(size: &str) -> Result<usize, String> {
+    match parse_size(size)? {
+        0 => Err("null size".into()),
+        size => Ok(size),
+    }
+}
+
+/// Parse a `--max-throughput`, a size optionally followed by `/s`.
+fn parse_throughput(throughput: &str) -> Result<usize, String> {
+    let size = throughput.strip_suffix("/s").unwrap_or(throughput);
+    match parse_size(size)? {
+        0 => Err("null throughput".into()),
+        size => Ok(size),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("8K"), Ok(8 * 1024));
+        assert_eq!(parse_size("1MiB"), Ok(1024 * 1024));
+        assert_eq!(parse_size("2GB"), Ok(2 << 30));
+        assert!(parse_size("1T").is_err());
+        assert!(parse_size("M").is_err());
+        assert_eq!(parse_size("16B"), Ok(16));
+        assert!(parse_size("1KBBB").is_err());
+        assert!(parse_size("1BiB").is_err());
+        assert!(parse_size("1iB").is_err());
+        assert!(parse_size("1KiBB").is_err());
+        assert_eq!(parse_nonzero_size("64K"), Ok(64 << 10));
+        assert!(parse_nonzero_size("0").is_err());
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let parse = |args: &[&str]| Args::try_parse_from([&["fpc"], args].concat());
+        let args = parse(&["a.txt", "--buffer-size", "1M", "--", "--b.txt"]).unwrap();
+        assert_eq!(args.command, Command::Words);
+        assert_eq!(args.files, ["a.txt", "--b.txt"]);
+        assert_eq!(args.buffer_size, Some(1 << 20));
+        assert_eq!(args.memory_budget, None);
+        let args = parse(&["a.txt", "lines", "b.txt"]).unwrap();
+        assert_eq!(args.command, Command::Lines);
+        assert_eq!(args.files, ["a.txt", "b.txt"]);
+        assert_eq!(
+            parse(&["--", "a.txt", "lines"]).unwrap().files,
+            ["a.txt", "lines"]
+        );
+        let args = parse(&["--memory-budget=64K", "--output", "out.parquet"]).unwrap();
+        assert_eq!(args.output.as_deref(), Some("out.parquet"));
+        assert_eq!(args.format, None);
+        assert_eq!(args.memory_budget, Some(64 << 10));
+        assert_eq!(args.over_budget, OverBudget::Spill);
+        let args = parse(&["--memory-budget=1M", "--over-budget=lines"]).unwrap();
+        assert_eq!(args.over_budget.action(), OnExceeded::Aggregate);
+        assert!(parse(&["--over-budget=fail"]).is_err());
+        assert!(!args.keep_open);
+        let args = parse(&["--format=csv"]).unwrap();
+        assert_eq!(args.format, Some(Format::Registered("csv")));
+        let args = parse(&["--format", "ndjson", "--per-line"]).unwrap();
+        assert_eq!(args.format, Some(Format::Ndjson));
+        assert!(args.per_line);
+        assert!(parse(&["--wc"]).unwrap().wc);
+        assert!(parse(&["--summary"]).unwrap().summary);
+        assert!(parse(&["--strict"]).unwrap().strict);
+        assert!(parse(&["--fail-fast"]).unwrap().fail_fast);
+        assert!(!args.strict);
+        assert!(parse(&["--with-meta"]).unwrap().with_meta);
+        assert_eq!(args.sort(), None);
+        let sort = parse(&["--sort=words", "--reverse"]).unwrap().sort();
+        assert_eq!(
+            sort,
+            Some(Sort {
+                key: SortKey::Words,
+                reverse: true
+            })
+        );
+        assert!(parse(&["--sort=size"]).is_err());
+        let args = parse(&["--post-to", "http://a/b", "--post-token=t"]).unwrap();
+        assert_eq!(args.post_to.as_deref(), Some("http://a/b"));
+        assert_eq!(
+            (args.post_token.as_deref(), args.post_retries),
+            (Some("t"), 3)
+        );
+        assert!(parse(&["--post-to", "http://a/b", "-o", "out.json"]).is_err());
+        let args = parse(&["-o", "out.json", "--gzip"]).unwrap();
+        assert_eq!(args.output.as_deref(), Some("out.json"));
+        assert_eq!(args.output_compression(), Some(OutputCompression::Gzip));
+        let args = parse(&["-o", "out.ndjson.zst"]).unwrap();
+        assert_eq!(args.output_compression(), Some(OutputCompression::Zstd));
+        let args = parse(&["-o", "out.csv.gz", "--output-compression=zstd"]).unwrap();
+        assert_eq!(args.output_compression(), Some(OutputCompression::Zstd));
+        assert_eq!(
+            parse(&["-o", "out.csv"]).unwrap().output_compression(),
+            None
+        );
+        assert!(parse(&["--gzip", "--output-compression=gzip"]).is_err());
+        assert_eq!(args.color, ColorChoice::Auto);
+        let args = parse(&["--color=never"]).unwrap();
+        assert_eq!(args.color, ColorChoice::Never);
+        let args = parse(&["--format-template", "{id} {words}"]).unwrap();
+        assert_eq!(
+            args.format_template,
+            Some(Template::parse("{id} {words}").unwrap())
+        );
+        assert!(parse(&["--format-template={x}"]).is_err());
+        let args = parse(&["--group-by", "regex:^(\\w+)/"]).unwrap();
+        assert_eq!(args.group_by.unwrap().key("logs/a.log"), "logs");
+        assert!(parse(&["--group-by=size"]).is_err());
+        assert!(parse(&["--group-by=ext", "--memory-budget=1M"]).is_err());
+        assert!(parse(&["freq", "--group-by=ext"]).is_err());
+        assert!(parse(&["--dedup-sources"]).unwrap().dedup_sources);
+        assert!(parse(&["stats", "--dedup-sources"]).is_err());
+        assert!(parse(&["--keep-open"]).unwrap().keep_open);
+        assert!(parse(&["--keep-open", "--wc"]).is_err());
+        let args = parse(&["--checkpoint", "run.json", "--resume"]).unwrap();
+        assert_eq!(args.checkpoint.as_deref(), Some("run.json"));
+        assert!(args.resume);
+        assert!(parse(&["--resume"]).is_err());
+        assert_eq!(
+            parse(&["--cache=.fpc"]).unwrap().cache.as_deref(),
+            Some(".fpc")
+        );
+        assert!(parse(&["stats", "--cache=.fpc"]).is_err());
+        assert!(parse(&["--checkpoint=run.json", "--memory-budget=1M"]).is_err());
+        let args = parse(&["--merge-with", "previous.json"]).unwrap();
+        assert_eq!(args.merge_with.as_deref(), Some("previous.json"));
+        assert!(parse(&["lines", "--merge-with=previous.json"]).is_err());
+        assert!(parse(&["--merge-with=previous.json", "--group-by=dir"]).is_err());
+        let args = parse(&["--files-from", "list.txt"]).unwrap();
+        assert_eq!(args.files_from.as_deref(), Some("list.txt"));
+        assert!(parse(&["--files-from", "-", "-0"]).unwrap().null);
+        assert_eq!(parse(&["-j", "4"]).unwrap().concurrency, Some(4));
+        assert_eq!(parse(&["lines", "--jobs=2"]).unwrap().concurrency, Some(2));
+        assert!(parse(&["-j0"]).is_err());
+        assert_eq!(
+            parse(&["--worker-threads=2"]).unwrap().worker_threads,
+            Some(2)
+        );
+        assert!(parse(&["--current-thread"]).unwrap().current_thread);
+        assert!(parse(&["--current-thread", "--worker-threads=2"]).is_err());
+        assert_eq!(parse(&[]).unwrap().verbosity(), None);
+        assert_eq!(
+            parse(&["-q"]).unwrap().verbosity(),
+            Some(LevelFilter::Error)
+        );
+        assert_eq!(
+            parse(&["-v"]).unwrap().verbosity(),
+            Some(LevelFilter::Debug)
+        );
+        assert_eq!(
+            parse(&["-vv"]).unwrap().verbosity(),
+            Some(LevelFilter::Trace)
+        );
+        assert!(parse(&["-q", "-v"]).is_err());
+        assert!(parse(&["stats", "--tui"]).unwrap().tui);
+        assert!(parse(&["--tui", "-q"]).is_err());
+        let args = parse(&["--log-format", "json"]).unwrap();
+        assert_eq!(args.log_format, LogFormat::Json);
+        assert!(parse(&["-0"]).is_err());
+        assert!(parse(&["--files-from", "-", "-"]).is_err());
+        assert!(parse(&["--watch", "a.txt"]).unwrap().watch);
+        assert!(parse(&["stats", "--dry-run"]).unwrap().dry_run);
+        assert!(parse(&["--dry-run", "--watch", "a.txt"]).is_err());
+        assert!(parse(&["--watch"]).is_err());
+        assert!(parse(&["--watch", "--files-from", "-"]).is_err());
+        assert!(parse(&["--buffer-size=x"]).is_err());
+        assert!(parse(&["--buffer-size=0"]).is_err());
+        assert!(parse(&["--max-rate=0"]).is_err());
+        assert!(parse(&["--unknown"]).is_err());
+        let args = parse(&["--tokenizer=ascii", "--config", "fpc.toml"]).unwrap();
+        assert_eq!(args.tokenizer, Some(Tokenizer::Ascii));
+        assert_eq!(args.config.as_deref(), Some("fpc.toml"));
+        assert!(parse(&["--tokenizer=bytes"]).is_err());
+        assert_eq!(parse_tokenizer("emoji"), Ok(Tokenizer::Emoji));
+        let args = parse(&["--record-separator", "\\n\\n"]).unwrap();
+        assert!(matches!(args.records(), Some(RecordSeparator::Str(s)) if s == b"\n\n"));
+        let args = parse(&["--record-start", "^\\d+ "]).unwrap();
+        assert!(matches!(
+            args.records(),
+            Some(RecordSeparator::LineStart(_))
+        ));
+        assert!(parse(&["--record-start", "(", "a.txt"]).is_err());
+        assert!(parse(&["--record-separator="]).is_err());
+        assert!(parse(&["--record-separator=;", "--cache=dir"]).is_err());
+        let args = parse(&["--record-length", "80"]).unwrap();
+        assert!(matches!(args.records(), Some(RecordSeparator::Fixed(80))));
+        assert!(parse(&["--record-length", "0"]).is_err());
+        assert!(parse(&["--record-length=8", "--record-separator=;"]).is_err());
+        let args = parse(&["--length-prefixed"]).unwrap();
+        assert!(matches!(
+            args.records(),
+            Some(RecordSeparator::LengthPrefixed)
+        ));
+        assert!(parse(&["--length-prefixed", "--record-length=8"]).is_err());
+        assert!(parse(&[]).unwrap().records().is_none());
+        assert_eq!(parse(&["--max-lines=10"]).unwrap().max_lines, Some(10));
+        assert!(parse(&["--max-lines=10", "--checkpoint=c.json"]).is_err());
+        let args = parse(&["--max-rate=1M"]).unwrap();
+        assert_eq!(args.rate_limit(), Some(RateLimit::BytesPerSec(1 << 20)));
+        let args = parse(&["--max-line-rate", "50"]).unwrap();
+        assert_eq!(args.rate_limit(), Some(RateLimit::LinesPerSec(50)));
+        assert!(parse(&["--max-line-rate=0"]).is_err());
+        assert!(parse(&["--max-rate=1K", "--max-line-rate=5"]).is_err());
+        assert_eq!(parse(&[]).unwrap().rate_limit(), None);
+        assert_eq!(parse_throughput("200MB/s"), Ok(200 << 20));
+        assert_eq!(parse_throughput("64K"), Ok(64 << 10));
+        assert!(parse_throughput("0/s").is_err());
+        assert!(parse_throughput("1M/h").is_err());
+        assert!(parse(&["--max-throughput=1G/s"])
+            .unwrap()
+            .throughput_limit()
+            .is_some());
+        let args = parse(&["--tokenizer=custom: \\t\\u{A0}\\\\"]).unwrap();
+        assert_eq!(
+            args.tokenizer,
+            Some(Tokenizer::Custom(&['\t', ' ', '\\', '\u{a0}']))
+        );
+        for tokenizer in ["custom:", "custom:\\x", "custom:\\u{d800}", "custom:\\u{a0"] {
+            assert!(parse_tokenizer(tokenizer).is_err(), "{tokenizer}");
+        }
+        let args = parse(&["--respect-gitignore", "--exclude=*.1", "--exclude", "*.gz"]).unwrap();
+        assert_eq!(
+            args.walk_options(),
+            WalkOptions {
+                gitignore: true,
+                excludes: vec![Pattern::new("*.1").unwrap(), Pattern::new("*.gz").unwrap()],
+                ..WalkOptions::default()
+            }
+        );
+        assert!(parse(&["--exclude=[a"]).is_err());
+        let walk = parse(&["--priority=*.md", "--priority", "small/*"])
+            .unwrap()
+            .walk_options();
+        assert_eq!(walk.priorities.len(), 2);
+        assert_eq!(walk.priority(Path::new("docs/a.md")), 0);
+        assert_eq!(walk.priority(Path::new("b.txt")), 2);
+        let walk = parse(&["--include-ext=txt,log", "--text-only"])
+            .unwrap()
+            .walk_options();
+        assert_eq!(walk.include_exts, ["txt", "log"]);
+        assert!(walk.text_only);
+        let walk = parse(&["--max-depth=2", "--follow-symlinks"])
+            .unwrap()
+            .walk_options();
+        assert_eq!((walk.max_depth, walk.follow_symlinks), (Some(2), true));
+        let args = parse(&["--follow-symlinks", "--no-follow-symlinks"]).unwrap();
+        assert!(!args.walk_options().follow_symlinks);
+        let walk = parse(&["--max-file-size=500M"]).unwrap().walk_options();
+        assert_eq!(walk.max_file_size, Some(500 << 20));
+        assert!(!walk.keep_duplicates);
+        assert!(
+            parse(&["--no-dedupe"])
+                .unwrap()
+                .walk_options()
+                .keep_duplicates
+        );
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        let parse = |args: &[&str]| Args::try_parse_from([&["fpc"], args].concat());
+        let args = parse(&["stats", "--format=table", "a.txt"]).unwrap();
+        assert_eq!(args.command, Command::Stats);
+        assert_eq!(args.files, ["a.txt"]);
+        assert_eq!(parse(&["lines"]).unwrap().command, Command::Lines);
+        assert_eq!(parse(&["words", "a.txt"]).unwrap().files, ["a.txt"]);
+        let args = parse(&["freq", "--top", "10"]).unwrap();
+        assert_eq!(args.command, Command::Freq { top: Some(10) });
+        assert_eq!(parse(&["./lines"]).unwrap().files, ["./lines"]);
+        let args = parse(&["--format=csv", "lines", "a.txt", "--strict"]).unwrap();
+        assert_eq!(args.command, Command::Lines);
+        assert_eq!(args.format, Some(Format::Registered("csv")));
+        assert!(args.strict);
+        let error = parse(&["lines", "--wc"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ArgumentConflict);
+        assert!(parse(&["freq", "--sort=words"]).is_err());
+        assert!(parse(&["stats", "--sort=words"]).is_ok());
+        assert!(parse(&["--top=10"]).is_err());
+        assert_eq!(parse(&[]).unwrap().serve, None);
+        let args = parse(&["serve", "--listen", "0.0.0.0:80"]).unwrap();
+        let serve = args.serve.unwrap();
+        assert_eq!(serve.listen, SocketAddr::from(([0, 0, 0, 0], 80)));
+        assert_eq!((serve.root, serve.allow_urls), (None, false));
+        let serve = parse(&["serve", "--root=/srv", "--allow-urls"])
+            .unwrap()
+            .serve;
+        assert_eq!(serve.unwrap().root, Some(PathBuf::from("/srv")));
+        assert!(parse(&["serve", "a.txt"]).is_err());
+        let args = parse(&["--watch", "--metrics-addr=127.0.0.1:9090", "a.txt"]).unwrap();
+        assert_eq!(
+            args.metrics_addr,
+            Some(SocketAddr::from(([127, 0, 0, 1], 9090)))
+        );
+        assert!(parse(&["--metrics-addr=127.0.0.1:9090", "a.txt"]).is_err());
+        let args = parse(&["diff", "old.json", "new.json"]).unwrap();
+        assert_eq!(args.diff, Some(["old.json".into(), "new.json".into()]));
+        assert!(parse(&["diff", "old.json"]).is_err());
+        assert_eq!(parse(&["schema"]).unwrap().schema, Some(SCHEMA_VERSION));
+        let args = parse(&["schema", "--format", "ndjson", "--schema-version", "1"]).unwrap();
+        assert_eq!((args.schema, args.format), (Some(1), Some(Format::Ndjson)));
+        let args = parse(&["bench", "-n3", "a.txt"]).unwrap();
+        assert_eq!(args.bench, Some(3));
+        assert_eq!(args.files, ["a.txt"]);
+        assert_eq!(parse(&["bench", "a.txt"]).unwrap().bench, Some(5));
+        assert!(parse(&["bench"]).is_err());
+        assert!(parse(&["bench", "-n0", "a.txt"]).is_err());
+        let args = parse(&["top-words", "-k3", "--min-length=2", "--stop-words=a,The"]).unwrap();
+        assert_eq!(args.command, Command::TopWords { top: 3 });
+        assert_eq!(args.min_length, 2);
+        assert_eq!(args.stop_words, ["a", "The"]);
+        assert_eq!(
+            parse(&["top-words"]).unwrap().command,
+            Command::TopWords { top: 10 }
+        );
+        assert!(parse(&["top-words", "--sort=words"]).is_ok());
+        assert!(parse(&["top-words", "--with-meta"]).unwrap().with_meta);
+        assert!(parse(&["code-stats", "--checksum"]).unwrap().checksum);
+        assert!(parse(&["--checksum"]).unwrap().checksum);
+        assert!(parse(&["freq", "--stop-words-file=stop.txt"]).is_ok());
+        assert!(parse(&["lines", "--min-length=2"]).is_err());
+        let args = parse(&["hist", "--bucket-width=5", "a.txt"]).unwrap();
+        assert_eq!(
+            args.command,
+            Command::Hist {
+                bucket_width: Some(5)
+            }
+        );
+        assert!(parse(&["hist", "--bucket-width=0"]).is_err());
+        assert!(parse(&["hist", "--min-length=2"]).is_err());
+        let args = parse(&["chars", "--per-line", "--format=ndjson", "a.txt"]).unwrap();
+        assert_eq!(args.command, Command::Chars);
+        assert_eq!(args.command.line_count(), Some("char_count"));
+        assert!(args.command.analyzer().is_some());
+        assert_eq!(parse(&["bytes"]).unwrap().command, Command::Bytes);
+        assert!(parse(&["bytes", "--checkpoint=run.json"]).is_err());
+        assert!(Command::Words.analyzer().is_none());
+        let args = parse(&["longest-word", "a.txt"]).unwrap();
+        assert_eq!(args.command.line_count(), Some("longest_word"));
+        assert!(args.command.analyzer().is_some());
+        assert!(Command::ShortestWord.analyzer().is_some());
+        let args = parse(&["code-stats"]).unwrap();
+        assert_eq!(args.command, Command::CodeStats);
+        assert_eq!(args.files, ["."]);
+        assert_eq!(parse(&["code-stats", "src"]).unwrap().files, ["src"]);
+        assert!(parse(&["code-stats", "--sort=words"]).is_err());
+        let args = parse(&["match-count", "-i", "^error", "a.txt", "b.txt"]).unwrap();
+        assert_eq!(args.command, Command::MatchCount);
+        assert_eq!(args.files, ["a.txt", "b.txt"]);
+        assert!(args.pattern.unwrap().is_match(b"ERROR: a"));
+        let error = parse(&["match-count", "(a"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ValueValidation);
+        assert!(parse(&["match-count"]).is_err());
+        let args = parse(&["dedup", "--approximate", "a.txt"]).unwrap();
+        assert_eq!(args.command, Command::Dedup { approximate: true });
+        let args = parse(&["diversity", "a.txt"]).unwrap();
+        assert_eq!(args.command, Command::Diversity { approximate: false });
+        let args = parse(&["readability", "a.txt"]).unwrap();
+        assert_eq!(args.command, Command::Readability);
+        let args = parse(&["char-classes", "a.txt"]).unwrap();
+        assert_eq!(args.command, Command::CharClasses);
+        let args = parse(&["dir-totals"]).unwrap();
+        assert_eq!(
+            (args.command, args.files),
+            (Command::DirTotals, vec![".".into()])
+        );
+    }
+}
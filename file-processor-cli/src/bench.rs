@@ -0,0 +1,188 @@
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use string_stream_processor::{ProcessorOptions, SourceProvider};
+
+use crate::{
+    color::Palette,
+    inputs::Inputs,
+    output::write_aligned,
+    summary::{serialize_ms, Summary, Totals},
+    tally::Tallied,
+    walk::WalkOptions,
+};
+
+/// Throughput of an iteration of a benchmark.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Iteration {
+    #[serde(flatten)]
+    pub summary: Summary,
+    pub mb_per_sec: f64,
+    pub lines_per_sec: f64,
+}
+
+impl Iteration {
+    fn new(summary: Summary) -> Self {
+        let secs = summary.elapsed.as_secs_f64().max(f64::EPSILON);
+        Self {
+            mb_per_sec: summary.bytes as f64 / 1e6 / secs,
+            lines_per_sec: summary.totals.lines as f64 / secs,
+            summary,
+        }
+    }
+}
+
+/// Statistics of the iterations of a benchmark, and of the time to read each file, from its
+/// opening to its end, over all of them.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Bench {
+    pub iterations: Vec<Iteration>,
+    pub mean_mb_per_sec: f64,
+    pub mean_lines_per_sec: f64,
+    #[serde(rename = "p50_latency_ms", serialize_with = "serialize_ms")]
+    pub p50_latency: Duration,
+    #[serde(rename = "p99_latency_ms", serialize_with = "serialize_ms")]
+    pub p99_latency: Duration,
+}
+
+impl Bench {
+    fn new(iterations: Vec<Iteration>, mut latencies: Vec<Duration>) -> Self {
+        latencies.sort_unstable();
+        let mean = |value: fn(&Iteration) -> f64| {
+            iterations.iter().map(value).sum::<f64>() / iterations.len().max(1) as f64
+        };
+        Self {
+            mean_mb_per_sec: mean(|iteration| iteration.mb_per_sec),
+            mean_lines_per_sec: mean(|iteration| iteration.lines_per_sec),
+            p50_latency: percentile(&latencies, 0.5),
+            p99_latency: percentile(&latencies, 0.99),
+            iterations,
+        }
+    }
+
+    /// Write the iterations as a table followed by their statistics, or as a JSON document.
+    pub fn write(&self, mut writer: impl Write, json: bool, palette: Palette) -> io::Result<()> {
+        if json {
+            serde_json::to_writer_pretty(&mut writer, self)?;
+            writeln!(writer)?;
+            return writer.flush();
+        }
+        const HEADER: [&str; 6] = ["ITERATION", "FILES", "LINES", "TIME", "MB/S", "LINES/S"];
+        let rows: Vec<Vec<String>> = self
+            .iterations
+            .iter()
+            .enumerate()
+            .map(|(i, iteration)| {
+                vec![
+                    (i + 1).to_string(),
+                    iteration.summary.processed.to_string(),
+                    iteration.summary.totals.lines.to_string(),
+                    format!("{:.1?}", iteration.summary.elapsed),
+                    format!("{:.1}", iteration.mb_per_sec),
+                    format!("{:.0}", iteration.lines_per_sec),
+                ]
+            })
+            .collect();
+        write_aligned(&mut writer, &HEADER, &rows, palette, |_, _| false)?;
+        writeln!(
+            writer,
+            "mean {:.1} MB/s, {:.0} lines/s, per file p50 {:.1?}, p99 {:.1?}",
+            self.mean_mb_per_sec, self.mean_lines_per_sec, self.p50_latency, self.p99_latency
+        )?;
+        writer.flush()
+    }
+}
+
+/// Nearest-rank percentile of sorted durations, zero without any.
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted
+        .get(rank.saturating_sub(1))
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Count the words of the lines of the files `iterations` times, discarding the counts. The
+/// directories are walked once.
+pub async fn run(
+    files: Vec<String>,
+    manifest: Option<Vec<String>>,
+    walk: &WalkOptions,
+    options: ProcessorOptions,
+    iterations: usize,
+) -> Bench {
+    let paths = Inputs::new(files, manifest, options)
+        .walk_dirs(walk)
+        .await
+        .paths()
+        .to_vec();
+    let mut runs = Vec::with_capacity(iterations);
+    let mut latencies = Vec::new();
+    for _ in 0..iterations {
+        let sources = Tallied::new(Inputs::new(Vec::new(), Some(paths.clone()), options));
+        let start = Instant::now();
+        let results = sources.count_line_words(options).await;
+        let elapsed = start.elapsed();
+        let mut totals = Totals::default();
+        for counts in results.values() {
+            totals.add(counts);
+        }
+        let tallies = sources.tallies();
+        latencies.extend(
+            tallies
+                .iter()
+                .filter(|(_, tally)| tally.is_finished())
+                .map(|(_, tally)| tally.duration()),
+        );
+        runs.push(Iteration::new(Summary::new(
+            paths.len(),
+            &tallies,
+            totals,
+            elapsed,
+        )));
+    }
+    Bench::new(runs, latencies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let latencies: Vec<_> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&latencies, 0.5), Duration::from_millis(50));
+        assert_eq!(percentile(&latencies, 0.99), Duration::from_millis(99));
+        assert_eq!(percentile(&latencies[..1], 0.99), Duration::from_millis(1));
+        assert_eq!(percentile(&[], 0.5), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_bench() {
+        let path = std::env::temp_dir().join("fpc_test_bench.txt");
+        std::fs::write(&path, "a b\nc\n").unwrap();
+        let files = vec![path.to_str().unwrap().to_string()];
+        let options = ProcessorOptions::default();
+        let bench = run(files, None, &WalkOptions::default(), options, 3).await;
+        assert_eq!(bench.iterations.len(), 3);
+        for iteration in &bench.iterations {
+            assert_eq!(iteration.summary.processed, 1);
+            assert_eq!(iteration.summary.bytes, 6);
+            assert_eq!(iteration.summary.totals, Totals { lines: 2, words: 3 });
+        }
+        assert!(bench.p50_latency <= bench.p99_latency);
+
+        let mut table = Vec::new();
+        bench.write(&mut table, false, Palette::new(false)).unwrap();
+        let table = String::from_utf8(table).unwrap();
+        assert!(table.starts_with("ITERATION  FILES  LINES"));
+        assert_eq!(table.lines().count(), 5);
+        let mut json = Vec::new();
+        bench.write(&mut json, true, Palette::new(false)).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["iterations"][0]["bytes"], 6);
+    }
+}
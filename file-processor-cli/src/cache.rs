@@ -0,0 +1,181 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use futures_util::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use string_stream_processor::ProcessorOptions;
+
+use crate::{
+    output::SCHEMA_VERSION,
+    sink::{AtomicFile, Sink},
+    walk::WALK_CONCURRENCY,
+};
+
+/// Length and modification time in nanoseconds of a file, which must be the same for its
+/// cached counts to be reused.
+type Version = (u64, u128);
+
+/// Cached counts of a file.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry<C> {
+    schema_version: u32,
+    path: String,
+    len: u64,
+    modified_ns: u128,
+    counts: C,
+}
+
+/// Directory of the counts of the files of the previous runs, reused while their length and
+/// modification time are the same. Each file has its own entry, named after the hash of its
+/// absolute path and of the tokenizer.
+#[derive(Debug)]
+pub struct Cache {
+    dir: PathBuf,
+    options: ProcessorOptions,
+    /// Versions of the files whose counts are not cached, taken before they are read.
+    missed: HashMap<String, Version>,
+}
+
+impl Cache {
+    /// Cache in `dir`, created if it does not exist, of the counts with the tokenizer of
+    /// `options`.
+    pub fn open(dir: &str, options: ProcessorOptions) -> Result<Self, String> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Could not create {dir}, {e}"))?;
+        Ok(Self {
+            dir: PathBuf::from(dir),
+            options,
+            missed: HashMap::new(),
+        })
+    }
+
+    /// Cached counts of the files which did not change, the others are stored once read, see
+    /// [`Cache::store`].
+    pub async fn lookup(&mut self, paths: &[String]) -> BTreeMap<String, Vec<usize>> {
+        let cache = &*self;
+        let entries: Vec<_> = stream::iter(paths)
+            .map(|path| async move {
+                let version = version(path).await;
+                let entry = match version {
+                    Some(_) => cache.read(path).await,
+                    None => None,
+                };
+                (path, version, entry)
+            })
+            .buffered(WALK_CONCURRENCY)
+            .collect()
+            .await;
+        let mut hits = BTreeMap::new();
+        for (path, version, entry) in entries {
+            let Some(version) = version else { continue };
+            match entry.filter(|entry| (entry.len, entry.modified_ns) == version) {
+                Some(entry) => {
+                    log::debug!("Reusing the cached counts of {path}.");
+                    hits.insert(path.clone(), entry.counts);
+                }
+                None => {
+                    self.missed.insert(path.clone(), version);
+                }
+            }
+        }
+        hits
+    }
+
+    /// Cache the counts of a file read until its end, if it was missed by [`Cache::lookup`].
+    pub fn store(&self, path: &str, counts: &[usize]) -> io::Result<()> {
+        let Some(&(len, modified_ns)) = self.missed.get(path) else {
+            return Ok(());
+        };
+        let entry = Entry {
+            schema_version: SCHEMA_VERSION,
+            path: path.to_string(),
+            len,
+            modified_ns,
+            counts,
+        };
+        let mut writer = Box::new(AtomicFile::create(self.entry_path(path))?);
+        serde_json::to_writer(&mut writer, &entry)?;
+        writer.write_all(b"\n")?;
+        writer.commit()
+    }
+
+    async fn read(&self, path: &str) -> Option<Entry<Vec<usize>>> {
+        let data = tokio::fs::read(self.entry_path(path)).await.ok()?;
+        let entry: Entry<Vec<usize>> = serde_json::from_slice(&data)
+            .inspect_err(|e| log::warn!("Ignoring the invalid cached counts of {path}, {e}."))
+            .ok()?;
+        // The hash of another path, or of an older schema.
+        (entry.schema_version == SCHEMA_VERSION && entry.path == path).then_some(entry)
+    }
+
+    fn entry_path(&self, path: &str) -> PathBuf {
+        let absolute = std::path::absolute(path).unwrap_or_else(|_| PathBuf::from(path));
+        let mut hasher = Sha256::new();
+        hasher.update(absolute.as_os_str().as_encoded_bytes());
+        hasher.update(format!("\0{:?}", self.options.tokenizer));
+        let hash: String = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        self.dir.join(Path::new(&hash).with_extension("json"))
+    }
+}
+
+async fn version(path: &str) -> Option<Version> {
+    let metadata = tokio::fs::metadata(path).await.ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    metadata
+        .is_file()
+        .then_some((metadata.len(), modified.as_nanos()))
+}
+
+#[cfg(test)]
+mod tests {
+    use string_stream_processor::Tokenizer;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cache() {
+        let dir = std::env::temp_dir().join("fpc_test_cache");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.txt");
+        std::fs::write(&file, "a b\nc\n").unwrap();
+        let paths = [
+            file.to_str().unwrap().to_string(),
+            "fpc_test_missing.txt".into(),
+        ];
+        let cache_dir = dir.join("cache");
+        let cache_dir = cache_dir.to_str().unwrap();
+        let options = ProcessorOptions::default();
+
+        let mut cache = Cache::open(cache_dir, options).unwrap();
+        assert!(cache.lookup(&paths).await.is_empty());
+        cache.store(&paths[0], &[2, 1]).unwrap();
+        cache.store(&paths[1], &[0]).unwrap();
+        let mut cache = Cache::open(cache_dir, options).unwrap();
+        assert_eq!(
+            cache.lookup(&paths).await,
+            BTreeMap::from([(paths[0].clone(), vec![2, 1])])
+        );
+        assert!(cache.missed.is_empty());
+
+        let ascii = ProcessorOptions {
+            tokenizer: Tokenizer::Ascii,
+            ..options
+        };
+        let mut cache = Cache::open(cache_dir, ascii).unwrap();
+        assert!(cache.lookup(&paths).await.is_empty());
+
+        std::fs::write(&file, "a b c d\n").unwrap();
+        let mut cache = Cache::open(cache_dir, options).unwrap();
+        assert!(cache.lookup(&paths).await.is_empty());
+        assert!(cache.missed.contains_key(&paths[0]));
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, Write},
+    path::Path,
+    pin::pin,
+    time::{Duration, Instant},
+};
+
+use futures_util::{FutureExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use string_stream_processor::{count_line_words_with, ProcessorOptions, SourceProvider};
+
+use crate::{
+    inputs::{Inputs, STDIN},
+    output::SCHEMA_VERSION,
+    sink::{AtomicFile, Sink},
+    tally::Tallied,
+};
+
+/// Interval between the saves of a checkpoint during a run.
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Content of a checkpoint file, versioned like the documents of the results.
+#[derive(Debug, Serialize, Deserialize)]
+struct CheckpointFile<R> {
+    schema_version: u32,
+    results: R,
+}
+
+/// Results of the files read until their end, saved to a file during a run for a later run to
+/// resume from it.
+#[derive(Debug)]
+pub struct Checkpoint {
+    path: String,
+    /// Results of the previous runs with `--resume`, then of the files of this run.
+    recorded: BTreeMap<String, Vec<usize>>,
+    saved: Instant,
+}
+
+impl Checkpoint {
+    /// Checkpoint saved to `path`, with the results it already records if `resume` is set and
+    /// it exists.
+    pub fn new(path: &str, resume: bool) -> Result<Self, String> {
+        let mut checkpoint = Self {
+            path: path.to_string(),
+            recorded: BTreeMap::new(),
+            saved: Instant::now(),
+        };
+        if !resume || !Path::new(path).exists() {
+            return Ok(checkpoint);
+        }
+        let invalid = |e: &dyn std::fmt::Display| format!("Could not resume from {path}, {e}");
+        let data = std::fs::read(path).map_err(|e| invalid(&e))?;
+        let file: CheckpointFile<_> = serde_json::from_slice(&data).map_err(|e| invalid(&e))?;
+        if file.schema_version != SCHEMA_VERSION {
+            let version = file.schema_version;
+            return Err(invalid(&format!("unsupported schema version {version}")));
+        }
+        checkpoint.recorded = file.results;
+        log::info!(
+            "Resuming from {path}, skipping {} finished files.",
+            checkpoint.recorded.len()
+        );
+        Ok(checkpoint)
+    }
+
+    /// Results recorded so far, the ones of the previous runs before [`Checkpoint::record`].
+    pub fn recorded(&self) -> &BTreeMap<String, Vec<usize>> {
+        &self.recorded
+    }
+
+    /// Record the results of a finished file, saved with the others every few seconds.
+    pub fn record(&mut self, id: &str, counts: &[usize]) -> io::Result<()> {
+        self.recorded.insert(id.to_string(), counts.to_vec());
+        if self.saved.elapsed() >= SAVE_INTERVAL {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Replace the file by the recorded results.
+    pub fn save(&mut self) -> io::Result<()> {
+        let file = CheckpointFile {
+            schema_version: SCHEMA_VERSION,
+            results: &self.recorded,
+        };
+        let mut writer = Box::new(AtomicFile::create(&self.path)?);
+        serde_json::to_writer(&mut writer, &file)?;
+        writer.write_all(b"\n")?;
+        writer.commit()?;
+        self.saved = Instant::now();
+        Ok(())
+    }
+}
+
+/// Count the words of each line of the sources, passing the results of each file read until
+/// its end to `finished` as soon as it is. The sources which failed or were interrupted, and
+/// the standard input, are left out, to be read again by the next run.
+pub async fn count_finished(
+    sources: &Tallied<Inputs>,
+    options: ProcessorOptions,
+    mut finished: impl FnMut(&str, &[usize]) -> io::Result<()>,
+) -> io::Result<HashMap<&str, Vec<usize>>> {
+    let counts = sources
+        .sources()
+        .map(|(id, rd)| {
+            count_line_words_with(rd, options)
+                .collect::<Vec<_>>()
+                .map(move |counts| (id, counts))
+        })
+        .buffer_unordered(options.max_concurrency.unwrap_or(usize::MAX));
+    let mut counts = pin!(counts);
+    let mut results = HashMap::new();
+    while let Some((id, counts)) = counts.next().await {
+        let complete = sources
+            .tally(id)
+            .is_some_and(|tally| tally.is_finished() && tally.error.get().is_none());
+        if complete && id != STDIN {
+            finished(id, &counts)?;
+        }
+        results.insert(id, counts);
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_checkpoint() {
+        let dir = std::env::temp_dir().join("fpc_test_checkpoint");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "a b\nc\n").unwrap();
+        std::fs::write(dir.join("b.txt"), "d\n").unwrap();
+        let files: Vec<_> = ["a.txt", "b.txt", "missing.txt"]
+            .map(|name| dir.join(name).to_str().unwrap().to_string())
+            .into();
+        let path = dir.join("checkpoint.json");
+        let path = path.to_str().unwrap();
+        let options = ProcessorOptions::default();
+
+        let mut checkpoint = Checkpoint::new(path, true).unwrap();
+        let sources = Tallied::new(Inputs::new(files.clone(), None, options));
+        let results = count_finished(&sources, options, |id, counts| {
+            checkpoint.record(id, counts)
+        });
+        let results = results.await.unwrap();
+        assert_eq!(
+            results,
+            HashMap::from([(files[0].as_str(), vec![2, 1]), (&files[1], vec![1])])
+        );
+        checkpoint.save().unwrap();
+
+        let checkpoint = Checkpoint::new(path, true).unwrap();
+        assert_eq!(
+            checkpoint.recorded(),
+            &BTreeMap::from([(files[0].clone(), vec![2, 1]), (files[1].clone(), vec![1])])
+        );
+        let saved: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+        assert_eq!(
+            saved,
+            serde_json::json!({
+                "schema_version": SCHEMA_VERSION,
+                "results": {&files[0]: [2, 1], &files[1]: [1]},
+            })
+        );
+
+        // Without --resume, the checkpoint starts over.
+        assert!(Checkpoint::new(path, false).unwrap().recorded.is_empty());
+        std::fs::write(path, "{}").unwrap();
+        assert!(Checkpoint::new(path, true).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+use std::{collections::HashMap, io, sync::Mutex};
+
+use string_stream_processor::{ProcessorOptions, SourceProvider};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+use crate::tally::Tallied;
+
+/// Numbers of characters of a source by class.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CharClasses {
+    /// Number of lines read.
+    pub lines: u64,
+    pub alphabetic: u64,
+    pub numeric: u64,
+    /// ASCII punctuation, the other punctuation characters are in `other`.
+    pub punctuation: u64,
+    pub whitespace: u64,
+    pub other: u64,
+}
+
+impl CharClasses {
+    fn add(&mut self, c: char) {
+        let class = if c.is_alphabetic() {
+            &mut self.alphabetic
+        } else if c.is_numeric() {
+            &mut self.numeric
+        } else if c.is_ascii_punctuation() {
+            &mut self.punctuation
+        } else if c.is_whitespace() {
+            &mut self.whitespace
+        } else {
+            &mut self.other
+        };
+        *class += 1;
+    }
+
+    pub fn chars(&self) -> u64 {
+        self.alphabetic + self.numeric + self.punctuation + self.whitespace + self.other
+    }
+}
+
+/// Count the characters of each source by class, reading at most `options.max_concurrency`
+/// sources at a time, see [`Tallied::count_each`].
+pub async fn count_char_classes<P: SourceProvider>(
+    provider: &Tallied<P>,
+    options: ProcessorOptions,
+) -> HashMap<&str, CharClasses> {
+    let merged = Mutex::new(HashMap::new());
+    let count = |_, rd| count_source_char_classes(rd);
+    provider
+        .count_each(options.max_concurrency, count, |id, classes| {
+            merged.lock().unwrap().insert(id, classes);
+        })
+        .await;
+    merged.into_inner().unwrap()
+}
+
+/// Count the characters of a source by class, without the line endings. The invalid UTF-8
+/// sequences are counted as other characters.
+async fn count_source_char_classes(mut rd: impl AsyncBufRead + Unpin) -> io::Result<CharClasses> {
+    let mut classes = CharClasses::default();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if rd.read_until(b'\n', &mut line).await? == 0 {
+            break;
+        }
+        classes.lines += 1;
+        let line = line.strip_suffix(b"\n").unwrap_or(&line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        for c in String::from_utf8_lossy(line).chars() {
+            classes.add(c);
+        }
+    }
+    Ok(classes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_count_source_char_classes() {
+        let source = "level=INFO t=12:30\r\n\n\tÉté, 42 ½ → ok\n".as_bytes();
+        assert_eq!(
+            count_source_char_classes(source).await.unwrap(),
+            CharClasses {
+                lines: 3,
+                alphabetic: 15,
+                numeric: 7,
+                punctuation: 4,
+                whitespace: 6,
+                other: 1,
+            }
+        );
+        let classes = count_source_char_classes(&b"a\xffb"[..]).await.unwrap();
+        assert_eq!(
+            (classes.alphabetic, classes.other, classes.chars()),
+            (2, 1, 3)
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use std::{collections::HashMap, io, path::Path, sync::Mutex};
+
+use string_stream_processor::{ProcessorOptions, SourceProvider};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+use crate::tally::Tallied;
+
+/// Comment syntax of a language, recognized by the extension of its files.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Language {
+    pub name: &'static str,
+    extensions: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+}
+
+const C_STYLE: (&str, &str) = ("/*", "*/");
+
+/// Languages detected, by name.
+const LANGUAGES: &[Language] = &[
+    Language {
+        name: "C",
+        extensions: &["c", "h"],
+        line_comments: &["//"],
+        block_comment: Some(C_STYLE),
+    },
+    Language {
+        name: "C++",
+        extensions: &["cc", "cpp", "cxx", "hh", "hpp", "hxx"],
+        line_comments: &["//"],
+        block_comment: Some(C_STYLE),
+    },
+    Language {
+        name: "CSS",
+        extensions: &["css"],
+        line_comments: &[],
+        block_comment: Some(C_STYLE),
+    },
+    Language {
+        name: "Go",
+        extensions: &["go"],
+        line_comments: &["//"],
+        block_comment: Some(C_STYLE),
+    },
+    Language {
+        name: "HTML",
+        extensions: &["htm", "html"],
+        line_comments: &[],
+        block_comment: Some(("<!--", "-->")),
+    },
+    Language {
+        name: "Java",
+        extensions: &["java"],
+        line_comments: &["//"],
+        block_comment: Some(C_STYLE),
+    },
+    Language {
+        name: "JavaScript",
+        extensions: &["cjs", "js", "jsx", "mjs"],
+        line_comments: &["//"],
+        block_comment: Some(C_STYLE),
+    },
+    Language {
+        name: "JSON",
+        extensions: &["json"],
+        line_comments: &[],
+        block_comment: None,
+    },
+    Language {
+        name: "Markdown",
+        extensions: &["md"],
+        line_comments: &[],
+        block_comment: Some(("<!--", "-->")),
+    },
+    Language {
+        name: "Protobuf",
+        extensions: &["proto"],
+        line_comments: &["//"],
+        block_comment: Some(C_STYLE),
+    },
+    Language {
+        name: "Python",
+        extensions: &["py", "pyi"],
+        line_comments: &["#"],
+        block_comment: None,
+    },
+    Language {
+        name: "Ruby",
+        extensions: &["rb"],
+        line_comments: &["#"],
+        block_comment: None,
+    },
+    Language {
+        name: "Rust",
+        extensions: &["rs"],
+        line_comments: &["//"],
+        block_comment: Some(C_STYLE),
+    },
+    Language {
+        name: "Shell",
+        extensions: &["bash", "sh", "zsh"],
+        line_comments: &["#"],
+        block_comment: None,
+    },
+    Language {
+        name: "SQL",
+        extensions: &["sql"],
+        line_comments: &["--"],
+        block_comment: Some(C_STYLE),
+    },
+    Language {
+        name: "TOML",
+        extensions: &["toml"],
+        line_comments: &["#"],
+        block_comment: None,
+    },
+    Language {
+        name: "TypeScript",
+        extensions: &["ts", "tsx"],
+        line_comments: &["//"],
+        block_comment: Some(C_STYLE),
+    },
+    Language {
+        name: "YAML",
+        extensions: &["yaml", "yml"],
+        line_comments: &["#"],
+        block_comment: None,
+    },
+];
+
+/// Kind of a line of source code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineKind {
+    Blank,
+    Comment,
+    Code,
+}
+
+impl Language {
+    /// Language of a file, from its extension.
+    pub fn detect(path: &str) -> Option<&'static Self> {
+        let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+        LANGUAGES
+            .iter()
+            .find(|language| language.extensions.contains(&extension.as_str()))
+    }
+
+    /// Kind of a line, `in_block` telling whether it starts in a block comment, and updated to
+    /// whether it ends in one. A line with code and comments is a line of code. The strings are
+    /// not parsed, so the comment markers in them are taken for comments.
+    fn classify(&self, line: &str, in_block: &mut bool) -> LineKind {
+        let mut rest = line.trim();
+        if rest.is_empty() {
+            return LineKind::Blank;
+        }
+        let (mut code, mut comment) = (false, false);
+        while !rest.is_empty() {
+            if let Some((start, end)) = self.block_comment {
+                if *in_block {
+                    comment = true;
+                    match rest.find(end) {
+                        Some(i) => {
+                            *in_block = false;
+                            rest = rest[i + end.len()..].trim_start();
+                            continue;
+                        }
+                        None => break,
+                    }
+                }
+                if let Some(after) = rest.strip_prefix(start) {
+                    *in_block = true;
+                    rest = after;
+                    continue;
+                }
+            }
+            if self
+                .line_comments
+                .iter()
+                .any(|prefix| rest.starts_with(prefix))
+            {
+                comment = true;
+                break;
+            }
+            code = true;
+            let markers = self
+                .line_comments
+                .iter()
+                .chain(self.block_comment.as_ref().map(|(start, _)| start));
+            match markers.filter_map(|marker| rest.find(marker)).min() {
+                Some(i) => rest = &rest[i..],
+                None => break,
+            }
+        }
+        match (code, comment) {
+            (true, _) => LineKind::Code,
+            (false, true) => LineKind::Comment,
+            (false, false) => LineKind::Blank,
+        }
+    }
+}
+
+/// Numbers of files and of lines of each kind of a language.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CodeStats {
+    pub files: u64,
+    pub blank: u64,
+    pub comment: u64,
+    pub code: u64,
+}
+
+impl CodeStats {
+    fn add(&mut self, other: &Self) {
+        self.files += other.files;
+        self.blank += other.blank;
+        self.comment += other.comment;
+        self.code += other.code;
+    }
+
+    pub fn lines(&self) -> u64 {
+        self.blank + self.comment + self.code
+    }
+}
+
+/// Count the blank, comment and code lines of the sources by language, reading at most
+/// `options.max_concurrency` sources at a time. The sources of an unknown language are left
+/// out, see [`Tallied::count_each`].
+pub async fn count_code_stats<P: SourceProvider>(
+    provider: &Tallied<P>,
+    options: ProcessorOptions,
+) -> HashMap<&'static str, CodeStats> {
+    let merged = Mutex::new(HashMap::new());
+    let count = |id, rd| async move {
+        let Some(language) = Language::detect(id) else {
+            log::warn!("Skipping {id}, its language is unknown.");
+            return Ok(None);
+        };
+        let stats = count_source_code_stats(rd, language).await?;
+        Ok(Some((language.name, stats)))
+    };
+    provider
+        .count_each(options.max_concurrency, count, |_, stats| {
+            if let Some((language, stats)) = stats {
+                let mut merged = merged.lock().unwrap();
+                merged
+                    .entry(language)
+                    .or_insert_with(CodeStats::default)
+                    .add(&stats);
+            }
+        })
+        .await;
+    merged.into_inner().unwrap()
+}
+
+/// Count the lines of each kind of a source.
+async fn count_source_code_stats(
+    mut rd: impl AsyncBufRead + Unpin,
+    language: &Language,
+) -> io::Result<CodeStats> {
+    let mut stats = CodeStats {
+        files: 1,
+        ..CodeStats::default()
+    };
+    let mut in_block = false;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if rd.read_until(b'\n', &mut line).await? == 0 {
+            break;
+        }
+        match language.classify(&String::from_utf8_lossy(&line), &mut in_block) {
+            LineKind::Blank => stats.blank += 1,
+            LineKind::Comment => stats.comment += 1,
+            LineKind::Code => stats.code += 1,
+        }
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Language::detect("src/main.rs").unwrap().name, "Rust");
+        assert_eq!(Language::detect("INDEX.HTML").unwrap().name, "HTML");
+        assert_eq!(Language::detect("Makefile"), None);
+        assert_eq!(Language::detect("a.txt"), None);
+    }
+
+    #[tokio::test]
+    async fn test_count_source_code_stats() {
+        let rust = Language::detect("a.rs").unwrap();
+        let source = b"// A comment\n\nfn main() { /* inline */ }\n/* block\n\n   end */ let a = 1;\n/* a\n b */\n  let b = 2; // trailing /*\n";
+        let stats = count_source_code_stats(&source[..], rust).await.unwrap();
+        assert_eq!(
+            stats,
+            CodeStats {
+                files: 1,
+                blank: 2,
+                comment: 4,
+                code: 3,
+            }
+        );
+        let python = Language::detect("a.py").unwrap();
+        let stats = count_source_code_stats(&b"# a\nx = 1\n   \n"[..], python)
+            .await
+            .unwrap();
+        assert_eq!((stats.blank, stats.comment, stats.code), (1, 1, 1));
+    }
+}
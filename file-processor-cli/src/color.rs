@@ -0,0 +1,102 @@
+use std::io::IsTerminal;
+
+use clap::ValueEnum;
+use env_logger::WriteStyle;
+
+/// When to color the output, from `--color`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color if the output is a terminal and `NO_COLOR` is not set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color the standard output, or a file with `to_stdout` false.
+    pub fn enabled(self, to_stdout: bool) -> bool {
+        match self {
+            Self::Auto => {
+                to_stdout
+                    && std::io::stdout().is_terminal()
+                    && std::env::var_os("NO_COLOR").is_none()
+            }
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+
+    /// Style of the log messages written to the standard error, whose levels mark the errors.
+    pub fn write_style(self) -> WriteStyle {
+        match self {
+            Self::Auto
+                if std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none() =>
+            {
+                WriteStyle::Always
+            }
+            Self::Auto => WriteStyle::Never,
+            Self::Always => WriteStyle::Always,
+            Self::Never => WriteStyle::Never,
+        }
+    }
+}
+
+/// ANSI styles of the highlighted cells, a no-op when the colors are disabled.
+#[derive(Debug, Clone, Copy)]
+pub struct Palette {
+    enabled: bool,
+}
+
+impl Palette {
+    const BOLD: &'static str = "1";
+    const BOLD_RED: &'static str = "1;31";
+
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+
+    /// Bold text, for the identifiers and headers.
+    pub fn bold(self, text: &str) -> String {
+        self.paint(Self::BOLD, text)
+    }
+
+    /// Bold red text, for the outlier counts.
+    pub fn outlier(self, text: &str) -> String {
+        self.paint(Self::BOLD_RED, text)
+    }
+
+    /// Wrap the text in the style, it should already be padded as the escape codes have no
+    /// width.
+    fn paint(self, style: &str, text: &str) -> String {
+        if self.enabled {
+            format!("\x1b[{style}m{text}\x1b[0m")
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_choice() {
+        assert_eq!(
+            ColorChoice::from_str("always", false),
+            Ok(ColorChoice::Always)
+        );
+        assert!(ColorChoice::from_str("yes", false).is_err());
+        assert!(ColorChoice::Always.enabled(false));
+        assert!(!ColorChoice::Never.enabled(true));
+        assert!(!ColorChoice::Auto.enabled(false));
+    }
+
+    #[test]
+    fn test_palette() {
+        assert_eq!(Palette::new(true).bold("a.txt"), "\x1b[1ma.txt\x1b[0m");
+        assert_eq!(Palette::new(true).outlier("12"), "\x1b[1;31m12\x1b[0m");
+        assert_eq!(Palette::new(false).outlier("12"), "12");
+    }
+}
@@ -0,0 +1,217 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    args::{parse_pattern, parse_tokenizer, Args},
+    output::Format,
+};
+
+/// Defaults of the arguments, from the `FILE_PROCESSOR_*` environment variables and the TOML
+/// file of `--config` or `~/.config/file-processor/config.toml`. The flags given on the command
+/// line override the variables, which override the file.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Format of the results, like `--format`.
+    pub format: Option<String>,
+    /// Maximum number of files read at the same time.
+    pub concurrency: Option<usize>,
+    /// Rules used to split the lines into words, like `--tokenizer`.
+    pub tokenizer: Option<String>,
+    /// Patterns of the files to skip, e.g. `*.gz`.
+    pub excludes: Vec<String>,
+    /// Level of the logs, or filter like `RUST_LOG` which overrides it.
+    pub log_level: Option<String>,
+    /// Bearer token of `--post-to`, kept out of the command line.
+    pub post_token: Option<String>,
+}
+
+/// Prefix of the environment variables of the configuration, followed by the name of an option
+/// in upper case, e.g. `FILE_PROCESSOR_LOG_LEVEL`. The excludes are separated by commas.
+pub const ENV_PREFIX: &str = "FILE_PROCESSOR_";
+
+impl Config {
+    /// Configuration of the environment of the process, falling back on the file of `path` or
+    /// the default one.
+    pub fn load_all(path: Option<&str>) -> Result<Self, String> {
+        let vars = env::vars_os().filter_map(|(name, value)| {
+            Some((name.into_string().ok()?, value.into_string().ok()?))
+        });
+        Ok(Self::from_env(vars)?.or(Self::load(path)?))
+    }
+
+    /// Configuration of the `FILE_PROCESSOR_*` variables among `vars`.
+    pub fn from_env(vars: impl IntoIterator<Item = (String, String)>) -> Result<Self, String> {
+        let mut config = Self::default();
+        for (name, value) in vars {
+            let Some(option) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            match option {
+                "FORMAT" => config.format = Some(value),
+                "CONCURRENCY" => {
+                    let concurrency = value.parse().map_err(|e| format!("invalid {name}, {e}"))?;
+                    config.concurrency = Some(concurrency);
+                }
+                "TOKENIZER" => config.tokenizer = Some(value),
+                "EXCLUDES" => {
+                    let excludes = value.split(',').filter(|exclude| !exclude.is_empty());
+                    config.excludes = excludes.map(String::from).collect();
+                }
+                "LOG_LEVEL" => config.log_level = Some(value),
+                "POST_TOKEN" => config.post_token = Some(value),
+                _ => return Err(format!("unknown configuration variable {name}")),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Options of `self`, falling back on the ones of `other`. The excludes of both apply.
+    fn or(mut self, other: Self) -> Self {
+        self.excludes.extend(other.excludes);
+        Self {
+            format: self.format.or(other.format),
+            concurrency: self.concurrency.or(other.concurrency),
+            tokenizer: self.tokenizer.or(other.tokenizer),
+            excludes: self.excludes,
+            log_level: self.log_level.or(other.log_level),
+            post_token: self.post_token.or(other.post_token),
+        }
+    }
+
+    /// Read the configuration file of `path`, or the default one if it exists.
+    pub fn load(path: Option<&str>) -> Result<Self, String> {
+        let (path, required) = match path {
+            Some(path) => (PathBuf::from(path), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+        match fs::read_to_string(&path) {
+            Ok(config) => Self::parse(&config).map_err(|e| format!("{}: {e}", path.display())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !required => Ok(Self::default()),
+            Err(e) => Err(format!("Could not read {}, {e}", path.display())),
+        }
+    }
+
+    fn parse(config: &str) -> Result<Self, String> {
+        toml::from_str(config).map_err(|e| e.message().to_string())
+    }
+
+    /// Set the arguments which were not given on the command line. The format applies only
+    /// without a known extension of `--output` either.
+    pub fn apply(self, args: &mut Args) -> Result<(), String> {
+        if let Some(format) = &self.format {
+            let format = Format::from_name(format)?;
+            let extension = args.output.as_deref().and_then(Format::from_extension);
+            if args.format.is_none() && extension.is_none() {
+                args.format = Some(format);
+            }
+        }
+        if let (None, Some(tokenizer)) = (args.tokenizer, &self.tokenizer) {
+            args.tokenizer = Some(parse_tokenizer(tokenizer)?);
+        }
+        match self.concurrency {
+            Some(0) => return Err("the concurrency must be at least 1".to_string()),
+            Some(concurrency) => args.concurrency = args.concurrency.or(Some(concurrency)),
+            None => {}
+        }
+        for exclude in &self.excludes {
+            args.excludes.push(parse_pattern(exclude)?);
+        }
+        args.log_level = self.log_level;
+        args.post_token = args.post_token.take().or(self.post_token);
+        Ok(())
+    }
+}
+
+/// `file-processor/config.toml` in `$XDG_CONFIG_HOME`, or in `~/.config` without it.
+fn default_path() -> Option<PathBuf> {
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| Some(Path::new(&env::var_os("HOME")?).join(".config")))?;
+    Some(config.join("file-processor").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use glob::Pattern;
+    use string_stream_processor::Tokenizer;
+
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        let config = Config::parse(
+            r#"
+            format = "csv"
+            concurrency = 4
+            tokenizer = "ascii"
+            excludes = ["*.gz"]
+            "#,
+        )
+        .unwrap();
+        let mut args = Args::try_parse_from(["fpc", "--format=yaml"]).unwrap();
+        config.apply(&mut args).unwrap();
+        assert_eq!(args.format, Some(Format::from_name("yaml").unwrap()));
+        assert_eq!(args.tokenizer, Some(Tokenizer::Ascii));
+        assert_eq!(args.concurrency, Some(4));
+        assert_eq!(args.excludes, [Pattern::new("*.gz").unwrap()]);
+        let mut args = Args::try_parse_from(["fpc", "-j2"]).unwrap();
+        Config::parse("concurrency = 4")
+            .unwrap()
+            .apply(&mut args)
+            .unwrap();
+        assert_eq!(args.concurrency, Some(2));
+
+        // The extension of the output file takes precedence over the configuration.
+        let config = || Config::parse(r#"format = "csv""#).unwrap();
+        for (output, format) in [("out.yaml", None), ("out.txt", Some("csv"))] {
+            let mut args = Args::try_parse_from(["fpc", "-o", output]).unwrap();
+            config().apply(&mut args).unwrap();
+            let format = format.map(|name| Format::from_name(name).unwrap());
+            assert_eq!(args.format, format, "{output}");
+        }
+
+        assert!(Config::parse("jobs = 4").is_err());
+        let mut args = Args::default();
+        let config = Config::parse(r#"format = "xml""#).unwrap();
+        assert!(config.apply(&mut args).is_err());
+        assert!(Config::load(Some("fpc_test_missing.toml")).is_err());
+    }
+
+    #[test]
+    fn test_config_from_env() {
+        let vars = [
+            ("FILE_PROCESSOR_FORMAT", "csv"),
+            ("FILE_PROCESSOR_CONCURRENCY", "2"),
+            ("FILE_PROCESSOR_EXCLUDES", "*.1,*.gz"),
+            ("FILE_PROCESSOR_LOG_LEVEL", "debug"),
+            ("FILE_PROCESSOR_POST_TOKEN", "secret"),
+            ("HOME", "/root"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+        let file = Config::parse("format = \"yaml\"\ntokenizer = \"ascii\"\nexcludes = [\"*.2\"]");
+        let config = Config::from_env(vars).unwrap().or(file.unwrap());
+        assert_eq!(
+            config,
+            Config {
+                format: Some("csv".into()),
+                concurrency: Some(2),
+                tokenizer: Some("ascii".into()),
+                excludes: vec!["*.1".into(), "*.gz".into(), "*.2".into()],
+                log_level: Some("debug".into()),
+                post_token: Some("secret".into()),
+            }
+        );
+        let vars = [("FILE_PROCESSOR_CONCURRENCY".into(), "many".into())];
+        assert!(Config::from_env(vars).is_err());
+        let vars = [("FILE_PROCESSOR_JOBS".into(), "2".into())];
+        assert!(Config::from_env(vars).is_err());
+    }
+}
@@ -0,0 +1,192 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    io,
+    sync::Mutex,
+};
+
+use string_stream_processor::{ProcessorOptions, SourceProvider};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+use crate::tally::Tallied;
+
+/// Number of bits of the hashes of the lines selecting a register of the [`HyperLogLog`], for a
+/// relative error of about 0.8% in 16 KiB.
+const PRECISION: u32 = 14;
+
+/// Numbers of lines of a source and of different lines among them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DedupCount {
+    pub lines: u64,
+    pub distinct: u64,
+}
+
+impl DedupCount {
+    /// Number of lines repeating a previous one.
+    pub fn duplicates(&self) -> u64 {
+        self.lines - self.distinct
+    }
+
+    /// Share of the lines repeating a previous one, zero without lines.
+    pub fn duplicate_ratio(&self) -> f64 {
+        match self.lines {
+            0 => 0.0,
+            lines => self.duplicates() as f64 / lines as f64,
+        }
+    }
+}
+
+/// Estimate of the number of different hashes, in constant memory.
+#[derive(Debug, Clone)]
+pub struct HyperLogLog {
+    /// Maximum rank of the first set bit of the hashes of each register.
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    fn new() -> Self {
+        Self {
+            registers: vec![0; 1 << PRECISION],
+        }
+    }
+
+    fn insert(&mut self, hash: u64) {
+        let register = (hash >> (64 - PRECISION)) as usize;
+        let rank = ((hash << PRECISION) | (1 << (PRECISION - 1))).leading_zeros() + 1;
+        self.registers[register] = self.registers[register].max(rank as u8);
+    }
+
+    fn estimate(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&rank| 2f64.powi(-i32::from(rank)))
+            .sum();
+        let estimate = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&rank| rank == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            // Linear counting, more accurate for the small cardinalities.
+            m * (m / zeros as f64).ln()
+        } else {
+            estimate
+        }
+    }
+}
+
+/// Hashes seen so far, kept or estimated.
+pub enum Seen {
+    Exact(HashSet<u64>),
+    Approximate(HyperLogLog),
+}
+
+impl Seen {
+    /// Hashes estimated in constant memory with `approximate`, kept otherwise.
+    pub fn new(approximate: bool) -> Self {
+        match approximate {
+            true => Self::Approximate(HyperLogLog::new()),
+            false => Self::Exact(HashSet::new()),
+        }
+    }
+
+    pub fn insert(&mut self, value: &(impl Hash + ?Sized)) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+        match self {
+            Self::Exact(hashes) => {
+                hashes.insert(hash);
+            }
+            Self::Approximate(hyper_log_log) => hyper_log_log.insert(hash),
+        }
+    }
+
+    /// Number of different hashes seen, at most `max`.
+    pub fn distinct(&self, max: u64) -> u64 {
+        match self {
+            Self::Exact(hashes) => hashes.len() as u64,
+            Self::Approximate(hyper_log_log) => (hyper_log_log.estimate().round() as u64).min(max),
+        }
+    }
+}
+
+/// Count the lines of each source and the different ones, reading at most
+/// `options.max_concurrency` sources at a time. The lines are compared by their 64-bit hash,
+/// without their line ending. With `approximate`, the different lines are estimated in constant
+/// memory by source instead of keeping a hash by different line. See [`Tallied::count_each`]
+/// for the errors.
+pub async fn count_distinct_lines<P: SourceProvider>(
+    provider: &Tallied<P>,
+    options: ProcessorOptions,
+    approximate: bool,
+) -> HashMap<&str, DedupCount> {
+    let merged = Mutex::new(HashMap::new());
+    let count = |_, rd| count_source_distinct_lines(rd, approximate);
+    provider
+        .count_each(options.max_concurrency, count, |id, count| {
+            merged.lock().unwrap().insert(id, count);
+        })
+        .await;
+    merged.into_inner().unwrap()
+}
+
+/// Count the lines of a source and the different ones.
+async fn count_source_distinct_lines(
+    mut rd: impl AsyncBufRead + Unpin,
+    approximate: bool,
+) -> io::Result<DedupCount> {
+    let mut seen = Seen::new(approximate);
+    let mut lines = 0;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if rd.read_until(b'\n', &mut line).await? == 0 {
+            break;
+        }
+        lines += 1;
+        let line = line.strip_suffix(b"\n").unwrap_or(&line);
+        seen.insert(line.strip_suffix(b"\r").unwrap_or(line));
+    }
+    Ok(DedupCount {
+        lines,
+        distinct: seen.distinct(lines),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_count_source_distinct_lines() {
+        let source = b"a\nb\r\na\nb\n\nc";
+        for approximate in [false, true] {
+            let count = count_source_distinct_lines(&source[..], approximate)
+                .await
+                .unwrap();
+            assert_eq!(
+                count,
+                DedupCount {
+                    lines: 6,
+                    distinct: 4
+                }
+            );
+            assert_eq!(count.duplicates(), 2);
+        }
+        let count = count_source_distinct_lines(&b""[..], false).await.unwrap();
+        assert_eq!(count.duplicate_ratio(), 0.0);
+    }
+
+    #[test]
+    fn test_hyper_log_log() {
+        let mut hyper_log_log = HyperLogLog::new();
+        for i in 0..100_000u64 {
+            let mut hasher = DefaultHasher::new();
+            (i % 50_000).hash(&mut hasher);
+            hyper_log_log.insert(hasher.finish());
+        }
+        let error = (hyper_log_log.estimate() - 50_000.0).abs() / 50_000.0;
+        assert!(error < 0.03, "{error}");
+    }
+}
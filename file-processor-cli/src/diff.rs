@@ -0,0 +1,209 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+use serde::{Deserialize, Serialize};
+use string_stream_processor::deserialize_line_words;
+
+use crate::{
+    color::Palette,
+    output::{write_aligned, SCHEMA_VERSION},
+};
+
+/// Envelope of a JSON document of the `words` results, the other fields are ignored.
+#[derive(Debug, Deserialize)]
+struct Document {
+    schema_version: u32,
+    /// Counts of each source, with its metadata or not.
+    #[serde(deserialize_with = "deserialize_line_words")]
+    results: BTreeMap<String, Vec<usize>>,
+}
+
+/// How a source differs between two documents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Removed => "removed",
+            Self::Changed => "changed",
+        }
+    }
+}
+
+/// Change of the counts of a source between two documents. The lines between the longest common
+/// prefix and suffix of the counts are removed from the old ones and added to the new ones.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Change {
+    pub id: String,
+    pub status: Status,
+    pub lines_added: usize,
+    pub lines_removed: usize,
+    /// Number of words of the new counts minus the one of the old counts.
+    pub words_delta: i64,
+}
+
+/// Load the results of a JSON document of the `words` command, with its metadata or not, for
+/// the `diff` command and `--merge-with`.
+pub fn load(path: &str) -> Result<BTreeMap<String, Vec<usize>>, String> {
+    let invalid = |e: &dyn std::fmt::Display| format!("Could not read {path}, {e}");
+    let data = std::fs::read(path).map_err(|e| invalid(&e))?;
+    let document: Document = serde_json::from_slice(&data).map_err(|e| invalid(&e))?;
+    if document.schema_version != SCHEMA_VERSION {
+        let version = document.schema_version;
+        return Err(invalid(&format!("unsupported schema version {version}")));
+    }
+    Ok(document.results)
+}
+
+/// Changes of the sources from the `old` results to the `new` ones, by identifier. The
+/// sources with the same counts are left out.
+pub fn diff(old: &BTreeMap<String, Vec<usize>>, new: &BTreeMap<String, Vec<usize>>) -> Vec<Change> {
+    let mut ids: Vec<_> = old.keys().chain(new.keys()).collect();
+    ids.sort_unstable();
+    ids.dedup();
+    ids.into_iter()
+        .filter_map(|id| {
+            let (status, old, new) = match (old.get(id), new.get(id)) {
+                (Some(old), Some(new)) if old == new => return None,
+                (Some(old), Some(new)) => (Status::Changed, &old[..], &new[..]),
+                (Some(old), None) => (Status::Removed, &old[..], &[][..]),
+                (None, Some(new)) => (Status::Added, &[][..], &new[..]),
+                (None, None) => unreachable!("the identifier comes from the results"),
+            };
+            let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+            let suffix = old[prefix..]
+                .iter()
+                .rev()
+                .zip(new[prefix..].iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count();
+            let words = |counts: &[usize]| counts.iter().sum::<usize>() as i64;
+            Some(Change {
+                id: id.clone(),
+                status,
+                lines_added: new.len() - prefix - suffix,
+                lines_removed: old.len() - prefix - suffix,
+                words_delta: words(new) - words(old),
+            })
+        })
+        .collect()
+}
+
+/// Write the changes as a table, or as a versioned JSON document.
+pub fn write(
+    mut writer: impl Write,
+    changes: &[Change],
+    json: bool,
+    palette: Palette,
+) -> io::Result<()> {
+    if json {
+        let document = serde_json::json!({
+            "schema_version": SCHEMA_VERSION,
+            "changes": changes,
+        });
+        serde_json::to_writer_pretty(&mut writer, &document)?;
+        writeln!(writer)?;
+        return writer.flush();
+    }
+    const HEADER: [&str; 5] = ["IDENTIFIER", "STATUS", "+LINES", "-LINES", "WORDS"];
+    let rows: Vec<Vec<String>> = changes
+        .iter()
+        .map(|change| {
+            vec![
+                change.id.clone(),
+                change.status.name().to_string(),
+                change.lines_added.to_string(),
+                change.lines_removed.to_string(),
+                format!("{:+}", change.words_delta),
+            ]
+        })
+        .collect();
+    write_aligned(&mut writer, &HEADER, &rows, palette, |row, column| {
+        column == 1 && changes[row].status == Status::Removed
+    })?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let old = BTreeMap::from([
+            ("a.txt".to_string(), vec![1, 2, 3, 4]),
+            ("b.txt".to_string(), vec![5]),
+            ("c.txt".to_string(), vec![6]),
+        ]);
+        let new = BTreeMap::from([
+            ("a.txt".to_string(), vec![1, 7, 8, 3, 4, 2]),
+            ("c.txt".to_string(), vec![6]),
+            ("d.txt".to_string(), vec![0, 1]),
+        ]);
+        let changes = diff(&old, &new);
+        assert_eq!(
+            changes,
+            [
+                Change {
+                    id: "a.txt".to_string(),
+                    status: Status::Changed,
+                    lines_added: 5,
+                    lines_removed: 3,
+                    words_delta: 15,
+                },
+                Change {
+                    id: "b.txt".to_string(),
+                    status: Status::Removed,
+                    lines_added: 0,
+                    lines_removed: 1,
+                    words_delta: -5,
+                },
+                Change {
+                    id: "d.txt".to_string(),
+                    status: Status::Added,
+                    lines_added: 2,
+                    lines_removed: 0,
+                    words_delta: 1,
+                },
+            ]
+        );
+        let mut table = Vec::new();
+        write(&mut table, &changes[1..], false, Palette::new(false)).unwrap();
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            "IDENTIFIER   STATUS  +LINES  -LINES  WORDS\n\
+             b.txt       removed       0       1     -5\n\
+             d.txt         added       2       0     +1\n"
+        );
+    }
+
+    #[test]
+    fn test_load() {
+        let path = std::env::temp_dir().join("fpc_test_diff_load.json");
+        let document = serde_json::json!({
+            "schema_version": SCHEMA_VERSION,
+            "results": {"a.txt": [1, 2], "b.txt": {"counts": [3], "lines": 1}},
+            "errors": [],
+        });
+        std::fs::write(&path, document.to_string()).unwrap();
+        let path = path.to_str().unwrap();
+        assert_eq!(
+            load(path).unwrap(),
+            BTreeMap::from([
+                ("a.txt".to_string(), vec![1, 2]),
+                ("b.txt".to_string(), vec![3]),
+            ])
+        );
+        std::fs::write(path, r#"{"schema_version": 0, "results": {}}"#).unwrap();
+        assert!(load(path).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use string_stream_processor::LineStats;
+
+use crate::output::Sort;
+
+/// Numbers of files, lines and words of the sources under a directory, recursively.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirTotals {
+    pub files: u64,
+    pub lines: u64,
+    pub words: u64,
+}
+
+impl DirTotals {
+    fn add(&mut self, stats: &LineStats) {
+        self.files += 1;
+        self.lines += stats.lines;
+        self.words += stats.words;
+    }
+}
+
+/// Roll the counts of the sources up to their directory and its ancestors, up to the deepest
+/// directory containing all the sources. Returns the directories in depth-first order, the
+/// subdirectories of each one in the order of `sort`, with their depth below the common one.
+pub fn roll_up(stats: &HashMap<&str, LineStats>, sort: Sort) -> Vec<(PathBuf, usize, DirTotals)> {
+    let parents = stats
+        .keys()
+        .map(|id| Path::new(id).parent().unwrap_or(Path::new("")));
+    let Some(root) = parents.reduce(common_ancestor) else {
+        return Vec::new();
+    };
+    let mut dirs: BTreeMap<&Path, DirTotals> = BTreeMap::new();
+    for (id, stats) in stats {
+        let mut dir = Path::new(id).parent().unwrap_or(Path::new(""));
+        while dir.starts_with(root) {
+            dirs.entry(dir).or_default().add(stats);
+            match dir.parent() {
+                Some(parent) if dir != root => dir = parent,
+                _ => break,
+            }
+        }
+    }
+    let mut rows = Vec::with_capacity(dirs.len());
+    visit(&dirs, root, 0, sort, &mut rows);
+    rows
+}
+
+/// Deepest directory containing both directories.
+fn common_ancestor<'a>(a: &'a Path, b: &Path) -> &'a Path {
+    let mut ancestor = a;
+    while !b.starts_with(ancestor) {
+        ancestor = ancestor.parent().unwrap_or(Path::new(""));
+    }
+    ancestor
+}
+
+fn visit(
+    dirs: &BTreeMap<&Path, DirTotals>,
+    dir: &Path,
+    depth: usize,
+    sort: Sort,
+    rows: &mut Vec<(PathBuf, usize, DirTotals)>,
+) {
+    let totals = dirs[dir];
+    let name = match dir.as_os_str().is_empty() {
+        true => PathBuf::from("."),
+        false => dir.to_path_buf(),
+    };
+    rows.push((name, depth, totals));
+    let children: Vec<_> = dirs
+        .iter()
+        .filter(|(child, _)| child.parent() == Some(dir) && **child != dir)
+        .map(|(child, totals)| {
+            (
+                child.to_str().unwrap_or_default(),
+                totals.words,
+                totals.lines,
+            )
+        })
+        .collect();
+    for child in sort.order(children) {
+        visit(dirs, Path::new(child), depth + 1, sort, rows);
+    }
+}
+
+/// Label of a directory in the tree drawn in the tables: its name indented by its depth.
+pub fn tree_label(dir: &str, depth: usize) -> String {
+    let name = match depth {
+        0 => dir,
+        _ => Path::new(dir)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(dir),
+    };
+    format!("{}{name}", "  ".repeat(depth))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::SortKey;
+
+    #[test]
+    fn test_roll_up() {
+        let stats = HashMap::from([
+            ("docs/a.md", [2, 3].into_iter().collect()),
+            ("docs/api/b.md", [4].into_iter().collect()),
+            ("docs/api/v1/c.md", [1].into_iter().collect()),
+            ("docs/guide/d.md", [5, 5].into_iter().collect()),
+        ]);
+        let rows: Vec<_> = roll_up(&stats, Sort::default())
+            .into_iter()
+            .map(|(dir, depth, totals)| {
+                let dir = dir.to_str().unwrap().to_string();
+                (dir, depth, totals.files, totals.lines, totals.words)
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("docs".to_string(), 0, 4, 6, 20),
+                ("docs/api".to_string(), 1, 2, 2, 5),
+                ("docs/api/v1".to_string(), 2, 1, 1, 1),
+                ("docs/guide".to_string(), 1, 1, 2, 10),
+            ]
+        );
+        let sort = Sort {
+            key: SortKey::Words,
+            reverse: true,
+        };
+        let rows: Vec<_> = roll_up(&stats, sort)
+            .into_iter()
+            .map(|(dir, ..)| dir)
+            .collect();
+        assert_eq!(
+            rows,
+            ["docs", "docs/guide", "docs/api", "docs/api/v1"].map(PathBuf::from)
+        );
+
+        let stats = HashMap::from([("a.txt", [1].into_iter().collect())]);
+        let rows = roll_up(&stats, Sort::default());
+        assert_eq!(rows[0].0, PathBuf::from("."));
+        assert!(roll_up(&HashMap::new(), Sort::default()).is_empty());
+        assert_eq!(tree_label("docs/api/v1", 2), "    v1");
+        assert_eq!(tree_label("docs", 0), "docs");
+    }
+}
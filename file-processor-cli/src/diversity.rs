@@ -0,0 +1,97 @@
+use std::{collections::HashMap, io, sync::Mutex};
+
+use string_stream_processor::{ProcessorOptions, SourceProvider, Tokenizer};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+use crate::{dedup::Seen, tally::Tallied};
+
+/// Numbers of words of a source and of different words among them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Diversity {
+    pub lines: u64,
+    pub words: u64,
+    pub distinct: u64,
+}
+
+impl Diversity {
+    /// Type-token ratio, the share of different words among the words, zero without words.
+    pub fn ratio(&self) -> f64 {
+        match self.words {
+            0 => 0.0,
+            words => self.distinct as f64 / words as f64,
+        }
+    }
+}
+
+/// Count the words of each source and the different ones, split by the tokenizer of the
+/// options, reading at most `options.max_concurrency` sources at a time. The words are compared
+/// by their 64-bit hash, whatever their case. With `approximate`, the different words are
+/// estimated in constant memory by source. See [`Tallied::count_each`] for the errors.
+pub async fn count_diversity<P: SourceProvider>(
+    provider: &Tallied<P>,
+    options: ProcessorOptions,
+    approximate: bool,
+) -> HashMap<&str, Diversity> {
+    let merged = Mutex::new(HashMap::new());
+    let count = |_, rd| count_source_diversity(rd, options.tokenizer, approximate);
+    provider
+        .count_each(options.max_concurrency, count, |id, diversity| {
+            merged.lock().unwrap().insert(id, diversity);
+        })
+        .await;
+    merged.into_inner().unwrap()
+}
+
+/// Count the words of a source and the different ones, an invalid UTF-8 line is an error.
+async fn count_source_diversity(
+    mut rd: impl AsyncBufRead + Unpin,
+    tokenizer: Tokenizer,
+    approximate: bool,
+) -> io::Result<Diversity> {
+    let mut seen = Seen::new(approximate);
+    let mut diversity = Diversity::default();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if rd.read_until(b'\n', &mut line).await? == 0 {
+            break;
+        }
+        let line = std::str::from_utf8(&line)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        diversity.lines += 1;
+        for word in tokenizer.split_words(line) {
+            diversity.words += 1;
+            seen.insert(&word.to_lowercase());
+        }
+    }
+    diversity.distinct = seen.distinct(diversity.words);
+    Ok(diversity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_count_source_diversity() {
+        let source = b"The cat and the dog\n\nTHE end";
+        for approximate in [false, true] {
+            let diversity = count_source_diversity(&source[..], Tokenizer::Unicode, approximate)
+                .await
+                .unwrap();
+            assert_eq!(
+                diversity,
+                Diversity {
+                    lines: 3,
+                    words: 7,
+                    distinct: 5,
+                }
+            );
+            assert!((diversity.ratio() - 5.0 / 7.0).abs() < f64::EPSILON);
+        }
+        let diversity = count_source_diversity(&b"\n"[..], Tokenizer::Ascii, false)
+            .await
+            .unwrap();
+        assert_eq!(diversity.ratio(), 0.0);
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    sync::OnceLock,
+};
+
+use crate::{args::Args, output::csv_field, report::Report, tally::Tally, template::Template};
+
+/// Format of the results written source by source, e.g. the CSV rows, without the core
+/// serialization of the documents and the tables. The registered ones are selected by name
+/// with `--format`, see [`Formatters`].
+pub trait Formatter: Send {
+    /// Write what precedes the sources, e.g. a header.
+    fn start(&mut self, _writer: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Write the counts of a source, with its tally with `--with-meta`.
+    fn write_source(
+        &mut self,
+        writer: &mut dyn Write,
+        id: &str,
+        counts: &[usize],
+        tally: Option<&Tally>,
+    ) -> io::Result<()>;
+
+    /// Write the report of a command other than `words`, unsupported by default.
+    fn write_report(&mut self, _writer: &mut dyn Write, _report: &Report) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the format only writes the results of the words command",
+        ))
+    }
+
+    /// Write what follows the sources, e.g. a footer.
+    fn finish(&mut self, _writer: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Format registered in [`Formatters`].
+#[derive(Debug, Clone, Copy)]
+pub struct Registration {
+    /// Media type of the results, for the `Content-Type` of `--post-to`.
+    pub content_type: &'static str,
+    /// Extensions of the output paths selecting the format, without the dot.
+    pub extensions: &'static [&'static str],
+    /// Create the formatter of a run.
+    pub new: fn(&Args) -> Box<dyn Formatter>,
+}
+
+/// Registry of the formats selectable by name, in addition to the built-in documents, NDJSON,
+/// Arrow, Parquet and tables. The formats of the CLI are the ones installed before parsing the
+/// arguments, see [`Formatters::install`].
+#[derive(Debug, Clone)]
+pub struct Formatters {
+    formats: BTreeMap<&'static str, Registration>,
+}
+
+static FORMATTERS: OnceLock<Formatters> = OnceLock::new();
+
+impl Formatters {
+    /// Formats of the CLI, the default ones if none were installed before.
+    pub fn global() -> &'static Self {
+        FORMATTERS.get_or_init(Formatters::default)
+    }
+
+    /// Make these formats the ones of the CLI. It fails, giving them back, once the formats of
+    /// the CLI are installed or used, e.g. to parse the arguments.
+    pub fn install(self) -> Result<(), Self> {
+        FORMATTERS.set(self)
+    }
+
+    /// Register a format, replacing the one with the same name.
+    pub fn register(&mut self, name: &'static str, registration: Registration) -> &mut Self {
+        self.formats.insert(name, registration);
+        self
+    }
+
+    /// Name and registration of a format.
+    pub fn get(&self, name: &str) -> Option<(&'static str, &Registration)> {
+        self.formats.get_key_value(name).map(|(name, r)| (*name, r))
+    }
+
+    /// Name of the format selected by the extension of an output path.
+    pub fn by_extension(&self, extension: &str) -> Option<&'static str> {
+        self.formats
+            .iter()
+            .find(|(_, registration)| registration.extensions.contains(&extension))
+            .map(|(name, _)| *name)
+    }
+}
+
+impl Default for Formatters {
+    /// The built-in formats selectable by name.
+    fn default() -> Self {
+        let mut formatters = Self {
+            formats: BTreeMap::new(),
+        };
+        formatters.register(
+            "csv",
+            Registration {
+                content_type: "text/csv",
+                extensions: &["csv"],
+                new: |args| Box::new(Csv(Report::columns(args.command))),
+            },
+        );
+        formatters
+    }
+}
+
+/// Rows of the identifier, the line number and the count of each line, with the columns of
+/// the command as header, or the rows of the reports.
+struct Csv(&'static [&'static str]);
+
+impl Formatter for Csv {
+    fn start(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        writeln!(writer, "{}", self.0.join(","))
+    }
+
+    fn write_source(
+        &mut self,
+        writer: &mut dyn Write,
+        id: &str,
+        counts: &[usize],
+        _tally: Option<&Tally>,
+    ) -> io::Result<()> {
+        let id = csv_field(id);
+        for (line, count) in counts.iter().enumerate() {
+            writeln!(writer, "{id},{},{count}", line + 1)?;
+        }
+        Ok(())
+    }
+
+    fn write_report(&mut self, writer: &mut dyn Write, report: &Report) -> io::Result<()> {
+        for (key, values) in &report.rows {
+            write!(writer, "{}", csv_field(key))?;
+            for value in values {
+                write!(writer, ",{}", csv_field(&value.to_string()))?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl Formatter for Template {
+    fn write_source(
+        &mut self,
+        mut writer: &mut dyn Write,
+        id: &str,
+        counts: &[usize],
+        _tally: Option<&Tally>,
+    ) -> io::Result<()> {
+        self.write(&mut writer, id, counts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts of each source on a single line, e.g. `a.txt: 2 1`.
+    struct Compact;
+
+    impl Formatter for Compact {
+        fn write_source(
+            &mut self,
+            writer: &mut dyn Write,
+            id: &str,
+            counts: &[usize],
+            _tally: Option<&Tally>,
+        ) -> io::Result<()> {
+            let counts: Vec<_> = counts.iter().map(ToString::to_string).collect();
+            writeln!(writer, "{id}: {}", counts.join(" "))
+        }
+    }
+
+    #[test]
+    fn test_formatters() {
+        let mut formatters = Formatters::default();
+        formatters.register(
+            "compact",
+            Registration {
+                content_type: "text/plain",
+                extensions: &["cpt"],
+                new: |_| Box::new(Compact),
+            },
+        );
+        assert_eq!(formatters.by_extension("cpt"), Some("compact"));
+        assert_eq!(formatters.by_extension("txt"), None);
+        assert!(formatters.get("xml").is_none());
+
+        let (name, registration) = formatters.get("compact").unwrap();
+        assert_eq!(name, "compact");
+        let mut formatter = (registration.new)(&Args::default());
+        let mut output = Vec::new();
+        formatter.start(&mut output).unwrap();
+        formatter
+            .write_source(&mut output, "a.txt", &[2, 1], None)
+            .unwrap();
+        formatter.finish(&mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "a.txt: 2 1\n");
+
+        let (_, csv) = Formatters::global().get("csv").unwrap();
+        assert!(Formatters::default().install().is_err());
+        let mut formatter = (csv.new)(&Args::default());
+        let mut output = Vec::new();
+        formatter.start(&mut output).unwrap();
+        formatter
+            .write_source(&mut output, "a,b.txt", &[3], None)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "identifier,line_number,word_count\n\"a,b.txt\",1,3\n"
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    sync::Mutex,
+};
+
+use string_stream_processor::{ProcessorOptions, SourceProvider, Tokenizer};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+use crate::{summary::Totals, tally::Tallied};
+
+/// Number of occurrences of each word.
+pub type Frequencies = HashMap<String, u64>;
+
+/// Most frequent words of a source, with their number of occurrences.
+pub type TopWords = Vec<(String, u64)>;
+
+/// Words left out of the frequencies, still counted in the totals.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WordFilter {
+    /// Minimum number of characters of the words.
+    pub min_length: usize,
+    /// Lowercase words left out whatever their case.
+    pub stop_words: HashSet<String>,
+}
+
+impl WordFilter {
+    /// Filter of the words shorter than `min_length` characters and of the stop words, read
+    /// from `path` too if given, one per line. The blank lines and the ones starting with `#`
+    /// are ignored.
+    pub fn new(
+        min_length: usize,
+        stop_words: &[String],
+        path: Option<&str>,
+    ) -> Result<Self, String> {
+        let mut filter = Self {
+            min_length,
+            stop_words: stop_words.iter().map(|word| word.to_lowercase()).collect(),
+        };
+        if let Some(path) = path {
+            let words = std::fs::read_to_string(path)
+                .map_err(|e| format!("Could not read the stop words of {path}, {e}"))?;
+            filter.stop_words.extend(
+                words
+                    .lines()
+                    .map(str::trim)
+                    .filter(|word| !word.is_empty() && !word.starts_with('#'))
+                    .map(str::to_lowercase),
+            );
+        }
+        Ok(filter)
+    }
+
+    fn keeps(&self, word: &str) -> bool {
+        (self.min_length <= 1 || word.chars().count() >= self.min_length)
+            && (self.stop_words.is_empty() || !self.stop_words.contains(&word.to_lowercase()))
+    }
+}
+
+/// Count the occurrences of the words of the sources kept by the filter, split by the tokenizer
+/// of the options, reading at most `options.max_concurrency` sources at a time. The totals are
+/// the ones of the lines read, see [`Tallied::count_each`] for the errors.
+pub async fn count_frequencies<P: SourceProvider>(
+    provider: &Tallied<P>,
+    options: ProcessorOptions,
+    filter: &WordFilter,
+) -> (Frequencies, Totals) {
+    count_merged(provider, options, filter, |_, _, _| {}).await
+}
+
+/// Count the `top` most frequent words of each source, with their totals, and the occurrences
+/// of the words of all the sources like [`count_frequencies`].
+pub async fn count_top_words<P: SourceProvider>(
+    provider: &Tallied<P>,
+    options: ProcessorOptions,
+    filter: &WordFilter,
+    top: usize,
+) -> (HashMap<String, (TopWords, Totals)>, Frequencies, Totals) {
+    let sources = Mutex::new(HashMap::new());
+    let (frequencies, totals) =
+        count_merged(provider, options, filter, |id, frequencies, totals| {
+            let top_words = most_frequent(frequencies, Some(top));
+            sources
+                .lock()
+                .unwrap()
+                .insert(id.to_string(), (top_words, totals));
+        })
+        .await;
+    (sources.into_inner().unwrap(), frequencies, totals)
+}
+
+/// Merge the frequencies of the sources, passed to `each` with their totals once merged.
+async fn count_merged<P: SourceProvider>(
+    provider: &Tallied<P>,
+    options: ProcessorOptions,
+    filter: &WordFilter,
+    each: impl Fn(&str, Frequencies, Totals),
+) -> (Frequencies, Totals) {
+    let merged = Mutex::new((Frequencies::new(), Totals::default()));
+    let count = |_, rd| count_source_frequencies(rd, options.tokenizer, filter);
+    provider
+        .count_each(
+            options.max_concurrency,
+            count,
+            |id, (frequencies, totals)| {
+                {
+                    let mut merged = merged.lock().unwrap();
+                    for (word, &count) in &frequencies {
+                        match merged.0.get_mut(word) {
+                            Some(merged) => *merged += count,
+                            None => {
+                                merged.0.insert(word.clone(), count);
+                            }
+                        }
+                    }
+                    merged.1.lines += totals.lines;
+                    merged.1.words += totals.words;
+                }
+                each(id, frequencies, totals);
+            },
+        )
+        .await;
+    merged.into_inner().unwrap()
+}
+
+/// The `top` most frequent words, all of them without `top`, the ties ordered alphabetically.
+pub fn most_frequent(frequencies: Frequencies, top: Option<usize>) -> TopWords {
+    let order = |(a, a_count): &(String, u64), (b, b_count): &(String, u64)| {
+        b_count.cmp(a_count).then_with(|| a.cmp(b))
+    };
+    let mut words: TopWords = frequencies.into_iter().collect();
+    if let Some(top) = top.filter(|top| *top < words.len()) {
+        words.select_nth_unstable_by(top, order);
+        words.truncate(top);
+    }
+    words.sort_unstable_by(order);
+    words
+}
+
+/// Count the occurrences of the words of a source, an invalid UTF-8 line is an error.
+async fn count_source_frequencies(
+    mut rd: impl AsyncBufRead + Unpin,
+    tokenizer: Tokenizer,
+    filter: &WordFilter,
+) -> io::Result<(Frequencies, Totals)> {
+    let mut frequencies = Frequencies::new();
+    let mut totals = Totals::default();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if rd.read_until(b'\n', &mut line).await? == 0 {
+            break;
+        }
+        let line = std::str::from_utf8(&line)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        totals.lines += 1;
+        for word in tokenizer.split_words(line) {
+            totals.words += 1;
+            if !filter.keeps(word) {
+                continue;
+            }
+            match frequencies.get_mut(word) {
+                Some(count) => *count += 1,
+                None => {
+                    frequencies.insert(word.to_string(), 1);
+                }
+            }
+        }
+    }
+    Ok((frequencies, totals))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_count_source_frequencies() {
+        let keep = WordFilter::default();
+        let (frequencies, totals) =
+            count_source_frequencies(&b"a b\n\nb  c b\n"[..], Tokenizer::Unicode, &keep)
+                .await
+                .unwrap();
+        assert_eq!(
+            frequencies,
+            Frequencies::from([("a".into(), 1), ("b".into(), 3), ("c".into(), 1)])
+        );
+        assert_eq!(totals, Totals { lines: 3, words: 5 });
+
+        let invalid = count_source_frequencies(&b"a\n\xff\nb\n"[..], Tokenizer::Ascii, &keep).await;
+        assert_eq!(invalid.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let filter = WordFilter::new(3, &["The".into()], None).unwrap();
+        let (frequencies, totals) =
+            count_source_frequencies(&b"the cat THE ox\nowl\n"[..], Tokenizer::Unicode, &filter)
+                .await
+                .unwrap();
+        assert_eq!(
+            frequencies,
+            Frequencies::from([("cat".into(), 1), ("owl".into(), 1)])
+        );
+        assert_eq!(totals, Totals { lines: 2, words: 5 });
+    }
+
+    #[test]
+    fn test_stop_words_file() {
+        let path = std::env::temp_dir().join("fpc_test_stop_words.txt");
+        std::fs::write(&path, "# English\nA\n\n an \n").unwrap();
+        let filter = WordFilter::new(0, &["the".into()], path.to_str()).unwrap();
+        assert_eq!(
+            filter.stop_words,
+            HashSet::from(["the".into(), "a".into(), "an".into()])
+        );
+        assert!(WordFilter::new(0, &[], Some("fpc_test_missing.txt")).is_err());
+    }
+
+    #[test]
+    fn test_most_frequent() {
+        let frequencies = Frequencies::from([
+            ("a".into(), 1),
+            ("b".into(), 3),
+            ("c".into(), 1),
+            ("d".into(), 2),
+        ]);
+        assert_eq!(
+            most_frequent(frequencies.clone(), Some(3)),
+            [("b".into(), 3), ("d".into(), 2), ("a".into(), 1)]
+        );
+        assert_eq!(most_frequent(frequencies.clone(), None).len(), 4);
+        assert_eq!(most_frequent(frequencies, Some(10)).len(), 4);
+    }
+}
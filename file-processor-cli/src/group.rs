@@ -0,0 +1,83 @@
+use std::{collections::HashMap, path::Path};
+
+use regex::Regex;
+use string_stream_processor::group_line_words;
+
+/// Group of the sources which have no key, e.g. the files without extension.
+pub const UNGROUPED: &str = "(none)";
+
+/// Key grouping the results of the sources by their identifier, from `--group-by`.
+#[derive(Debug, Clone)]
+pub enum GroupBy {
+    /// The extension of the file, without the dot.
+    Extension,
+    /// The parent directory of the file, `.` for the current one.
+    Directory,
+    /// The first capture group of a regular expression, or the whole match without groups.
+    Pattern(Regex),
+}
+
+impl GroupBy {
+    /// Parse `ext`, `dir` or `regex:PATTERN`.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        if let Some(pattern) = name.strip_prefix("regex:") {
+            return Regex::new(pattern)
+                .map(Self::Pattern)
+                .map_err(|e| format!("invalid pattern {pattern}, {e}"));
+        }
+        match name {
+            "ext" => Ok(Self::Extension),
+            "dir" => Ok(Self::Directory),
+            _ => Err(format!("unknown grouping {name}")),
+        }
+    }
+
+    /// Group of a source, [`UNGROUPED`] if it has no key.
+    pub fn key<'a>(&self, id: &'a str) -> &'a str {
+        let key = match self {
+            Self::Extension => Path::new(id).extension().and_then(|ext| ext.to_str()),
+            Self::Directory => match Path::new(id).parent().and_then(Path::to_str) {
+                Some("") => Some("."),
+                parent => parent,
+            },
+            Self::Pattern(pattern) => pattern.captures(id).and_then(|captures| {
+                let capture = captures.get(1).or_else(|| captures.get(0))?;
+                Some(capture.as_str())
+            }),
+        };
+        key.unwrap_or(UNGROUPED)
+    }
+
+    /// Counts of the lines of the sources of each group, one after the other in the order of
+    /// their identifiers.
+    pub fn group<'a>(&self, results: HashMap<&'a str, Vec<usize>>) -> HashMap<&'a str, Vec<usize>> {
+        group_line_words(results, |id| self.key(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_by() {
+        let ext = GroupBy::parse("ext").unwrap();
+        assert_eq!(ext.key("docs/a.md"), "md");
+        assert_eq!(ext.key("Makefile"), UNGROUPED);
+        let dir = GroupBy::parse("dir").unwrap();
+        assert_eq!(dir.key("docs/api/a.md"), "docs/api");
+        assert_eq!(dir.key("a.md"), ".");
+        let pattern = GroupBy::parse(r"regex:^logs/(\w+)-").unwrap();
+        assert_eq!(pattern.key("logs/web-1.log"), "web");
+        assert_eq!(pattern.key("a.log"), UNGROUPED);
+        assert_eq!(GroupBy::parse(r"regex:\d+").unwrap().key("a-12.log"), "12");
+        assert!(GroupBy::parse("regex:(").is_err());
+        assert!(GroupBy::parse("size").is_err());
+
+        let results = HashMap::from([("b.md", vec![3]), ("a.md", vec![1, 2]), ("c.txt", vec![4])]);
+        let groups = ext.group(results);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups["md"], [1, 2, 3]);
+        assert_eq!(groups["txt"], [4]);
+    }
+}
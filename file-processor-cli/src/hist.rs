@@ -0,0 +1,215 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    pin::pin,
+};
+
+use futures_util::{FutureExt, StreamExt};
+use string_stream_processor::{count_line_words_with, LineStats, ProcessorOptions, SourceProvider};
+
+use crate::{
+    color::Palette,
+    report::{Report, Value},
+};
+
+/// Number of buckets of the histograms without a bucket width.
+const BUCKETS: usize = 10;
+
+/// Width of the bar of the most frequent bucket of a source in the tables.
+const BAR_WIDTH: usize = 40;
+
+/// Number of lines of a source by number of words.
+pub type LineHistogram = Vec<u64>;
+
+/// Count the lines of each source by number of words, reading at most
+/// `options.max_concurrency` sources at a time.
+pub async fn count_histograms<P: SourceProvider>(
+    provider: &P,
+    options: ProcessorOptions,
+) -> HashMap<&str, LineHistogram> {
+    let histograms = provider
+        .sources()
+        .map(|(id, rd)| {
+            count_line_words_with(rd, options)
+                .fold(LineHistogram::new(), |mut histogram, words| async move {
+                    if histogram.len() <= words {
+                        histogram.resize(words + 1, 0);
+                    }
+                    histogram[words] += 1;
+                    histogram
+                })
+                .map(move |histogram| (id, histogram))
+        })
+        .buffer_unordered(options.max_concurrency.unwrap_or(usize::MAX));
+    pin!(histograms).collect().await
+}
+
+/// Width of the buckets of the histograms, `width` or the one of about [`BUCKETS`] buckets up
+/// to the longest line of all the sources.
+pub fn bucket_width<'a>(
+    histograms: impl IntoIterator<Item = &'a LineHistogram>,
+    width: Option<usize>,
+) -> usize {
+    width.unwrap_or_else(|| {
+        let max = histograms.into_iter().map(Vec::len).max().unwrap_or(0);
+        max.div_ceil(BUCKETS).max(1)
+    })
+}
+
+/// Labels and numbers of lines of the buckets of `width` words from zero up to the longest line
+/// of a histogram, the empty buckets included.
+pub fn buckets(histogram: &LineHistogram, width: usize) -> Vec<(String, u64)> {
+    histogram
+        .chunks(width)
+        .enumerate()
+        .map(|(i, lines)| {
+            let label = match width {
+                1 => i.to_string(),
+                _ => format!("{}-{}", i * width, (i + 1) * width - 1),
+            };
+            (label, lines.iter().sum())
+        })
+        .collect()
+}
+
+/// Sum of histograms.
+pub fn merge<'a>(histograms: impl IntoIterator<Item = &'a LineHistogram>) -> LineHistogram {
+    let mut merged = LineHistogram::new();
+    for histogram in histograms {
+        if merged.len() < histogram.len() {
+            merged.resize(histogram.len(), 0);
+        }
+        for (merged, lines) in merged.iter_mut().zip(histogram) {
+            *merged += lines;
+        }
+    }
+    merged
+}
+
+/// Statistics of the numbers of words of the lines of a histogram.
+pub fn line_stats(histogram: &LineHistogram) -> LineStats {
+    histogram
+        .iter()
+        .enumerate()
+        .flat_map(|(words, &lines)| std::iter::repeat_n(words, lines as usize))
+        .collect()
+}
+
+/// Nearest-rank percentile of the numbers of words of the lines of a histogram, zero without
+/// any.
+pub fn percentile(histogram: &LineHistogram, p: f64) -> usize {
+    let lines: u64 = histogram.iter().sum();
+    let rank = ((p * lines as f64).ceil() as u64).max(1);
+    let mut seen = 0;
+    histogram
+        .iter()
+        .position(|&count| {
+            seen += count;
+            seen >= rank
+        })
+        .unwrap_or(0)
+}
+
+/// Write the histogram report as an ASCII histogram for each source, the bars relative to the
+/// bucket of the source with the most lines.
+pub fn write_bars(writer: &mut impl Write, report: &Report, palette: Palette) -> io::Result<()> {
+    let label_width = report
+        .rows
+        .iter()
+        .map(|(_, values)| values[0].to_string().len())
+        .max()
+        .unwrap_or(0);
+    let groups = report.rows.chunk_by(|(a, _), (b, _)| a == b);
+    for (i, rows) in groups.enumerate() {
+        if i > 0 {
+            writeln!(writer)?;
+        }
+        writeln!(writer, "{}", palette.bold(&rows[0].0))?;
+        let lines = |values: &[Value]| match values[1] {
+            Value::Count(lines) => lines,
+            _ => 0,
+        };
+        let max = rows
+            .iter()
+            .map(|(_, values)| lines(values))
+            .max()
+            .unwrap_or(0);
+        let count_width = max.to_string().len();
+        for (_, values) in rows {
+            let lines = lines(values);
+            let bar = (lines * BAR_WIDTH as u64).div_ceil(max.max(1)) as usize;
+            let row = format!(
+                "{:>label_width$}  {lines:>count_width$}  {}",
+                values[0],
+                "#".repeat(bar)
+            );
+            writeln!(writer, "{}", row.trim_end())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{inputs::Inputs, output::Sort, summary::Totals};
+
+    #[test]
+    fn test_buckets() {
+        let histogram = vec![1, 0, 2, 3, 0, 1];
+        assert_eq!(bucket_width([&histogram], None), 1);
+        assert_eq!(bucket_width([&histogram], Some(4)), 4);
+        assert_eq!(bucket_width([&vec![0; 95]], None), 10);
+        assert_eq!(bucket_width([], None), 1);
+        assert_eq!(
+            buckets(&histogram, 4),
+            [("0-3".to_string(), 6), ("4-7".to_string(), 1)]
+        );
+        assert_eq!(buckets(&histogram, 1)[3], ("3".to_string(), 3));
+    }
+
+    #[test]
+    fn test_percentile() {
+        // 100 lines of 1 to 100 words.
+        let histogram: LineHistogram = (0..=100).map(|words| u64::from(words > 0)).collect();
+        assert_eq!(percentile(&histogram, 0.5), 50);
+        assert_eq!(percentile(&histogram, 0.95), 95);
+        assert_eq!(percentile(&histogram, 0.99), 99);
+        assert_eq!(percentile(&vec![0, 0, 3], 0.01), 2);
+        assert_eq!(percentile(&vec![], 0.5), 0);
+        assert_eq!(merge([&vec![1, 2], &vec![0, 1, 5]]), [1, 3, 5]);
+        let stats = line_stats(&vec![1, 0, 2]);
+        assert_eq!(
+            (stats.lines, stats.words, stats.min, stats.max),
+            (3, 4, 0, 2)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_histograms() {
+        let path = std::env::temp_dir().join("fpc_test_hist.txt");
+        std::fs::write(&path, "a b\n\nc d\ne\n").unwrap();
+        let files = vec![path.to_str().unwrap().to_string()];
+        let options = ProcessorOptions::default();
+        let inputs = Inputs::new(files.clone(), None, options);
+        let histograms = count_histograms(&inputs, options).await;
+        assert_eq!(
+            histograms,
+            HashMap::from([(files[0].as_str(), vec![1, 1, 2])])
+        );
+
+        let report = Report::hist(&histograms, Some(2), Sort::default());
+        assert_eq!(report.totals, Totals { lines: 4, words: 5 });
+        let mut table = Vec::new();
+        write_bars(&mut table, &report, Palette::new(false)).unwrap();
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            format!(
+                "{}\n0-1  2  {}\n2-3  2  {}\n",
+                files[0],
+                "#".repeat(BAR_WIDTH),
+                "#".repeat(BAR_WIDTH)
+            )
+        );
+    }
+}
@@ -0,0 +1,428 @@
+use std::{
+    io::{self, Read, Write},
+    path::Path,
+    pin::Pin,
+    str::Utf8Error,
+    sync::OnceLock,
+};
+
+use futures_util::{stream, Stream, StreamExt};
+use string_stream_processor::{
+    Decompress, FileProvider, ProcessorOptions, RateLimit, RecordSeparator, Records,
+    SharedRateLimit, SourceProvider, Throttle,
+};
+use tokio::{io::AsyncBufRead, sync::Notify};
+
+use crate::{
+    remote::RemoteSources,
+    run::SourceError,
+    walk::{dedupe_files, skip_large_files, walk_dir, WalkOptions},
+};
+
+/// Identifier of the standard input in the results.
+pub const STDIN: &str = "stdin";
+
+/// Sources given on the command line: files, URLs and S3 URIs routed by their scheme, see
+/// [`Location`](crate::remote::Location), and the standard input for `-` or when no file is
+/// given. The glob patterns are expanded, see [`expand_globs`], and the directories walked
+/// with [`Inputs::walk_dirs`].
+#[derive(Debug)]
+pub struct Inputs {
+    files: FileProvider,
+    remote: RemoteSources,
+    stdin: bool,
+    stdin_error: OnceLock<SourceError>,
+    /// Errors of the directories which could not be walked and of the files too large.
+    discovery_errors: Vec<SourceError>,
+    /// Notified when a source cannot be opened, see [`Inputs::failure`].
+    failures: Notify,
+    /// Separator of the records read as lines, see [`Records`].
+    records: Option<RecordSeparator>,
+    /// Rate limit of each source, see [`Throttle`].
+    rate_limit: Option<RateLimit>,
+    /// Rate limit of all the sources together.
+    throughput: Option<SharedRateLimit>,
+    options: ProcessorOptions,
+}
+
+impl Inputs {
+    /// Sources of the files, followed by the paths of a manifest as is, see [`read_manifest`].
+    /// With a manifest, the standard input is only read for `-`.
+    pub fn new(
+        files: Vec<String>,
+        manifest: Option<Vec<String>>,
+        options: ProcessorOptions,
+    ) -> Self {
+        let stdin =
+            (files.is_empty() && manifest.is_none()) || files.iter().any(|file| file == "-");
+        let mut remote = RemoteSources::new(options);
+        let files = files.into_iter().chain(manifest.into_iter().flatten());
+        let mut files: Vec<_> = files.filter_map(|file| remote.push(file)).collect();
+        files = expand_globs(files);
+        files.retain(|file| file != "-");
+        Self {
+            files: FileProvider::new(files, options),
+            remote,
+            stdin,
+            stdin_error: OnceLock::new(),
+            discovery_errors: Vec::new(),
+            failures: Notify::new(),
+            records: None,
+            rate_limit: None,
+            throughput: None,
+            options,
+        }
+    }
+
+    /// Number of sources, the standard input, the directories which could not be walked and the
+    /// files too large included.
+    pub fn len(&self) -> usize {
+        let files = self.files.paths().len() + self.remote.len();
+        files + usize::from(self.stdin) + self.discovery_errors.len()
+    }
+
+    /// Replace the directories among the files by the regular files they contain, recursively,
+    /// see [`walk_dir`], skip the excluded, duplicated and too large files, and move the priority
+    /// files first. The standard input is kept, even if all the files are skipped. The objects
+    /// under the S3 URIs are listed.
+    pub async fn walk_dirs(mut self, options: &WalkOptions) -> Self {
+        let errors = self.remote.list_buckets().await;
+        self.discovery_errors.extend(errors);
+        let mut paths = Vec::new();
+        for path in self.files.paths() {
+            if options.is_excluded(Path::new(path)) {
+                continue;
+            }
+            if tokio::fs::metadata(path).await.is_ok_and(|m| m.is_dir()) {
+                let (files, errors) = walk_dir(path, options).await;
+                paths.extend(files);
+                self.discovery_errors.extend(errors);
+            } else {
+                paths.push(path.clone());
+            }
+        }
+        if !options.keep_duplicates {
+            paths = dedupe_files(paths).await;
+        }
+        if let Some(max_size) = options.max_file_size {
+            let (files, errors) = skip_large_files(paths, max_size).await;
+            paths = files;
+            self.discovery_errors.extend(errors);
+        }
+        if !options.priorities.is_empty() {
+            paths.sort_by_key(|path| options.priority(Path::new(path)));
+        }
+        self.files = FileProvider::new(paths, self.options);
+        self
+    }
+
+    /// Paths of the local files, once the directories are walked.
+    pub fn paths(&self) -> &[String] {
+        self.files.paths()
+    }
+
+    /// Identifiers of the sources, the standard input, the files and the remote ones.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        let stdin = self.stdin.then_some(STDIN);
+        stdin
+            .into_iter()
+            .chain(self.files.paths().iter().map(String::as_str))
+            .chain(self.remote.ids())
+    }
+
+    /// Skip the files for which `skipped` is true.
+    pub fn skip(mut self, skipped: impl Fn(&str) -> bool) -> Self {
+        let mut paths = self.files.paths().to_vec();
+        paths.retain(|path| !skipped(path));
+        self.files = FileProvider::new(paths, self.options);
+        self.remote.skip(skipped);
+        self
+    }
+
+    /// Keep reading the named pipes among the files, see [`FileProvider::with_keep_open`].
+    pub fn with_keep_open(mut self, keep_open: bool) -> Self {
+        self.files = self.files.with_keep_open(keep_open);
+        self
+    }
+
+    /// Read the records of the sources ended by the separator as lines.
+    pub fn with_records(mut self, records: Option<RecordSeparator>) -> Self {
+        self.records = records;
+        self
+    }
+
+    /// Limit the rate at which each source is read.
+    pub fn with_rate_limit(mut self, rate_limit: Option<RateLimit>) -> Self {
+        self.rate_limit = rate_limit;
+        self
+    }
+
+    /// Limit the rate at which all the sources are read together.
+    pub fn with_throughput(mut self, throughput: Option<SharedRateLimit>) -> Self {
+        self.throughput = throughput;
+        self
+    }
+
+    /// Errors of the sources which could not be opened so far.
+    pub fn errors(&self) -> Vec<SourceError> {
+        let files = self.files.errors();
+        let files = files.iter().map(|(path, e)| SourceError::new(path, e));
+        self.stdin_error
+            .get()
+            .cloned()
+            .into_iter()
+            .chain(self.discovery_errors.iter().cloned())
+            .chain(files)
+            .chain(self.remote.errors())
+            .collect()
+    }
+
+    /// Write the size in bytes and the path of each file to process on a line, `-` as the size
+    /// of the standard input, of the remote sources and of the files whose size is unknown, then
+    /// the total size of the files.
+    pub async fn write_dry_run(&self, mut writer: impl Write) -> io::Result<()> {
+        if self.stdin {
+            writeln!(writer, "-\t{STDIN}")?;
+        }
+        let mut total = 0;
+        for path in self.files.paths() {
+            match tokio::fs::metadata(path).await {
+                Ok(metadata) => {
+                    total += metadata.len();
+                    writeln!(writer, "{}\t{path}", metadata.len())?;
+                }
+                Err(_) => writeln!(writer, "-\t{path}")?,
+            }
+        }
+        for id in self.remote.ids() {
+            writeln!(writer, "-\t{id}")?;
+        }
+        writeln!(writer, "{total}\ttotal")?;
+        writer.flush()
+    }
+
+    /// Wait for the first source which cannot be opened while reading the sources, the errors
+    /// of the directories which could not be walked and of the files too large first.
+    pub async fn failure(&self) -> SourceError {
+        loop {
+            if let Some(error) = self.errors().into_iter().next() {
+                return error;
+            }
+            self.failures.notified().await;
+        }
+    }
+
+    /// Number of sources which could not be opened so far.
+    fn open_errors(&self) -> usize {
+        let stdin = usize::from(self.stdin_error.get().is_some());
+        stdin + self.files.errors().len() + self.remote.errors().len()
+    }
+
+    async fn open_stdin(&self) -> Option<Decompress<impl AsyncBufRead + Unpin>> {
+        let rd = self.options.buf_reader(tokio::io::stdin());
+        Decompress::detect(STDIN, rd, self.options.read_buffer_size)
+            .await
+            .inspect_err(|e| log::warn!("Could not read {STDIN}, {e}, skipping it."))
+            .map_err(|e| self.stdin_error.set(SourceError::new(STDIN, &e)))
+            .ok()
+    }
+}
+
+/// Replace the glob patterns, like `logs/**/*.log`, by the paths they match in alphabetical
+/// order. A pattern which matches nothing, or is an existing path, is kept as is.
+pub fn expand_globs(files: Vec<String>) -> Vec<String> {
+    let mut expanded = Vec::with_capacity(files.len());
+    for file in files {
+        let is_pattern = file.contains(['*', '?', '[']) && !Path::new(&file).exists();
+        let paths = is_pattern.then(|| glob::glob(&file).ok()).flatten();
+        let matches: Vec<_> = paths
+            .into_iter()
+            .flatten()
+            .filter_map(|path| path.ok()?.into_os_string().into_string().ok())
+            .collect();
+        if matches.is_empty() {
+            expanded.push(file);
+        } else {
+            expanded.extend(matches);
+        }
+    }
+    expanded
+}
+
+/// Paths of a manifest file, or of the standard input for `-`: one per line, or separated by
+/// NUL bytes with `null`, for the paths with newlines. The empty paths are skipped.
+pub fn read_manifest(path: &str, null: bool) -> Result<Vec<String>, String> {
+    let manifest = if path == "-" {
+        let mut manifest = Vec::new();
+        std::io::stdin()
+            .lock()
+            .read_to_end(&mut manifest)
+            .map(|_| manifest)
+    } else {
+        std::fs::read(path)
+    };
+    let manifest = manifest.map_err(|e| format!("Could not read {path}, {e}"))?;
+    split_manifest(&manifest, null).map_err(|e| format!("Could not read {path}, {e}"))
+}
+
+fn split_manifest(manifest: &[u8], null: bool) -> Result<Vec<String>, Utf8Error> {
+    let delimiter = if null { b'\0' } else { b'\n' };
+    manifest
+        .split(|&byte| byte == delimiter)
+        .map(|path| match null {
+            true => path,
+            false => path.strip_suffix(b"\r").unwrap_or(path),
+        })
+        .filter(|path| !path.is_empty())
+        .map(|path| std::str::from_utf8(path).map(String::from))
+        .collect()
+}
+
+impl SourceProvider for Inputs {
+    type Reader = Pin<Box<dyn AsyncBufRead + Send>>;
+
+    fn sources(&self) -> impl Stream<Item = (&str, Self::Reader)> {
+        let stdin = stream::iter(self.stdin.then_some(())).filter_map(move |()| async move {
+            let rd: Self::Reader = Box::pin(self.open_stdin().await?);
+            Some((STDIN, rd))
+        });
+        let files = self.files.sources().map(|(path, rd)| {
+            let rd: Self::Reader = Box::pin(rd);
+            (path, rd)
+        });
+        let remote = self.remote.sources();
+        let mut sources = Box::pin(stdin.chain(files).chain(remote));
+        // The sources which cannot be opened are skipped while polling the next one.
+        let sources = stream::poll_fn(move |cx| {
+            let next = sources.as_mut().poll_next(cx);
+            if self.open_errors() > 0 {
+                self.failures.notify_one();
+            }
+            next
+        });
+        sources
+            .map(|(id, rd)| match &self.throughput {
+                Some(limit) => (id, Box::pin(Throttle::shared(rd, limit)) as _),
+                None => (id, rd),
+            })
+            .map(|(id, rd)| match self.rate_limit {
+                Some(limit) => (id, Box::pin(Throttle::new(rd, limit)) as _),
+                None => (id, rd),
+            })
+            .map(|(id, rd)| match &self.records {
+                Some(separator) => (id, Box::pin(Records::new(rd, separator.clone())) as _),
+                None => (id, rd),
+            })
+    }
+
+    fn lines_hint(&self, id: &str) -> usize {
+        self.files.lines_hint(id).max(self.remote.lines_hint(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_inputs() {
+        let options = ProcessorOptions::default();
+        let inputs = Inputs::new(vec!["a.txt".into(), "-".into()], None, options);
+        assert!(inputs.stdin);
+        assert_eq!(inputs.len(), 2);
+        assert_eq!(inputs.files.paths(), ["a.txt"]);
+        assert!(Inputs::new(Vec::new(), None, options).stdin);
+        assert!(!Inputs::new(vec!["a.txt".into()], None, options).stdin);
+        let manifest = Some(vec!["b.txt".into(), "*.txt".into()]);
+        let inputs = Inputs::new(vec!["a.txt".into()], manifest, options);
+        assert_eq!(inputs.files.paths(), ["a.txt", "b.txt", "*.txt"]);
+        assert!(!Inputs::new(Vec::new(), Some(Vec::new()), options).stdin);
+        let inputs = Inputs::new(vec!["a.txt".into(), "b.txt".into()], None, options);
+        assert_eq!(inputs.skip(|path| path == "a.txt").paths(), ["b.txt"]);
+        let walk = WalkOptions {
+            excludes: vec![glob::Pattern::new("*.gz").unwrap()],
+            ..WalkOptions::default()
+        };
+        let inputs = Inputs::new(vec!["a.txt".into(), "logs/b.gz".into()], None, options);
+        assert_eq!(inputs.walk_dirs(&walk).await.files.paths(), ["a.txt"]);
+        let inputs = Inputs::new(vec!["b.gz".into()], None, options)
+            .walk_dirs(&walk)
+            .await;
+        assert!(!inputs.stdin);
+        assert_eq!(inputs.len(), 0);
+        let walk = WalkOptions {
+            priorities: ["*.md", "small/*"]
+                .map(|p| glob::Pattern::new(p).unwrap())
+                .to_vec(),
+            keep_duplicates: true,
+            ..WalkOptions::default()
+        };
+        let files = ["a.txt", "small/b.txt", "c.txt", "docs/d.md"].map(String::from);
+        let inputs = Inputs::new(files.to_vec(), None, options);
+        assert_eq!(
+            inputs.walk_dirs(&walk).await.paths(),
+            ["docs/d.md", "small/b.txt", "a.txt", "c.txt"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failure() {
+        let options = ProcessorOptions::default();
+        let missing = "fpc_test_failure_missing.txt".to_string();
+        let inputs = Inputs::new(vec![missing.clone()], None, options);
+        assert_eq!(inputs.sources().count().await, 0);
+        assert_eq!(inputs.failure().await.id, missing);
+    }
+
+    #[tokio::test]
+    async fn test_write_dry_run() {
+        let path = std::env::temp_dir().join("fpc_test_write_dry_run.txt");
+        std::fs::write(&path, "a b\nc\n").unwrap();
+        let path = path.to_str().unwrap().to_string();
+        let files = vec!["-".into(), path.clone(), "fpc_test_missing.txt".into()];
+        let inputs = Inputs::new(files, None, ProcessorOptions::default());
+        let mut listing = Vec::new();
+        inputs.write_dry_run(&mut listing).await.unwrap();
+        assert_eq!(
+            String::from_utf8(listing).unwrap(),
+            format!("-\tstdin\n6\t{path}\n-\tfpc_test_missing.txt\n6\ttotal\n")
+        );
+    }
+
+    #[test]
+    fn test_read_manifest() {
+        let path = std::env::temp_dir().join("fpc_test_read_manifest.txt");
+        std::fs::write(&path, "a.txt\r\n\nlogs/b c.log\n").unwrap();
+        let manifest = read_manifest(path.to_str().unwrap(), false).unwrap();
+        assert_eq!(manifest, ["a.txt", "logs/b c.log"]);
+        assert!(read_manifest("fpc_test_missing.txt", false).is_err());
+        let manifest = split_manifest(b"a.txt\0logs/b\nc.log\0\0", true).unwrap();
+        assert_eq!(manifest, ["a.txt", "logs/b\nc.log"]);
+        assert!(split_manifest(b"a.txt\n\xff\n", false).is_err());
+    }
+
+    #[test]
+    fn test_expand_globs() {
+        let dir = std::env::temp_dir().join("fpc_test_expand_globs");
+        std::fs::create_dir_all(dir.join("logs/old")).unwrap();
+        for file in ["logs/b.log", "logs/a.log", "logs/old/c.log", "logs/d.txt"] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        let dir = dir.to_str().unwrap();
+        let files = vec![
+            format!("{dir}/logs/**/*.log"),
+            format!("{dir}/*.missing"),
+            "-".to_string(),
+        ];
+        assert_eq!(
+            expand_globs(files),
+            [
+                format!("{dir}/logs/a.log"),
+                format!("{dir}/logs/b.log"),
+                format!("{dir}/logs/old/c.log"),
+                format!("{dir}/*.missing"),
+                "-".to_string(),
+            ]
+        );
+    }
+}
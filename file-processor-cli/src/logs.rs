@@ -0,0 +1,105 @@
+use std::{
+    io::{self, Write},
+    sync::Mutex,
+};
+
+use clap::ValueEnum;
+use log::{LevelFilter, Record};
+
+use crate::args::Args;
+
+/// Format of the log messages, from `--log-format`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Lines of the level, the module and the message.
+    #[default]
+    Text,
+    /// A JSON object on each line, for the log pipelines.
+    Json,
+}
+
+/// Log messages held while the terminal user interface is shown, written once it is closed.
+static HELD: Mutex<Option<Vec<u8>>> = Mutex::new(None);
+
+/// Standard error of the logs, or the held messages while [`hold`] is in effect.
+struct Stderr;
+
+impl Write for Stderr {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match HELD.lock().unwrap().as_mut() {
+            Some(held) => held.write(buf),
+            None => io::stderr().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+/// Hold the log messages instead of writing them, until [`release`].
+pub fn hold() {
+    HELD.lock().unwrap().get_or_insert_with(Vec::new);
+}
+
+/// Write the held log messages, and the next ones as they come.
+pub fn release() {
+    if let Some(held) = HELD.lock().unwrap().take() {
+        let _ = io::stderr().write_all(&held);
+    }
+}
+
+/// Set up the logger of the arguments. `-q` and `-v` override the level of the configuration
+/// and of `RUST_LOG`, whose filters of modules still apply.
+pub fn init(args: &Args) {
+    let mut logger = env_logger::builder();
+    logger.filter_level(LevelFilter::Info);
+    if let Some(filters) = &args.log_level {
+        logger.parse_filters(filters);
+    }
+    logger.parse_default_env();
+    if let Some(level) = args.verbosity() {
+        logger.filter_level(level);
+    }
+    if args.log_format == LogFormat::Json {
+        logger.format(|buf, record| {
+            let timestamp = buf.timestamp().to_string();
+            writeln!(buf, "{}", json_line(&timestamp, record))
+        });
+    }
+    logger
+        .target(env_logger::Target::Pipe(Box::new(Stderr)))
+        .write_style(args.color.write_style())
+        .init();
+}
+
+/// JSON object of a log message.
+fn json_line(timestamp: &str, record: &Record) -> serde_json::Value {
+    serde_json::json!({
+        "timestamp": timestamp,
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "message": record.args().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_line() {
+        let line = json_line(
+            "2024-01-01T00:00:00Z",
+            &Record::builder()
+                .args(format_args!("Skipping {}, it was already walked.", "a"))
+                .level(log::Level::Warn)
+                .target("file_processor_cli::walk")
+                .build(),
+        );
+        assert_eq!(
+            line.to_string(),
+            r#"{"level":"WARN","message":"Skipping a, it was already walked.","target":"file_processor_cli::walk","timestamp":"2024-01-01T00:00:00Z"}"#
+        );
+    }
+}
@@ -1,25 +1,438 @@
-use futures_util::{stream, StreamExt};
-use string_stream_processor::StringMultiStreamExt;
-use tokio::{fs::File, io::BufReader};
-
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    env_logger::builder()
-        .filter_level(log::LevelFilter::Info)
-        .parse_default_env()
-        .init();
-
-    let files = std::env::args().skip(1).collect::<Vec<_>>();
-    let data_stream = stream::iter(files.iter()).filter_map(open_file);
-    let result = data_stream.count_line_words_concurrent().await;
-    serde_json::to_writer_pretty(std::io::stdout(), &result)?;
-    Ok(())
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::IsTerminal,
+    process::ExitCode,
+    sync::{atomic::Ordering, Arc},
+    time::Instant,
+};
+
+use args::{Args, Command};
+use cache::Cache;
+use checkpoint::{count_finished, Checkpoint};
+use color::Palette;
+use config::Config;
+use formatter::Formatters;
+use inputs::{read_manifest, Inputs};
+use metrics::{Metrics, SourceGauge};
+use output::{Document, Format, Output, Streamed};
+use report::Report;
+use run::{Run, SourceError};
+use string_stream_processor::{
+    merge_line_words, BudgetedCounts, MemoryBudget, MergeMode, SourceProvider, StringMultiStreamExt,
+};
+use summary::{Summary, Totals, INTERRUPTED};
+use tally::Tallied;
+use tokio::runtime;
+use tokio_util::{sync::CancellationToken, task::AbortOnDropHandle};
+use wc::Wc;
+
+mod args;
+mod bench;
+mod cache;
+mod checkpoint;
+mod classes;
+mod code;
+mod color;
+mod config;
+mod dedup;
+mod diff;
+mod dirs;
+mod diversity;
+mod formatter;
+mod freq;
+mod group;
+mod hist;
+mod inputs;
+mod logs;
+mod matches;
+mod metrics;
+mod output;
+mod progress;
+mod readability;
+mod remote;
+mod report;
+mod run;
+mod schema;
+mod serve;
+mod sink;
+mod summary;
+mod systemd;
+mod tally;
+mod template;
+mod tui;
+mod walk;
+mod watch;
+mod wc;
+
+fn main() -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    // The formats are selected by name while parsing the arguments, the ones added to the
+    // default formats are registered here.
+    let formatters = Formatters::default();
+    formatters
+        .install()
+        .expect("the formats should be installed before being used");
+    let mut args = Args::parse();
+    Config::load_all(args.config.as_deref())?.apply(&mut args)?;
+    logs::init(&args);
+    // The variables of the socket activation are removed before the threads of the runtime
+    // start.
+    let activated = match args.serve {
+        Some(_) => systemd::listener()?,
+        None => None,
+    };
+
+    let mut runtime = if args.current_thread {
+        runtime::Builder::new_current_thread()
+    } else {
+        runtime::Builder::new_multi_thread()
+    };
+    if let Some(threads) = args.worker_threads {
+        runtime.worker_threads(threads);
+    }
+    let runtime = runtime.enable_all().build()?;
+    if let Some([old, new]) = &args.diff {
+        let changes = diff::diff(&diff::load(old)?, &diff::load(new)?);
+        let json = table_or_json(&args, "diff")?;
+        let palette = Palette::new(args.color.enabled(true));
+        diff::write(std::io::stdout().lock(), &changes, json, palette)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(version) = args.schema {
+        let schema = schema::schema(args.format, version, args.per_line)?;
+        serde_json::to_writer_pretty(std::io::stdout().lock(), &schema)?;
+        println!();
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(iterations) = args.bench {
+        let json = table_or_json(&args, "bench")?;
+        let manifest = match &args.files_from {
+            Some(path) => Some(read_manifest(path, args.null)?),
+            None => None,
+        };
+        let bench = runtime.block_on(bench::run(
+            args.files.clone(),
+            manifest,
+            &args.walk_options(),
+            args.options(),
+            iterations,
+        ));
+        let palette = Palette::new(args.color.enabled(true));
+        bench.write(std::io::stdout().lock(), json, palette)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(serve) = &args.serve {
+        runtime.block_on(serve::serve(serve, args.options(), activated))?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.watch {
+        let metrics = match args.metrics_addr {
+            Some(addr) => {
+                let metrics = Arc::new(Metrics::default());
+                let listener = runtime.block_on(tokio::net::TcpListener::bind(addr))?;
+                runtime.spawn(metrics::serve(listener, Arc::clone(&metrics)));
+                Some(metrics)
+            }
+            None => None,
+        };
+        let process = || process(&args, Instant::now(), metrics.as_deref());
+        return runtime.block_on(watch::watch(&args, process));
+    }
+    runtime.block_on(process(&args, start, None))
 }
 
-async fn open_file(path: &String) -> Option<(&str, BufReader<File>)> {
-    let file = File::open(&path)
+/// Process the sources of the arguments and write their results, recorded in the metrics if
+/// any.
+async fn process(
+    args: &Args,
+    start: Instant,
+    metrics: Option<&Metrics>,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let options = args.options();
+    let walk = args.walk_options();
+    let manifest = match &args.files_from {
+        Some(path) => Some(read_manifest(path, args.null)?),
+        None => None,
+    };
+    if args.dry_run {
+        let mut inputs = Inputs::new(args.files.clone(), manifest, options)
+            .walk_dirs(&walk)
+            .await;
+        if let Some(path) = &args.checkpoint {
+            let checkpoint = Checkpoint::new(path, args.resume)?;
+            inputs = inputs.skip(|path| checkpoint.recorded().contains_key(path));
+        }
+        inputs.write_dry_run(std::io::stdout().lock()).await?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.wc {
+        let explicit_stdin = args.files.iter().any(|file| file == "-");
+        let inputs = Inputs::new(args.files.clone(), manifest, options)
+            .walk_dirs(&walk)
+            .await;
+        let wc = Wc::new(inputs, explicit_stdin);
+        wc.write(options, std::io::stdout().lock()).await?;
+        let sources = wc.sources();
+        let requested = sources.inner().len();
+        let summary = Summary::new(
+            requested,
+            &sources.tallies(),
+            Totals::default(),
+            start.elapsed(),
+        );
+        return Ok(summary.exit_code(args.strict).into());
+    }
+    if args.tui && !std::io::stderr().is_terminal() {
+        return Err("--tui needs a terminal on the standard error".into());
+    }
+    let filter = args.word_filter()?;
+    let output = Output::create(args)?;
+    let sort = args.sort();
+    let inputs = Inputs::new(args.files.clone(), manifest, options)
+        .walk_dirs(&walk)
         .await
-        .inspect_err(|e| log::warn!("Could not open {path}, {e}, skipping it."))
-        .ok()?;
-    Some((path, BufReader::new(file)))
+        .with_keep_open(args.keep_open)
+        .with_records(args.records())
+        .with_rate_limit(args.rate_limit())
+        .with_throughput(args.throughput_limit());
+    let mut checkpoint = match &args.checkpoint {
+        Some(path) => Some(Checkpoint::new(path, args.resume)?),
+        None => None,
+    };
+    let mut cache = match &args.cache {
+        Some(dir) => Some(Cache::open(dir, options)?),
+        None => None,
+    };
+    let earlier = match &args.merge_with {
+        Some(path) => diff::load(path)?,
+        None => BTreeMap::new(),
+    };
+    // Results of the previous runs, whose files are not read again.
+    let mut reused = BTreeMap::new();
+    if let Some(checkpoint) = &checkpoint {
+        reused.extend(checkpoint.recorded().clone());
+    }
+    if let Some(cache) = &mut cache {
+        let paths: Vec<_> = inputs
+            .paths()
+            .iter()
+            .filter(|path| !reused.contains_key(*path))
+            .cloned()
+            .collect();
+        reused.extend(cache.lookup(&paths).await);
+    }
+    let duplicates = match args.dedup_sources {
+        true => walk::identical_files(inputs.paths()).await,
+        false => BTreeMap::new(),
+    };
+    let code_stats = args.command == Command::CodeStats;
+    let inputs = inputs.skip(|path| {
+        reused.contains_key(path)
+            || duplicates.contains_key(path)
+            || (code_stats && code::Language::detect(path).is_none())
+    });
+    let provider = match args.tui {
+        true => Tallied::new(inputs).with_newlines(),
+        false => Tallied::new(inputs),
+    };
+    let provider = match args.checksum {
+        true => provider.with_checksums(),
+        false => provider,
+    };
+    let provider = provider
+        .with_duplicates(duplicates)
+        .with_max_lines(args.max_lines);
+    let _signals = AbortOnDropHandle::new(tokio::spawn(interrupt_on_signal(provider.interrupt())));
+    // The earlier results of the sources not read again are written as is, like the results
+    // of the previous runs.
+    let ids: HashSet<_> = provider.inner().ids().collect();
+    let merged = earlier
+        .keys()
+        .filter(|id| !ids.contains(id.as_str()) && !reused.contains_key(*id));
+    let run = Run {
+        sources: &provider,
+        sort,
+        with_meta: args.with_meta || args.checksum,
+        reused: merged.count() + reused.len(),
+        start,
+    };
+    let work = async {
+        if args.command.line_count().is_none() {
+            let pattern = args.pattern.as_ref();
+            let report =
+                Report::compute(args.command, &provider, options, sort, &filter, pattern).await;
+            return output.write_report(&report, &run);
+        }
+        if let Some(analyzer) = args.command.analyzer() {
+            let result = provider
+                .sources()
+                .analyze_lines_concurrent(options, analyzer)
+                .await;
+            return output.write(&finished(args, result, &earlier), &run);
+        }
+        if let Some(limit) = args.memory_budget {
+            let budget = MemoryBudget::new(limit, args.over_budget.action());
+            let result = provider
+                .sources()
+                .count_line_words_budgeted(options, &budget)
+                .await?;
+            log::debug!(
+                "Peak memory of the counts: {} bytes.",
+                budget.usage().peak()
+            );
+            return match result {
+                BudgetedCounts::Counts(result) => output.write(&result, &run),
+                BudgetedCounts::Spilled(result) => output.write_spilled(&result, &run),
+                BudgetedCounts::Stats(stats) => {
+                    let report = Report::lines(&stats, sort.unwrap_or_default());
+                    output.write_report(&report, &run)
+                }
+            };
+        }
+        if checkpoint.is_some() || cache.is_some() {
+            let mut result = count_finished(&provider, options, |id, counts| {
+                if let Some(cache) = &cache {
+                    cache.store(id, counts)?;
+                }
+                match &mut checkpoint {
+                    Some(checkpoint) => checkpoint.record(id, counts),
+                    None => Ok(()),
+                }
+            })
+            .await?;
+            if let Some(checkpoint) = &mut checkpoint {
+                checkpoint.save()?;
+            }
+            for (id, counts) in &reused {
+                result.entry(id).or_insert_with(|| counts.clone());
+            }
+            return output.write(&finished(args, result, &earlier), &run);
+        }
+        // The groups are only known once all the sources are read, and the earlier results are
+        // merged with all the new ones.
+        let streamed = match args.group_by.is_some() || !earlier.is_empty() {
+            true => Streamed::Pending(Box::new(output)),
+            false => output.stream(&run, options).await?,
+        };
+        match streamed {
+            Streamed::Written(totals) => Ok(totals),
+            Streamed::Pending(output) => {
+                let result = provider.count_line_words(options).await;
+                (*output).write(&finished(args, result, &earlier), &run)
+            }
+        }
+    };
+    let show_progress = !args.quiet && std::io::stderr().is_terminal();
+    let work = async {
+        match args.tui {
+            true => tui::track(&run, work).await?,
+            false => progress::track(&run, show_progress, work).await,
+        }
+    };
+    let totals = if args.fail_fast {
+        // The sources which could not be walked or opened abort the run like the read errors.
+        tokio::select! {
+            biased;
+            failure = provider.inner().failure() => return Err(aborted(&failure)),
+            failure = provider.failure() => return Err(aborted(&failure)),
+            totals = work => totals?,
+        }
+    } else {
+        work.await?
+    };
+    let summary = run.summary(totals);
+    if let Some(metrics) = metrics {
+        let errors = summary.failed + summary.skipped;
+        metrics.record_run(summary.processed, errors, summary.totals, summary.bytes);
+        for (id, tally) in provider.tallies().iter() {
+            let bytes = tally.bytes.load(Ordering::Relaxed) as f64;
+            let duration = tally.duration().as_secs_f64();
+            let gauges = [
+                (
+                    SourceGauge::Lines,
+                    tally.lines.load(Ordering::Relaxed) as f64,
+                ),
+                (
+                    SourceGauge::Words,
+                    tally.words.load(Ordering::Relaxed) as f64,
+                ),
+                (SourceGauge::Bytes, bytes),
+                (SourceGauge::Duration, duration),
+            ];
+            metrics.set_source(id, &gauges);
+        }
+    }
+    if args.summary {
+        eprintln!("{summary}");
+    }
+    if let Some(error) = run.errors().first().filter(|_| args.fail_fast) {
+        return Err(aborted(error));
+    }
+    if provider.is_interrupted() {
+        return Ok(INTERRUPTED.into());
+    }
+    Ok(summary.exit_code(args.strict).into())
+}
+
+/// Stop reading the sources on the first `SIGINT` or `SIGTERM`, for the partial results to be
+/// written, and exit at once on the second one.
+async fn interrupt_on_signal(interrupt: CancellationToken) {
+    for signals in 0.. {
+        if terminated().await.is_err() {
+            return;
+        }
+        if signals > 0 {
+            std::process::exit(INTERRUPTED.into());
+        }
+        log::warn!("Interrupted, writing the partial results.");
+        interrupt.cancel();
+    }
+}
+
+/// Wait for a `SIGINT`, or a `SIGTERM` on unix.
+async fn terminated() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result,
+            _ = terminate.recv() => Ok(()),
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await
+}
+
+/// Whether the report of a command is written as JSON rather than as a table, the only formats
+/// it supports.
+fn table_or_json(args: &Args, command: &str) -> Result<bool, String> {
+    match args.format {
+        None | Some(Format::Table) => Ok(false),
+        Some(Format::Document(Document::Json)) => Ok(true),
+        Some(_) => Err(format!("the {command} command only writes tables and JSON")),
+    }
+}
+
+/// Error of a run aborted by `--fail-fast`.
+fn aborted(error: &SourceError) -> Box<dyn std::error::Error> {
+    format!("Aborting on {}: {}.", error.id, error.message).into()
+}
+
+/// Results to write: of the groups of `--group-by` if any, or merged with the `earlier` ones
+/// of `--merge-with`.
+fn finished<'a>(
+    args: &Args,
+    results: HashMap<&'a str, Vec<usize>>,
+    earlier: &'a BTreeMap<String, Vec<usize>>,
+) -> HashMap<&'a str, Vec<usize>> {
+    let mut results = match &args.group_by {
+        Some(group_by) => group_by.group(results),
+        None => results,
+    };
+    let earlier = earlier
+        .iter()
+        .map(|(id, counts)| (id.as_str(), counts.clone()));
+    merge_line_words(&mut results, earlier, MergeMode::Replace);
+    results
 }
@@ -0,0 +1,83 @@
+use std::{collections::HashMap, io, sync::Mutex};
+
+use regex::bytes::Regex;
+use string_stream_processor::{ProcessorOptions, SourceProvider};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+use crate::tally::Tallied;
+
+/// Numbers of lines of a source matching a pattern, and of matches in them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchCount {
+    /// Number of lines read.
+    pub read: u64,
+    pub lines: u64,
+    pub matches: u64,
+}
+
+/// Count the lines of each source matching the pattern and their matches, reading at most
+/// `options.max_concurrency` sources at a time. The sources without a match are counted too,
+/// see [`Tallied::count_each`] for the errors.
+pub async fn count_matches<'a, P: SourceProvider>(
+    provider: &'a Tallied<P>,
+    options: ProcessorOptions,
+    pattern: &Regex,
+) -> HashMap<&'a str, MatchCount> {
+    let merged = Mutex::new(HashMap::new());
+    let count = |_, rd| count_source_matches(rd, pattern);
+    provider
+        .count_each(options.max_concurrency, count, |id, count| {
+            merged.lock().unwrap().insert(id, count);
+        })
+        .await;
+    merged.into_inner().unwrap()
+}
+
+/// Count the lines of a source matching the pattern, without their line ending, and their
+/// matches.
+async fn count_source_matches(
+    mut rd: impl AsyncBufRead + Unpin,
+    pattern: &Regex,
+) -> io::Result<MatchCount> {
+    let mut count = MatchCount::default();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if rd.read_until(b'\n', &mut line).await? == 0 {
+            break;
+        }
+        count.read += 1;
+        let line = line.strip_suffix(b"\n").unwrap_or(&line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let matches = pattern.find_iter(line).count() as u64;
+        if matches > 0 {
+            count.lines += 1;
+            count.matches += matches;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_count_source_matches() {
+        let pattern = Regex::new(r"\berror\b").unwrap();
+        let source = b"error: a, error: b\nok\r\nno errors\nerror\r\n\xff error";
+        assert_eq!(
+            count_source_matches(&source[..], &pattern).await.unwrap(),
+            MatchCount {
+                read: 5,
+                lines: 3,
+                matches: 4,
+            }
+        );
+        let pattern = Regex::new(r"^$").unwrap();
+        let count = count_source_matches(&b"a\n\r\n\nb\n"[..], &pattern)
+            .await
+            .unwrap();
+        assert_eq!((count.lines, count.matches), (2, 2));
+    }
+}
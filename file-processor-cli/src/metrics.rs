@@ -0,0 +1,229 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use axum::{extract::State, http::header, response::IntoResponse, routing::get, Router};
+use tokio::net::TcpListener;
+
+use crate::summary::Totals;
+
+/// Media type of the Prometheus text format.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Maximum number of sources with gauges, the ones set the longest ago are removed first, for
+/// the memory and the series to stay bounded when new sources keep being read, e.g. uploads.
+const MAX_SOURCES: usize = 1000;
+
+/// Value of a source set after each of its reads, see [`Metrics::set_source`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SourceGauge {
+    Lines,
+    Words,
+    Bytes,
+    /// Time to read the source, in seconds.
+    Duration,
+}
+
+impl SourceGauge {
+    const ALL: [Self; 4] = [Self::Lines, Self::Words, Self::Bytes, Self::Duration];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Lines => "fpc_source_lines",
+            Self::Words => "fpc_source_words",
+            Self::Bytes => "fpc_source_bytes",
+            Self::Duration => "fpc_source_duration_seconds",
+        }
+    }
+
+    fn help(self) -> &'static str {
+        match self {
+            Self::Lines => "Lines of the last read of the source.",
+            Self::Words => "Words of the last read of the source.",
+            Self::Bytes => "Bytes of the last read of the source.",
+            Self::Duration => "Duration of the last read of the source.",
+        }
+    }
+}
+
+/// Counters of the runs of a long-running instance, the ones of `--watch` or the requests of
+/// `serve`, and the gauges of the last read of the [`MAX_SOURCES`] sources read last, served in
+/// the Prometheus text format on `GET /metrics`.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    runs: AtomicU64,
+    sources: AtomicU64,
+    errors: AtomicU64,
+    lines: AtomicU64,
+    words: AtomicU64,
+    bytes: AtomicU64,
+    gauges: Mutex<SourceGauges>,
+}
+
+/// Gauges of the sources, with the order of their last update.
+#[derive(Debug, Default)]
+struct SourceGauges {
+    /// Update number and values of each source.
+    sources: BTreeMap<String, (u64, BTreeMap<SourceGauge, f64>)>,
+    /// Sources by update number.
+    updates: BTreeMap<u64, String>,
+    next_update: u64,
+}
+
+impl Metrics {
+    /// Count a run of `sources` read and `errors` which could not be, with the totals of their
+    /// results.
+    pub fn record_run(&self, sources: usize, errors: usize, totals: Totals, bytes: u64) {
+        self.runs.fetch_add(1, Ordering::Relaxed);
+        self.sources.fetch_add(sources as u64, Ordering::Relaxed);
+        self.errors.fetch_add(errors as u64, Ordering::Relaxed);
+        self.lines.fetch_add(totals.lines, Ordering::Relaxed);
+        self.words.fetch_add(totals.words, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Set the gauges of the last read of a source, removing the ones of the source updated the
+    /// longest ago beyond [`MAX_SOURCES`].
+    pub fn set_source(&self, id: &str, gauges: &[(SourceGauge, f64)]) {
+        let mut sources = self.gauges.lock().unwrap();
+        let sources = &mut *sources;
+        let update = sources.next_update;
+        sources.next_update += 1;
+        let (last_update, values) = sources.sources.entry(id.to_string()).or_default();
+        sources.updates.remove(last_update);
+        *last_update = update;
+        values.extend(gauges.iter().copied());
+        sources.updates.insert(update, id.to_string());
+        if sources.sources.len() > MAX_SOURCES {
+            if let Some((_, oldest)) = sources.updates.pop_first() {
+                sources.sources.remove(&oldest);
+            }
+        }
+    }
+
+    /// Set the lines and words gauges of a source from its counts.
+    pub fn set_counts(&self, id: &str, counts: &[usize], bytes: Option<u64>) {
+        let words = counts.iter().sum::<usize>();
+        let mut gauges = vec![
+            (SourceGauge::Lines, counts.len() as f64),
+            (SourceGauge::Words, words as f64),
+        ];
+        gauges.extend(bytes.map(|bytes| (SourceGauge::Bytes, bytes as f64)));
+        self.set_source(id, &gauges);
+    }
+
+    /// Metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        let counters = [
+            ("fpc_runs_total", "Runs of the sources.", &self.runs),
+            ("fpc_sources_total", "Sources read.", &self.sources),
+            (
+                "fpc_source_errors_total",
+                "Sources which could not be opened or read.",
+                &self.errors,
+            ),
+            ("fpc_lines_total", "Lines of the results.", &self.lines),
+            ("fpc_words_total", "Words of the results.", &self.words),
+            (
+                "fpc_bytes_total",
+                "Bytes read from the sources.",
+                &self.bytes,
+            ),
+        ];
+        for (name, help, value) in counters {
+            let value = value.load(Ordering::Relaxed);
+            let _ = writeln!(
+                text,
+                "# HELP {name} {help}\n# TYPE {name} counter\n{name} {value}"
+            );
+        }
+        let gauges = self.gauges.lock().unwrap();
+        for gauge in SourceGauge::ALL {
+            let name = gauge.name();
+            let mut values = gauges
+                .sources
+                .iter()
+                .filter_map(|(id, (_, values))| Some((id, values.get(&gauge)?)))
+                .peekable();
+            if values.peek().is_some() {
+                let _ = writeln!(text, "# HELP {name} {}\n# TYPE {name} gauge", gauge.help());
+            }
+            for (id, value) in values {
+                let _ = writeln!(text, "{name}{{source=\"{}\"}} {value}", escape_label(id));
+            }
+        }
+        text
+    }
+}
+
+/// Escape a label value of the text format.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+/// Route of `GET /metrics`.
+pub fn router(metrics: Arc<Metrics>) -> Router {
+    Router::new()
+        .route("/metrics", get(scrape))
+        .with_state(metrics)
+}
+
+async fn scrape(State(metrics): State<Arc<Metrics>>) -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, CONTENT_TYPE)], metrics.render())
+}
+
+/// Serve `GET /metrics` on the listener, for `--metrics-addr`.
+pub async fn serve(listener: TcpListener, metrics: Arc<Metrics>) -> io::Result<()> {
+    log::info!(
+        "Serving the metrics on http://{}/metrics.",
+        listener.local_addr()?
+    );
+    axum::serve(listener, router(metrics)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics() {
+        let metrics = Metrics::default();
+        let totals = Totals { lines: 3, words: 5 };
+        metrics.record_run(2, 1, totals, 20);
+        metrics.record_run(1, 0, totals, 10);
+        metrics.set_counts("a.txt", &[2, 1], Some(6));
+        metrics.set_source("b \"1\".txt", &[(SourceGauge::Duration, 0.5)]);
+        metrics.set_counts("a.txt", &[4], None);
+        let text = metrics.render();
+        assert!(text.contains("# TYPE fpc_runs_total counter\nfpc_runs_total 2\n"));
+        assert!(text.contains("fpc_sources_total 3\n"));
+        assert!(text.contains("fpc_source_errors_total 1\n"));
+        assert!(text.contains("fpc_words_total 10\n"));
+        assert!(text.contains("fpc_bytes_total 30\n"));
+        assert!(
+            text.contains("# TYPE fpc_source_lines gauge\nfpc_source_lines{source=\"a.txt\"} 1\n")
+        );
+        assert!(text.contains("fpc_source_bytes{source=\"a.txt\"} 6\n"));
+        assert!(text.contains("fpc_source_duration_seconds{source=\"b \\\"1\\\".txt\"} 0.5\n"));
+
+        // b.txt is the source updated the longest ago once a.txt is updated again.
+        for source in 1..MAX_SOURCES {
+            metrics.set_counts(&format!("{source}.txt"), &[1], None);
+        }
+        let text = metrics.render();
+        assert!(!text.contains("b \\\"1\\\".txt"));
+        assert!(text.contains("fpc_source_lines{source=\"a.txt\"} 1\n"));
+        assert!(text.contains("fpc_source_lines{source=\"999.txt\"} 1\n"));
+        assert_eq!(metrics.gauges.lock().unwrap().updates.len(), MAX_SOURCES);
+    }
+}
@@ -0,0 +1,1103 @@
+use std::{
+    borrow::Cow,
+    cell::Cell,
+    collections::HashMap,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+
+use clap::ValueEnum;
+use futures_util::{pin_mut, FutureExt, StreamExt};
+use serde::{
+    ser::{SerializeMap, SerializeSeq},
+    Serialize, Serializer,
+};
+use serde_json::json;
+use string_stream_processor::{
+    count_source_line_words, count_source_line_words_numbered, ArrowLineWordsWriter, LineStats,
+    ParquetLineWordsWriter, ProcessorOptions, RetryPolicy, SourceProvider, SpilledCounts,
+};
+
+use crate::{
+    args::{Args, Command},
+    color::Palette,
+    dirs,
+    formatter::{Formatter, Formatters},
+    hist,
+    report::{Layout, Report, Value},
+    run::Run,
+    sink::{AtomicFile, HttpPost, Sink},
+    summary::Totals,
+    tally::Tally,
+};
+
+/// Format of the results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Envelope of the map of the identifiers to their counts, serialized at once.
+    Document(Document),
+    /// One JSON object per source, or per line, written as soon as it is read.
+    Ndjson,
+    /// Format of the [`Formatters`] registry, e.g. `csv`.
+    Registered(&'static str),
+    /// Arrow IPC file of `id`, `line_no` and `word_count` rows.
+    Arrow,
+    /// Parquet file of `id`, `line_no` and `word_count` rows.
+    Parquet,
+    /// Aligned table of the statistics of each source, for terminals.
+    Table,
+}
+
+impl Format {
+    /// Format of a `--format` name.
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        if let Some(document) = Document::from_name(name) {
+            return Ok(Self::Document(document));
+        }
+        match name {
+            "ndjson" => Ok(Self::Ndjson),
+            "arrow" => Ok(Self::Arrow),
+            "parquet" => Ok(Self::Parquet),
+            "table" => Ok(Self::Table),
+            _ => match Formatters::global().get(name) {
+                Some((name, _)) => Ok(Self::Registered(name)),
+                None => Err(format!("unknown format {name}")),
+            },
+        }
+    }
+
+    /// Media type of the results, for the `Content-Type` of `--post-to`.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Document(Document::Json) => "application/json",
+            Self::Document(Document::Yaml) => "application/yaml",
+            Self::Document(Document::Toml) => "application/toml",
+            Self::Ndjson => "application/x-ndjson",
+            Self::Registered(name) => match Formatters::global().get(name) {
+                Some((_, registration)) => registration.content_type,
+                None => "application/octet-stream",
+            },
+            Self::Arrow => "application/vnd.apache.arrow.file",
+            Self::Parquet => "application/vnd.apache.parquet",
+            Self::Table => "text/plain",
+        }
+    }
+
+    /// Format of an output path, from its extension before a `.gz` one. JSON for the standard
+    /// output.
+    pub fn from_path(path: Option<&str>) -> Self {
+        path.and_then(Self::from_extension)
+            .unwrap_or(Self::Document(Document::Json))
+    }
+
+    /// Format of the extension of an output path before a `.gz` one, if it is known.
+    pub fn from_extension(path: &str) -> Option<Self> {
+        let path = path.strip_suffix(".gz").unwrap_or(path);
+        let path = path.strip_suffix(".zst").unwrap_or(path);
+        match Path::new(path).extension()?.to_str()? {
+            "json" => Some(Self::Document(Document::Json)),
+            "ndjson" | "jsonl" => Some(Self::Ndjson),
+            "arrow" => Some(Self::Arrow),
+            "parquet" => Some(Self::Parquet),
+            "yaml" | "yml" => Some(Self::Document(Document::Yaml)),
+            "toml" => Some(Self::Document(Document::Toml)),
+            extension => Formatters::global()
+                .by_extension(extension)
+                .map(Self::Registered),
+        }
+    }
+}
+
+/// Key of the order of the sources, from `--sort`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SortKey {
+    #[default]
+    Id,
+    /// Total number of words of the source.
+    Words,
+    /// Number of lines of the source.
+    Lines,
+}
+
+/// Order of the sources in the output, ascending unless reversed. The ties are ordered by
+/// identifier.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sort {
+    pub key: SortKey,
+    pub reverse: bool,
+}
+
+impl Sort {
+    /// Sort the identifiers of `(id, words, lines)` rows.
+    pub fn order(self, mut rows: Vec<(&str, u64, u64)>) -> Vec<&str> {
+        rows.sort_unstable_by(|(a, a_words, a_lines), (b, b_words, b_lines)| {
+            let order = match self.key {
+                SortKey::Id => a.cmp(b),
+                SortKey::Words => a_words.cmp(b_words).then_with(|| a.cmp(b)),
+                SortKey::Lines => a_lines.cmp(b_lines).then_with(|| a.cmp(b)),
+            };
+            if self.reverse {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+        rows.into_iter().map(|(id, ..)| id).collect()
+    }
+}
+
+/// Serializer of the complete results. A new document format only needs a variant, its name
+/// and its serializer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Document {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl Document {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Self::Json),
+            "yaml" => Some(Self::Yaml),
+            "toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+
+    fn serialize(self, writer: &mut impl Write, results: &impl Serialize) -> io::Result<()> {
+        match self {
+            Self::Json => serde_json::to_writer_pretty(writer, results)?,
+            Self::Yaml => serde_yaml_ng::to_writer(writer, results).map_err(io::Error::other)?,
+            Self::Toml => {
+                let results = toml::to_string_pretty(results).map_err(io::Error::other)?;
+                writer.write_all(results.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+type Writer = Box<dyn Sink>;
+
+/// Tallies of the sources, by identifier.
+pub type Tallies = HashMap<String, Arc<Tally>>;
+
+/// Outcome of [`Output::stream`].
+pub enum Streamed {
+    /// The results were written, with these totals.
+    Written(Totals),
+    /// The output needs the complete results.
+    Pending(Box<Output>),
+}
+
+/// Destination of the results, a file or the standard output.
+pub enum Output {
+    Document {
+        writer: Writer,
+        document: Document,
+        /// Name of the count of the records of each line with `--per-line`, instead of the map
+        /// of the sources to their counts.
+        per_line: Option<&'static str>,
+    },
+    Ndjson {
+        writer: Writer,
+        per_line: bool,
+        /// Name of the number of each line of their objects.
+        line_count: &'static str,
+    },
+    Arrow(ArrowLineWordsWriter<Writer>),
+    Parquet(ParquetLineWordsWriter<Writer>),
+    Table {
+        writer: Writer,
+        /// What is counted in each line, `words` by default.
+        unit: &'static str,
+        rows: Vec<(String, LineStats)>,
+        /// Cells of each line with `--per-line`, instead of the statistics of the sources.
+        lines: Option<Vec<Vec<String>>>,
+        palette: Palette,
+    },
+    /// A registered format or a template.
+    Formatted {
+        writer: Writer,
+        formatter: Box<dyn Formatter>,
+    },
+}
+
+impl Output {
+    /// Create the output file of the arguments, or use the standard output without path. The
+    /// format defaults to the one of the path, the table is colored according to `--color`. A
+    /// template replaces the format. The file is only replaced once the results are complete,
+    /// and compressed with `--output-compression`, `--gzip` or a `.gz` or `.zst` extension. The
+    /// reports of the commands other than `words` cannot be written as Arrow, Parquet or
+    /// templates, and the references of `--dedup-sources` only as documents and NDJSON.
+    pub fn create(args: &Args) -> io::Result<Self> {
+        let path = args.output.as_deref();
+        let format = args.format.unwrap_or_else(|| Format::from_path(path));
+        let references = matches!(format, Format::Document(_) | Format::Ndjson);
+        if args.dedup_sources && (!references || args.format_template.is_some()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--dedup-sources only applies to the documents and NDJSON",
+            ));
+        }
+        if args.command != Command::Words && matches!(format, Format::Arrow | Format::Parquet) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "the {} command cannot write Arrow nor Parquet files",
+                    args.command.name()
+                ),
+            ));
+        }
+        let compression = args.output_compression();
+        let mut writer: Writer = match (path, &args.post_to) {
+            (_, Some(url)) => {
+                let retry = RetryPolicy::new(args.post_retries, POST_BACKOFF);
+                let token = args.post_token.clone();
+                Box::new(HttpPost::new(
+                    url,
+                    token,
+                    format.content_type(),
+                    compression,
+                    retry,
+                ))
+            }
+            (Some(path), None) => Box::new(AtomicFile::create(path)?),
+            (None, None) => Box::new(BufWriter::new(io::stdout())),
+        };
+        if let Some(compression) = compression {
+            writer = compression.encoder(writer)?;
+        }
+        if let Some(template) = &args.format_template {
+            return Ok(Self::Formatted {
+                writer,
+                formatter: Box::new(template.clone()),
+            });
+        }
+        Ok(match format {
+            Format::Document(document) => Self::Document {
+                writer,
+                document,
+                per_line: args.command.line_count().filter(|_| args.per_line),
+            },
+            Format::Ndjson => Self::Ndjson {
+                writer,
+                per_line: args.per_line,
+                line_count: args.command.line_count().unwrap_or("word_count"),
+            },
+            Format::Registered(name) => {
+                let (_, registration) = Formatters::global()
+                    .get(name)
+                    .expect("the format should be registered");
+                let mut formatter = (registration.new)(args);
+                formatter.start(&mut writer)?;
+                Self::Formatted { writer, formatter }
+            }
+            Format::Arrow => Self::Arrow(ArrowLineWordsWriter::new(writer)?),
+            Format::Parquet => Self::Parquet(ParquetLineWordsWriter::new(writer)?),
+            Format::Table => Self::Table {
+                writer,
+                unit: args.command.name(),
+                rows: Vec::new(),
+                lines: args.per_line.then(Vec::new),
+                palette: Palette::new(args.color.enabled(path.is_none() && args.post_to.is_none())),
+            },
+        })
+    }
+
+    /// Write the results of the sources as soon as they are read, for the unsorted NDJSON
+    /// output. Returns the output unchanged otherwise, as it needs the complete results.
+    pub async fn stream(self, run: &Run<'_>, options: ProcessorOptions) -> io::Result<Streamed> {
+        let Self::Ndjson {
+            mut writer,
+            per_line,
+            line_count,
+        } = self
+        else {
+            return Ok(Streamed::Pending(Box::new(self)));
+        };
+        if run.sort.is_some() {
+            return Ok(Streamed::Pending(Box::new(Self::Ndjson {
+                writer,
+                per_line,
+                line_count,
+            })));
+        }
+        let mut totals = Totals::default();
+        let sources = run.sources.sources();
+        if per_line {
+            let counts = sources.flat_map_unordered(options.max_concurrency, |src| {
+                Box::pin(count_source_line_words_numbered(src, options))
+            });
+            pin_mut!(counts);
+            let mut written = HashMap::new();
+            while let Some((id, line, count)) = counts.next().await {
+                totals.add(&[count]);
+                written.entry(id).or_insert_with(Vec::new).push(count);
+                write_ndjson_line(&mut writer, line_count, id, line, count)?;
+            }
+            for (id, counts) in written {
+                if let Some(tally) = run.sources.tally(id) {
+                    tally.set_counts(&counts);
+                }
+            }
+            write_ndjson_meta(&mut writer, run)?;
+        } else {
+            let counts = sources
+                .map(|(id, rd)| {
+                    count_source_line_words((id, rd), options)
+                        .map(|(_, count)| count)
+                        .collect::<Vec<_>>()
+                        .map(move |counts| (id, counts))
+                })
+                .buffer_unordered(options.max_concurrency.unwrap_or(usize::MAX));
+            pin_mut!(counts);
+            while let Some((id, counts)) = counts.next().await {
+                totals.add(&counts);
+                let tally = run.sources.tally(id);
+                if let Some(tally) = &tally {
+                    tally.set_counts(&counts);
+                }
+                let tally = tally.filter(|_| run.with_meta);
+                write_ndjson_source(&mut writer, id, &counts, tally.as_deref())?;
+            }
+        }
+        write_ndjson_duplicates(&mut writer, run)?;
+        writer.commit()?;
+        Ok(Streamed::Written(totals))
+    }
+
+    /// Write the results kept in memory, in the order of the run or in any order without one,
+    /// and with the metadata of the sources in the documents and NDJSON objects if requested.
+    /// The documents are wrapped in an envelope, see [`SCHEMA_VERSION`].
+    pub fn write(self, results: &HashMap<&str, Vec<usize>>, run: &Run) -> io::Result<Totals> {
+        let ids = match self.sort(run.sort) {
+            Some(sort) => sort.order(
+                results
+                    .iter()
+                    .map(|(id, counts)| {
+                        (
+                            *id,
+                            counts.iter().sum::<usize>() as u64,
+                            counts.len() as u64,
+                        )
+                    })
+                    .collect(),
+            ),
+            None => results.keys().copied().collect(),
+        };
+        self.write_ordered(&ids, |id| Ok(Cow::Borrowed(&results[id])), run)
+    }
+
+    /// Write the results spilled to disk, like [`Output::write`].
+    pub fn write_spilled(self, results: &SpilledCounts<&str>, run: &Run) -> io::Result<Totals> {
+        let counts = |id: &str| -> io::Result<Cow<'_, [usize]>> {
+            let counts = results.counts(&id).expect("the id should have counts")?;
+            Ok(Cow::Owned(counts.collect::<io::Result<_>>()?))
+        };
+        let ids = match self.sort(run.sort) {
+            Some(sort) => {
+                let rows = results
+                    .ids()
+                    .map(|id| {
+                        let words = match sort.key {
+                            SortKey::Words => counts(id)?.iter().sum::<usize>() as u64,
+                            _ => 0,
+                        };
+                        let lines = results.lines(id).unwrap_or_default() as u64;
+                        Ok((*id, words, lines))
+                    })
+                    .collect::<io::Result<_>>()?;
+                sort.order(rows)
+            }
+            None => results.ids().copied().collect(),
+        };
+        self.write_ordered(&ids, counts, run)
+    }
+
+    /// Write the report of a command other than `words`, in the documents, NDJSON objects, CSV
+    /// rows or table. The documents are wrapped in an envelope.
+    pub fn write_report(self, report: &Report, run: &Run) -> io::Result<Totals> {
+        let writer = match self {
+            Self::Document {
+                mut writer,
+                document,
+                ..
+            } => {
+                let meta = run.meta();
+                let envelope = Envelope {
+                    results: report,
+                    run,
+                    totals: &Cell::new(report.totals),
+                    sources: meta.as_ref(),
+                };
+                document.serialize(&mut writer, &envelope)?;
+                writer
+            }
+            Self::Ndjson { mut writer, .. } => {
+                for record in report.records() {
+                    serde_json::to_writer(&mut writer, &record)?;
+                    writeln!(writer)?;
+                }
+                write_ndjson_meta(&mut writer, run)?;
+                writer
+            }
+            Self::Formatted {
+                mut writer,
+                mut formatter,
+            } => {
+                formatter.write_report(&mut writer, report)?;
+                formatter.finish(&mut writer)?;
+                writer
+            }
+            Self::Table {
+                mut writer,
+                palette,
+                ..
+            } if report.layout == Layout::Histogram => {
+                hist::write_bars(&mut writer, report, palette)?;
+                writer
+            }
+            Self::Table {
+                mut writer,
+                palette,
+                ..
+            } => {
+                let header: Vec<_> = report.columns.iter().map(|c| c.to_uppercase()).collect();
+                let rows: Vec<Vec<String>> = report
+                    .rows
+                    .iter()
+                    .map(|(key, values)| {
+                        let key = match (report.layout, &values[0]) {
+                            (Layout::Tree, Value::Count(depth)) => {
+                                dirs::tree_label(key, *depth as usize)
+                            }
+                            _ => key.clone(),
+                        };
+                        let values = values.iter().map(ToString::to_string);
+                        std::iter::once(key).chain(values).collect()
+                    })
+                    .collect();
+                write_aligned(&mut writer, &header, &rows, palette, |_, _| false)?;
+                writer
+            }
+            Self::Arrow(_) | Self::Parquet(_) => {
+                unreachable!("rejected by the arguments and Output::create")
+            }
+        };
+        writer.commit()?;
+        Ok(report.totals)
+    }
+
+    /// Order of the sources, the table is sorted by identifier by default.
+    fn sort(&self, sort: Option<Sort>) -> Option<Sort> {
+        sort.or_else(|| matches!(self, Self::Table { .. }).then(Sort::default))
+    }
+
+    fn write_ordered<'a>(
+        mut self,
+        ids: &[&str],
+        counts: impl Fn(&str) -> io::Result<Cow<'a, [usize]>>,
+        run: &Run,
+    ) -> io::Result<Totals> {
+        let meta = run.meta();
+        let meta = meta.as_ref();
+        let totals = Cell::new(Totals::default());
+        let counts = |id: &str| {
+            let counts = counts(id)?;
+            let mut sum = totals.get();
+            sum.add(&counts);
+            totals.set(sum);
+            if let Some(tally) = run.sources.tally(id) {
+                tally.set_counts(&counts);
+            }
+            Ok(counts)
+        };
+        if let Self::Document {
+            writer,
+            document,
+            per_line,
+        } = &mut self
+        {
+            let results = OrderedResults { ids, counts, meta };
+            match per_line {
+                Some(line_count) => {
+                    let results = LineRecords(results, line_count);
+                    let envelope = Envelope {
+                        results: &results,
+                        run,
+                        totals: &totals,
+                        sources: meta,
+                    };
+                    document.serialize(writer, &envelope)?;
+                }
+                None => {
+                    let envelope = Envelope {
+                        results: &results,
+                        run,
+                        totals: &totals,
+                        sources: None,
+                    };
+                    document.serialize(writer, &envelope)?;
+                }
+            }
+        } else {
+            for id in ids {
+                let tally = meta.and_then(|meta| meta.get(*id));
+                self.write_counts(id, counts(id)?.iter().copied(), tally)?;
+            }
+            if let Self::Ndjson {
+                writer, per_line, ..
+            } = &mut self
+            {
+                if *per_line {
+                    write_ndjson_meta(writer, run)?;
+                }
+                write_ndjson_duplicates(writer, run)?;
+            }
+        }
+        self.finish()?;
+        Ok(totals.get())
+    }
+
+    fn write_counts(
+        &mut self,
+        id: &str,
+        counts: impl IntoIterator<Item = usize>,
+        tally: Option<&Arc<Tally>>,
+    ) -> io::Result<()> {
+        match self {
+            Self::Document { .. } => unreachable!("the documents are written at once"),
+            Self::Ndjson {
+                writer,
+                per_line,
+                line_count,
+            } => {
+                if *per_line {
+                    for (line, count) in counts.into_iter().enumerate() {
+                        write_ndjson_line(writer, line_count, id, line + 1, count)?;
+                    }
+                    Ok(())
+                } else {
+                    let counts: Vec<_> = counts.into_iter().collect();
+                    write_ndjson_source(writer, id, &counts, tally.map(AsRef::as_ref))
+                }
+            }
+            Self::Arrow(writer) => writer.write(id, counts),
+            Self::Parquet(writer) => writer.write(id, counts),
+            Self::Table {
+                lines: Some(lines), ..
+            } => {
+                lines.extend(counts.into_iter().enumerate().map(|(line, count)| {
+                    vec![id.to_string(), (line + 1).to_string(), count.to_string()]
+                }));
+                Ok(())
+            }
+            Self::Table { rows, .. } => {
+                rows.push((id.to_string(), counts.into_iter().collect()));
+                Ok(())
+            }
+            Self::Formatted { writer, formatter } => {
+                let counts: Vec<_> = counts.into_iter().collect();
+                formatter.write_source(writer, id, &counts, tally.map(AsRef::as_ref))
+            }
+        }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        let writer = match self {
+            Self::Document { writer, .. } | Self::Ndjson { writer, .. } => writer,
+            Self::Formatted {
+                mut writer,
+                mut formatter,
+            } => {
+                formatter.finish(&mut writer)?;
+                writer
+            }
+            Self::Arrow(writer) => writer.finish()?,
+            Self::Parquet(writer) => writer.finish()?,
+            Self::Table {
+                mut writer,
+                unit,
+                lines: Some(lines),
+                palette,
+                ..
+            } => {
+                let header = ["IDENTIFIER", "LINE", &unit.to_uppercase()];
+                write_aligned(&mut writer, &header, &lines, palette, |_, _| false)?;
+                writer
+            }
+            Self::Table {
+                mut writer,
+                unit,
+                rows,
+                palette,
+                ..
+            } => {
+                write_table(&mut writer, unit, &rows, palette)?;
+                writer
+            }
+        };
+        writer.commit()
+    }
+}
+
+/// Version of the envelope of the documents: `schema_version`, `results` (the map of the
+/// identifiers to their counts), `errors` (the `id` and `error` of the sources which could not
+/// be opened or read), `summary`, `duplicates` (the map of the sources identical to an earlier
+/// one to it, with `--dedup-sources`), `sources` (the metadata of the sources of the reports
+/// and of the lines, with `--with-meta`) and `partial` set to true for a run interrupted before
+/// all its sources were read or with skipped sources. It changes only if these fields change in
+/// an incompatible way, new fields can be added.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Delay before the first retry of the post of the results, doubled after each one.
+const POST_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Versioned document of the results, with the errors and the summary of the run.
+struct Envelope<'r, R> {
+    results: &'r R,
+    run: &'r Run<'r>,
+    /// Totals of the results, complete once they are serialized.
+    totals: &'r Cell<Totals>,
+    /// Metadata of the sources of the results without it, with `--with-meta`.
+    sources: Option<&'r Tallies>,
+}
+
+impl<R: Serialize> Serialize for Envelope<'_, R> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let partial = self.run.sources.is_partial();
+        let duplicates = self.run.sources.duplicates();
+        let len = 4
+            + usize::from(partial)
+            + usize::from(!duplicates.is_empty())
+            + usize::from(self.sources.is_some());
+        let mut map = serializer.serialize_map(Some(len))?;
+        map.serialize_entry("schema_version", &SCHEMA_VERSION)?;
+        map.serialize_entry("results", self.results)?;
+        map.serialize_entry("errors", &self.run.errors())?;
+        map.serialize_entry("summary", &self.run.summary(self.totals.get()))?;
+        if !duplicates.is_empty() {
+            map.serialize_entry("duplicates", &duplicates)?;
+        }
+        if let Some(sources) = self.sources {
+            map.serialize_entry("sources", &SourcesMeta(sources))?;
+        }
+        if partial {
+            map.serialize_entry("partial", &true)?;
+        }
+        map.end()
+    }
+}
+
+/// Map of the identifiers to their counts, serialized in the order of the identifiers.
+struct OrderedResults<'i, F> {
+    ids: &'i [&'i str],
+    counts: F,
+    meta: Option<&'i Tallies>,
+}
+
+/// Counts of a source with its metadata.
+#[derive(Serialize)]
+struct SourceMeta<'a> {
+    counts: &'a [usize],
+    lines: usize,
+    #[serde(flatten)]
+    tally: TallyMeta<'a>,
+}
+
+impl<'a> SourceMeta<'a> {
+    fn new(counts: &'a [usize], tally: &'a Tally) -> Self {
+        Self {
+            counts,
+            lines: counts.len(),
+            tally: TallyMeta::new(tally),
+        }
+    }
+}
+
+/// NDJSON object of a source, its counts with their metadata if given.
+#[derive(Serialize)]
+struct NdjsonSource<'a> {
+    id: &'a str,
+    #[serde(flatten)]
+    source: SourceCounts<'a>,
+}
+
+impl<'a> NdjsonSource<'a> {
+    fn new(id: &'a str, counts: &'a [usize], tally: Option<&'a Tally>) -> Self {
+        let source = match tally {
+            Some(tally) => SourceCounts::Meta(SourceMeta::new(counts, tally)),
+            None => SourceCounts::Counts { counts },
+        };
+        Self { id, source }
+    }
+}
+
+/// Counts of a source, alone or with its metadata.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum SourceCounts<'a> {
+    Counts { counts: &'a [usize] },
+    Meta(SourceMeta<'a>),
+}
+
+/// Metadata of a source, with its counts or alone for the results without them.
+#[derive(Serialize)]
+struct TallyMeta<'a> {
+    bytes: u64,
+    duration_ms: f64,
+    /// Checksum of the content of the source, after its decompression.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_sha256: Option<&'a str>,
+    /// Whether the reading stopped at `--max-lines` or was skipped before the end.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    truncated: bool,
+}
+
+impl<'a> TallyMeta<'a> {
+    fn new(tally: &'a Tally) -> Self {
+        Self {
+            bytes: tally.bytes.load(Ordering::Relaxed),
+            duration_ms: tally.duration().as_secs_f64() * 1e3,
+            content_sha256: tally.checksum.get().map(String::as_str),
+            truncated: tally.is_truncated(),
+        }
+    }
+}
+
+impl<'a, F: Fn(&str) -> io::Result<Cow<'a, [usize]>>> Serialize for OrderedResults<'_, F> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.ids.len()))?;
+        for id in self.ids {
+            let counts = (self.counts)(id).map_err(serde::ser::Error::custom)?;
+            match self.meta.and_then(|meta| meta.get(*id)) {
+                Some(tally) => map.serialize_entry(id, &SourceMeta::new(&counts, tally))?,
+                None => map.serialize_entry(id, &counts)?,
+            }
+        }
+        map.end()
+    }
+}
+
+/// Map of the identifiers to the metadata of the sources, serialized in their order.
+struct SourcesMeta<'a>(&'a Tallies);
+
+impl Serialize for SourcesMeta<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tallies: Vec<_> = self.0.iter().collect();
+        tallies.sort_unstable_by_key(|(id, _)| *id);
+        serializer.collect_map(
+            tallies
+                .into_iter()
+                .map(|(id, tally)| (id, TallyMeta::new(tally))),
+        )
+    }
+}
+
+/// Records of the identifier, the line number and the count named by the second field of each
+/// line of the results, in the order of the identifiers. The metadata of the sources is written
+/// apart, in the `sources` of the envelope.
+struct LineRecords<'i, F>(OrderedResults<'i, F>, &'static str);
+
+impl<'a, F: Fn(&str) -> io::Result<Cow<'a, [usize]>>> Serialize for LineRecords<'_, F> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Self(results, line_count) = self;
+        let mut seq = serializer.serialize_seq(None)?;
+        for id in results.ids {
+            let counts = (results.counts)(id).map_err(serde::ser::Error::custom)?;
+            for (line, count) in counts.iter().enumerate() {
+                seq.serialize_element(
+                    &json!({ "id": id, "line_number": line + 1, *line_count: count }),
+                )?;
+            }
+        }
+        seq.end()
+    }
+}
+
+/// Write the metadata of the sources after the results without it, the reports and the lines,
+/// with `--with-meta`.
+fn write_ndjson_meta(writer: &mut Writer, run: &Run) -> io::Result<()> {
+    let Some(meta) = run.meta() else {
+        return Ok(());
+    };
+    let mut tallies: Vec<_> = meta.iter().collect();
+    tallies.sort_unstable_by_key(|(id, _)| *id);
+    for (id, tally) in tallies {
+        let record = json!({ "id": id, "meta": TallyMeta::new(tally) });
+        serde_json::to_writer(&mut *writer, &record)?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Write the references of the sources skipped by `--dedup-sources` to the first identical one.
+fn write_ndjson_duplicates(writer: &mut Writer, run: &Run) -> io::Result<()> {
+    for (id, first) in run.sources.duplicates() {
+        let reference = json!({ "id": id, "duplicate_of": first });
+        serde_json::to_writer(&mut *writer, &reference)?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Write the NDJSON object of a source, with its metadata if given, flushed to be read right
+/// away.
+fn write_ndjson_source(
+    writer: &mut Writer,
+    id: &str,
+    counts: &[usize],
+    tally: Option<&Tally>,
+) -> io::Result<()> {
+    let object = NdjsonSource::new(id, counts, tally);
+    serde_json::to_writer(&mut *writer, &object)?;
+    writeln!(writer)?;
+    writer.flush()
+}
+
+/// Write the NDJSON object of a line, with its count named `line_count`, flushed to be read
+/// right away.
+fn write_ndjson_line(
+    writer: &mut Writer,
+    line_count: &str,
+    id: &str,
+    line: usize,
+    count: usize,
+) -> io::Result<()> {
+    let object = json!({ "id": id, "line_number": line, line_count: count });
+    serde_json::to_writer(&mut *writer, &object)?;
+    writeln!(writer)?;
+    writer.flush()
+}
+
+/// Write the statistics of the counts of `unit` of the sources as a table, see
+/// [`write_aligned`]. The maximums more than [`OUTLIER_STD_DEVS`] standard deviations above the
+/// mean are highlighted.
+fn write_table(
+    writer: &mut impl Write,
+    unit: &str,
+    rows: &[(String, LineStats)],
+    palette: Palette,
+) -> io::Result<()> {
+    let header = ["IDENTIFIER", "LINES", &unit.to_uppercase(), "MIN", "MAX"];
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|(id, stats)| {
+            vec![
+                id.clone(),
+                stats.lines.to_string(),
+                stats.words.to_string(),
+                stats.min.to_string(),
+                stats.max.to_string(),
+            ]
+        })
+        .collect();
+    write_aligned(writer, &header, &cells, palette, |row, column| {
+        column == 4 && is_outlier(&rows[row].1)
+    })
+}
+
+/// Write rows of cells as a table, with the first column aligned on the left and the others on
+/// the right. The header and the first column are in bold, and the cells for which
+/// `highlight(row, column)` is true are highlighted.
+pub fn write_aligned(
+    writer: &mut impl Write,
+    header: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+    palette: Palette,
+    highlight: impl Fn(usize, usize) -> bool,
+) -> io::Result<()> {
+    let mut widths: Vec<_> = header.iter().map(|cell| cell.as_ref().len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let pad = |column: usize, cell: &str| {
+        let width = widths[column];
+        if column == 0 {
+            format!("{cell:<width$}")
+        } else {
+            format!("{cell:>width$}")
+        }
+    };
+    let header: Vec<_> = header
+        .iter()
+        .enumerate()
+        .map(|(column, cell)| pad(column, cell.as_ref()))
+        .collect();
+    writeln!(writer, "{}", palette.bold(&header.join("  ")))?;
+    for (i, row) in rows.iter().enumerate() {
+        let cells: Vec<_> = row
+            .iter()
+            .enumerate()
+            .map(|(column, cell)| {
+                let cell = pad(column, cell);
+                match column {
+                    0 => palette.bold(&cell),
+                    _ if highlight(i, column) => palette.outlier(&cell),
+                    _ => cell,
+                }
+            })
+            .collect();
+        writeln!(writer, "{}", cells.join("  "))?;
+    }
+    Ok(())
+}
+
+/// Number of standard deviations above the mean of a line count to be an outlier.
+const OUTLIER_STD_DEVS: f64 = 2.0;
+
+/// Whether the longest line of a source is an outlier among its lines.
+fn is_outlier(stats: &LineStats) -> bool {
+    stats.max as f64 > stats.mean() + OUTLIER_STD_DEVS * stats.std_dev()
+}
+
+/// Quote a CSV field if needed, doubling its quotes.
+pub fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(Format::from_path(None), Format::Document(Document::Json));
+        assert_eq!(
+            Format::from_path(Some("out.json")),
+            Format::Document(Document::Json)
+        );
+        assert_eq!(
+            Format::from_path(Some("out.yml")),
+            Format::Document(Document::Yaml)
+        );
+        assert_eq!(
+            Format::from_name("toml"),
+            Ok(Format::Document(Document::Toml))
+        );
+        assert_eq!(Format::from_path(Some("out.jsonl")), Format::Ndjson);
+        assert_eq!(
+            Format::from_path(Some("out.csv")),
+            Format::Registered("csv")
+        );
+        assert_eq!(
+            Format::from_path(Some("out.csv.gz")),
+            Format::Registered("csv")
+        );
+        assert_eq!(
+            Format::from_path(Some("out.gz")),
+            Format::Document(Document::Json)
+        );
+        assert_eq!(Format::from_path(Some("out.arrow")), Format::Arrow);
+        assert_eq!(Format::from_path(Some("out.parquet")), Format::Parquet);
+        assert_eq!(Format::from_name("csv"), Ok(Format::Registered("csv")));
+        assert!(Format::from_name("xml").is_err());
+    }
+
+    #[test]
+    fn test_sort_order() {
+        let rows = vec![("b", 3, 1), ("a", 3, 2), ("c", 1, 3)];
+        let sort = |key, reverse| Sort { key, reverse }.order(rows.clone());
+        assert_eq!(sort(SortKey::Id, false), ["a", "b", "c"]);
+        assert_eq!(sort(SortKey::Words, false), ["c", "a", "b"]);
+        assert_eq!(sort(SortKey::Lines, true), ["c", "a", "b"]);
+        assert_eq!(sort(SortKey::Words, true), ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn test_document_serialize() {
+        let results = HashMap::from([("logs/a.txt", vec![2, 1])]);
+        let serialize = |document: Document| {
+            let mut writer = Vec::new();
+            document.serialize(&mut writer, &results).unwrap();
+            String::from_utf8(writer).unwrap()
+        };
+        assert_eq!(serialize(Document::Yaml), "logs/a.txt:\n- 2\n- 1\n");
+        assert_eq!(
+            serialize(Document::Toml),
+            "\"logs/a.txt\" = [\n    2,\n    1,\n]\n"
+        );
+    }
+
+    #[test]
+    fn test_line_records() {
+        let results = HashMap::from([("a", vec![2, 1]), ("b", vec![3])]);
+        let records = LineRecords(
+            OrderedResults {
+                ids: &["b", "a"],
+                counts: |id: &str| Ok(Cow::Borrowed(&results[id][..])),
+                meta: None,
+            },
+            "char_count",
+        );
+        assert_eq!(
+            serde_json::to_value(&records).unwrap(),
+            json!([
+                {"id": "b", "line_number": 1, "char_count": 3},
+                {"id": "a", "line_number": 1, "char_count": 2},
+                {"id": "a", "line_number": 2, "char_count": 1},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_ndjson_source() {
+        let tally = Tally::default();
+        tally.bytes.store(4, Ordering::Relaxed);
+        tally.truncated.store(true, Ordering::Relaxed);
+        let object = serde_json::to_value(NdjsonSource::new("a.txt", &[2], Some(&tally))).unwrap();
+        assert_eq!(object["lines"], 1);
+        assert_eq!(object["bytes"], 4);
+        assert_eq!(object["truncated"], true);
+        assert!(object["duration_ms"].is_f64());
+        assert_eq!(
+            serde_json::to_value(NdjsonSource::new("a.txt", &[2], None)).unwrap(),
+            json!({"id": "a.txt", "counts": [2]})
+        );
+    }
+
+    #[test]
+    fn test_write_table() {
+        let rows = [
+            ("a.txt".to_string(), [2, 0, 5].into_iter().collect()),
+            ("logs/b.txt".to_string(), [12].into_iter().collect()),
+        ];
+        let mut table = Vec::new();
+        write_table(&mut table, "words", &rows, Palette::new(false)).unwrap();
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            "IDENTIFIER  LINES  WORDS  MIN  MAX\n\
+             a.txt           3      7    0    5\n\
+             logs/b.txt      1     12   12   12\n"
+        );
+
+        let rows = [(
+            "a".to_string(),
+            [1, 1, 1, 1, 1, 1, 1, 1, 1, 10].into_iter().collect(),
+        )];
+        let mut table = Vec::new();
+        write_table(&mut table, "chars", &rows, Palette::new(true)).unwrap();
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            "\x1b[1mIDENTIFIER  LINES  CHARS  MIN  MAX\x1b[0m\n\
+             \x1b[1ma         \x1b[0m     10     19    1  \x1b[1;31m 10\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("logs/a.txt"), "logs/a.txt");
+        assert_eq!(csv_field("a,b.txt"), "\"a,b.txt\"");
+        assert_eq!(csv_field("a\"b"), "\"a\"\"b\"");
+    }
+}
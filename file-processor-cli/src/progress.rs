@@ -0,0 +1,86 @@
+use std::{future::Future, time::Duration};
+
+use futures_util::StreamExt;
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+use string_stream_processor::ProgressSnapshot;
+
+use crate::run::Run;
+
+/// Minimum interval between the updates of the progress bar.
+const TICK: Duration = Duration::from_millis(100);
+
+/// Run `work` while showing the progress of the sources of `run` on the standard error: the
+/// sources done out of all of them, the bytes read per second and the remaining time, updated
+/// from the progress of the readers of the sources. Nothing is shown if `show` is false.
+pub async fn track<T>(run: &Run<'_>, show: bool, work: impl Future<Output = T>) -> T {
+    if !show {
+        return work.await;
+    }
+    let bar = ProgressBar::new(run.sources.inner().len() as u64).with_style(
+        ProgressStyle::with_template("[{bar:30}] {pos}/{len} files, {msg}, ETA {eta}")
+            .unwrap()
+            .progress_chars("=> "),
+    );
+    let updates = run.sources.progress().updates(TICK);
+    tokio::pin!(work, updates);
+    loop {
+        tokio::select! {
+            output = &mut work => {
+                bar.finish_and_clear();
+                return output;
+            }
+            Some(snapshot) = updates.next() => {
+                let (done, bytes) = progress(run, snapshot);
+                let rate = bytes as f64 / run.start.elapsed().as_secs_f64().max(f64::EPSILON);
+                bar.set_position(done as u64);
+                bar.set_message(format!("{} at {}/s", HumanBytes(bytes), HumanBytes(rate as u64)));
+            }
+        }
+    }
+}
+
+/// Number of sources done, read until their end, failed or not opened, and bytes read so far.
+fn progress(run: &Run, snapshot: ProgressSnapshot) -> (usize, u64) {
+    let unopened = run.sources.inner().errors().len();
+    (snapshot.done + unopened, snapshot.bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use futures_util::StreamExt;
+    use string_stream_processor::{ProcessorOptions, SourceProvider};
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+    use crate::{inputs::Inputs, tally::Tallied};
+
+    #[tokio::test]
+    async fn test_progress() {
+        let path = std::env::temp_dir().join("fpc_test_progress.txt");
+        std::fs::write(&path, "a b\nc\n").unwrap();
+        let files = vec![
+            path.to_str().unwrap().to_string(),
+            "fpc_test_missing.txt".into(),
+        ];
+        let sources = Tallied::new(Inputs::new(files, None, ProcessorOptions::default()));
+        let run = Run {
+            sources: &sources,
+            sort: None,
+            with_meta: false,
+            reused: 0,
+            start: Instant::now(),
+        };
+        let mut opened = std::pin::pin!(sources.sources());
+        let (_, mut rd) = opened.next().await.unwrap();
+        let snapshot = || sources.progress().snapshot();
+        assert_eq!(progress(&run, snapshot()), (0, 0));
+        rd.read_to_end(&mut Vec::new()).await.unwrap();
+        drop(rd);
+        assert!(opened.next().await.is_none());
+        assert_eq!(progress(&run, snapshot()), (2, 6));
+        let output = track(&run, true, async { 1 }).await;
+        assert_eq!(output, 1);
+    }
+}
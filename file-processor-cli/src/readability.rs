@@ -0,0 +1,158 @@
+use std::{collections::HashMap, io, sync::Mutex};
+
+use string_stream_processor::{ProcessorOptions, SourceProvider};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+use crate::tally::Tallied;
+
+/// Numbers of sentences, words and syllables of a source, estimated from its text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Readability {
+    /// Number of lines read.
+    pub lines: u64,
+    pub sentences: u64,
+    pub words: u64,
+    pub syllables: u64,
+}
+
+impl Readability {
+    fn words_per_sentence(&self) -> f64 {
+        self.words as f64 / self.sentences.max(1) as f64
+    }
+
+    fn syllables_per_word(&self) -> f64 {
+        self.syllables as f64 / self.words.max(1) as f64
+    }
+
+    /// Flesch reading ease, about 0 for the hardest texts to 100 for the easiest, zero without
+    /// words.
+    pub fn reading_ease(&self) -> f64 {
+        match self.words {
+            0 => 0.0,
+            _ => 206.835 - 1.015 * self.words_per_sentence() - 84.6 * self.syllables_per_word(),
+        }
+    }
+
+    /// Flesch-Kincaid grade level, the years of schooling needed to understand the text, zero
+    /// without words.
+    pub fn grade_level(&self) -> f64 {
+        match self.words {
+            0 => 0.0,
+            _ => 0.39 * self.words_per_sentence() + 11.8 * self.syllables_per_word() - 15.59,
+        }
+    }
+}
+
+/// Estimate the readability of each source, reading at most `options.max_concurrency` sources
+/// at a time, see [`Tallied::count_each`].
+pub async fn count_readability<P: SourceProvider>(
+    provider: &Tallied<P>,
+    options: ProcessorOptions,
+) -> HashMap<&str, Readability> {
+    let merged = Mutex::new(HashMap::new());
+    let count = |_, rd| count_source_readability(rd);
+    provider
+        .count_each(options.max_concurrency, count, |id, readability| {
+            merged.lock().unwrap().insert(id, readability);
+        })
+        .await;
+    merged.into_inner().unwrap()
+}
+
+/// Count the sentences, words and syllables of a source. The words are the tokens separated by
+/// whitespace with a letter, a sentence ends with a word followed by `.`, `!` or `?`, or with
+/// the source.
+async fn count_source_readability(mut rd: impl AsyncBufRead + Unpin) -> io::Result<Readability> {
+    let mut readability = Readability::default();
+    // Whether words were read since the end of the last sentence.
+    let mut open = false;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if rd.read_until(b'\n', &mut line).await? == 0 {
+            break;
+        }
+        readability.lines += 1;
+        for token in String::from_utf8_lossy(&line).split_whitespace() {
+            let word = token.trim_matches(|c: char| !c.is_alphanumeric());
+            if word.chars().any(char::is_alphabetic) {
+                readability.words += 1;
+                readability.syllables += syllables(word);
+                open = true;
+            }
+            let end = token.trim_end_matches(['"', '\'', ')', ']', '»', '”', '’']);
+            if open && end.ends_with(['.', '!', '?']) {
+                readability.sentences += 1;
+                open = false;
+            }
+        }
+    }
+    readability.sentences += u64::from(open);
+    Ok(readability)
+}
+
+/// Estimate of the number of syllables of an English word: its groups of vowels, without a
+/// final silent `e`, and at least one.
+fn syllables(word: &str) -> u64 {
+    let word = word.to_lowercase();
+    let is_vowel = |c: char| matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y');
+    let mut groups = 0;
+    let mut previous = false;
+    for c in word.chars() {
+        let vowel = is_vowel(c);
+        if vowel && !previous {
+            groups += 1;
+        }
+        previous = vowel;
+    }
+    let silent_e = word.ends_with('e')
+        && !word.ends_with("le")
+        && !word.ends_with("ee")
+        && word.chars().rev().nth(1).is_some_and(|c| !is_vowel(c));
+    if silent_e && groups > 1 {
+        groups -= 1;
+    }
+    groups.max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_syllables() {
+        let words = [
+            ("cat", 1),
+            ("table", 2),
+            ("make", 1),
+            ("readability", 5),
+            ("the", 1),
+            ("free", 1),
+            ("rhythm", 1),
+            ("42x", 1),
+        ];
+        for (word, expected) in words {
+            assert_eq!(syllables(word), expected, "{word}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_count_source_readability() {
+        let source = b"The cat sat. It was on the\nmat! \"Is it?\" Yes\n\n- 42 -\n";
+        let readability = count_source_readability(&source[..]).await.unwrap();
+        assert_eq!(
+            readability,
+            Readability {
+                lines: 4,
+                sentences: 4,
+                words: 11,
+                syllables: 11,
+            }
+        );
+        assert_eq!(readability.words_per_sentence(), 2.75);
+        assert!((readability.reading_ease() - 119.444).abs() < 0.01);
+        assert!((readability.grade_level() + 2.7175).abs() < 0.01);
+        let empty = count_source_readability(&b"\n...\n"[..]).await.unwrap();
+        assert_eq!((empty.sentences, empty.reading_ease()), (0, 0.0));
+    }
+}
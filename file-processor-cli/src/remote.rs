@@ -0,0 +1,254 @@
+use std::{borrow::Cow, cell::RefCell, io, pin::Pin, sync::OnceLock};
+
+use futures_util::{stream, Stream, StreamExt};
+use reqwest::{Client, Url};
+#[cfg(feature = "s3")]
+use string_stream_processor::S3Provider;
+use string_stream_processor::{open_url, Decompress, ProcessorOptions, SourceProvider};
+use tokio::io::AsyncBufRead;
+
+use crate::run::SourceError;
+
+/// Where a source given on the command line is read from, according to its scheme.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location<'a> {
+    /// A local path, given as is or as a `file://` URI.
+    File(Cow<'a, str>),
+    /// An `http://` or `https://` URL.
+    Url(&'a str),
+    /// The objects under a `s3://bucket/prefix` URI.
+    S3(&'a str),
+}
+
+impl<'a> Location<'a> {
+    /// Detect the scheme of a source, the arguments without a known scheme are local paths.
+    pub fn detect(source: &'a str) -> Self {
+        let scheme = source.split_once("://").map(|(scheme, _)| scheme);
+        match scheme.map(str::to_ascii_lowercase).as_deref() {
+            Some("file") => Self::File(file_path(source)),
+            Some("http" | "https") => Self::Url(source),
+            Some("s3") => Self::S3(source),
+            _ => Self::File(source.into()),
+        }
+    }
+}
+
+/// Path of a `file://` URI, percent-decoded, of the local host or `localhost`. The rest of the
+/// URIs with another host is kept as is, e.g. the relative `file://a.txt`.
+fn file_path(uri: &str) -> Cow<'_, str> {
+    let path = Url::parse(uri)
+        .ok()
+        .and_then(|url| url.to_file_path().ok())
+        .and_then(|path| path.into_os_string().into_string().ok());
+    match path {
+        Some(path) => Cow::Owned(path),
+        None => Cow::Borrowed(&uri["file://".len()..]),
+    }
+}
+
+/// Sources read from the network: the bodies of HTTP(S) responses and the objects of S3
+/// buckets, identified by their URL or URI.
+#[derive(Debug)]
+pub struct RemoteSources {
+    urls: Vec<String>,
+    /// Prefixes of the S3 buckets, listed by [`RemoteSources::list_buckets`].
+    s3_uris: Vec<String>,
+    #[cfg(feature = "s3")]
+    buckets: Vec<S3Provider>,
+    client: OnceLock<Client>,
+    /// Errors of the URLs which could not be requested.
+    errors: RefCell<Vec<SourceError>>,
+    options: ProcessorOptions,
+}
+
+impl RemoteSources {
+    pub fn new(options: ProcessorOptions) -> Self {
+        Self {
+            urls: Vec::new(),
+            s3_uris: Vec::new(),
+            #[cfg(feature = "s3")]
+            buckets: Vec::new(),
+            client: OnceLock::new(),
+            errors: RefCell::default(),
+            options,
+        }
+    }
+
+    /// Add a URL or an S3 URI, returns the local path of the other sources.
+    pub fn push(&mut self, source: String) -> Option<String> {
+        match Location::detect(&source) {
+            Location::File(path) if path.len() == source.len() => return Some(source),
+            Location::File(path) => return Some(path.into_owned()),
+            Location::Url(_) => self.urls.push(source),
+            Location::S3(_) => self.s3_uris.push(source),
+        }
+        None
+    }
+
+    /// Number of URLs and of S3 prefixes or, once listed, objects.
+    pub fn len(&self) -> usize {
+        #[cfg(feature = "s3")]
+        let s3 = self
+            .buckets
+            .iter()
+            .map(|bucket| bucket.uris().len())
+            .sum::<usize>();
+        #[cfg(not(feature = "s3"))]
+        let s3 = 0;
+        self.urls.len() + self.s3_uris.len() + s3
+    }
+
+    /// URLs and URIs of the provided sources.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        #[cfg(feature = "s3")]
+        let objects = self.buckets.iter().flat_map(|bucket| bucket.uris());
+        #[cfg(not(feature = "s3"))]
+        let objects = std::iter::empty::<&String>();
+        self.urls
+            .iter()
+            .chain(&self.s3_uris)
+            .chain(objects)
+            .map(String::as_str)
+    }
+
+    /// List the objects under the S3 prefixes, returns the errors of the ones which could not
+    /// be listed.
+    pub async fn list_buckets(&mut self) -> Vec<SourceError> {
+        let mut errors = Vec::new();
+        for uri in std::mem::take(&mut self.s3_uris) {
+            #[cfg(feature = "s3")]
+            match S3Provider::from_uri(&uri, self.options).await {
+                Ok(bucket) => self.buckets.push(bucket),
+                Err(e) => errors.push(SourceError::new(&uri, &e)),
+            }
+            #[cfg(not(feature = "s3"))]
+            {
+                let message = "the S3 sources need the `s3` feature of the CLI";
+                let e = io::Error::new(io::ErrorKind::Unsupported, message);
+                errors.push(SourceError::new(&uri, &e));
+            }
+        }
+        errors
+    }
+
+    /// Skip the URLs for which `skipped` is true, the objects are only known once listed.
+    pub fn skip(&mut self, skipped: impl Fn(&str) -> bool) {
+        self.urls.retain(|url| !skipped(url));
+    }
+
+    /// Errors of the URLs which could not be requested so far.
+    pub fn errors(&self) -> Vec<SourceError> {
+        self.errors.borrow().clone()
+    }
+
+    async fn open_url(&self, url: &str) -> Option<Decompress<Pin<Box<dyn AsyncBufRead + Send>>>> {
+        let client = self.client.get_or_init(Client::new);
+        let opened = async {
+            let rd: Pin<Box<dyn AsyncBufRead + Send>> =
+                Box::pin(open_url(client, url, self.options).await?);
+            Decompress::detect(url, rd, self.options.read_buffer_size).await
+        };
+        opened
+            .await
+            .inspect_err(|e| log::warn!("Could not open {url}, {e}, skipping it."))
+            .map_err(|e| self.errors.borrow_mut().push(SourceError::new(url, &e)))
+            .ok()
+    }
+}
+
+impl SourceProvider for RemoteSources {
+    type Reader = Pin<Box<dyn AsyncBufRead + Send>>;
+
+    fn sources(&self) -> impl Stream<Item = (&str, Self::Reader)> {
+        let urls = stream::iter(&self.urls).filter_map(move |url| async move {
+            let rd: Self::Reader = Box::pin(self.open_url(url).await?);
+            Some((url.as_str(), rd))
+        });
+        #[cfg(feature = "s3")]
+        let objects = stream::iter(&self.buckets)
+            .flat_map(|bucket| bucket.sources())
+            .map(|(uri, rd)| (uri, Box::pin(rd) as Self::Reader))
+            // Boxed, the futures of the S3 client are too deep to be nested in the callers.
+            .boxed_local();
+        #[cfg(not(feature = "s3"))]
+        let objects = stream::empty();
+        urls.chain(objects)
+    }
+
+    #[cfg(feature = "s3")]
+    fn lines_hint(&self, id: &str) -> usize {
+        let hints = self.buckets.iter().map(|bucket| bucket.lines_hint(id));
+        hints.max().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_location_detect() {
+        let file = |path: &str| Location::File(path.to_string().into());
+        assert_eq!(Location::detect("a.txt"), file("a.txt"));
+        assert_eq!(Location::detect("file:///tmp/a.txt"), file("/tmp/a.txt"));
+        assert_eq!(
+            Location::detect("file:///tmp/a%20b.txt"),
+            file("/tmp/a b.txt")
+        );
+        assert_eq!(
+            Location::detect("file://localhost/tmp/a.txt"),
+            file("/tmp/a.txt")
+        );
+        assert_eq!(Location::detect("file://a.txt"), file("a.txt"));
+        assert_eq!(
+            Location::detect("HTTPS://example.com/a"),
+            Location::Url("HTTPS://example.com/a")
+        );
+        assert_eq!(
+            Location::detect("http://example.com/a"),
+            Location::Url("http://example.com/a")
+        );
+        assert_eq!(
+            Location::detect("s3://logs/2024/"),
+            Location::S3("s3://logs/2024/")
+        );
+        assert_eq!(Location::detect("logs/a://b"), file("logs/a://b"));
+    }
+
+    #[tokio::test]
+    async fn test_remote_sources() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = [0; 1024];
+                let len = socket.read(&mut request).await.unwrap();
+                let request = String::from_utf8_lossy(&request[..len]);
+                let response = match request.split(' ').nth(1) {
+                    Some("/a") => "HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\na b\nc\n",
+                    _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n",
+                };
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let mut remote = RemoteSources::new(ProcessorOptions::default());
+        let (a, missing) = (format!("http://{addr}/a"), format!("http://{addr}/missing"));
+        assert_eq!(remote.push("file://b.txt".into()), Some("b.txt".into()));
+        assert_eq!(remote.push(a.clone()), None);
+        assert_eq!(remote.push(missing.clone()), None);
+        assert_eq!(remote.len(), 2);
+        let result = remote.count_line_words(ProcessorOptions::default()).await;
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[a.as_str()], [2, 1]);
+        let errors = remote.errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].id, missing);
+    }
+}
@@ -0,0 +1,784 @@
+use std::{collections::HashMap, fmt};
+
+use regex::bytes::Regex;
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use string_stream_processor::{LineStats, ProcessorOptions, SourceProvider, StringMultiStreamExt};
+
+use crate::{
+    args::Command,
+    classes::{count_char_classes, CharClasses},
+    code::{count_code_stats, CodeStats},
+    dedup::{count_distinct_lines, DedupCount},
+    dirs::roll_up,
+    diversity::{count_diversity, Diversity},
+    freq::{count_frequencies, count_top_words, most_frequent, Frequencies, TopWords, WordFilter},
+    hist::{bucket_width, buckets, count_histograms, line_stats, merge, percentile, LineHistogram},
+    matches::{count_matches, MatchCount},
+    output::Sort,
+    readability::{count_readability, Readability},
+    summary::Totals,
+    tally::Tallied,
+};
+
+/// Value of a report.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Value {
+    Count(u64),
+    /// Written with two decimals in the tables and the CSV files.
+    Ratio(f64),
+    Text(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Count(count) => write!(f, "{count}"),
+            Self::Ratio(ratio) => write!(f, "{ratio:.2}"),
+            Self::Text(text) => f.pad(text),
+        }
+    }
+}
+
+/// How the rows of a report are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Layout {
+    /// A document maps each key to its value, or to a map of its values with several columns.
+    #[default]
+    Rows,
+    /// The keys have several rows of two values, a document maps each key to the map of the
+    /// first values of its rows to the second ones.
+    Groups,
+    /// Groups drawn in the tables as a histogram of the second values of each key.
+    Histogram,
+    /// Rows whose first value is the depth of the key in a tree, drawn as a tree in the tables.
+    Tree,
+}
+
+/// Results of the `lines`, `stats`, `freq`, `top-words` and `hist` commands: a row of values
+/// for each key, in order, or several with the [`Layout::Groups`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    /// Names of the key and of the values.
+    pub columns: &'static [&'static str],
+    pub rows: Vec<(String, Vec<Value>)>,
+    pub totals: Totals,
+    pub layout: Layout,
+}
+
+/// Key of the results of all the sources in the `top-words` and `stats` reports.
+pub const ALL_SOURCES: &str = "*";
+
+impl Report {
+    /// Compute the report of a command on the sources, in the order of `sort` or by identifier.
+    /// The words kept by the filter are ordered by decreasing frequency, the `match-count`
+    /// command needs a pattern.
+    pub async fn compute<P: SourceProvider>(
+        command: Command,
+        provider: &Tallied<P>,
+        options: ProcessorOptions,
+        sort: Option<Sort>,
+        filter: &WordFilter,
+        pattern: Option<&Regex>,
+    ) -> Self {
+        match command {
+            Command::Words
+            | Command::Chars
+            | Command::Bytes
+            | Command::LongestWord
+            | Command::ShortestWord => {
+                unreachable!("the lines are counted one by one")
+            }
+            Command::Lines => {
+                let stats = provider.sources().count_line_words_stats(options).await;
+                Self::lines(&stats, sort.unwrap_or_default())
+            }
+            Command::Stats => {
+                let histograms = count_histograms(provider, options).await;
+                Self::stats(&histograms, sort.unwrap_or_default())
+            }
+            Command::Freq { top } => {
+                let (frequencies, totals) = count_frequencies(provider, options, filter).await;
+                Self::freq(frequencies, totals, top)
+            }
+            Command::TopWords { top } => {
+                let (sources, frequencies, totals) =
+                    count_top_words(provider, options, filter, top).await;
+                let global = most_frequent(frequencies, Some(top));
+                Self::top_words(&sources, global, totals, sort.unwrap_or_default())
+            }
+            Command::CodeStats => Self::code_stats(count_code_stats(provider, options).await),
+            Command::DirTotals => {
+                let stats = provider.sources().count_line_words_stats(options).await;
+                Self::dir_totals(&stats, sort.unwrap_or_default())
+            }
+            Command::MatchCount => {
+                let pattern = pattern.expect("the match-count command has a pattern");
+                let counts = count_matches(provider, options, pattern).await;
+                Self::match_count(&counts, sort.unwrap_or_default())
+            }
+            Command::Dedup { approximate } => {
+                let counts = count_distinct_lines(provider, options, approximate).await;
+                Self::dedup(&counts, sort.unwrap_or_default())
+            }
+            Command::Diversity { approximate } => {
+                let counts = count_diversity(provider, options, approximate).await;
+                Self::diversity(&counts, sort.unwrap_or_default())
+            }
+            Command::CharClasses => {
+                let counts = count_char_classes(provider, options).await;
+                Self::char_classes(&counts, sort.unwrap_or_default())
+            }
+            Command::Readability => {
+                let counts = count_readability(provider, options).await;
+                Self::readability(&counts, sort.unwrap_or_default())
+            }
+            Command::Hist { bucket_width } => {
+                let histograms = count_histograms(provider, options).await;
+                Self::hist(&histograms, bucket_width, sort.unwrap_or_default())
+            }
+        }
+    }
+
+    /// Columns of the report of a command.
+    pub fn columns(command: Command) -> &'static [&'static str] {
+        match command {
+            Command::Words => &["identifier", "line_number", "word_count"],
+            Command::Chars => &["identifier", "line_number", "char_count"],
+            Command::Bytes => &["identifier", "line_number", "byte_count"],
+            Command::LongestWord => &["identifier", "line_number", "longest_word"],
+            Command::ShortestWord => &["identifier", "line_number", "shortest_word"],
+            Command::Lines => &["identifier", "lines"],
+            Command::Freq { .. } => &["word", "count"],
+            Command::TopWords { .. } => &["identifier", "word", "count"],
+            Command::Hist { .. } => &["identifier", "words", "lines"],
+            Command::CodeStats => &["language", "files", "blank", "comment", "code"],
+            Command::DirTotals => &["directory", "depth", "files", "lines", "words"],
+            Command::MatchCount => &["identifier", "lines", "matches"],
+            Command::Dedup { .. } => &[
+                "identifier",
+                "lines",
+                "distinct",
+                "duplicates",
+                "duplicate_ratio",
+            ],
+            Command::Diversity { .. } => &["identifier", "words", "distinct", "ratio"],
+            Command::CharClasses => &[
+                "identifier",
+                "alphabetic",
+                "numeric",
+                "punctuation",
+                "whitespace",
+                "other",
+            ],
+            Command::Readability => &[
+                "identifier",
+                "sentences",
+                "words",
+                "syllables",
+                "reading_ease",
+                "grade_level",
+            ],
+            Command::Stats => &[
+                "identifier",
+                "lines",
+                "words",
+                "min",
+                "max",
+                "mean",
+                "median",
+                "p95",
+                "p99",
+                "std_dev",
+            ],
+        }
+    }
+
+    /// Report of the number of lines of each source, in the order of `sort`.
+    pub fn lines(stats: &HashMap<&str, LineStats>, sort: Sort) -> Self {
+        let ids = sort.order(
+            stats
+                .iter()
+                .map(|(id, stats)| (*id, stats.words, stats.lines))
+                .collect(),
+        );
+        let mut totals = Totals::default();
+        let rows = ids
+            .into_iter()
+            .map(|id| {
+                let stats = &stats[id];
+                totals.lines += stats.lines;
+                totals.words += stats.words;
+                (id.to_string(), vec![Value::Count(stats.lines)])
+            })
+            .collect();
+        Self {
+            columns: Self::columns(Command::Lines),
+            rows,
+            totals,
+            layout: Layout::Rows,
+        }
+    }
+
+    /// Report of the statistics of the numbers of words of the lines of all the sources, keyed
+    /// by [`ALL_SOURCES`], then of each source.
+    fn stats(histograms: &HashMap<&str, LineHistogram>, sort: Sort) -> Self {
+        let stats: HashMap<_, _> = histograms
+            .iter()
+            .map(|(id, histogram)| (*id, line_stats(histogram)))
+            .collect();
+        let ids = sort.order(
+            stats
+                .iter()
+                .map(|(id, stats)| (*id, stats.words, stats.lines))
+                .collect(),
+        );
+        let global = merge(histograms.values());
+        let global = (ALL_SOURCES, line_stats(&global), &global);
+        let sources = ids.into_iter().map(|id| (id, stats[id], &histograms[id]));
+        let rows = std::iter::once(global)
+            .chain(sources)
+            .map(|(id, stats, histogram)| {
+                let values = vec![
+                    Value::Count(stats.lines),
+                    Value::Count(stats.words),
+                    Value::Count(stats.min as u64),
+                    Value::Count(stats.max as u64),
+                    Value::Ratio(stats.mean()),
+                    Value::Count(percentile(histogram, 0.5) as u64),
+                    Value::Count(percentile(histogram, 0.95) as u64),
+                    Value::Count(percentile(histogram, 0.99) as u64),
+                    Value::Ratio(stats.std_dev()),
+                ];
+                (id.to_string(), values)
+            })
+            .collect();
+        let global = &global.1;
+        Self {
+            columns: Self::columns(Command::Stats),
+            rows,
+            totals: Totals {
+                lines: global.lines,
+                words: global.words,
+            },
+            layout: Layout::Rows,
+        }
+    }
+
+    /// Report of the `top` most frequent words, the ties ordered alphabetically.
+    fn freq(frequencies: Frequencies, totals: Totals, top: Option<usize>) -> Self {
+        Self {
+            columns: Self::columns(Command::Freq { top }),
+            rows: most_frequent(frequencies, top)
+                .into_iter()
+                .map(|(word, count)| (word, vec![Value::Count(count)]))
+                .collect(),
+            totals,
+            layout: Layout::Rows,
+        }
+    }
+
+    /// Report of the most frequent words of all the sources, then of each source.
+    fn top_words(
+        sources: &HashMap<String, (TopWords, Totals)>,
+        global: TopWords,
+        totals: Totals,
+        sort: Sort,
+    ) -> Self {
+        let ids = sort.order(
+            sources
+                .iter()
+                .map(|(id, (_, totals))| (id.as_str(), totals.words, totals.lines))
+                .collect(),
+        );
+        let sources = ids
+            .into_iter()
+            .flat_map(|id| sources[id].0.iter().map(move |word| (id, word.clone())));
+        let rows = global
+            .into_iter()
+            .map(|word| (ALL_SOURCES, word))
+            .chain(sources)
+            .map(|(id, (word, count))| {
+                (id.to_string(), vec![Value::Text(word), Value::Count(count)])
+            })
+            .collect();
+        Self {
+            columns: Self::columns(Command::TopWords { top: 0 }),
+            rows,
+            totals,
+            layout: Layout::Groups,
+        }
+    }
+
+    /// Report of the lines of each language, the most lines of code first. The totals count the
+    /// lines of all the languages.
+    fn code_stats(stats: HashMap<&str, CodeStats>) -> Self {
+        let mut stats: Vec<_> = stats.into_iter().collect();
+        stats.sort_unstable_by(|(a, a_stats), (b, b_stats)| {
+            b_stats.code.cmp(&a_stats.code).then_with(|| a.cmp(b))
+        });
+        let lines = stats.iter().map(|(_, stats)| stats.lines()).sum();
+        Self {
+            columns: Self::columns(Command::CodeStats),
+            rows: stats
+                .into_iter()
+                .map(|(language, stats)| {
+                    let values = [stats.files, stats.blank, stats.comment, stats.code];
+                    (language.to_string(), values.map(Value::Count).into())
+                })
+                .collect(),
+            totals: Totals { lines, words: 0 },
+            layout: Layout::Rows,
+        }
+    }
+
+    /// Report of the files, lines and words under each directory, in depth-first order, see
+    /// [`roll_up`]. The totals count the lines and words of all the sources.
+    fn dir_totals(stats: &HashMap<&str, LineStats>, sort: Sort) -> Self {
+        let rows = roll_up(stats, sort)
+            .into_iter()
+            .map(|(dir, depth, totals)| {
+                let values = [depth as u64, totals.files, totals.lines, totals.words];
+                let dir = dir.to_string_lossy().into_owned();
+                (dir, values.map(Value::Count).into())
+            })
+            .collect();
+        Self {
+            columns: Self::columns(Command::DirTotals),
+            rows,
+            totals: Totals {
+                lines: stats.values().map(|stats| stats.lines).sum(),
+                words: stats.values().map(|stats| stats.words).sum(),
+            },
+            layout: Layout::Tree,
+        }
+    }
+
+    /// Report of the matching lines and matches of each source. The matches are the words of
+    /// `--sort`, the totals count the lines read.
+    fn match_count(counts: &HashMap<&str, MatchCount>, sort: Sort) -> Self {
+        let ids = sort.order(
+            counts
+                .iter()
+                .map(|(id, count)| (*id, count.matches, count.lines))
+                .collect(),
+        );
+        Self {
+            columns: Self::columns(Command::MatchCount),
+            rows: ids
+                .into_iter()
+                .map(|id| {
+                    let count = &counts[id];
+                    let values = vec![Value::Count(count.lines), Value::Count(count.matches)];
+                    (id.to_string(), values)
+                })
+                .collect(),
+            totals: Totals {
+                lines: counts.values().map(|count| count.read).sum(),
+                words: 0,
+            },
+            layout: Layout::Rows,
+        }
+    }
+
+    /// Report of the different and duplicate lines of each source. The duplicates are the words
+    /// of `--sort`.
+    fn dedup(counts: &HashMap<&str, DedupCount>, sort: Sort) -> Self {
+        let ids = sort.order(
+            counts
+                .iter()
+                .map(|(id, count)| (*id, count.duplicates(), count.lines))
+                .collect(),
+        );
+        Self {
+            columns: Self::columns(Command::Dedup { approximate: false }),
+            rows: ids
+                .into_iter()
+                .map(|id| {
+                    let count = &counts[id];
+                    let values = vec![
+                        Value::Count(count.lines),
+                        Value::Count(count.distinct),
+                        Value::Count(count.duplicates()),
+                        Value::Ratio(count.duplicate_ratio()),
+                    ];
+                    (id.to_string(), values)
+                })
+                .collect(),
+            totals: Totals {
+                lines: counts.values().map(|count| count.lines).sum(),
+                words: 0,
+            },
+            layout: Layout::Rows,
+        }
+    }
+
+    /// Report of the words and different words of each source. The different words are the
+    /// words of `--sort`.
+    fn diversity(counts: &HashMap<&str, Diversity>, sort: Sort) -> Self {
+        let ids = sort.order(
+            counts
+                .iter()
+                .map(|(id, count)| (*id, count.distinct, count.lines))
+                .collect(),
+        );
+        Self {
+            columns: Self::columns(Command::Diversity { approximate: false }),
+            rows: ids
+                .into_iter()
+                .map(|id| {
+                    let count = &counts[id];
+                    let values = vec![
+                        Value::Count(count.words),
+                        Value::Count(count.distinct),
+                        Value::Ratio(count.ratio()),
+                    ];
+                    (id.to_string(), values)
+                })
+                .collect(),
+            totals: Totals {
+                lines: counts.values().map(|count| count.lines).sum(),
+                words: counts.values().map(|count| count.words).sum(),
+            },
+            layout: Layout::Rows,
+        }
+    }
+
+    /// Report of the characters of each source by class. The characters are the words of
+    /// `--sort`, the totals count the lines read.
+    fn char_classes(counts: &HashMap<&str, CharClasses>, sort: Sort) -> Self {
+        let ids = sort.order(
+            counts
+                .iter()
+                .map(|(id, count)| (*id, count.chars(), count.lines))
+                .collect(),
+        );
+        Self {
+            columns: Self::columns(Command::CharClasses),
+            rows: ids
+                .into_iter()
+                .map(|id| {
+                    let count = &counts[id];
+                    let values = [
+                        count.alphabetic,
+                        count.numeric,
+                        count.punctuation,
+                        count.whitespace,
+                        count.other,
+                    ];
+                    (id.to_string(), values.map(Value::Count).into())
+                })
+                .collect(),
+            totals: Totals {
+                lines: counts.values().map(|count| count.lines).sum(),
+                words: 0,
+            },
+            layout: Layout::Rows,
+        }
+    }
+
+    /// Report of the readability of each source. The words and sentences are the words and lines
+    /// of `--sort`, the totals count the lines read and the words.
+    fn readability(counts: &HashMap<&str, Readability>, sort: Sort) -> Self {
+        let ids = sort.order(
+            counts
+                .iter()
+                .map(|(id, count)| (*id, count.words, count.sentences))
+                .collect(),
+        );
+        Self {
+            columns: Self::columns(Command::Readability),
+            rows: ids
+                .into_iter()
+                .map(|id| {
+                    let count = &counts[id];
+                    let values = vec![
+                        Value::Count(count.sentences),
+                        Value::Count(count.words),
+                        Value::Count(count.syllables),
+                        Value::Ratio(count.reading_ease()),
+                        Value::Ratio(count.grade_level()),
+                    ];
+                    (id.to_string(), values)
+                })
+                .collect(),
+            totals: Totals {
+                lines: counts.values().map(|count| count.lines).sum(),
+                words: counts.values().map(|count| count.words).sum(),
+            },
+            layout: Layout::Rows,
+        }
+    }
+
+    /// Report of the numbers of lines of each source by bucket of `width` words, the same for
+    /// all the sources.
+    pub fn hist(
+        histograms: &HashMap<&str, LineHistogram>,
+        width: Option<usize>,
+        sort: Sort,
+    ) -> Self {
+        let mut totals = Totals::default();
+        let rows: Vec<_> = histograms
+            .iter()
+            .map(|(id, histogram)| {
+                let lines = histogram.iter().sum::<u64>();
+                let words = histogram
+                    .iter()
+                    .enumerate()
+                    .map(|(words, lines)| words as u64 * lines)
+                    .sum::<u64>();
+                totals.lines += lines;
+                totals.words += words;
+                (*id, words, lines)
+            })
+            .collect();
+        let width = bucket_width(histograms.values(), width);
+        let rows = sort
+            .order(rows)
+            .into_iter()
+            .flat_map(|id| {
+                buckets(&histograms[id], width)
+                    .into_iter()
+                    .map(move |(label, lines)| {
+                        (
+                            id.to_string(),
+                            vec![Value::Text(label), Value::Count(lines)],
+                        )
+                    })
+            })
+            .collect();
+        Self {
+            columns: Self::columns(Command::Hist { bucket_width: None }),
+            rows,
+            totals,
+            layout: Layout::Histogram,
+        }
+    }
+
+    /// Rows as maps of all the columns to their value, the key included.
+    pub fn records(&self) -> impl Iterator<Item = impl Serialize + '_> {
+        self.rows
+            .iter()
+            .map(|(key, values)| Row(self.columns, Some(key), values))
+    }
+}
+
+/// Map of columns to the values of a row, after its key if given.
+struct Row<'a>(&'a [&'a str], Option<&'a str>, &'a [Value]);
+
+impl Serialize for Row<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Self(mut columns, key, values) = *self;
+        let mut map = serializer.serialize_map(Some(values.len() + usize::from(key.is_some())))?;
+        if let Some(key) = key {
+            map.serialize_entry(columns[0], key)?;
+            columns = &columns[1..];
+        }
+        for (column, value) in columns.iter().zip(values) {
+            map.serialize_entry(column, value)?;
+        }
+        map.end()
+    }
+}
+
+impl Serialize for Report {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if matches!(self.layout, Layout::Groups | Layout::Histogram) {
+            return serializer.collect_map(
+                self.rows
+                    .chunk_by(|(a, _), (b, _)| a == b)
+                    .map(|rows| (&rows[0].0, Group(rows))),
+            );
+        }
+        let mut map = serializer.serialize_map(Some(self.rows.len()))?;
+        for (key, values) in &self.rows {
+            match values.as_slice() {
+                [value] => map.serialize_entry(key, value)?,
+                values => map.serialize_entry(key, &Row(&self.columns[1..], None, values))?,
+            }
+        }
+        map.end()
+    }
+}
+
+/// Map of the first values of rows to their second value.
+struct Group<'a>(&'a [(String, Vec<Value>)]);
+
+impl Serialize for Group<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            self.0
+                .iter()
+                .map(|(_, values)| (values[0].to_string(), &values[1])),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::SortKey;
+
+    #[test]
+    fn test_report() {
+        let stats = HashMap::from([
+            ("b", [2, 4].into_iter().collect()),
+            ("a", [3].into_iter().collect()),
+        ]);
+        let report = Report::lines(&stats, Sort::default());
+        assert_eq!(report.totals, Totals { lines: 3, words: 9 });
+        assert_eq!(serde_json::to_string(&report).unwrap(), r#"{"a":1,"b":2}"#);
+        let histograms = HashMap::from([("b", vec![0, 0, 1, 0, 1]), ("a", vec![0, 0, 0, 1])]);
+        let report = Report::stats(&histograms, Sort::default());
+        assert_eq!(report.totals, Totals { lines: 3, words: 9 });
+        let report_json = serde_json::to_value(&report).unwrap();
+        assert_eq!(
+            report_json["b"],
+            serde_json::json!({
+                "lines": 2, "words": 6, "min": 2, "max": 4, "mean": 3.0,
+                "median": 2, "p95": 4, "p99": 4, "std_dev": 1.0
+            })
+        );
+        assert_eq!(
+            report_json[ALL_SOURCES],
+            serde_json::json!({
+                "lines": 3, "words": 9, "min": 2, "max": 4, "mean": 3.0,
+                "median": 3, "p95": 4, "p99": 4, "std_dev": (2.0f64 / 3.0).sqrt()
+            })
+        );
+        assert_eq!(report.rows[2].1[4].to_string(), "3.00");
+        let record = serde_json::to_string(&report.records().nth(1).unwrap()).unwrap();
+        assert!(record.starts_with(r#"{"identifier":"a","lines":1,"#));
+
+        let frequencies = Frequencies::from([("a".into(), 1), ("b".into(), 3), ("c".into(), 1)]);
+        let report = Report::freq(frequencies, Totals::default(), Some(2));
+        assert_eq!(serde_json::to_string(&report).unwrap(), r#"{"b":3,"a":1}"#);
+
+        let sources = HashMap::from([
+            (
+                "b".into(),
+                (vec![("x".into(), 2)], Totals { lines: 1, words: 2 }),
+            ),
+            (
+                "a".into(),
+                (vec![("y".into(), 1), ("x".into(), 1)], Totals::default()),
+            ),
+        ]);
+        let global = vec![("x".into(), 3), ("y".into(), 1)];
+        let counts = HashMap::from([
+            (
+                "a",
+                MatchCount {
+                    read: 3,
+                    lines: 1,
+                    matches: 2,
+                },
+            ),
+            (
+                "b",
+                MatchCount {
+                    read: 2,
+                    lines: 2,
+                    matches: 1,
+                },
+            ),
+        ]);
+        let sort = Sort {
+            key: SortKey::Words,
+            reverse: true,
+        };
+        let report = Report::match_count(&counts, sort);
+        assert_eq!(report.totals.lines, 5);
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            r#"{"a":{"lines":1,"matches":2},"b":{"lines":2,"matches":1}}"#
+        );
+
+        let counts = HashMap::from([
+            (
+                "a",
+                DedupCount {
+                    lines: 4,
+                    distinct: 1,
+                },
+            ),
+            (
+                "b",
+                DedupCount {
+                    lines: 2,
+                    distinct: 2,
+                },
+            ),
+        ]);
+        let report = Report::dedup(&counts, Sort::default());
+        assert_eq!(report.totals.lines, 6);
+        assert_eq!(report.rows[0].1[3].to_string(), "0.75");
+        assert_eq!(
+            serde_json::to_value(&report).unwrap()["b"],
+            serde_json::json!({
+                "lines": 2, "distinct": 2, "duplicates": 0, "duplicate_ratio": 0.0
+            })
+        );
+
+        let diversity = Diversity {
+            lines: 1,
+            words: 4,
+            distinct: 3,
+        };
+        let report = Report::diversity(&HashMap::from([("a", diversity)]), Sort::default());
+        assert_eq!(report.rows[0].1[2].to_string(), "0.75");
+        assert_eq!(
+            serde_json::to_value(&report).unwrap()["a"],
+            serde_json::json!({ "words": 4, "distinct": 3, "ratio": 0.75 })
+        );
+
+        let readability = Readability {
+            lines: 2,
+            sentences: 1,
+            words: 4,
+            syllables: 4,
+        };
+        let report = Report::readability(&HashMap::from([("a", readability)]), Sort::default());
+        assert_eq!(report.totals, Totals { lines: 2, words: 4 });
+        assert_eq!(report.rows[0].1[3].to_string(), "118.18");
+        assert_eq!(report.rows[0].1[4].to_string(), "-2.23");
+
+        let report = Report::code_stats(HashMap::from([
+            (
+                "Rust",
+                CodeStats {
+                    files: 2,
+                    blank: 1,
+                    comment: 2,
+                    code: 3,
+                },
+            ),
+            (
+                "TOML",
+                CodeStats {
+                    files: 1,
+                    blank: 0,
+                    comment: 0,
+                    code: 4,
+                },
+            ),
+        ]));
+        assert_eq!(report.totals.lines, 10);
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            r#"{"TOML":{"files":1,"blank":0,"comment":0,"code":4},"Rust":{"files":2,"blank":1,"comment":2,"code":3}}"#
+        );
+
+        let report = Report::top_words(&sources, global, Totals::default(), Sort::default());
+        assert_eq!(
+            report.rows[2],
+            ("a".into(), vec![Value::Text("y".into()), Value::Count(1)])
+        );
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            r#"{"*":{"x":3,"y":1},"a":{"y":1,"x":1},"b":{"x":2}}"#
+        );
+        let record = serde_json::to_string(&report.records().next().unwrap()).unwrap();
+        assert_eq!(record, r#"{"identifier":"*","word":"x","count":3}"#);
+    }
+}
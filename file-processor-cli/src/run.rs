@@ -0,0 +1,105 @@
+use std::{io, time::Instant};
+
+use serde::Serialize;
+
+use crate::{
+    inputs::Inputs,
+    output::{Sort, Tallies},
+    summary::{Summary, Totals},
+    tally::Tallied,
+};
+
+/// Error of a source which could not be opened or read until its end.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SourceError {
+    pub id: String,
+    /// Kind of the I/O error, e.g. `NotFound` or `PermissionDenied`.
+    pub kind: String,
+    pub message: String,
+}
+
+impl SourceError {
+    pub fn new(id: &str, error: &io::Error) -> Self {
+        Self {
+            id: id.to_string(),
+            kind: format!("{:?}", error.kind()),
+            message: error.to_string(),
+        }
+    }
+}
+
+/// Sources and settings of a run, used to write its results.
+pub struct Run<'a> {
+    pub sources: &'a Tallied<Inputs>,
+    pub sort: Option<Sort>,
+    /// Add the metadata of the sources to the results.
+    pub with_meta: bool,
+    /// Number of sources not read whose earlier results are written, see [`Summary::reused`].
+    pub reused: usize,
+    pub start: Instant,
+}
+
+impl Run<'_> {
+    /// Tallies of the sources if their metadata is written.
+    pub fn meta(&self) -> Option<Tallies> {
+        let tallies = self.sources.tallies();
+        self.with_meta.then(|| tallies.iter().cloned().collect())
+    }
+
+    /// Errors of the sources, the ones which could not be opened first.
+    pub fn errors(&self) -> Vec<SourceError> {
+        let mut errors = self.sources.inner().errors();
+        let tallies = self.sources.tallies();
+        let failed = tallies
+            .iter()
+            .filter_map(|(id, tally)| Some(SourceError::new(id, tally.error.get()?)));
+        errors.extend(failed);
+        errors
+    }
+
+    /// Summary of the run so far, with the totals of the written results.
+    pub fn summary(&self, totals: Totals) -> Summary {
+        let mut summary = Summary::new(
+            self.sources.inner().len(),
+            &self.sources.tallies(),
+            totals,
+            self.start.elapsed(),
+        );
+        summary.duplicates = self.sources.duplicates().len();
+        summary.reused = self.reused;
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+    use string_stream_processor::{ProcessorOptions, SourceProvider};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_errors() {
+        let options = ProcessorOptions::default();
+        let inputs = Inputs::new(vec!["fpc_test_missing.txt".into()], None, options);
+        let sources = Tallied::new(inputs);
+        assert_eq!(sources.sources().count().await, 0);
+        let run = Run {
+            sources: &sources,
+            sort: None,
+            with_meta: false,
+            reused: 0,
+            start: Instant::now(),
+        };
+        assert_eq!(
+            run.errors(),
+            [SourceError {
+                id: "fpc_test_missing.txt".to_string(),
+                kind: "NotFound".to_string(),
+                message: "No such file or directory (os error 2)".to_string()
+            }]
+        );
+        assert_eq!(run.summary(Totals::default()).skipped, 1);
+        assert!(run.meta().is_none());
+    }
+}
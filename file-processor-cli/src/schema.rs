@@ -0,0 +1,401 @@
+use serde_json::{json, Value};
+
+use crate::output::{Format, SCHEMA_VERSION};
+
+/// Draft of the JSON Schemas.
+const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// JSON Schema of the results written in a format, for the `schema` command: the envelope of
+/// the documents in its version `version`, the JSON, YAML and TOML ones, with the results of
+/// each line with `per_line`, or a line of NDJSON. The other formats are not JSON.
+pub fn schema(format: Option<Format>, version: u32, per_line: bool) -> Result<Value, String> {
+    if version != SCHEMA_VERSION {
+        return Err(format!("unknown schema version {version}"));
+    }
+    match format {
+        None | Some(Format::Document(_)) => Ok(document(version, per_line)),
+        Some(Format::Ndjson) => Ok(ndjson()),
+        Some(_) => Err("the schema command only describes the documents and NDJSON".into()),
+    }
+}
+
+/// Schema of the envelope of the documents, see [`SCHEMA_VERSION`].
+fn document(version: u32, per_line: bool) -> Value {
+    let results = match per_line {
+        true => json!({
+            "description": "Result of each line of the sources, with --per-line.",
+            "type": "array",
+            "items": line(),
+        }),
+        false => json!({
+            "description": "Results of each source identifier, or of each key of a report.",
+            "type": "object",
+            "additionalProperties": {
+                "anyOf": [
+                    { "$ref": "#/$defs/counts" },
+                    { "$ref": "#/$defs/source_meta" },
+                    { "$ref": "#/$defs/value" },
+                    { "$ref": "#/$defs/row" },
+                ],
+            },
+        }),
+    };
+    json!({
+        "$schema": DIALECT,
+        "title": format!("file-processor-cli document, version {version}"),
+        "type": "object",
+        "required": ["schema_version", "results", "errors", "summary"],
+        "properties": {
+            "schema_version": { "const": version },
+            "results": results,
+            "errors": { "type": "array", "items": { "$ref": "#/$defs/error" } },
+            "summary": { "$ref": "#/$defs/summary" },
+            "duplicates": {
+                "description": "Sources identical to an earlier one, with --dedup-sources.",
+                "type": "object",
+                "additionalProperties": { "type": "string" },
+            },
+            "sources": {
+                "description": "Metadata of the sources of the reports and of the lines, with \
+                                --with-meta.",
+                "type": "object",
+                "additionalProperties": meta(),
+            },
+            "partial": { "const": true },
+        },
+        "$defs": {
+            "counts": counts(),
+            "source_meta": {
+                "description": "Counts of a source with its metadata, with --with-meta.",
+                "type": "object",
+                "required": ["counts", "lines", "bytes", "duration_ms"],
+                "properties": {
+                    "counts": counts(),
+                    "lines": count(),
+                    "bytes": count(),
+                    "duration_ms": { "type": "number" },
+                    "content_sha256": { "type": "string" },
+                    "truncated": { "type": "boolean" },
+                },
+            },
+            "value": value(),
+            "row": {
+                "description": "Values of a report row by column, or of a group by first value.",
+                "type": "object",
+                "additionalProperties": value(),
+            },
+            "error": {
+                "type": "object",
+                "required": ["id", "kind", "message"],
+                "properties": {
+                    "id": { "type": "string" },
+                    "kind": { "type": "string" },
+                    "message": { "type": "string" },
+                },
+            },
+            "summary": {
+                "type": "object",
+                "required": [
+                    "processed", "skipped", "failed", "truncated", "duplicates", "reused",
+                    "lines", "words", "bytes", "elapsed_ms",
+                ],
+                "properties": {
+                    "processed": count(),
+                    "skipped": count(),
+                    "failed": count(),
+                    "truncated": count(),
+                    "duplicates": count(),
+                    "reused": count(),
+                    "lines": count(),
+                    "words": count(),
+                    "bytes": count(),
+                    "elapsed_ms": { "type": "number" },
+                },
+            },
+        },
+    })
+}
+
+/// Schema of a line of the NDJSON results: a source, a line with `--per-line` and the commands
+/// counting each line, a reference to a duplicated source, the metadata of a source or a row of a
+/// report.
+fn ndjson() -> Value {
+    json!({
+        "$schema": DIALECT,
+        "title": "file-processor-cli NDJSON line",
+        "anyOf": [
+            {
+                "type": "object",
+                "required": ["id", "counts"],
+                "properties": {
+                    "id": { "type": "string" },
+                    "counts": counts(),
+                    "lines": count(),
+                    "bytes": count(),
+                    "duration_ms": { "type": "number" },
+                    "content_sha256": { "type": "string" },
+                },
+            },
+            line(),
+            {
+                "type": "object",
+                "required": ["id", "duplicate_of"],
+                "properties": {
+                    "id": { "type": "string" },
+                    "duplicate_of": { "type": "string" },
+                },
+                "additionalProperties": false,
+            },
+            {
+                "description": "Metadata of a source of a report or of lines, with --with-meta.",
+                "type": "object",
+                "required": ["id", "meta"],
+                "properties": {
+                    "id": { "type": "string" },
+                    "meta": meta(),
+                },
+                "additionalProperties": false,
+            },
+            {
+                "description": "Row of a report, by column.",
+                "type": "object",
+                "additionalProperties": value(),
+            },
+        ],
+    })
+}
+
+/// Line of a source with `--per-line`, with its count named after the command.
+fn line() -> Value {
+    json!({
+        "description": "Line of a source, with its count named after the command.",
+        "type": "object",
+        "required": ["id", "line_number"],
+        "properties": {
+            "id": { "type": "string" },
+            "line_number": { "type": "integer", "minimum": 1 },
+        },
+        "minProperties": 3,
+        "maxProperties": 3,
+        "additionalProperties": value(),
+    })
+}
+
+/// Metadata of a source written apart from its results.
+fn meta() -> Value {
+    json!({
+        "type": "object",
+        "required": ["bytes", "duration_ms"],
+        "properties": {
+            "bytes": count(),
+            "duration_ms": { "type": "number" },
+            "content_sha256": { "type": "string" },
+            "truncated": { "type": "boolean" },
+        },
+        "additionalProperties": false,
+    })
+}
+
+fn count() -> Value {
+    json!({ "type": "integer", "minimum": 0 })
+}
+
+fn counts() -> Value {
+    json!({ "description": "Count of each line, in order.", "type": "array", "items": count() })
+}
+
+/// A count, a ratio or a text.
+fn value() -> Value {
+    json!({ "type": ["number", "string"] })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use string_stream_processor::{ProcessorOptions, SourceProvider, StringMultiStreamExt};
+
+    use super::*;
+    use crate::{
+        args::{Args, Command},
+        inputs::Inputs,
+        output::{Document, Output, Sort},
+        report::Report,
+        run::{Run, SourceError},
+        summary::{Summary, Totals},
+        tally::{Tallied, Tally},
+    };
+
+    /// Whether a value is valid against a schema, for the keywords of the schemas of the results.
+    fn is_valid(root: &Value, schema: &Value, value: &Value) -> bool {
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            let name = reference.strip_prefix("#/$defs/").unwrap();
+            return is_valid(root, &root["$defs"][name], value);
+        }
+        let has_type = |name: &Value| match name.as_str().unwrap() {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "integer" => value.is_u64() || value.is_i64(),
+            "number" => value.is_number(),
+            "boolean" => value.is_boolean(),
+            name => panic!("unknown type {name}"),
+        };
+        let valid = |schema| is_valid(root, schema, value);
+        let checks = [
+            schema.get("type").is_none_or(|name| match name {
+                Value::Array(names) => names.iter().any(has_type),
+                name => has_type(name),
+            }),
+            schema.get("const").is_none_or(|constant| constant == value),
+            schema
+                .get("minimum")
+                .is_none_or(|minimum| value.as_f64() >= minimum.as_f64()),
+            schema
+                .get("anyOf")
+                .is_none_or(|schemas| schemas.as_array().unwrap().iter().any(valid)),
+        ];
+        if checks.contains(&false) {
+            return false;
+        }
+        if let (Some(items), Some(values)) = (schema.get("items"), value.as_array()) {
+            return values.iter().all(|value| is_valid(root, items, value));
+        }
+        let Some(object) = value.as_object() else {
+            return true;
+        };
+        let properties = schema.get("properties").and_then(Value::as_object);
+        let size = |keyword| schema.get(keyword).and_then(Value::as_u64);
+        let len = object.len() as u64;
+        let required = schema.get("required").and_then(Value::as_array);
+        required.is_none_or(|names| {
+            names
+                .iter()
+                .all(|name| object.contains_key(name.as_str().unwrap()))
+        }) && size("minProperties").is_none_or(|min| len >= min)
+            && size("maxProperties").is_none_or(|max| len <= max)
+            && object.iter().all(|(name, value)| {
+                match (
+                    properties.and_then(|properties| properties.get(name)),
+                    schema.get("additionalProperties"),
+                ) {
+                    (Some(property), _) => is_valid(root, property, value),
+                    (None, Some(Value::Bool(additional))) => *additional,
+                    (None, Some(additional)) => is_valid(root, additional, value),
+                    (None, None) => true,
+                }
+            })
+    }
+
+    /// Names of the required properties of a schema.
+    fn required(schema: &Value) -> Vec<&str> {
+        let required = schema["required"].as_array().unwrap();
+        required.iter().map(|name| name.as_str().unwrap()).collect()
+    }
+
+    /// Names of the fields of a serialized value.
+    fn fields(value: impl serde::Serialize) -> Vec<String> {
+        let value = serde_json::to_value(value).unwrap();
+        let mut fields: Vec<_> = value.as_object().unwrap().keys().cloned().collect();
+        fields.sort_unstable();
+        fields
+    }
+
+    #[test]
+    fn test_schema() {
+        let document = schema(
+            Some(Format::Document(Document::Yaml)),
+            SCHEMA_VERSION,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            document["properties"]["schema_version"]["const"],
+            SCHEMA_VERSION
+        );
+        let no_tallies: &[(String, Arc<Tally>)] = &[];
+        let summary = Summary::new(0, no_tallies, Totals::default(), Duration::ZERO);
+        let mut summary_fields = required(&document["$defs"]["summary"]);
+        summary_fields.sort_unstable();
+        assert_eq!(fields(summary), summary_fields);
+        let error = SourceError::new("a.txt", &io::Error::other("oops"));
+        let mut error_fields = required(&document["$defs"]["error"]);
+        error_fields.sort_unstable();
+        assert_eq!(fields(error), error_fields);
+
+        let ndjson = schema(Some(Format::Ndjson), SCHEMA_VERSION, false).unwrap();
+        assert_eq!(ndjson["anyOf"].as_array().unwrap().len(), 5);
+        let per_line = schema(None, SCHEMA_VERSION, true).unwrap();
+        assert_eq!(per_line["properties"]["results"]["type"], "array");
+        assert!(schema(Some(Format::Parquet), SCHEMA_VERSION, false).is_err());
+        assert!(schema(None, SCHEMA_VERSION + 1, false).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_schema_of_results() {
+        let dir = std::env::temp_dir().join("fpc_test_schema_of_results");
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.txt");
+        std::fs::write(&file, "a b\n\nc").unwrap();
+        let file = file.to_str().unwrap();
+        let runs: [(&str, &[&str]); 10] = [
+            ("out.json", &[]),
+            ("out.json", &["--per-line"]),
+            ("out.json", &["--checksum"]),
+            ("out.json", &["--per-line", "--checksum"]),
+            ("out.ndjson", &[]),
+            ("out.ndjson", &["--per-line"]),
+            ("out.ndjson", &["--with-meta"]),
+            ("out.ndjson", &["--per-line", "--with-meta"]),
+            ("out.json", &["lines", "--checksum"]),
+            ("out.ndjson", &["lines", "--with-meta"]),
+        ];
+        for (output, flags) in runs {
+            let output = dir.join(output);
+            let output = output.to_str().unwrap();
+            let cli = [&["fpc", "-o", output], flags, &[file]].concat();
+            let args = Args::try_parse_from(cli).unwrap();
+            let options = ProcessorOptions::default();
+            let sources = Tallied::new(Inputs::new(args.files.clone(), None, options));
+            let sources = match args.checksum {
+                true => sources.with_checksums(),
+                false => sources,
+            };
+            let run = Run {
+                sources: &sources,
+                sort: None,
+                with_meta: args.with_meta || args.checksum,
+                reused: 0,
+                start: Instant::now(),
+            };
+            let output_file = Output::create(&args).unwrap();
+            if args.command == Command::Lines {
+                let stats = sources.sources().count_line_words_stats(options).await;
+                let report = Report::lines(&stats, Sort::default());
+                output_file.write_report(&report, &run).unwrap();
+            } else {
+                let results = sources.count_line_words(options).await;
+                output_file.write(&results, &run).unwrap();
+            }
+
+            let format = Format::from_path(Some(output));
+            let schema = schema(Some(format), SCHEMA_VERSION, args.per_line).unwrap();
+            let written = std::fs::read_to_string(output).unwrap();
+            let values: Vec<Value> = match format {
+                Format::Ndjson => written
+                    .lines()
+                    .map(|line| serde_json::from_str(line).unwrap())
+                    .collect(),
+                _ => vec![serde_json::from_str(&written).unwrap()],
+            };
+            for value in values {
+                assert!(is_valid(&schema, &schema, &value), "{flags:?}: {value}");
+            }
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,482 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    io,
+    path::{Path, PathBuf},
+    pin::pin,
+    sync::Arc,
+};
+
+use axum::{
+    extract::{
+        ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        Multipart, State,
+    },
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use futures_util::{stream, StreamExt};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use string_stream_processor::{
+    count_line_words_with, count_url_line_words, Decompress, FileProvider, ProcessorOptions,
+    SourceMeta, SourceProvider, StringMultiStreamExt,
+};
+use tokio::{net::TcpListener, runtime::Handle, sync::mpsc};
+
+use crate::{
+    args::Serve, metrics::Metrics, output::SCHEMA_VERSION, run::SourceError, summary::Totals,
+};
+
+/// Body of `POST /count`: local paths of the server and HTTP(S) URLs to count.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CountRequest {
+    paths: Vec<String>,
+    urls: Vec<String>,
+}
+
+impl CountRequest {
+    /// Keep the sources the server may read, with the errors of the others: the paths under
+    /// the canonical `root`, relative ones resolved from it, and the URLs if `urls` is set.
+    fn allowed(self, root: Option<&Path>, urls: bool) -> (Self, Vec<SourceError>) {
+        let mut errors = Vec::new();
+        let paths = self
+            .paths
+            .into_iter()
+            .filter_map(|path| {
+                resolve(root, &path)
+                    .inspect_err(|e| errors.push(SourceError::new(&path, e)))
+                    .ok()
+            })
+            .collect();
+        let urls = match urls {
+            true => self.urls,
+            false => {
+                let denied = denied("the server fetches no URL without --allow-urls");
+                errors.extend(self.urls.iter().map(|url| SourceError::new(url, &denied)));
+                Vec::new()
+            }
+        };
+        (Self { paths, urls }, errors)
+    }
+}
+
+/// Canonical path of a local file under the canonical `root`, from it if relative. The checked
+/// path is the one read, a link replaced in between is not followed out of `root`.
+fn resolve(root: Option<&Path>, path: &str) -> io::Result<String> {
+    let root = root.ok_or_else(|| denied("the server reads no local path without --root"))?;
+    let path = root.join(path).canonicalize()?;
+    match path.starts_with(root) {
+        true => Ok(path.to_string_lossy().into_owned()),
+        false => Err(denied("the path is outside of --root")),
+    }
+}
+
+fn denied(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, reason)
+}
+
+/// Counts of the words of each line of the sources of a request, the metadata of the local
+/// files collected while reading them, and the errors of the ones which could not be read,
+/// versioned like the documents of the command line.
+#[derive(Debug, Default, Serialize)]
+struct CountResponse {
+    schema_version: u32,
+    results: BTreeMap<String, Vec<usize>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: BTreeMap<String, SourceMeta>,
+    errors: Vec<SourceError>,
+}
+
+impl CountResponse {
+    fn new() -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            ..Self::default()
+        }
+    }
+
+    /// Count the request in the metrics, with the gauges of its sources.
+    fn record(&self, metrics: &Metrics) {
+        let mut totals = Totals::default();
+        for (id, counts) in &self.results {
+            totals.add(counts);
+            metrics.set_counts(id, counts, self.metadata.get(id).map(|meta| meta.bytes));
+        }
+        let bytes = self.metadata.values().map(|meta| meta.bytes).sum();
+        metrics.record_run(self.results.len(), self.errors.len(), totals, bytes);
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Server {
+    options: ProcessorOptions,
+    client: Client,
+    metrics: Arc<Metrics>,
+    /// Canonical directory of the local paths of the requests, none are read without it.
+    root: Option<Arc<Path>>,
+    /// Whether the URLs of the requests are fetched.
+    urls: bool,
+}
+
+/// Event of a count streamed over a WebSocket, see [`count_ws`].
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event {
+    /// Counts of a source, once it is read entirely.
+    Result { id: String, counts: Vec<usize> },
+    /// Error of a source which could not be read.
+    Error {
+        #[serde(flatten)]
+        error: SourceError,
+    },
+    /// End of the count, after the results and errors of all the sources.
+    Done { results: usize, errors: usize },
+}
+
+/// Number of events buffered for a WebSocket client, above which the sources are not read
+/// anymore until it receives them.
+const BUFFERED_EVENTS: usize = 64;
+
+/// Serve the API on `addr` until the process is interrupted:
+/// - `POST /count` with a JSON object of `paths` and `urls` to count,
+/// - `GET /count/ws` upgraded to a WebSocket, receiving the same object in a text message and
+///   sending an event for each source as soon as it is counted, see [`Event`],
+/// - `POST /upload` with a `multipart/form-data` body of the files to count, identified by their
+///   file name,
+/// - `GET /metrics` with the counters of the requests and the gauges of their sources, in the
+///   Prometheus text format, see [`Metrics`].
+///
+/// The paths are read by the server under its `--root` only, and the URLs fetched with
+/// `--allow-urls` only, the other sources are errors of the responses. It listens on the
+/// loopback interface by default, or on the `activated` socket passed by systemd with socket
+/// activation, see [`listener`](crate::systemd::listener).
+pub async fn serve(
+    serve: &Serve,
+    options: ProcessorOptions,
+    activated: Option<std::net::TcpListener>,
+) -> io::Result<()> {
+    let root = serve.root.as_deref().map(Path::canonicalize).transpose()?;
+    let listener = match activated {
+        Some(listener) => TcpListener::from_std(listener)?,
+        None => TcpListener::bind(serve.listen).await?,
+    };
+    log::info!("Listening on http://{}.", listener.local_addr()?);
+    let router = router(options, Arc::default(), root, serve.allow_urls);
+    axum::serve(listener, router).await
+}
+
+fn router(
+    options: ProcessorOptions,
+    metrics: Arc<Metrics>,
+    root: Option<PathBuf>,
+    urls: bool,
+) -> Router {
+    let server = Server {
+        options,
+        client: Client::new(),
+        metrics: Arc::clone(&metrics),
+        root: root.map(Arc::from),
+        urls,
+    };
+    Router::new()
+        .route("/count", post(count))
+        .route("/count/ws", get(count_ws))
+        .route("/upload", post(upload))
+        .with_state(server)
+        .merge(crate::metrics::router(metrics))
+}
+
+async fn count(
+    State(server): State<Server>,
+    Json(request): Json<CountRequest>,
+) -> Result<Json<CountResponse>, (StatusCode, String)> {
+    let Server {
+        options,
+        client,
+        metrics,
+        root,
+        urls,
+    } = server;
+    let (request, errors) = request.allowed(root.as_deref(), urls);
+    counted(metrics, move || async move {
+        let mut response = CountResponse::new();
+        let files = FileProvider::new(request.paths, options);
+        for (path, result) in files.count_line_words_with_meta(options).await {
+            response.results.insert(path.to_string(), result.counts);
+            response.metadata.insert(path.to_string(), result.meta);
+        }
+        response.errors = errors;
+        response.errors.extend(
+            files
+                .errors()
+                .iter()
+                .map(|(path, e)| SourceError::new(path, e)),
+        );
+        for (url, counts) in count_url_line_words(&client, &request.urls, options).await {
+            match counts {
+                Ok(counts) => {
+                    response.results.insert(url.to_string(), counts);
+                }
+                Err(e) => response.errors.push(SourceError::new(url, &e)),
+            }
+        }
+        response
+    })
+    .await
+}
+
+async fn count_ws(State(server): State<Server>, ws: WebSocketUpgrade) -> axum::response::Response {
+    ws.on_upgrade(move |socket| stream_counts(server, socket))
+}
+
+/// Count the sources of the first message of the socket, sending their events as they come,
+/// until the client is gone.
+async fn stream_counts(server: Server, mut socket: WebSocket) {
+    let request = match socket.recv().await {
+        Some(Ok(Message::Text(request))) => serde_json::from_str::<CountRequest>(&request),
+        _ => return,
+    };
+    let request = match request {
+        Ok(request) => request,
+        Err(e) => {
+            let close = CloseFrame {
+                code: close_code::INVALID,
+                reason: format!("invalid request, {e}").into(),
+            };
+            let _ = socket.send(Message::Close(Some(close))).await;
+            return;
+        }
+    };
+    let (tx, mut events) = mpsc::channel(BUFFERED_EVENTS);
+    let handle = Handle::current();
+    tokio::task::spawn_blocking(move || handle.block_on(count_events(server, request, tx)));
+    while let Some(event) = events.recv().await {
+        let event = serde_json::to_string(&event).expect("the events are serializable");
+        if socket.send(Message::Text(event.into())).await.is_err() {
+            return;
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
+
+/// Send the event of each source of the request as soon as it is counted, then the end of the
+/// count, until the receiver is dropped.
+async fn count_events(server: Server, request: CountRequest, tx: mpsc::Sender<Event>) {
+    let Server {
+        options,
+        client,
+        metrics,
+        root,
+        urls,
+    } = server;
+    let (request, denied) = request.allowed(root.as_deref(), urls);
+    let denied = stream::iter(denied).map(|error| Event::Error { error });
+    let files = FileProvider::new(request.paths, options);
+    let file_events = files
+        .sources()
+        .flat_map_unordered(options.max_concurrency, |(id, rd)| {
+            stream::once(Box::pin(async move {
+                let counts = count_line_words_with(rd, options).collect().await;
+                Event::Result {
+                    id: id.to_string(),
+                    counts,
+                }
+            }))
+        });
+    // The files which cannot be opened are known once the others are counted.
+    let file_errors = stream::once(async {
+        let errors = files.errors();
+        let errors = errors.iter().map(|(path, e)| SourceError::new(path, e));
+        stream::iter(
+            errors
+                .map(|error| Event::Error { error })
+                .collect::<Vec<_>>(),
+        )
+    })
+    .flatten();
+    let client = &client;
+    let url_events =
+        stream::iter(&request.urls).flat_map_unordered(options.max_concurrency, |url| {
+            stream::once(Box::pin(async move {
+                let urls = std::slice::from_ref(url);
+                let mut counts = count_url_line_words(client, urls, options).await;
+                match counts.remove(url.as_str()).expect("the URL is counted") {
+                    Ok(counts) => Event::Result {
+                        id: url.clone(),
+                        counts,
+                    },
+                    Err(e) => Event::Error {
+                        error: SourceError::new(url, &e),
+                    },
+                }
+            }))
+        });
+    let file_events = denied.chain(file_events).chain(file_errors);
+    let mut events = pin!(stream::select(file_events, url_events));
+    let (mut results, mut errors) = (0, 0);
+    let mut totals = Totals::default();
+    while let Some(event) = events.next().await {
+        match &event {
+            Event::Result { id, counts } => {
+                results += 1;
+                totals.add(counts);
+                metrics.set_counts(id, counts, None);
+            }
+            _ => errors += 1,
+        }
+        if tx.send(event).await.is_err() {
+            return;
+        }
+    }
+    metrics.record_run(results, errors, totals, 0);
+    let _ = tx.send(Event::Done { results, errors }).await;
+}
+
+async fn upload(
+    State(server): State<Server>,
+    mut multipart: Multipart,
+) -> Result<Json<CountResponse>, (StatusCode, String)> {
+    let bad_request = |e: axum::extract::multipart::MultipartError| (e.status(), e.body_text());
+    let mut files = Vec::new();
+    while let Some(field) = multipart.next_field().await.map_err(bad_request)? {
+        let name = field.file_name().or(field.name()).unwrap_or("upload");
+        let name = name.to_string();
+        files.push((name, field.bytes().await.map_err(bad_request)?));
+    }
+    let options = server.options;
+    counted(server.metrics, move || async move {
+        let mut response = CountResponse::new();
+        let mut sources = Vec::with_capacity(files.len());
+        for (name, data) in &files {
+            match Decompress::detect(name, &data[..], options.read_buffer_size).await {
+                Ok(rd) => sources.push((name.as_str(), rd)),
+                Err(e) => response.errors.push(SourceError::new(name, &e)),
+            }
+        }
+        let results = stream::iter(sources)
+            .count_line_words_concurrent_with(options)
+            .await;
+        for (name, counts) in results {
+            response.results.insert(name.to_string(), counts);
+        }
+        response
+    })
+    .await
+}
+
+/// Run the future of a count on a blocking thread of the runtime, as the providers cannot be
+/// shared between its worker threads, and record its response in the metrics.
+async fn counted<F: Future<Output = CountResponse>>(
+    metrics: Arc<Metrics>,
+    count: impl FnOnce() -> F + Send + 'static,
+) -> Result<Json<CountResponse>, (StatusCode, String)> {
+    let handle = Handle::current();
+    tokio::task::spawn_blocking(move || handle.block_on(count()))
+        .await
+        .inspect(|response| response.record(&metrics))
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::SinkExt;
+    use reqwest::multipart::{Form, Part};
+    use tokio_tungstenite::tungstenite;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let root = std::env::temp_dir().canonicalize().unwrap();
+        let router = router(
+            ProcessorOptions::default(),
+            Arc::default(),
+            Some(root.clone()),
+            false,
+        );
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        let client = Client::new();
+
+        let path = root.join("fpc_test_serve.txt");
+        std::fs::write(&path, "a b\nc\n").unwrap();
+        let path = path.to_str().unwrap().to_string();
+        let request = serde_json::json!({
+            "paths": [path, "fpc_test_missing.txt", ".."],
+            "urls": ["http://127.0.0.1:1/a.txt"],
+        });
+        let response = client.post(format!("{url}/count")).json(&request).send();
+        let response: serde_json::Value = response.await.unwrap().json().await.unwrap();
+        assert_eq!(response["results"], serde_json::json!({&path: [2, 1]}));
+        let metadata = &response["metadata"][&path];
+        assert_eq!(
+            (&metadata["bytes"], &metadata["size"]),
+            (&6.into(), &6.into())
+        );
+        assert_eq!(metadata["encoding"], "ascii");
+        assert!(metadata["modified"].is_f64());
+        assert_eq!(response["errors"][0]["id"], "fpc_test_missing.txt");
+        let denied = &response["errors"].as_array().unwrap()[1..];
+        assert_eq!(
+            (&denied[0]["id"], &denied[1]["id"]),
+            (&"..".into(), &request["urls"][0])
+        );
+        assert!(denied
+            .iter()
+            .all(|error| error["kind"] == "PermissionDenied"));
+
+        let ws_url = format!("{}/count/ws", url.replace("http", "ws"));
+        let (mut socket, _) = tokio_tungstenite::connect_async(ws_url).await.unwrap();
+        let request = tungstenite::Message::text(request.to_string());
+        socket.send(request).await.unwrap();
+        let events: Vec<serde_json::Value> = socket
+            .filter_map(|message| async move { message.unwrap().into_text().ok() })
+            .filter(|text| std::future::ready(!text.is_empty()))
+            .map(|text| serde_json::from_str(&text).unwrap())
+            .collect()
+            .await;
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[0]["kind"], "NotFound");
+        assert_eq!(events[1]["kind"], "PermissionDenied");
+        assert_eq!(
+            events[3],
+            serde_json::json!({"event": "result", "id": path, "counts": [2, 1]})
+        );
+        assert_eq!(
+            events[4],
+            serde_json::json!({"event": "done", "results": 1, "errors": 3})
+        );
+
+        let form = Form::new()
+            .part("a", Part::bytes(&b"a b c\n"[..]).file_name("a.txt"))
+            .part("b", Part::bytes(&b"d\n\n"[..]));
+        let response = client.post(format!("{url}/upload")).multipart(form).send();
+        let response: serde_json::Value = response.await.unwrap().json().await.unwrap();
+        assert_eq!(
+            response,
+            serde_json::json!({
+                "schema_version": SCHEMA_VERSION,
+                "results": {"a.txt": [3], "b": [1, 0]},
+                "errors": [],
+            })
+        );
+
+        let response = client.post(format!("{url}/count")).body("{}").send();
+        assert_eq!(
+            response.await.unwrap().status(),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+
+        let metrics = client.get(format!("{url}/metrics")).send().await.unwrap();
+        let metrics = metrics.text().await.unwrap();
+        assert!(metrics.contains("fpc_runs_total 3\n"));
+        assert!(metrics.contains("fpc_source_errors_total 6\n"));
+        assert!(metrics.contains(&format!("fpc_source_bytes{{source=\"{path}\"}} 6\n")));
+        assert!(metrics.contains("fpc_source_words{source=\"a.txt\"} 3\n"));
+    }
+}
@@ -0,0 +1,341 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Stdout, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use flate2::write::GzEncoder;
+use reqwest::{
+    header::{CONTENT_ENCODING, CONTENT_TYPE},
+    StatusCode,
+};
+use string_stream_processor::RetryPolicy;
+
+/// Destination of the results, which must be committed once they are all written.
+pub trait Sink: Write + Send {
+    /// Flush the results and make them visible.
+    fn commit(self: Box<Self>) -> io::Result<()>;
+}
+
+impl Sink for BufWriter<Stdout> {
+    fn commit(mut self: Box<Self>) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+/// Gzip compression of the results, the trailer is written on commit.
+impl Sink for GzEncoder<Box<dyn Sink>> {
+    fn commit(self: Box<Self>) -> io::Result<()> {
+        self.finish()?.commit()
+    }
+}
+
+/// Zstandard compression of the results, the last frame is written on commit.
+impl Sink for zstd::Encoder<'static, Box<dyn Sink>> {
+    fn commit(self: Box<Self>) -> io::Result<()> {
+        self.finish()?.commit()
+    }
+}
+
+/// Compression of the results, from `--output-compression`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputCompression {
+    Gzip,
+    Zstd,
+}
+
+impl OutputCompression {
+    /// Compression implied by the `.gz` or `.zst` extension of an output file.
+    pub fn from_path(path: &str) -> Option<Self> {
+        match Path::new(path).extension()?.to_str()? {
+            "gz" => Some(Self::Gzip),
+            "zst" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Value of the `Content-Encoding` header of the compressed results.
+    pub fn content_encoding(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    /// Compress the results written to a sink, with the default level.
+    pub fn encoder(self, sink: Box<dyn Sink>) -> io::Result<Box<dyn Sink>> {
+        Ok(match self {
+            Self::Gzip => Box::new(GzEncoder::new(sink, flate2::Compression::default())),
+            Self::Zstd => Box::new(zstd::Encoder::new(sink, 0)?),
+        })
+    }
+}
+
+/// File written into a temporary file of the same directory, renamed to its path on commit so
+/// that an interrupted run never leaves a truncated file. The temporary file is removed if the
+/// results are not committed.
+#[derive(Debug)]
+pub struct AtomicFile {
+    file: BufWriter<File>,
+    tmp: PathBuf,
+    path: PathBuf,
+}
+
+impl AtomicFile {
+    /// Create the temporary file of `path`, `path` itself is left untouched until the commit.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let name = path.file_name().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "output path is a directory")
+        })?;
+        let mut tmp_name = std::ffi::OsString::from(".");
+        tmp_name.push(name);
+        tmp_name.push(format!(".{}.tmp", std::process::id()));
+        let tmp = path.with_file_name(tmp_name);
+        let file = BufWriter::new(File::create(&tmp)?);
+        Ok(Self { file, tmp, path })
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Sink for AtomicFile {
+    fn commit(mut self: Box<Self>) -> io::Result<()> {
+        self.file.flush()?;
+        self.file.get_ref().sync_all()?;
+        fs::rename(&self.tmp, &self.path)
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        // Already renamed once committed.
+        let _ = fs::remove_file(&self.tmp);
+    }
+}
+
+/// Results posted to an HTTP endpoint on commit, with a bearer token if given. The connection
+/// errors, the timeouts and the server errors are retried with the delays of the policy.
+#[derive(Debug)]
+pub struct HttpPost {
+    url: String,
+    token: Option<String>,
+    content_type: &'static str,
+    /// Compression of the body by a sink around this one, if any.
+    compression: Option<OutputCompression>,
+    retry: RetryPolicy,
+    body: Vec<u8>,
+}
+
+impl HttpPost {
+    pub fn new(
+        url: &str,
+        token: Option<String>,
+        content_type: &'static str,
+        compression: Option<OutputCompression>,
+        retry: RetryPolicy,
+    ) -> Self {
+        Self {
+            url: url.to_string(),
+            token,
+            content_type,
+            compression,
+            retry,
+            body: Vec::new(),
+        }
+    }
+
+    fn post(&self) -> io::Result<()> {
+        let client = reqwest::blocking::Client::new();
+        let mut attempt = 0;
+        loop {
+            let mut request = client
+                .post(&self.url)
+                .header(CONTENT_TYPE, self.content_type)
+                .body(self.body.clone());
+            if let Some(compression) = self.compression {
+                request = request.header(CONTENT_ENCODING, compression.content_encoding());
+            }
+            if let Some(token) = &self.token {
+                request = request.bearer_auth(token);
+            }
+            let (transient, error) = match request.send() {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) => {
+                    let status = response.status();
+                    let transient =
+                        status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS;
+                    let error = format!("{} answered {status}", self.url);
+                    (transient, io::Error::other(error))
+                }
+                Err(e) => (e.is_connect() || e.is_timeout(), io::Error::other(e)),
+            };
+            attempt += 1;
+            if !transient || attempt > self.retry.max_attempts {
+                let error = format!("Could not post the results to {}, {error}", self.url);
+                return Err(io::Error::other(error));
+            }
+            log::warn!(
+                "Could not post the results to {}, {error}, retrying.",
+                self.url
+            );
+            std::thread::sleep(self.retry.delay(attempt));
+        }
+    }
+}
+
+impl Write for HttpPost {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.body.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Sink for HttpPost {
+    fn commit(self: Box<Self>) -> io::Result<()> {
+        // The blocking client cannot run on a thread of the runtime.
+        std::thread::scope(|scope| scope.spawn(|| self.post()).join())
+            .unwrap_or_else(|_| Err(io::Error::other("the post of the results panicked")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read},
+        net::TcpListener,
+        time::Duration,
+    };
+
+    use flate2::{read::GzDecoder, Compression};
+
+    use super::*;
+
+    #[test]
+    fn test_atomic_file() {
+        let path = std::env::temp_dir().join("fpc_test_atomic_file.json");
+        let _ = fs::remove_file(&path);
+        let mut file = Box::new(AtomicFile::create(&path).unwrap());
+        let tmp = file.tmp.clone();
+        file.write_all(b"{}").unwrap();
+        file.flush().unwrap();
+        assert!(!path.exists());
+        file.commit().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"{}");
+        assert!(!tmp.exists());
+
+        let mut file = AtomicFile::create(&path).unwrap();
+        file.write_all(b"{").unwrap();
+        drop(file);
+        assert_eq!(fs::read(&path).unwrap(), b"{}");
+        assert!(!tmp.exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    /// Answer the requests with the statuses in order, returning their headers and bodies.
+    fn serve(statuses: &'static [u16]) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/results", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for status in statuses {
+                let (socket, _) = listener.accept().unwrap();
+                let mut rd = BufReader::new(&socket);
+                let mut request = String::new();
+                let mut len = 0;
+                loop {
+                    let mut line = String::new();
+                    rd.read_line(&mut line).unwrap();
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length: ") {
+                        len = value.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                    request.push_str(&line);
+                }
+                let mut body = vec![0; len];
+                rd.read_exact(&mut body).unwrap();
+                request.push_str(&String::from_utf8(body).unwrap());
+                requests.push(request);
+                let response = format!("HTTP/1.1 {status} Status\r\ncontent-length: 0\r\n\r\n");
+                (&socket).write_all(response.as_bytes()).unwrap();
+            }
+            requests
+        });
+        (url, server)
+    }
+
+    #[test]
+    fn test_http_post() {
+        let (url, server) = serve(&[503, 200]);
+        let retry = RetryPolicy::new(2, Duration::from_millis(1));
+        let mut post = Box::new(HttpPost::new(
+            &url,
+            Some("secret".into()),
+            "application/json",
+            None,
+            retry,
+        ));
+        post.write_all(b"{}").unwrap();
+        post.commit().unwrap();
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].starts_with("POST /results HTTP/1.1"));
+        assert!(requests[1].contains("authorization: Bearer secret"));
+        assert!(requests[1].contains("content-type: application/json"));
+        assert!(requests[1].ends_with("{}"));
+
+        let (url, server) = serve(&[404]);
+        let post = Box::new(HttpPost::new(&url, None, "text/csv", None, retry));
+        assert!(post.commit().is_err());
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_gzip_sink() {
+        let path = std::env::temp_dir().join("fpc_test_gzip_sink.json.gz");
+        let file: Box<dyn Sink> = Box::new(AtomicFile::create(&path).unwrap());
+        let mut gzip = Box::new(GzEncoder::new(file, Compression::default()));
+        gzip.write_all(b"{}").unwrap();
+        gzip.commit().unwrap();
+
+        let mut data = String::new();
+        GzDecoder::new(File::open(&path).unwrap())
+            .read_to_string(&mut data)
+            .unwrap();
+        assert_eq!(data, "{}");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_zstd_sink() {
+        let path = std::env::temp_dir().join("fpc_test_zstd_sink.ndjson.zst");
+        assert_eq!(
+            OutputCompression::from_path(path.to_str().unwrap()),
+            Some(OutputCompression::Zstd)
+        );
+        assert_eq!(OutputCompression::from_path("out.ndjson"), None);
+        let file: Box<dyn Sink> = Box::new(AtomicFile::create(&path).unwrap());
+        let mut zstd = OutputCompression::Zstd.encoder(file).unwrap();
+        zstd.write_all(b"{}\n{}\n").unwrap();
+        zstd.commit().unwrap();
+
+        let data = zstd::decode_all(File::open(&path).unwrap()).unwrap();
+        assert_eq!(data, b"{}\n{}\n");
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,192 @@
+use std::{fmt, sync::atomic::Ordering, time::Duration};
+
+use serde::{Serialize, Serializer};
+
+use crate::tally::Tally;
+
+/// Number of lines and words of the written results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Totals {
+    pub lines: u64,
+    pub words: u64,
+}
+
+impl Totals {
+    /// Add the counts of the lines of a source.
+    pub fn add(&mut self, counts: &[usize]) {
+        self.lines += counts.len() as u64;
+        self.words += counts.iter().sum::<usize>() as u64;
+    }
+}
+
+/// Summary of a run, printed on the standard error with `--summary` and in the envelope of
+/// the documents.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    pub processed: usize,
+    /// Sources which could not be opened.
+    pub skipped: usize,
+    /// Sources which could not be read until their end.
+    pub failed: usize,
+    /// Sources whose reading stopped at `--max-lines` or was skipped before their end.
+    pub truncated: usize,
+    /// Sources identical to another one with `--dedup-sources`, written as references to it.
+    pub duplicates: usize,
+    /// Sources not read again whose results of an earlier run are written, e.g. with
+    /// `--merge-with`.
+    pub reused: usize,
+    #[serde(flatten)]
+    pub totals: Totals,
+    pub bytes: u64,
+    #[serde(rename = "elapsed_ms", serialize_with = "serialize_ms")]
+    pub elapsed: Duration,
+}
+
+pub fn serialize_ms<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64() * 1e3)
+}
+
+/// Exit code of a run where some sources could not be processed, with `--strict`.
+pub const PARTIAL_FAILURE: u8 = 2;
+/// Exit code of a run where no source could be processed.
+pub const TOTAL_FAILURE: u8 = 3;
+/// Exit code of a run interrupted by a signal, whose partial results were written, like the
+/// shells for `SIGINT`.
+pub const INTERRUPTED: u8 = 130;
+
+impl Summary {
+    /// Summary of the `requested` sources, of which the `tallies` ones were opened.
+    pub fn new(
+        requested: usize,
+        tallies: &[(String, impl AsRef<Tally>)],
+        totals: Totals,
+        elapsed: Duration,
+    ) -> Self {
+        let failed = tallies
+            .iter()
+            .filter(|(_, tally)| tally.as_ref().error.get().is_some())
+            .count();
+        let truncated = tallies
+            .iter()
+            .filter(|(_, tally)| tally.as_ref().is_truncated())
+            .count();
+        let bytes = tallies
+            .iter()
+            .map(|(_, tally)| tally.as_ref().bytes.load(Ordering::Relaxed))
+            .sum();
+        Self {
+            processed: tallies.len() - failed,
+            skipped: requested.saturating_sub(tallies.len()),
+            failed,
+            truncated,
+            duplicates: 0,
+            reused: 0,
+            totals,
+            bytes,
+            elapsed,
+        }
+    }
+
+    /// Exit code of the run: [`TOTAL_FAILURE`] if no source could be processed,
+    /// [`PARTIAL_FAILURE`] if some could not and `strict` is set, and 0 otherwise.
+    pub fn exit_code(&self, strict: bool) -> u8 {
+        let errors = self.skipped + self.failed;
+        if errors > 0 && self.processed == 0 {
+            TOTAL_FAILURE
+        } else if errors > 0 && strict {
+            PARTIAL_FAILURE
+        } else {
+            0
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.elapsed.as_secs_f64();
+        let throughput = if secs > 0.0 {
+            self.bytes as f64 / 1e6 / secs
+        } else {
+            0.0
+        };
+        write!(
+            f,
+            "{} files processed, {} skipped, {} failed",
+            self.processed, self.skipped, self.failed
+        )?;
+        if self.truncated > 0 {
+            write!(f, ", {} truncated", self.truncated)?;
+        }
+        if self.duplicates > 0 {
+            write!(f, ", {} duplicates", self.duplicates)?;
+        }
+        if self.reused > 0 {
+            write!(f, ", {} reused", self.reused)?;
+        }
+        write!(
+            f,
+            ": {} lines, {} words, {} bytes in {secs:.3}s ({throughput:.2} MB/s)",
+            self.totals.lines, self.totals.words, self.bytes
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let read = Arc::new(Tally::default());
+        read.bytes.store(1_500_000, Ordering::Relaxed);
+        let failed = Arc::new(Tally::default());
+        let error = std::io::Error::from(std::io::ErrorKind::BrokenPipe);
+        failed.error.set(error).unwrap();
+        let tallies = [("a".to_string(), read), ("b".to_string(), failed)];
+        let truncated = Arc::new(Tally::default());
+        truncated.truncated.store(true, Ordering::Relaxed);
+        let skipped = Arc::new(Tally::default());
+        skipped.skip();
+        let sampled = [("c".to_string(), truncated), ("d".to_string(), skipped)];
+        let summary = Summary::new(2, &sampled, Totals::default(), Duration::ZERO);
+        assert_eq!((summary.processed, summary.truncated), (2, 2));
+        assert!(summary
+            .to_string()
+            .starts_with("2 files processed, 0 skipped, 0 failed, 2 truncated: "));
+        let mut totals = Totals::default();
+        totals.add(&[2, 0, 3]);
+        let summary = Summary::new(3, &tallies, totals, Duration::from_millis(500));
+        assert_eq!(
+            (summary.processed, summary.skipped, summary.failed),
+            (1, 1, 1)
+        );
+        assert_eq!(
+            summary.to_string(),
+            "1 files processed, 1 skipped, 1 failed: 3 lines, 5 words, 1500000 bytes in 0.500s \
+             (3.00 MB/s)"
+        );
+        assert_eq!(summary.exit_code(false), 0);
+        assert_eq!(summary.exit_code(true), PARTIAL_FAILURE);
+        let failed = Summary::new(1, &tallies[1..], Totals::default(), Duration::ZERO);
+        assert_eq!(failed.exit_code(false), TOTAL_FAILURE);
+        let done = Summary::new(1, &tallies[..1], Totals::default(), Duration::ZERO);
+        assert_eq!(done.exit_code(true), 0);
+        assert_eq!(
+            serde_json::to_value(&summary).unwrap(),
+            serde_json::json!({
+                "processed": 1,
+                "skipped": 1,
+                "failed": 1,
+                "truncated": 0,
+                "duplicates": 0,
+                "reused": 0,
+                "lines": 3,
+                "words": 5,
+                "bytes": 1_500_000,
+                "elapsed_ms": 500.0
+            })
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use std::io;
+
+/// First file descriptor passed by systemd, `SD_LISTEN_FDS_START`.
+#[cfg(unix)]
+const LISTEN_FDS_START: i32 = 3;
+
+/// Listening socket passed by systemd with socket activation, like `sd_listen_fds`, e.g. to
+/// listen on a privileged port without running as root. `None` when the process was not
+/// activated by a socket.
+///
+/// The variables of the activation are removed so that they are not inherited by the child
+/// processes, it must be called before any other thread is started, e.g. the ones of the
+/// runtime. Only the first socket is served when several are passed, it must be a TCP socket.
+#[cfg(unix)]
+pub fn listener() -> io::Result<Option<std::net::TcpListener>> {
+    use std::{env, os::fd::FromRawFd};
+
+    let (Ok(pid), Ok(fds)) = (env::var("LISTEN_PID"), env::var("LISTEN_FDS")) else {
+        return Ok(None);
+    };
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+    let Some(fd) = listen_fd(&pid, &fds, std::process::id())? else {
+        return Ok(None);
+    };
+    check_tcp(fd)?;
+    // SAFETY: systemd passes the ownership of the sockets from `LISTEN_FDS_START` to the
+    // process of `LISTEN_PID`, which is checked above.
+    let listener = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+    listener.set_nonblocking(true)?;
+    Ok(Some(listener))
+}
+
+/// Check that a file descriptor is a stream socket of the IPv4 or IPv6 family.
+#[cfg(unix)]
+fn check_tcp(fd: i32) -> io::Result<()> {
+    use std::mem;
+
+    let mut kind: libc::c_int = 0;
+    let mut len = mem::size_of_val(&kind) as libc::socklen_t;
+    // SAFETY: `kind` is valid for writes of `len` bytes.
+    let got = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_TYPE,
+            (&mut kind as *mut libc::c_int).cast(),
+            &mut len,
+        )
+    };
+    if got != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: the address is plain data, valid when zeroed.
+    let mut addr: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let mut len = mem::size_of_val(&addr) as libc::socklen_t;
+    // SAFETY: `addr` is valid for writes of `len` bytes.
+    let got = unsafe {
+        libc::getsockname(
+            fd,
+            (&mut addr as *mut libc::sockaddr_storage).cast(),
+            &mut len,
+        )
+    };
+    if got != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let family = i32::from(addr.ss_family);
+    if kind != libc::SOCK_STREAM || ![libc::AF_INET, libc::AF_INET6].contains(&family) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the socket passed by systemd is not a TCP socket",
+        ));
+    }
+    Ok(())
+}
+
+/// Socket activation is only supported on Unix.
+#[cfg(not(unix))]
+pub fn listener() -> io::Result<Option<std::net::TcpListener>> {
+    Ok(None)
+}
+
+/// File descriptor of the first socket of the `LISTEN_PID` and `LISTEN_FDS` variables, if they
+/// are meant for the process `pid`.
+#[cfg(unix)]
+fn listen_fd(listen_pid: &str, listen_fds: &str, pid: u32) -> io::Result<Option<i32>> {
+    let invalid = |name| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid {name} of the socket activation"),
+        )
+    };
+    let listen_pid: u32 = listen_pid.parse().map_err(|_| invalid("LISTEN_PID"))?;
+    if listen_pid != pid {
+        return Ok(None);
+    }
+    match listen_fds
+        .parse::<i32>()
+        .map_err(|_| invalid("LISTEN_FDS"))?
+    {
+        0 => Ok(None),
+        1 => Ok(Some(LISTEN_FDS_START)),
+        fds if fds > 1 => {
+            log::warn!("Only the first of the {fds} sockets passed by systemd is served.");
+            Ok(Some(LISTEN_FDS_START))
+        }
+        _ => Err(invalid("LISTEN_FDS")),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listen_fd() {
+        assert_eq!(listen_fd("42", "1", 42).unwrap(), Some(3));
+        assert_eq!(listen_fd("42", "2", 42).unwrap(), Some(3));
+        assert_eq!(listen_fd("42", "0", 42).unwrap(), None);
+        assert_eq!(listen_fd("7", "1", 42).unwrap(), None);
+        assert!(listen_fd("42", "-1", 42).is_err());
+        assert!(listen_fd("pid", "1", 42).is_err());
+    }
+
+    #[test]
+    fn test_check_tcp() {
+        use std::os::fd::AsRawFd;
+
+        let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        assert!(check_tcp(tcp.as_raw_fd()).is_ok());
+        let udp = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let error = check_tcp(udp.as_raw_fd()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        let (unix, _) = std::os::unix::net::UnixStream::pair().unwrap();
+        assert!(check_tcp(unix.as_raw_fd()).is_err());
+        let file = std::fs::File::open("/dev/null").unwrap();
+        assert!(check_tcp(file.as_raw_fd()).is_err());
+    }
+}
@@ -0,0 +1,528 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, OnceLock,
+    },
+    task::{ready, Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures_util::{Stream, StreamExt};
+use sha2::{Digest, Sha256};
+use string_stream_processor::{LineLimit, Progress, ProgressReader, SourceProvider};
+use tokio::{
+    io::{AsyncBufRead, AsyncRead, ReadBuf},
+    sync::Notify,
+    time::Sleep,
+};
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+
+use crate::run::SourceError;
+
+/// Bytes read from a source, and its line endings if they are counted.
+#[derive(Debug)]
+pub struct Tally {
+    pub bytes: AtomicU64,
+    pub newlines: AtomicU64,
+    /// Lines and words of the written results of the source, see [`Tally::set_counts`].
+    pub lines: AtomicU64,
+    pub words: AtomicU64,
+    /// Error which stopped the reading of the source.
+    pub error: OnceLock<io::Error>,
+    /// Hexadecimal SHA-256 digest of the content of the source once read entirely, after its
+    /// decompression, if computed.
+    pub checksum: OnceLock<String>,
+    /// Whether the reading stopped at the maximum number of lines before the end of the source.
+    pub truncated: AtomicBool,
+    opened: Instant,
+    /// Time to read the source once its end is reached.
+    finished: OnceLock<Duration>,
+    /// Ends the source as if it was read entirely, cancelled with the interrupt of its provider.
+    skip: CancellationToken,
+    paused: AtomicBool,
+}
+
+impl Tally {
+    /// Time spent reading the source, until its end or until now.
+    pub fn duration(&self) -> Duration {
+        self.finished
+            .get()
+            .copied()
+            .unwrap_or_else(|| self.opened.elapsed())
+    }
+
+    /// Whether the end of the source was reached.
+    pub fn is_finished(&self) -> bool {
+        self.finished.get().is_some()
+    }
+
+    /// Stop reading the source, its counts so far are kept.
+    pub fn skip(&self) {
+        self.skip.cancel();
+    }
+
+    /// Whether the source was skipped or interrupted before its end.
+    pub fn is_skipped(&self) -> bool {
+        self.skip.is_cancelled() && !self.is_finished()
+    }
+
+    /// Whether the reading stopped before the end of the source, at the maximum number of lines
+    /// or once skipped.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated.load(Ordering::Relaxed) || self.is_skipped()
+    }
+
+    /// Record the counts of the lines of the source once written.
+    pub fn set_counts(&self, counts: &[usize]) {
+        let words = counts.iter().sum::<usize>();
+        self.lines.store(counts.len() as u64, Ordering::Relaxed);
+        self.words.store(words as u64, Ordering::Relaxed);
+    }
+
+    /// Stop reading the source until it is resumed.
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for Tally {
+    fn default() -> Self {
+        Self {
+            bytes: AtomicU64::default(),
+            newlines: AtomicU64::default(),
+            lines: AtomicU64::default(),
+            words: AtomicU64::default(),
+            error: OnceLock::new(),
+            checksum: OnceLock::new(),
+            truncated: AtomicBool::new(false),
+            opened: Instant::now(),
+            finished: OnceLock::new(),
+            skip: CancellationToken::new(),
+            paused: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Provider recording a [`Tally`] of each source of another provider, in source order.
+///
+/// Once interrupted, see [`Tallied::interrupt`], no source is opened anymore and the opened ones
+/// end as if they were read entirely, for their counts so far to be written.
+#[derive(Debug)]
+pub struct Tallied<P> {
+    inner: P,
+    newlines: bool,
+    checksums: bool,
+    /// Sources skipped as identical to an earlier one, see [`Tallied::duplicates`].
+    duplicates: BTreeMap<String, String>,
+    max_lines: Option<usize>,
+    tallies: Mutex<Vec<(String, Arc<Tally>)>>,
+    /// Notified when reading a source fails.
+    failures: Arc<Notify>,
+    progress: Progress,
+    interrupt: CancellationToken,
+}
+
+impl<P: SourceProvider> Tallied<P> {
+    /// Count the bytes of the sources of `inner`.
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            newlines: false,
+            checksums: false,
+            duplicates: BTreeMap::new(),
+            max_lines: None,
+            tallies: Mutex::default(),
+            failures: Arc::default(),
+            progress: Progress::default(),
+            interrupt: CancellationToken::new(),
+        }
+    }
+
+    /// Token interrupting the reading of the sources once cancelled.
+    pub fn interrupt(&self) -> CancellationToken {
+        self.interrupt.clone()
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupt.is_cancelled()
+    }
+
+    /// Whether some sources were not read until their end, the run being interrupted or some
+    /// sources skipped, e.g. from the TUI.
+    pub fn is_partial(&self) -> bool {
+        let tallies = self.tallies();
+        self.is_interrupted() || tallies.iter().any(|(_, tally)| tally.is_skipped())
+    }
+
+    /// Also count the line endings of the sources.
+    pub fn with_newlines(mut self) -> Self {
+        self.newlines = true;
+        self
+    }
+
+    /// Also compute the SHA-256 checksum of the sources read entirely.
+    pub fn with_checksums(mut self) -> Self {
+        self.checksums = true;
+        self
+    }
+
+    /// Also report the sources skipped as identical to another one, mapped to it, see
+    /// [`identical_files`](crate::walk::identical_files).
+    pub fn with_duplicates(mut self, duplicates: BTreeMap<String, String>) -> Self {
+        self.duplicates = duplicates;
+        self
+    }
+
+    /// Stop reading each source after this number of lines, see [`Tally::truncated`].
+    pub fn with_max_lines(mut self, max_lines: Option<usize>) -> Self {
+        self.max_lines = max_lines;
+        self
+    }
+
+    /// Tallies of the sources opened so far.
+    pub fn tallies(&self) -> MutexGuard<'_, Vec<(String, Arc<Tally>)>> {
+        self.tallies.lock().unwrap()
+    }
+
+    /// Progress of the reading of the sources, updated by their readers.
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// Tally of an opened source.
+    pub fn tally(&self, id: &str) -> Option<Arc<Tally>> {
+        let tallies = self.tallies();
+        let (_, tally) = tallies.iter().rev().find(|(source, _)| source == id)?;
+        Some(tally.clone())
+    }
+
+    /// Sources identical to an earlier one, see [`Tallied::with_duplicates`].
+    pub fn duplicates(&self) -> &BTreeMap<String, String> {
+        &self.duplicates
+    }
+
+    /// Count each source with `count`, reading at most `concurrency` sources at a time, and give
+    /// the counts of the ones read entirely to `merge`. The sources whose count fails, e.g. on an
+    /// invalid UTF-8 line, are dropped and their error is recorded in their tally like the read
+    /// errors, for the errors of the run.
+    pub async fn count_each<'a, T, F>(
+        &'a self,
+        concurrency: Option<usize>,
+        count: impl Fn(&'a str, TallyReader<ProgressReader<P::Reader>>) -> F,
+        merge: impl Fn(&'a str, T),
+    ) where
+        F: Future<Output = io::Result<T>>,
+    {
+        let (count, merge) = (&count, &merge);
+        self.sources()
+            .for_each_concurrent(concurrency, |(id, rd)| {
+                let tally = rd.tally.clone();
+                async move {
+                    match count(id, rd).await {
+                        Ok(counts) => merge(id, counts),
+                        Err(e) => {
+                            let _ = tally.error.set(e);
+                            self.failures.notify_one();
+                        }
+                    }
+                }
+            })
+            .await;
+    }
+
+    /// Wait for the first source whose reading fails.
+    pub async fn failure(&self) -> SourceError {
+        loop {
+            self.failures.notified().await;
+            let tallies = self.tallies();
+            let failed = tallies
+                .iter()
+                .find_map(|(id, tally)| Some((id, tally.error.get()?)));
+            if let Some((id, error)) = failed {
+                return SourceError::new(id, error);
+            }
+        }
+    }
+}
+
+impl<P: SourceProvider> SourceProvider for Tallied<P> {
+    type Reader = TallyReader<ProgressReader<P::Reader>>;
+
+    fn sources(&self) -> impl Stream<Item = (&str, Self::Reader)> {
+        let interrupted = self.interrupt.cancelled();
+        self.inner
+            .sources()
+            .take_until(interrupted)
+            .map(|(id, rd)| {
+                let tally = Arc::new(Tally {
+                    skip: self.interrupt.child_token(),
+                    ..Tally::default()
+                });
+                self.tallies().push((id.to_string(), tally.clone()));
+                let rd = LineLimit::new(self.progress.reader(rd), self.max_lines);
+                let mut rd = TallyReader::new(rd, tally, self.newlines, self.failures.clone());
+                if self.checksums {
+                    rd.hasher = Some(Sha256::new());
+                }
+                (id, rd)
+            })
+    }
+
+    fn lines_hint(&self, id: &str) -> usize {
+        self.inner.lines_hint(id)
+    }
+}
+
+/// Reader counting the bytes, and optionally the line endings, of the data it buffers, and
+/// optionally hashing it, up to the maximum number of lines of its [`LineLimit`].
+pub struct TallyReader<R> {
+    rd: LineLimit<R>,
+    tally: Arc<Tally>,
+    newlines: bool,
+    /// Hash of the data buffered so far, finalized in the checksum of the tally at the end.
+    hasher: Option<Sha256>,
+    failures: Arc<Notify>,
+    /// Length of the start of the current buffer already counted.
+    seen: usize,
+    skipped: Pin<Box<WaitForCancellationFutureOwned>>,
+    /// Delay before checking again whether a paused source is resumed.
+    paused: Option<Pin<Box<Sleep>>>,
+}
+
+/// Interval between the checks of a paused source.
+const PAUSE_POLL: Duration = Duration::from_millis(100);
+
+impl<R> TallyReader<R> {
+    fn new(rd: LineLimit<R>, tally: Arc<Tally>, newlines: bool, failures: Arc<Notify>) -> Self {
+        Self {
+            skipped: Box::pin(tally.skip.clone().cancelled_owned()),
+            rd,
+            tally,
+            newlines,
+            hasher: None,
+            failures,
+            seen: 0,
+            paused: None,
+        }
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncRead for TallyReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let data = ready!(self.as_mut().poll_fill_buf(cx))?;
+        let len = data.len().min(buf.remaining());
+        buf.put_slice(&data[..len]);
+        self.consume(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncBufRead for TallyReader<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        if this.skipped.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(Ok(&[]));
+        }
+        while this.tally.is_paused() {
+            let paused = this
+                .paused
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep(PAUSE_POLL)));
+            ready!(paused.as_mut().poll(cx));
+            this.paused = None;
+        }
+        if this.rd.is_exhausted() {
+            // The limit is reached, reading once more tells whether the source had more lines.
+            let _ = ready!(Pin::new(&mut this.rd).poll_fill_buf(cx));
+            let truncated = this.rd.is_truncated();
+            this.tally.truncated.store(truncated, Ordering::Relaxed);
+        }
+        let data = ready!(Pin::new(&mut this.rd).poll_fill_buf(cx)).inspect_err(|e| {
+            let _ = this
+                .tally
+                .error
+                .set(io::Error::new(e.kind(), e.to_string()));
+            this.failures.notify_one();
+        })?;
+        if data.is_empty() {
+            this.tally
+                .finished
+                .get_or_init(|| this.tally.opened.elapsed());
+            if this.tally.truncated.load(Ordering::Relaxed) {
+                this.hasher = None;
+            } else if let Some(hasher) = this.hasher.take() {
+                let checksum = hasher
+                    .finalize()
+                    .iter()
+                    .map(|byte| format!("{byte:02x}"))
+                    .collect();
+                let _ = this.tally.checksum.set(checksum);
+            }
+        }
+        if let Some(new) = data.get(this.seen..).filter(|new| !new.is_empty()) {
+            this.tally
+                .bytes
+                .fetch_add(new.len() as u64, Ordering::Relaxed);
+            if this.newlines {
+                let newlines = new.iter().filter(|&&b| b == b'\n').count();
+                this.tally
+                    .newlines
+                    .fetch_add(newlines as u64, Ordering::Relaxed);
+            }
+            if let Some(hasher) = &mut this.hasher {
+                hasher.update(new);
+            }
+            this.seen = data.len();
+        }
+        Poll::Ready(Ok(data))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        this.seen = this.seen.saturating_sub(amt);
+        Pin::new(&mut this.rd).consume(amt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tally_reader() {
+        let tally = Arc::<Tally>::default();
+        let data = tokio::io::BufReader::with_capacity(2, &b"a b\nc"[..]);
+        let mut rd = TallyReader::new(
+            LineLimit::new(data, None),
+            tally.clone(),
+            true,
+            Arc::default(),
+        );
+        let mut data = String::new();
+        rd.read_to_string(&mut data).await.unwrap();
+        assert_eq!(data, "a b\nc");
+        assert_eq!(tally.bytes.load(Ordering::Relaxed), 5);
+        assert_eq!(tally.newlines.load(Ordering::Relaxed), 1);
+        assert!(tally.error.get().is_none());
+        assert!(tally.finished.get().is_some());
+        assert!(tally.checksum.get().is_none());
+
+        let tally = Arc::<Tally>::default();
+        let data = tokio::io::BufReader::with_capacity(2, &b"abc"[..]);
+        let mut rd = TallyReader::new(
+            LineLimit::new(data, None),
+            tally.clone(),
+            false,
+            Arc::default(),
+        );
+        rd.hasher = Some(Sha256::new());
+        rd.read_to_end(&mut Vec::new()).await.unwrap();
+        assert_eq!(
+            tally.checksum.get().unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        for (data, truncated) in [(&b"a b\nc\nd"[..], true), (&b"a b\nc\n"[..], false)] {
+            let tally = Arc::<Tally>::default();
+            let data = tokio::io::BufReader::with_capacity(2, data);
+            let limit = LineLimit::new(data, Some(2));
+            let mut rd = TallyReader::new(limit, tally.clone(), true, Arc::default());
+            rd.hasher = Some(Sha256::new());
+            let mut data = String::new();
+            rd.read_to_string(&mut data).await.unwrap();
+            assert_eq!(data, "a b\nc\n");
+            assert_eq!(tally.truncated.load(Ordering::Relaxed), truncated);
+            // The checksum is only of the sources read entirely.
+            assert_eq!(tally.checksum.get().is_some(), !truncated);
+        }
+
+        let interrupt = CancellationToken::new();
+        let tally = Arc::new(Tally {
+            skip: interrupt.child_token(),
+            ..Tally::default()
+        });
+        let data = tokio::io::BufReader::with_capacity(2, &b"a b\nc"[..]);
+        let mut rd = TallyReader::new(
+            LineLimit::new(data, None),
+            tally.clone(),
+            true,
+            Arc::default(),
+        );
+        let mut data = [0; 2];
+        rd.read_exact(&mut data).await.unwrap();
+        interrupt.cancel();
+        assert_eq!(rd.read(&mut data).await.unwrap(), 0);
+        assert!(tally.is_skipped());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_pause_and_skip() {
+        let tally = Arc::<Tally>::default();
+        let data = tokio::io::BufReader::with_capacity(2, &b"a b\nc"[..]);
+        let mut rd = TallyReader::new(
+            LineLimit::new(data, None),
+            tally.clone(),
+            false,
+            Arc::default(),
+        );
+        let mut data = [0; 2];
+        tally.set_paused(true);
+        let read = tokio::time::timeout(Duration::from_secs(1), rd.read(&mut data));
+        assert!(read.await.is_err());
+        tally.set_paused(false);
+        assert_eq!(rd.read(&mut data).await.unwrap(), 2);
+        tally.set_paused(true);
+        tally.skip();
+        assert_eq!(rd.read(&mut data).await.unwrap(), 0);
+        assert!(tally.is_skipped() && tally.is_truncated());
+    }
+
+    #[tokio::test]
+    async fn test_count_each() {
+        let dir = std::env::temp_dir().join("fpc_test_count_each");
+        std::fs::create_dir_all(&dir).unwrap();
+        let files: Vec<_> = [("a.txt", &b"a b\n"[..]), ("b.txt", b"c\n\xff\n")]
+            .into_iter()
+            .map(|(name, data)| {
+                let path = dir.join(name);
+                std::fs::write(&path, data).unwrap();
+                path.to_str().unwrap().to_string()
+            })
+            .collect();
+        let options = string_stream_processor::ProcessorOptions::default();
+        let sources = Tallied::new(crate::inputs::Inputs::new(files.clone(), None, options));
+        let counted = Mutex::new(Vec::new());
+        let count = |_, mut rd: TallyReader<_>| async move {
+            let mut data = String::new();
+            rd.read_to_string(&mut data).await?;
+            Ok(data.lines().count())
+        };
+        sources
+            .count_each(None, count, |id, lines| {
+                counted.lock().unwrap().push((id.to_string(), lines));
+            })
+            .await;
+        assert_eq!(counted.into_inner().unwrap(), [(files[0].clone(), 1)]);
+        let error = sources.tally(&files[1]).unwrap();
+        let error = error.error.get().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
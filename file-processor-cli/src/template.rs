@@ -0,0 +1,188 @@
+use std::io::{self, Write};
+
+use string_stream_processor::LineStats;
+
+/// Value of a placeholder of a template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    /// `{id}`, the identifier of the source.
+    Id,
+    /// `{line}`, the line number, from 1.
+    Line,
+    /// `{count}`, the number of words of the line.
+    Count,
+    /// `{lines}`, the number of lines of the source.
+    Lines,
+    /// `{words}`, the total number of words of the source.
+    Words,
+    /// `{min}`, the minimum number of words of a line.
+    Min,
+    /// `{max}`, the maximum number of words of a line.
+    Max,
+    /// `{mean}`, the mean number of words per line.
+    Mean,
+    /// `{std_dev}`, the standard deviation of the number of words per line.
+    StdDev,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "id" => Self::Id,
+            "line" => Self::Line,
+            "count" => Self::Count,
+            "lines" => Self::Lines,
+            "words" => Self::Words,
+            "min" => Self::Min,
+            "max" => Self::Max,
+            "mean" => Self::Mean,
+            "std_dev" => Self::StdDev,
+            _ => return None,
+        })
+    }
+
+    fn is_per_line(self) -> bool {
+        matches!(self, Self::Line | Self::Count)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    Field(Field),
+}
+
+/// Format of the rows of a `--format-template`, e.g. `{id}\t{line}\t{count}`.
+///
+/// The placeholders are `{id}`, the line ones `{line}` and `{count}`, and the source statistics
+/// `{lines}`, `{words}`, `{min}`, `{max}`, `{mean}` and `{std_dev}`. A row is written per line
+/// if the template has a line placeholder and per source otherwise, followed by a newline.
+/// `{{` and `}}` are literal braces, and `\t`, `\n` and `\\` are escaped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    segments: Vec<Segment>,
+    per_line: bool,
+}
+
+impl Template {
+    /// Parse a template, failing on unknown or unclosed placeholders.
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut segments = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('t') => text.push('\t'),
+                    Some('n') => text.push('\n'),
+                    Some('\\') | None => text.push('\\'),
+                    Some(c) => text.extend(['\\', c]),
+                },
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let (name, rest) = chars
+                        .as_str()
+                        .split_once('}')
+                        .ok_or_else(|| format!("unclosed placeholder in {template}"))?;
+                    let field = Field::from_name(name)
+                        .ok_or_else(|| format!("unknown placeholder {{{name}}}"))?;
+                    chars = rest.chars();
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+                    segments.push(Segment::Field(field));
+                }
+                '}' => return Err(format!("unmatched }} in {template}")),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+        let per_line = segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Field(field) if field.is_per_line()));
+        Ok(Self { segments, per_line })
+    }
+
+    /// Write the rows of a source.
+    pub fn write(&self, writer: &mut impl Write, id: &str, counts: &[usize]) -> io::Result<()> {
+        let stats: LineStats = counts.iter().copied().collect();
+        if self.per_line {
+            for (line, &count) in counts.iter().enumerate() {
+                self.write_row(writer, id, &stats, Some((line + 1, count)))?;
+            }
+            Ok(())
+        } else {
+            self.write_row(writer, id, &stats, None)
+        }
+    }
+
+    fn write_row(
+        &self,
+        writer: &mut impl Write,
+        id: &str,
+        stats: &LineStats,
+        line: Option<(usize, usize)>,
+    ) -> io::Result<()> {
+        for segment in &self.segments {
+            let field = match segment {
+                Segment::Text(text) => {
+                    writer.write_all(text.as_bytes())?;
+                    continue;
+                }
+                Segment::Field(field) => *field,
+            };
+            match (field, line) {
+                (Field::Id, _) => write!(writer, "{id}")?,
+                (Field::Line, Some((line, _))) => write!(writer, "{line}")?,
+                (Field::Count, Some((_, count))) => write!(writer, "{count}")?,
+                (Field::Line | Field::Count, None) => unreachable!("rows are per line"),
+                (Field::Lines, _) => write!(writer, "{}", stats.lines)?,
+                (Field::Words, _) => write!(writer, "{}", stats.words)?,
+                (Field::Min, _) => write!(writer, "{}", stats.min)?,
+                (Field::Max, _) => write!(writer, "{}", stats.max)?,
+                (Field::Mean, _) => write!(writer, "{:.2}", stats.mean())?,
+                (Field::StdDev, _) => write!(writer, "{:.2}", stats.std_dev())?,
+            }
+        }
+        writeln!(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, counts: &[usize]) -> String {
+        let mut output = Vec::new();
+        Template::parse(template)
+            .unwrap()
+            .write(&mut output, "a.txt", counts)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_template() {
+        assert_eq!(
+            render(r"{id}\t{line}\t{count}", &[2, 0]),
+            "a.txt\t1\t2\na.txt\t2\t0\n"
+        );
+        assert_eq!(
+            render("{id}: {words}/{lines} {{mean {mean}}}", &[2, 1]),
+            "a.txt: 3/2 {mean 1.50}\n"
+        );
+        assert_eq!(render("{count} of {max}", &[]), "");
+        assert!(Template::parse("{name}").is_err());
+        assert!(Template::parse("{id").is_err());
+        assert!(Template::parse("id}").is_err());
+    }
+}
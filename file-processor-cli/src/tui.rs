@@ -0,0 +1,363 @@
+use std::{
+    future::Future,
+    io,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+
+use indicatif::{HumanBytes, HumanDuration};
+use ratatui::{
+    backend::CrosstermBackend,
+    crossterm::{
+        event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+        execute,
+        terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+    },
+    layout::{Constraint, Layout},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Cell, List, Paragraph, Row, Table, TableState},
+    Frame, Terminal,
+};
+
+use crate::{logs, run::Run, tally::Tally};
+
+/// Interval between the redraws of the interface and the checks of the keys.
+const TICK: Duration = Duration::from_millis(100);
+
+/// Number of errors shown under the table, the last ones.
+const SHOWN_ERRORS: usize = 5;
+
+const KEYS: &str = "↑↓ select  s sort  r reverse  p pause  x skip  q stop";
+
+/// Column of the table by which the sources are sorted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum SortColumn {
+    #[default]
+    Opened,
+    File,
+    Bytes,
+    Lines,
+    Rate,
+}
+
+impl SortColumn {
+    fn next(self) -> Self {
+        match self {
+            Self::Opened => Self::File,
+            Self::File => Self::Bytes,
+            Self::Bytes => Self::Lines,
+            Self::Lines => Self::Rate,
+            Self::Rate => Self::Opened,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Opened => "opening",
+            Self::File => "file",
+            Self::Bytes => "bytes",
+            Self::Lines => "lines",
+            Self::Rate => "rate",
+        }
+    }
+}
+
+/// State of the interface between two redraws.
+#[derive(Debug, Default)]
+struct App {
+    sort: SortColumn,
+    reverse: bool,
+    /// Index of the selected row, in the sorted rows.
+    selected: usize,
+    /// Whether the run was stopped from the interface.
+    stopped: bool,
+}
+
+/// Source opened by the run, as shown in the table.
+struct SourceRow {
+    id: String,
+    tally: Arc<Tally>,
+    bytes: u64,
+    lines: u64,
+    /// Bytes read per second.
+    rate: f64,
+}
+
+impl SourceRow {
+    fn new(id: &str, tally: &Arc<Tally>) -> Self {
+        let bytes = tally.bytes.load(Ordering::Relaxed);
+        Self {
+            id: id.to_string(),
+            tally: tally.clone(),
+            bytes,
+            lines: tally.newlines.load(Ordering::Relaxed),
+            rate: bytes as f64 / tally.duration().as_secs_f64().max(f64::EPSILON),
+        }
+    }
+
+    fn status(&self) -> &'static str {
+        match () {
+            () if self.tally.error.get().is_some() => "failed",
+            () if self.tally.is_finished() => "done",
+            () if self.tally.is_skipped() => "skipped",
+            () if self.tally.is_paused() => "paused",
+            () => "reading",
+        }
+    }
+}
+
+/// Run `work` while showing a table of the sources of `run` on the standard error, refreshed as
+/// they are read, with their bytes, lines and throughput, and the errors of the run. The sources
+/// can be sorted, and the selected one paused or skipped, see [`KEYS`]. Stopping the run ends the
+/// opened sources as an interrupt does. The log messages are held until the table is closed.
+pub async fn track<T>(run: &Run<'_>, work: impl Future<Output = T>) -> io::Result<T> {
+    let mut terminal = Screen::open()?;
+    let mut app = App::default();
+    let mut ticks = tokio::time::interval(TICK);
+    tokio::pin!(work);
+    loop {
+        tokio::select! {
+            output = &mut work => return Ok(output),
+            _ = ticks.tick() => {
+                while event::poll(Duration::ZERO)? {
+                    if let Event::Key(key) = event::read()? {
+                        app.handle(key, run);
+                    }
+                }
+                terminal.0.draw(|frame| draw(frame, &mut app, run))?;
+            }
+        }
+    }
+}
+
+/// Terminal of the interface, restored when dropped.
+struct Screen(Terminal<CrosstermBackend<io::Stderr>>);
+
+impl Screen {
+    fn open() -> io::Result<Self> {
+        logs::hold();
+        terminal::enable_raw_mode()?;
+        let screen = Self(Terminal::new(CrosstermBackend::new(io::stderr()))?);
+        execute!(io::stderr(), EnterAlternateScreen)?;
+        Ok(screen)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+        let _ = execute!(io::stderr(), LeaveAlternateScreen);
+        let _ = self.0.show_cursor();
+        logs::release();
+    }
+}
+
+impl App {
+    /// Rows of the opened sources of the run, in the order of the table.
+    fn rows(&self, run: &Run) -> Vec<SourceRow> {
+        let tallies = run.sources.tallies();
+        let mut rows: Vec<_> = tallies
+            .iter()
+            .map(|(id, tally)| SourceRow::new(id, tally))
+            .collect();
+        drop(tallies);
+        match self.sort {
+            SortColumn::Opened => {}
+            SortColumn::File => rows.sort_by(|a, b| a.id.cmp(&b.id)),
+            SortColumn::Bytes => rows.sort_by_key(|row| std::cmp::Reverse(row.bytes)),
+            SortColumn::Lines => rows.sort_by_key(|row| std::cmp::Reverse(row.lines)),
+            SortColumn::Rate => rows.sort_by(|a, b| b.rate.total_cmp(&a.rate)),
+        }
+        if self.reverse {
+            rows.reverse();
+        }
+        rows
+    }
+
+    fn handle(&mut self, key: KeyEvent, run: &Run) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        let rows = self.rows(run);
+        let selected = rows.get(self.selected);
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => self.stop(run),
+            KeyCode::Char('q') | KeyCode::Esc => self.stop(run),
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1).min(rows.len().saturating_sub(1));
+            }
+            KeyCode::Char('s') => self.sort = self.sort.next(),
+            KeyCode::Char('r') => self.reverse = !self.reverse,
+            KeyCode::Char('p') => {
+                if let Some(row) = selected {
+                    row.tally.set_paused(!row.tally.is_paused());
+                }
+            }
+            KeyCode::Char('x') => {
+                if let Some(row) = selected {
+                    row.tally.skip();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn stop(&mut self, run: &Run) {
+        if !self.stopped {
+            log::warn!("Interrupted, writing the partial results.");
+        }
+        self.stopped = true;
+        run.sources.interrupt().cancel();
+    }
+}
+
+fn draw(frame: &mut Frame, app: &mut App, run: &Run) {
+    let rows = app.rows(run);
+    app.selected = app.selected.min(rows.len().saturating_sub(1));
+    let errors = run.errors();
+    let shown_errors = errors.len().min(SHOWN_ERRORS);
+    let [header, table, errors_area, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(3),
+        Constraint::Length(shown_errors as u16 + 2),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let bytes: u64 = rows.iter().map(|row| row.bytes).sum();
+    let elapsed = run.start.elapsed();
+    let done = rows
+        .iter()
+        .filter(|row| row.tally.error.get().is_none() && row.tally.is_finished())
+        .count()
+        + errors.len();
+    let rate = bytes as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+    let status = if app.stopped { ", stopping" } else { "" };
+    let summary = format!(
+        "{done}/{} files, {} at {}/s, {}, sorted by {}{}{status}",
+        run.sources.inner().len(),
+        HumanBytes(bytes),
+        HumanBytes(rate as u64),
+        HumanDuration(elapsed),
+        app.sort.name(),
+        if app.reverse { " reversed" } else { "" },
+    );
+    frame.render_widget(Paragraph::new(summary), header);
+
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let cells = ["FILE", "STATUS", "BYTES", "LINES", "RATE"].map(Cell::from);
+    let table_rows = rows.iter().map(|row| {
+        Row::new([
+            Cell::from(row.id.as_str()),
+            Cell::from(row.status()),
+            Cell::from(Line::from(HumanBytes(row.bytes).to_string()).right_aligned()),
+            Cell::from(Line::from(row.lines.to_string()).right_aligned()),
+            Cell::from(Line::from(format!("{}/s", HumanBytes(row.rate as u64))).right_aligned()),
+        ])
+    });
+    let widths = [
+        Constraint::Fill(1),
+        Constraint::Length(7),
+        Constraint::Length(11),
+        Constraint::Length(10),
+        Constraint::Length(13),
+    ];
+    let widget = Table::new(table_rows, widths)
+        .header(Row::new(cells).style(bold))
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = TableState::default().with_selected((!rows.is_empty()).then_some(app.selected));
+    frame.render_stateful_widget(widget, table, &mut state);
+
+    let shown = errors[errors.len() - shown_errors..]
+        .iter()
+        .map(|error| format!("{}: {}", error.id, error.message));
+    let title = format!(" Errors ({}) ", errors.len());
+    frame.render_widget(
+        List::new(shown).block(Block::bordered().title(title)),
+        errors_area,
+    );
+    frame.render_widget(Paragraph::new(KEYS).style(bold), footer);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use futures_util::StreamExt;
+    use ratatui::{backend::TestBackend, crossterm::event::KeyEventState};
+    use string_stream_processor::{ProcessorOptions, SourceProvider};
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+    use crate::{inputs::Inputs, tally::Tallied};
+
+    fn press(code: KeyCode) -> KeyEvent {
+        KeyEvent {
+            code,
+            modifiers: KeyModifiers::NONE,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_draw() {
+        let dir = std::env::temp_dir().join("fpc_test_tui");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "a b\nc\n").unwrap();
+        std::fs::write(dir.join("b.txt"), "d\n").unwrap();
+        let files = vec![
+            dir.join("b.txt").to_str().unwrap().to_string(),
+            dir.join("a.txt").to_str().unwrap().to_string(),
+            "fpc_test_missing.txt".into(),
+        ];
+        let options = ProcessorOptions::default();
+        let sources = Tallied::new(Inputs::new(files.clone(), None, options)).with_newlines();
+        let run = Run {
+            sources: &sources,
+            sort: None,
+            with_meta: false,
+            reused: 0,
+            start: Instant::now(),
+        };
+        let mut opened = std::pin::pin!(sources.sources());
+        let (_, mut b) = opened.next().await.unwrap();
+        b.read_to_end(&mut Vec::new()).await.unwrap();
+        let (_, _a) = opened.next().await.unwrap();
+        assert!(opened.next().await.is_none());
+
+        let mut app = App::default();
+        let ids = |app: &App| -> Vec<_> { app.rows(&run).into_iter().map(|row| row.id).collect() };
+        assert_eq!(ids(&app), files[..2]);
+        app.handle(press(KeyCode::Char('s')), &run);
+        assert_eq!(ids(&app), [files[1].clone(), files[0].clone()]);
+        app.handle(press(KeyCode::Char('r')), &run);
+        assert_eq!(ids(&app), files[..2]);
+        app.handle(press(KeyCode::Down), &run);
+        app.handle(press(KeyCode::Char('p')), &run);
+        assert!(sources.tally(&files[1]).unwrap().is_paused());
+        app.handle(press(KeyCode::Char('x')), &run);
+        assert!(sources.tally(&files[1]).unwrap().is_skipped());
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 12)).unwrap();
+        terminal.draw(|frame| draw(frame, &mut app, &run)).unwrap();
+        let screen: Vec<String> = terminal
+            .backend()
+            .buffer()
+            .content
+            .chunks(100)
+            .map(|line| line.iter().map(|cell| cell.symbol()).collect())
+            .collect();
+        assert!(screen[0].starts_with("2/3 files, 2 B at"), "{}", screen[0]);
+        assert!(screen[1].starts_with("FILE"));
+        assert!(screen[2].contains("b.txt") && screen[2].contains("done"));
+        assert!(screen[3].contains("a.txt") && screen[3].contains("skipped"));
+        assert!(screen.iter().any(|line| line.contains("Errors (1)")));
+        assert!(screen[11].starts_with("↑↓ select"));
+
+        app.handle(press(KeyCode::Char('q')), &run);
+        assert!(app.stopped && sources.is_interrupted());
+    }
+}
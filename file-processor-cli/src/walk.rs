@@ -0,0 +1,600 @@
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap, HashSet},
+    io, mem,
+    path::{Path, PathBuf},
+};
+
+use futures_util::{stream, StreamExt};
+use glob::Pattern;
+use ignore::WalkBuilder;
+use sha2::{Digest, Sha256};
+use string_stream_processor::Compression;
+use tokio::io::AsyncReadExt;
+
+use crate::run::SourceError;
+
+/// Maximum number of directories read at the same time.
+pub const WALK_CONCURRENCY: usize = 16;
+
+/// How directories are walked.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WalkOptions {
+    /// Skip the files ignored by the `.gitignore` files, the global one and `.git/info/exclude`,
+    /// and the `.git` directories.
+    pub gitignore: bool,
+    /// Skip the files and directories whose path or name matches one of these patterns.
+    pub excludes: Vec<Pattern>,
+    /// Only keep the files with one of these extensions, without the dot, if any.
+    pub include_exts: Vec<String>,
+    /// Only keep the files which look like text, see [`looks_like_text`].
+    pub text_only: bool,
+    /// Depth of the deepest files kept, 1 for the files of the directory only.
+    pub max_depth: Option<usize>,
+    /// Walk the symbolic links to directories, skipping the ones which loop.
+    pub follow_symlinks: bool,
+    /// Skip the files larger than this number of bytes, see [`skip_large_files`].
+    pub max_file_size: Option<u64>,
+    /// Keep the paths of a file after the first one, see [`dedupe_files`].
+    pub keep_duplicates: bool,
+    /// Read first the files whose path or name matches one of these patterns, in the order of
+    /// the patterns, see [`WalkOptions::priority`].
+    pub priorities: Vec<Pattern>,
+}
+
+impl WalkOptions {
+    /// Whether the path or the name of a file or directory matches an exclude pattern.
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        self.excludes.iter().any(|pattern| matches(pattern, path))
+    }
+
+    /// Rank of a file in the reading order, the index of the first priority pattern matching its
+    /// path or its name, after all of them if none does.
+    pub fn priority(&self, path: &Path) -> usize {
+        let mut priorities = self.priorities.iter();
+        priorities
+            .position(|pattern| matches(pattern, path))
+            .unwrap_or(self.priorities.len())
+    }
+
+    /// Whether the extension of a file is included, always without `include_exts`.
+    fn is_included(&self, path: &Path) -> bool {
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        self.include_exts.is_empty()
+            || extension.is_some_and(|extension| {
+                let mut exts = self.include_exts.iter();
+                exts.any(|included| included.eq_ignore_ascii_case(extension))
+            })
+    }
+}
+
+/// Whether the path or the name of a file or directory matches the pattern.
+fn matches(pattern: &Pattern, path: &Path) -> bool {
+    let name = path.file_name().map(Path::new);
+    pattern.matches_path(path) || name.is_some_and(|name| pattern.matches_path(name))
+}
+
+/// Length of the start of the files sniffed by [`looks_like_text`].
+const SNIFF_LEN: usize = 8 * 1024;
+
+/// Whether the start of a file looks like text: it has no NUL byte, or it is compressed in a
+/// format which is decompressed when read. A file which cannot be read is kept, to report its
+/// error when it is opened.
+async fn looks_like_text(path: &Path) -> bool {
+    let mut data = Vec::with_capacity(SNIFF_LEN);
+    let read = match tokio::fs::File::open(path).await {
+        Ok(file) => file.take(SNIFF_LEN as u64).read_to_end(&mut data).await,
+        Err(e) => Err(e),
+    };
+    read.is_err() || Compression::from_magic(&data) != Compression::None || !data.contains(&0)
+}
+
+/// Regular files of a directory and of its subdirectories which are included, sorted by path,
+/// with the errors of the directories which could not be read. The symbolic links to files are
+/// kept, the ones to directories are only followed with `follow_symlinks`.
+pub async fn walk_dir(dir: &str, options: &WalkOptions) -> (Vec<String>, Vec<SourceError>) {
+    let (files, errors) = if options.gitignore {
+        let (dir, options) = (dir.to_string(), options.clone());
+        tokio::task::spawn_blocking(move || walk_dir_ignoring(&dir, options))
+            .await
+            .expect("the walk should not panic")
+    } else {
+        walk_dir_concurrent(dir, options).await
+    };
+    let files = files.into_iter().filter(|path| options.is_included(path));
+    let files: Vec<_> = if options.text_only {
+        stream::iter(files)
+            .map(|path| async move { looks_like_text(&path).await.then_some(path) })
+            .buffer_unordered(WALK_CONCURRENCY)
+            .filter_map(|path| async move { path })
+            .collect()
+            .await
+    } else {
+        files.collect()
+    };
+    let mut files: Vec<_> = files
+        .into_iter()
+        .filter_map(|path| match path.into_os_string().into_string() {
+            Ok(path) => Some(path),
+            Err(path) => {
+                log::warn!("Skipping non UTF-8 path {}.", path.to_string_lossy());
+                None
+            }
+        })
+        .collect();
+    files.sort_unstable();
+    (files, errors)
+}
+
+/// Subdirectories of a directory at any depth which are not excluded, none if it is not a
+/// directory. A directory reached again through a symbolic link is not listed again.
+pub async fn walk_subdirs(dir: &str, options: &WalkOptions) -> Vec<String> {
+    let mut visited = HashSet::new();
+    let mut pending = vec![PathBuf::from(dir)];
+    let mut subdirs = Vec::new();
+    while let Some(dir) = pending.pop() {
+        let Ok(listing) = list_dir(&dir, options).await else {
+            continue;
+        };
+        for (path, _) in listing.into_iter().filter(|(_, is_dir)| *is_dir) {
+            let Some(path) = path.to_str().map(String::from) else {
+                continue;
+            };
+            if FileId::of(&path).await.is_ok_and(|id| visited.insert(id)) {
+                pending.push(path.clone().into());
+                subdirs.push(path);
+            }
+        }
+    }
+    subdirs.sort_unstable();
+    subdirs
+}
+
+/// Identity of a physical file: its device and inode on Unix, its canonical path elsewhere.
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum FileId {
+    #[cfg(unix)]
+    Inode(u64, u64),
+    #[cfg(not(unix))]
+    Path(PathBuf),
+}
+
+impl FileId {
+    async fn of(path: &str) -> io::Result<Self> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let metadata = tokio::fs::metadata(path).await?;
+            Ok(Self::Inode(metadata.dev(), metadata.ino()))
+        }
+        #[cfg(not(unix))]
+        Ok(Self::Path(tokio::fs::canonicalize(path).await?))
+    }
+}
+
+/// Files without the paths of a physical file after its first one, through symbolic or hard
+/// links or given twice, for its counts to not be merged. The files which cannot be identified
+/// are kept, to report their error when they are opened.
+pub async fn dedupe_files(paths: Vec<String>) -> Vec<String> {
+    let mut ids = stream::iter(paths)
+        .map(|path| async move {
+            let id = FileId::of(&path).await;
+            (path, id)
+        })
+        .buffered(WALK_CONCURRENCY);
+    let mut seen = HashMap::new();
+    let mut files = Vec::new();
+    while let Some((path, id)) = ids.next().await {
+        match id.map(|id| seen.entry(id)) {
+            Ok(Entry::Occupied(first)) => {
+                log::info!("Skipping {path}, it is the same file as {}.", first.get());
+            }
+            Ok(Entry::Vacant(entry)) => {
+                entry.insert(path.clone());
+                files.push(path);
+            }
+            Err(_) => files.push(path),
+        }
+    }
+    files
+}
+
+/// Files byte-identical to another one of `paths` before them in the order of the identifiers,
+/// mapped to the first one, for `--dedup-sources` to skip them before they are counted. Only the files
+/// of the same size are hashed, the ones which cannot be read are not duplicates.
+pub async fn identical_files(paths: &[String]) -> BTreeMap<String, String> {
+    let mut sizes = stream::iter(paths)
+        .map(|path| async move { (path, tokio::fs::metadata(path).await) })
+        .buffered(WALK_CONCURRENCY);
+    let mut by_size: HashMap<u64, Vec<&String>> = HashMap::new();
+    while let Some((path, metadata)) = sizes.next().await {
+        match metadata {
+            Ok(metadata) if metadata.is_file() => {
+                by_size.entry(metadata.len()).or_default().push(path)
+            }
+            _ => {}
+        }
+    }
+    let candidates = by_size.into_values().filter(|paths| paths.len() > 1);
+    let mut digests = stream::iter(candidates.flatten())
+        .map(|path| async move { (path, sha256(path).await) })
+        .buffered(WALK_CONCURRENCY);
+    let mut by_digest: HashMap<_, Vec<&String>> = HashMap::new();
+    while let Some((path, digest)) = digests.next().await {
+        match digest {
+            Ok(digest) => by_digest.entry(digest).or_default().push(path),
+            Err(e) => log::debug!("Could not hash {path}, {e}."),
+        }
+    }
+    let mut duplicates = BTreeMap::new();
+    for mut paths in by_digest.into_values() {
+        paths.sort_unstable();
+        for path in &paths[1..] {
+            duplicates.insert(path.to_string(), paths[0].clone());
+        }
+    }
+    duplicates
+}
+
+/// SHA-256 digest of the content of a file.
+async fn sha256(path: &str) -> io::Result<[u8; 32]> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match file.read(&mut buf).await? {
+            0 => return Ok(hasher.finalize().into()),
+            read => hasher.update(&buf[..read]),
+        }
+    }
+}
+
+/// Files of at most `max_size` bytes, in order, with the errors of the larger ones. The files
+/// whose size cannot be read are kept, to report their error when they are opened.
+pub async fn skip_large_files(
+    paths: Vec<String>,
+    max_size: u64,
+) -> (Vec<String>, Vec<SourceError>) {
+    let mut sizes = stream::iter(paths)
+        .map(|path| async move {
+            let size = tokio::fs::metadata(&path).await.map(|m| m.len());
+            (path, size)
+        })
+        .buffered(WALK_CONCURRENCY);
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+    while let Some((path, size)) = sizes.next().await {
+        match size {
+            Ok(size) if size > max_size => {
+                log::warn!("Skipping {path}, its {size} bytes are above {max_size} bytes.");
+                let message = format!("{size} bytes, above the maximum of {max_size} bytes");
+                let error = io::Error::new(io::ErrorKind::FileTooLarge, message);
+                errors.push(SourceError::new(&path, &error));
+            }
+            _ => files.push(path),
+        }
+    }
+    (files, errors)
+}
+
+/// Walk a directory, reading up to [`WALK_CONCURRENCY`] directories at the same time.
+async fn walk_dir_concurrent(dir: &str, options: &WalkOptions) -> (Vec<PathBuf>, Vec<SourceError>) {
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+    let mut visited = HashSet::new();
+    let mut dirs = Vec::new();
+    if options.max_depth != Some(0) {
+        dirs.push((PathBuf::from(dir), 0));
+    }
+    while !dirs.is_empty() {
+        let mut listings = stream::iter(mem::take(&mut dirs))
+            .map(|(dir, depth)| async move {
+                let canonical = match options.follow_symlinks {
+                    true => tokio::fs::canonicalize(&dir).await.ok(),
+                    false => None,
+                };
+                let entries = list_dir(&dir, options).await;
+                (dir, depth, canonical, entries)
+            })
+            .buffer_unordered(WALK_CONCURRENCY);
+        while let Some((dir, depth, canonical, entries)) = listings.next().await {
+            if canonical.is_some_and(|canonical| !visited.insert(canonical)) {
+                log::warn!("Skipping {}, it was already walked.", dir.display());
+                continue;
+            }
+            match entries {
+                Ok(entries) => {
+                    for (path, is_dir) in entries {
+                        if !is_dir {
+                            files.push(path);
+                        } else if options.max_depth.is_none_or(|max| depth + 1 < max) {
+                            dirs.push((path, depth + 1));
+                        }
+                    }
+                }
+                Err(e) => errors.push(skipped_dir(&dir, &e)),
+            }
+        }
+    }
+    (files, errors)
+}
+
+/// Walk a directory with the rules of git, blocking.
+fn walk_dir_ignoring(dir: &str, options: WalkOptions) -> (Vec<PathBuf>, Vec<SourceError>) {
+    let walker = WalkBuilder::new(dir)
+        .standard_filters(false)
+        .git_ignore(true)
+        .git_global(true)
+        .git_exclude(true)
+        .require_git(false)
+        .max_depth(options.max_depth)
+        .follow_links(options.follow_symlinks)
+        .filter_entry(move |entry| {
+            entry.file_name() != ".git"
+                && (entry.depth() == 0 || !options.is_excluded(entry.path()))
+        })
+        .build();
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+    for entry in walker {
+        match entry {
+            Ok(entry) => {
+                let Some(file_type) = entry.file_type() else {
+                    continue;
+                };
+                let is_file = file_type.is_file()
+                    || file_type.is_symlink() && entry.path().metadata().is_ok_and(|m| m.is_file());
+                if is_file {
+                    files.push(entry.into_path());
+                }
+            }
+            Err(e) if is_loop(&e) => log::warn!("Skipping the symbolic link loop, {e}."),
+            Err(e) => {
+                let path = match &e {
+                    ignore::Error::WithPath { path, .. } => path.as_path(),
+                    _ => Path::new(dir),
+                };
+                let error = match e.io_error() {
+                    Some(error) => io::Error::new(error.kind(), error.to_string()),
+                    None => io::Error::other(e.to_string()),
+                };
+                errors.push(skipped_dir(path, &error));
+            }
+        }
+    }
+    (files, errors)
+}
+
+/// Whether an error of the git walk is a symbolic link loop.
+fn is_loop(error: &ignore::Error) -> bool {
+    match error {
+        ignore::Error::Loop { .. } => true,
+        ignore::Error::WithPath { err, .. }
+        | ignore::Error::WithDepth { err, .. }
+        | ignore::Error::WithLineNumber { err, .. } => is_loop(err),
+        _ => false,
+    }
+}
+
+/// Error of a directory which could not be read, logged.
+fn skipped_dir(dir: &Path, error: &io::Error) -> SourceError {
+    let dir = dir.display().to_string();
+    log::warn!("Could not read {dir}, {error}, skipping it.");
+    SourceError::new(&dir, error)
+}
+
+/// Files and subdirectories of a directory which are not excluded, the latter flagged.
+async fn list_dir(dir: &Path, options: &WalkOptions) -> io::Result<Vec<(PathBuf, bool)>> {
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    let mut listing = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let file_type = entry.file_type().await?;
+        let path = entry.path();
+        if options.is_excluded(&path) {
+            continue;
+        }
+        if file_type.is_dir() {
+            listing.push((path, true));
+        } else if file_type.is_file() {
+            listing.push((path, false));
+        } else if file_type.is_symlink() {
+            match tokio::fs::metadata(&path).await {
+                Ok(target) if target.is_file() => listing.push((path, false)),
+                Ok(target) if target.is_dir() && options.follow_symlinks => {
+                    listing.push((path, true))
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(listing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_walk_dir() {
+        let dir = std::env::temp_dir().join("fpc_test_walk_dir");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("b/c")).unwrap();
+        std::fs::create_dir_all(dir.join("a")).unwrap();
+        for file in ["z.txt", "b/c/d.txt", "b/a.txt", "a/e.txt"] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        let dir = dir.to_str().unwrap();
+        let options = WalkOptions::default();
+        let (files, errors) = walk_dir(dir, &options).await;
+        assert_eq!(
+            files,
+            ["a/e.txt", "b/a.txt", "b/c/d.txt", "z.txt"].map(|file| format!("{dir}/{file}"))
+        );
+        assert!(errors.is_empty());
+
+        let (files, errors) = walk_dir("fpc_test_missing_dir", &options).await;
+        assert!(files.is_empty());
+        assert_eq!(errors[0].kind, "NotFound");
+
+        let options = WalkOptions {
+            excludes: vec![Pattern::new("c").unwrap(), Pattern::new("*/z.*").unwrap()],
+            ..WalkOptions::default()
+        };
+        let (files, _) = walk_dir(dir, &options).await;
+        assert_eq!(
+            files,
+            ["a/e.txt", "b/a.txt"].map(|file| format!("{dir}/{file}"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_walk_subdirs() {
+        let dir = std::env::temp_dir().join("fpc_test_walk_subdirs");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("b/c")).unwrap();
+        std::fs::create_dir_all(dir.join("a")).unwrap();
+        std::fs::write(dir.join("z.txt"), "").unwrap();
+        let dir = dir.to_str().unwrap();
+        let options = WalkOptions::default();
+        assert_eq!(
+            walk_subdirs(dir, &options).await,
+            ["a", "b", "b/c"].map(|subdir| format!("{dir}/{subdir}"))
+        );
+        assert!(walk_subdirs(&format!("{dir}/z.txt"), &options)
+            .await
+            .is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_walk_dir_symlinks() {
+        let dir = std::env::temp_dir().join("fpc_test_walk_dir_symlinks");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("a/b")).unwrap();
+        std::fs::write(dir.join("a/b/c.txt"), "").unwrap();
+        std::fs::write(dir.join("a/d.txt"), "").unwrap();
+        std::os::unix::fs::symlink(dir.join("a"), dir.join("a/b/loop")).unwrap();
+        let dir = dir.to_str().unwrap();
+        for gitignore in [false, true] {
+            let options = WalkOptions {
+                gitignore,
+                follow_symlinks: true,
+                ..WalkOptions::default()
+            };
+            let (files, errors) = walk_dir(dir, &options).await;
+            let expected = ["a/b/c.txt", "a/d.txt"];
+            assert_eq!(files, expected.map(|file| format!("{dir}/{file}")));
+            assert!(errors.is_empty());
+            let options = WalkOptions {
+                max_depth: Some(2),
+                ..options
+            };
+            let (files, _) = walk_dir(dir, &options).await;
+            assert_eq!(files, [format!("{dir}/a/d.txt")]);
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_dedupe_files() {
+        let dir = std::env::temp_dir().join("fpc_test_dedupe_files");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "").unwrap();
+        std::fs::write(dir.join("b.txt"), "").unwrap();
+        std::fs::hard_link(dir.join("a.txt"), dir.join("hard.txt")).unwrap();
+        std::os::unix::fs::symlink(dir.join("a.txt"), dir.join("soft.txt")).unwrap();
+        let paths = [
+            "a.txt",
+            "b.txt",
+            "hard.txt",
+            "soft.txt",
+            "a.txt",
+            "missing.txt",
+        ]
+        .map(|file| dir.join(file).to_str().unwrap().to_string());
+        let files = dedupe_files(paths.to_vec()).await;
+        assert_eq!(files, [0, 1, 5].map(|i| paths[i].clone()));
+    }
+
+    #[tokio::test]
+    async fn test_identical_files() {
+        let dir = std::env::temp_dir().join("fpc_test_identical_files");
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = [("c.txt", "a b\n"), ("a.txt", "a b\n"), ("b.txt", "c d\n")];
+        for (name, data) in files {
+            std::fs::write(dir.join(name), data).unwrap();
+        }
+        let paths = ["c.txt", "a.txt", "b.txt", "missing.txt"]
+            .map(|file| dir.join(file).to_str().unwrap().to_string());
+        let duplicates = identical_files(&paths).await;
+        assert_eq!(
+            duplicates,
+            BTreeMap::from([(paths[0].clone(), paths[1].clone())])
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_skip_large_files() {
+        let dir = std::env::temp_dir().join("fpc_test_skip_large_files");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("small.txt"), "a b").unwrap();
+        std::fs::write(dir.join("large.txt"), "a b c d").unwrap();
+        let paths = ["large.txt", "small.txt", "missing.txt"]
+            .map(|file| dir.join(file).to_str().unwrap().to_string());
+        let (files, errors) = skip_large_files(paths.to_vec(), 4).await;
+        assert_eq!(files, paths[1..]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].id, paths[0]);
+        assert_eq!(errors[0].kind, "FileTooLarge");
+        assert_eq!(errors[0].message, "7 bytes, above the maximum of 4 bytes");
+    }
+
+    #[tokio::test]
+    async fn test_walk_dir_filters() {
+        let dir = std::env::temp_dir().join("fpc_test_walk_dir_filters");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "text").unwrap();
+        std::fs::write(dir.join("b.LOG"), "text").unwrap();
+        std::fs::write(dir.join("c.log"), b"\x7fELF\0\0").unwrap();
+        std::fs::write(dir.join("d.log"), b"\x1f\x8b\0\0").unwrap();
+        std::fs::write(dir.join("e.csv"), "text").unwrap();
+        let dir = dir.to_str().unwrap();
+        let options = WalkOptions {
+            include_exts: vec!["txt".into(), "log".into()],
+            ..WalkOptions::default()
+        };
+        let (files, _) = walk_dir(dir, &options).await;
+        let expected = ["a.txt", "b.LOG", "c.log", "d.log"];
+        assert_eq!(files, expected.map(|file| format!("{dir}/{file}")));
+        let options = WalkOptions {
+            text_only: true,
+            ..options
+        };
+        let (files, _) = walk_dir(dir, &options).await;
+        let expected = ["a.txt", "b.LOG", "d.log"];
+        assert_eq!(files, expected.map(|file| format!("{dir}/{file}")));
+    }
+
+    #[tokio::test]
+    async fn test_walk_dir_gitignore() {
+        let dir = std::env::temp_dir().join("fpc_test_walk_dir_gitignore");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("target")).unwrap();
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        std::fs::write(dir.join(".gitignore"), "target/\n*.log\n").unwrap();
+        for file in ["a.txt", "b.log", "target/c.txt", ".git/HEAD"] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        let dir = dir.to_str().unwrap();
+        let options = WalkOptions {
+            gitignore: true,
+            excludes: vec![Pattern::new(".gitignore").unwrap()],
+            ..WalkOptions::default()
+        };
+        let (files, errors) = walk_dir(dir, &options).await;
+        assert_eq!(files, [format!("{dir}/a.txt")]);
+        assert!(errors.is_empty());
+    }
+}
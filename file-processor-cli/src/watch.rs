@@ -0,0 +1,110 @@
+use std::{
+    future::Future,
+    process::ExitCode,
+    time::{Duration, SystemTime},
+};
+
+use futures_util::{stream, StreamExt};
+
+use crate::{
+    args::Args,
+    inputs::{read_manifest, Inputs},
+    summary::INTERRUPTED,
+    walk::{walk_subdirs, WALK_CONCURRENCY},
+};
+
+/// Interval between the checks of the watched files.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Last modification time and length of a file, none if it does not exist.
+type Version = Option<(SystemTime, u64)>;
+
+/// Run `process` on the sources of the arguments, then again each time one of their files is
+/// modified, created or removed, until the process is interrupted. The directories are walked
+/// again after each run, for their new files to be watched, and a signal between the runs ends
+/// the watch, as an interrupted run. A failed run is logged and the files are still watched.
+pub async fn watch<F: Future<Output = Result<ExitCode, Box<dyn std::error::Error>>>>(
+    args: &Args,
+    mut process: impl FnMut() -> F,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    loop {
+        let paths = watched_paths(args).await?;
+        let watched = versions(&paths).await;
+        match process().await {
+            Ok(code) if code == ExitCode::from(INTERRUPTED) => return Ok(code),
+            Ok(_) => {}
+            Err(e) => log::error!("{e}"),
+        }
+        log::info!("Watching {} files for changes.", paths.len());
+        let changed = async {
+            loop {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                if versions(&paths).await != watched {
+                    break;
+                }
+            }
+        };
+        tokio::select! {
+            () = changed => {}
+            _ = crate::terminated() => return Ok(INTERRUPTED.into()),
+        }
+    }
+}
+
+/// Files of the arguments, with the directories and their subdirectories, whose modification
+/// time changes when a file is created or removed in them, the manifest and the files found.
+async fn watched_paths(args: &Args) -> Result<Vec<String>, String> {
+    let manifest = match &args.files_from {
+        Some(path) => Some(read_manifest(path, args.null)?),
+        None => None,
+    };
+    let walk = args.walk_options();
+    let inputs = Inputs::new(args.files.clone(), manifest, args.options())
+        .walk_dirs(&walk)
+        .await;
+    let mut paths = args.files.clone();
+    for path in &args.files {
+        paths.extend(walk_subdirs(path, &walk).await);
+    }
+    paths.extend(args.files_from.clone());
+    paths.extend(inputs.paths().iter().cloned());
+    paths.sort_unstable();
+    paths.dedup();
+    Ok(paths)
+}
+
+async fn versions(paths: &[String]) -> Vec<Version> {
+    stream::iter(paths)
+        .map(|path| async move {
+            let metadata = tokio::fs::metadata(path).await.ok()?;
+            Some((metadata.modified().ok()?, metadata.len()))
+        })
+        .buffered(WALK_CONCURRENCY)
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_watched_paths() {
+        let dir = std::env::temp_dir().join("fpc_test_watched_paths");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("logs/old")).unwrap();
+        std::fs::write(dir.join("logs/a.log"), "a").unwrap();
+        std::fs::write(dir.join("b.txt"), "b").unwrap();
+        let dir = dir.to_str().unwrap();
+        let args = Args::try_parse_from(["fpc", "--watch", &format!("{dir}/logs")]).unwrap();
+        let paths = watched_paths(&args).await.unwrap();
+        let expected = ["logs", "logs/a.log", "logs/old"].map(|path| format!("{dir}/{path}"));
+        assert_eq!(paths, expected);
+
+        let paths = [format!("{dir}/b.txt"), format!("{dir}/missing.txt")];
+        let before = versions(&paths).await;
+        assert!(before[0].is_some() && before[1].is_none());
+        std::fs::write(&paths[0], "bb").unwrap();
+        assert_ne!(versions(&paths).await, before);
+    }
+}
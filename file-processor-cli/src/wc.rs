@@ -0,0 +1,147 @@
+use std::{
+    io::{self, Write},
+    sync::atomic::Ordering,
+};
+
+use string_stream_processor::{LineStats, ProcessorOptions, SourceProvider, StringMultiStreamExt};
+
+use crate::{inputs::STDIN, tally::Tallied};
+
+/// Minimum width of the columns when an input is not a regular file, as `wc` does.
+const NON_REGULAR_WIDTH: usize = 7;
+
+/// Counts of the sources of a provider, with their bytes and line endings, printed like
+/// `wc -l -w -c`. The bytes of the regular files are their size on disk, before their
+/// decompression like `wc -c`.
+pub struct Wc<P> {
+    sources: Tallied<P>,
+    /// Name of the standard input in the output, `wc` prints no name for an implicit one.
+    stdin_name: Option<&'static str>,
+}
+
+impl<P: SourceProvider> Wc<P> {
+    /// Wrap the sources of `inner`, `explicit_stdin` tells if the standard input was given as `-`.
+    pub fn new(inner: P, explicit_stdin: bool) -> Self {
+        Self {
+            sources: Tallied::new(inner).with_newlines(),
+            stdin_name: explicit_stdin.then_some("-"),
+        }
+    }
+
+    /// Tallies of the sources.
+    pub fn sources(&self) -> &Tallied<P> {
+        &self.sources
+    }
+
+    /// Count the sources and write their lines, words and bytes, then their total if there are
+    /// several sources.
+    pub async fn write(&self, options: ProcessorOptions, writer: impl Write) -> io::Result<()> {
+        let stats = self.sources.sources().count_line_words_stats(options).await;
+        let tallies = self.sources.tallies().clone();
+        let mut rows = Vec::with_capacity(tallies.len());
+        for (id, tally) in &tallies {
+            let words = stats
+                .get(id.as_str())
+                .map_or(0, |stats: &LineStats| stats.words);
+            let name = match id.as_str() {
+                STDIN => self.stdin_name,
+                id => Some(id),
+            };
+            let bytes = match raw_size(id).await {
+                Some(size) => size,
+                None => tally.bytes.load(Ordering::Relaxed),
+            };
+            let counts = [tally.newlines.load(Ordering::Relaxed), words, bytes];
+            rows.push((name, counts));
+        }
+        let regular = tallies.iter().all(|(id, _)| id != STDIN || stdin_is_file());
+        write_rows(writer, &rows, regular)
+    }
+}
+
+/// Size of a source which is a regular file, the bytes read from it being counted after its
+/// decompression.
+async fn raw_size(id: &str) -> Option<u64> {
+    if id == STDIN {
+        return None;
+    }
+    let metadata = tokio::fs::metadata(id).await.ok()?;
+    metadata.is_file().then_some(metadata.len())
+}
+
+/// Whether the standard input is redirected from a regular file.
+fn stdin_is_file() -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::fd::AsFd;
+        io::stdin()
+            .as_fd()
+            .try_clone_to_owned()
+            .map(std::fs::File::from)
+            .and_then(|file| file.metadata())
+            .is_ok_and(|metadata| metadata.is_file())
+    }
+    #[cfg(not(unix))]
+    false
+}
+
+/// Write the rows in the `wc` format: the columns are as wide as the total number of bytes, or
+/// at least [`NON_REGULAR_WIDTH`] if an input is not a regular file.
+fn write_rows(
+    mut writer: impl Write,
+    rows: &[(Option<&str>, [u64; 3])],
+    regular: bool,
+) -> io::Result<()> {
+    let mut total = [0; 3];
+    for (_, counts) in rows {
+        for (total, count) in total.iter_mut().zip(counts) {
+            *total += count;
+        }
+    }
+    let min_width = if regular { 1 } else { NON_REGULAR_WIDTH };
+    let width = total[2].to_string().len().max(min_width);
+    let total_row = (rows.len() > 1).then_some((Some("total"), total));
+    for (name, [lines, words, bytes]) in rows.iter().copied().chain(total_row) {
+        write!(writer, "{lines:>width$} {words:>width$} {bytes:>width$}")?;
+        match name {
+            Some(name) => writeln!(writer, " {name}")?,
+            None => writeln!(writer)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows_output(rows: &[(Option<&str>, [u64; 3])], regular: bool) -> String {
+        let mut output = Vec::new();
+        write_rows(&mut output, rows, regular).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_write_rows() {
+        let rows = [(Some("in.txt"), [2, 3, 6]), (Some("nonl.txt"), [1, 3, 5])];
+        assert_eq!(
+            rows_output(&rows, true),
+            " 2  3  6 in.txt\n 1  3  5 nonl.txt\n 3  6 11 total\n"
+        );
+        assert_eq!(rows_output(&rows[..1], true), "2 3 6 in.txt\n");
+        assert_eq!(
+            rows_output(&[(None, [2, 3, 6])], false),
+            "      2       3       6\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_raw_size() {
+        let path = std::env::temp_dir().join("fpc_test_raw_size.gz");
+        std::fs::write(&path, [0x1f, 0x8b, 0x08]).unwrap();
+        assert_eq!(raw_size(path.to_str().unwrap()).await, Some(3));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(raw_size(STDIN).await, None);
+        assert_eq!(raw_size(std::env::temp_dir().to_str().unwrap()).await, None);
+    }
+}
@@ -0,0 +1,33 @@
+#ifndef FILE_PROCESSOR_H
+#define FILE_PROCESSOR_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The call succeeded. */
+#define FP_OK 0
+/* A pointer is null or a path is not valid UTF-8. */
+#define FP_INVALID_ARGUMENT 1
+/* The runtime could not be started or the result could not be serialized. */
+#define FP_INTERNAL_ERROR 2
+
+/*
+ * Count the number of words for each line of the `len` files of `paths`.
+ *
+ * On success, `*out_json` is set to a JSON object of the file paths to the word counts of
+ * their lines, which must be freed with `fp_free_string`. Files that cannot be opened are
+ * skipped.
+ */
+int fp_count_files(const char *const *paths, size_t len, char **out_json);
+
+/* Free a string returned by `fp_count_files`. Does nothing if `s` is null. */
+void fp_free_string(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FILE_PROCESSOR_H */
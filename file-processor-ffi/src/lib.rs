@@ -0,0 +1,126 @@
+//! C ABI of the concurrent word counter, see `include/file_processor.h`.
+
+use std::{
+    ffi::{c_char, c_int, CStr, CString},
+    ptr, slice,
+};
+
+use futures_util::{stream, StreamExt};
+use string_stream_processor::{ProcessorOptions, StringMultiStreamExt};
+use tokio::{fs::File, io::BufReader};
+
+/// The call succeeded.
+pub const FP_OK: c_int = 0;
+/// A pointer is null or a path is not valid UTF-8.
+pub const FP_INVALID_ARGUMENT: c_int = 1;
+/// The runtime could not be started or the result could not be serialized.
+pub const FP_INTERNAL_ERROR: c_int = 2;
+
+/// Count the number of words for each line of the given files.
+///
+/// On success, `out_json` is set to a JSON object of the file paths to the word counts of their
+/// lines, which must be freed with [`fp_free_string`]. Files that cannot be opened are skipped.
+///
+/// # Safety
+/// `paths` must point to `len` valid nul-terminated strings and `out_json` must be valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn fp_count_files(
+    paths: *const *const c_char,
+    len: usize,
+    out_json: *mut *mut c_char,
+) -> c_int {
+    if out_json.is_null() || (paths.is_null() && len > 0) {
+        return FP_INVALID_ARGUMENT;
+    }
+    let paths = if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(paths, len)
+    };
+    let mut files = Vec::with_capacity(len);
+    for &path in paths {
+        if path.is_null() {
+            return FP_INVALID_ARGUMENT;
+        }
+        match CStr::from_ptr(path).to_str() {
+            Ok(path) => files.push(path),
+            Err(_) => return FP_INVALID_ARGUMENT,
+        }
+    }
+
+    match count_files(&files) {
+        Ok(json) => {
+            *out_json = json.into_raw();
+            FP_OK
+        }
+        Err(e) => {
+            log::error!("Could not count the files, {e}.");
+            *out_json = ptr::null_mut();
+            FP_INTERNAL_ERROR
+        }
+    }
+}
+
+/// Free a string returned by [`fp_count_files`]. Does nothing if `s` is null.
+///
+/// # Safety
+/// `s` must be null or a string returned by this library, not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn fp_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+fn count_files(files: &[&str]) -> Result<CString, Box<dyn std::error::Error>> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let options = ProcessorOptions::default();
+    let result = rt.block_on(
+        stream::iter(files.iter().copied())
+            .filter_map(|path| open_file(path, &options))
+            .count_line_words_concurrent_with(options),
+    );
+    // JSON strings escape nul characters, this never fails.
+    Ok(CString::new(serde_json::to_vec(&result)?)?)
+}
+
+async fn open_file<'a>(
+    path: &'a str,
+    options: &ProcessorOptions,
+) -> Option<(&'a str, BufReader<File>)> {
+    let file = File::open(path)
+        .await
+        .inspect_err(|e| log::warn!("Could not open {path}, {e}, skipping it."))
+        .ok()?;
+    Some((path, options.buf_reader(file)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fp_count_files() {
+        let path = std::env::temp_dir().join("fp_test_count_files.txt");
+        std::fs::write(&path, "Hello world\n\nfoo bar baz").unwrap();
+        let path = CString::new(path.to_str().unwrap()).unwrap();
+        let missing = CString::new("missing.txt").unwrap();
+        let paths = [path.as_ptr(), missing.as_ptr()];
+
+        let mut json = ptr::null_mut();
+        let code = unsafe { fp_count_files(paths.as_ptr(), paths.len(), &mut json) };
+        assert_eq!(code, FP_OK);
+        let result: serde_json::Value =
+            serde_json::from_slice(unsafe { CStr::from_ptr(json) }.to_bytes()).unwrap();
+        assert_eq!(result[path.to_str().unwrap()], serde_json::json!([2, 0, 3]));
+        assert!(result.get("missing.txt").is_none());
+        unsafe { fp_free_string(json) };
+
+        let code = unsafe { fp_count_files(paths.as_ptr(), paths.len(), ptr::null_mut()) };
+        assert_eq!(code, FP_INVALID_ARGUMENT);
+        std::fs::remove_file(path.to_str().unwrap()).unwrap();
+    }
+}
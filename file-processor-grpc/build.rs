@@ -0,0 +1,9 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // The protobuf files are compiled by protox, without a `protoc` binary.
+    let files = protox::compile(["proto/file_processor.proto"], ["proto"])?;
+    tonic_prost_build::configure()
+        .bytes(".")
+        .compile_fds(files)?;
+    println!("cargo:rerun-if-changed=proto");
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use std::{cell::Cell, io, pin::pin, time::Duration};
+
+use futures_util::{StreamExt, TryStreamExt};
+use string_stream_processor::{count_line_words_with, ProcessorOptions};
+use tokio::{runtime::Handle, sync::mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::io::StreamReader;
+use tonic::{metadata::MetadataMap, Request, Response, Status, Streaming};
+
+/// Messages and stubs of `proto/file_processor.proto`.
+pub mod proto {
+    tonic::include_proto!("file_processor.v1");
+}
+
+pub use proto::file_processor_server::FileProcessorServer;
+use proto::{file_processor_server::FileProcessor, Chunk, LineCount};
+
+/// Number of counts buffered for a client, above which its chunks are not read anymore until
+/// it receives them.
+const BUFFERED_COUNTS: usize = 64;
+
+/// `FileProcessor` service counting the words of the text streams with the same options.
+///
+/// The counts of a stream end with a `DEADLINE_EXCEEDED` status once the `grpc-timeout` of its
+/// request is over, and its chunks are not read anymore once the client cancels it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileProcessorService {
+    options: ProcessorOptions,
+}
+
+impl FileProcessorService {
+    pub fn new(options: ProcessorOptions) -> Self {
+        Self { options }
+    }
+}
+
+#[tonic::async_trait]
+impl FileProcessor for FileProcessorService {
+    type CountWordsStream = ReceiverStream<Result<LineCount, Status>>;
+
+    async fn count_words(
+        &self,
+        request: Request<Streaming<Chunk>>,
+    ) -> Result<Response<Self::CountWordsStream>, Status> {
+        let timeout = grpc_timeout(request.metadata())?;
+        let chunks = request.into_inner();
+        let (tx, rx) = mpsc::channel(BUFFERED_COUNTS);
+        let options = self.options;
+        // The counting of a stream cannot be moved between the worker threads of the runtime.
+        let handle = Handle::current();
+        tokio::task::spawn_blocking(move || {
+            handle.block_on(async {
+                let counted = count_chunks(chunks, options, &tx);
+                let expired = async {
+                    match timeout {
+                        Some(timeout) => tokio::time::sleep(timeout).await,
+                        None => std::future::pending().await,
+                    }
+                };
+                tokio::select! {
+                    () = counted => {}
+                    () = tx.closed() => log::debug!("The client cancelled a stream."),
+                    () = expired => {
+                        let _ = tx.send(Err(Status::deadline_exceeded("deadline exceeded"))).await;
+                    }
+                }
+            })
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+/// Send the word count of each line of the chunks, then the error of the chunks which ended
+/// them, if any.
+async fn count_chunks(
+    chunks: Streaming<Chunk>,
+    options: ProcessorOptions,
+    tx: &mpsc::Sender<Result<LineCount, Status>>,
+) {
+    // The counting core ends the stream of a source on a read error, keep it to report it.
+    let error = Cell::new(None);
+    let data = chunks
+        .map_ok(|chunk| chunk.data)
+        .inspect_err(|status| error.set(Some(status.clone())))
+        .map_err(io::Error::other);
+    let mut counts = pin!(count_line_words_with(StreamReader::new(data), options));
+    let mut line_number = 0;
+    while let Some(word_count) = counts.next().await {
+        line_number += 1;
+        let count = LineCount {
+            line_number,
+            word_count: word_count as u64,
+        };
+        if tx.send(Ok(count)).await.is_err() {
+            return;
+        }
+    }
+    if let Some(status) = error.take() {
+        let _ = tx.send(Err(status)).await;
+    }
+}
+
+/// Timeout of the `grpc-timeout` header of a request, e.g. `30S`, none without it.
+fn grpc_timeout(metadata: &MetadataMap) -> Result<Option<Duration>, Status> {
+    let Some(timeout) = metadata.get("grpc-timeout") else {
+        return Ok(None);
+    };
+    let invalid = || Status::invalid_argument("invalid grpc-timeout");
+    let timeout = timeout.to_str().map_err(|_| invalid())?;
+    let digits = timeout.len().checked_sub(1).ok_or_else(invalid)?;
+    let value: u64 = timeout[..digits].parse().map_err(|_| invalid())?;
+    let timeout = match &timeout[digits..] {
+        "H" => Duration::from_secs(value * 60 * 60),
+        "M" => Duration::from_secs(value * 60),
+        "S" => Duration::from_secs(value),
+        "m" => Duration::from_millis(value),
+        "u" => Duration::from_micros(value),
+        "n" => Duration::from_nanos(value),
+        _ => return Err(invalid()),
+    };
+    Ok(Some(timeout))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Server;
+
+    use super::*;
+    use crate::proto::file_processor_client::FileProcessorClient;
+
+    #[test]
+    fn test_grpc_timeout() {
+        let mut metadata = MetadataMap::new();
+        assert_eq!(grpc_timeout(&metadata).unwrap(), None);
+        metadata.insert("grpc-timeout", "250m".parse().unwrap());
+        assert_eq!(
+            grpc_timeout(&metadata).unwrap(),
+            Some(Duration::from_millis(250))
+        );
+        metadata.insert("grpc-timeout", "5X".parse().unwrap());
+        assert!(grpc_timeout(&metadata).is_err());
+        metadata.insert("grpc-timeout", "S".parse().unwrap());
+        assert!(grpc_timeout(&metadata).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_count_words() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let service = FileProcessorServer::new(FileProcessorService::default());
+        let server = Server::builder().add_service(service);
+        tokio::spawn(server.serve_with_incoming(TcpListenerStream::new(listener)));
+
+        let mut client = FileProcessorClient::connect(url).await.unwrap();
+        // The chunks split a line and the bytes of a character.
+        let chunks = [&b"a b"[..], b"\nc\n\nd \xc3", b"\xa9 f"].map(|data| Chunk {
+            data: data.to_vec().into(),
+        });
+        let counts = client
+            .count_words(tokio_stream::iter(chunks))
+            .await
+            .unwrap()
+            .into_inner();
+        let counts: Vec<_> = counts
+            .map_ok(|count| (count.line_number, count.word_count))
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(counts, [(1, 2), (2, 1), (3, 0), (4, 3)]);
+
+        let mut request = Request::new(tokio_stream::pending());
+        request.set_timeout(Duration::from_millis(50));
+        let mut counts = client.count_words(request).await.unwrap().into_inner();
+        let status = counts.next().await.unwrap().unwrap_err();
+        assert_eq!(status.code(), tonic::Code::DeadlineExceeded);
+    }
+}
@@ -0,0 +1,29 @@
+use std::net::SocketAddr;
+
+use clap::Parser;
+use file_processor_grpc::{FileProcessorServer, FileProcessorService};
+use tonic::transport::Server;
+
+/// Serve the `FileProcessor` gRPC service, counting the words of each line of streamed text.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+    /// Address to listen on.
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:50051")]
+    listen: SocketAddr,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Info)
+        .parse_default_env()
+        .init();
+    log::info!("Listening on {}.", args.listen);
+    Server::builder()
+        .add_service(FileProcessorServer::new(FileProcessorService::default()))
+        .serve(args.listen)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! Python bindings of the concurrent word counter.
+
+use std::{collections::HashMap, io};
+
+use futures_util::{stream, StreamExt};
+use pyo3::{exceptions::PyOSError, prelude::*};
+use string_stream_processor::{ProcessorOptions, StringMultiStreamExt};
+use tokio::{fs::File, io::BufReader};
+
+/// Count the number of words for each line of the given files.
+///
+/// Returns a dict of the file paths to the word counts of their lines. Files that cannot be
+/// opened are skipped.
+#[pyfunction]
+fn count_line_words(py: Python<'_>, paths: Vec<String>) -> PyResult<HashMap<String, Vec<usize>>> {
+    // The GIL is released while the files are read.
+    py.allow_threads(|| count_files(&paths))
+        .map_err(|e| PyOSError::new_err(e.to_string()))
+}
+
+#[pymodule]
+fn file_processor(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(count_line_words, m)?)
+}
+
+fn count_files(paths: &[String]) -> io::Result<HashMap<String, Vec<usize>>> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let options = ProcessorOptions::default();
+    let result = rt.block_on(
+        stream::iter(paths)
+            .filter_map(|path| open_file(path, &options))
+            .count_line_words_concurrent_with(options),
+    );
+    Ok(result
+        .into_iter()
+        .map(|(path, counts)| (path.to_string(), counts))
+        .collect())
+}
+
+async fn open_file<'a>(
+    path: &'a str,
+    options: &ProcessorOptions,
+) -> Option<(&'a str, BufReader<File>)> {
+    let file = File::open(path)
+        .await
+        .inspect_err(|e| log::warn!("Could not open {path}, {e}, skipping it."))
+        .ok()?;
+    Some((path, options.buf_reader(file)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_files() {
+        let path = std::env::temp_dir().join("fp_py_test_count_files.txt");
+        std::fs::write(&path, "Hello world\n\nfoo bar baz").unwrap();
+        let path = path.to_str().unwrap().to_string();
+
+        let result = count_files(&[path.clone(), "missing.txt".to_string()]).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[&path], [2, 0, 3]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{BuildHasher, Hash},
+    io, mem,
+    pin::pin,
+};
+
+use futures_util::{future, stream, Stream, StreamExt};
+use tokio::sync::mpsc;
+
+use crate::{LineCount, SourceError};
+
+/// Per-identifier accumulator of line word counts.
+pub(crate) trait Accumulate: Default {
+    fn push(&mut self, count: usize);
+
+    /// Create an accumulator for a source expected to have `lines` lines.
+    fn with_capacity(_lines: usize) -> Self {
+        Self::default()
+    }
+}
+
+impl<C: LineCount> Accumulate for Vec<C> {
+    fn with_capacity(lines: usize) -> Self {
+        Vec::with_capacity(lines)
+    }
+
+    #[inline]
+    fn push(&mut self, count: usize) {
+        Vec::push(self, C::from_count(count));
+    }
+}
+
+/// Custom computation over the line word counts of a source, e.g. a sum, a sketch or the top
+/// lines, instead of storing the count of each line.
+///
+/// An aggregator is created for each source, see
+/// [`count_line_words_aggregated`](crate::StringMultiStreamExt::count_line_words_aggregated).
+pub trait Aggregator {
+    type Output;
+
+    /// Add the word count of the next line of the source.
+    fn update(&mut self, count: usize);
+
+    /// Returns the result of the source once all its lines are read.
+    fn finish(self) -> Self::Output;
+}
+
+impl<C: LineCount> Aggregator for Vec<C> {
+    type Output = Self;
+
+    #[inline]
+    fn update(&mut self, count: usize) {
+        Vec::push(self, C::from_count(count));
+    }
+
+    fn finish(self) -> Self {
+        self
+    }
+}
+
+/// Caller-owned container of the line word counts of each identifier, see
+/// [`count_line_words_into`](crate::StringMultiStreamExt::count_line_words_into).
+///
+/// Implemented for the hash and B-tree maps of [`Aggregator`]s, e.g. `Vec<usize>` or
+/// [`LineStats`](crate::LineStats). The counts are added to the existing values of the
+/// identifiers, a map reused across runs should be cleared first.
+pub trait ResultMap<I> {
+    /// Add the word count of the next line of the identifier.
+    fn push(&mut self, id: I, count: usize);
+}
+
+impl<I, A, S> ResultMap<I> for HashMap<I, A, S>
+where
+    I: Hash + Eq,
+    A: Aggregator + Default,
+    S: BuildHasher,
+{
+    #[inline]
+    fn push(&mut self, id: I, count: usize) {
+        self.entry(id).or_default().update(count);
+    }
+}
+
+impl<I: Ord, A: Aggregator + Default> ResultMap<I> for BTreeMap<I, A> {
+    #[inline]
+    fn push(&mut self, id: I, count: usize) {
+        self.entry(id).or_default().update(count);
+    }
+}
+
+/// Storage of the aggregated line counts of all the identifiers.
+pub(crate) trait Aggregate<I, T = usize> {
+    fn push(&mut self, id: I, count: T);
+
+    /// Whether the storage takes no more counts, which stops reading the sources.
+    fn is_closed(&self) -> bool {
+        false
+    }
+}
+
+impl<I, M: ResultMap<I>> Aggregate<I> for &mut M {
+    #[inline]
+    fn push(&mut self, id: I, count: usize) {
+        ResultMap::push(*self, id, count);
+    }
+}
+
+impl<I, A, S> Aggregate<I> for HashMap<I, A, S>
+where
+    I: Hash + Eq,
+    A: Accumulate,
+    S: BuildHasher,
+{
+    #[inline]
+    fn push(&mut self, id: I, count: usize) {
+        self.entry(id).or_default().push(count);
+    }
+}
+
+/// The counts of a source are kept until its error, if any.
+impl<I: Hash + Eq> Aggregate<I, io::Result<usize>> for HashMap<I, Result<Vec<usize>, SourceError>> {
+    #[inline]
+    fn push(&mut self, id: I, count: io::Result<usize>) {
+        let result = self.entry(id).or_insert_with(|| Ok(Vec::new()));
+        match (result, count) {
+            (Ok(counts), Ok(count)) => counts.push(count),
+            (result @ Ok(_), Err(error)) => {
+                let counts = result.as_mut().map(mem::take).unwrap_or_default();
+                *result = Err(SourceError { counts, error });
+            }
+            // A failed source yields no more counts.
+            (Err(_), _) => {}
+        }
+    }
+}
+
+/// Map of identifiers to accumulators, each one pre-sized with a hint of its number of lines.
+pub(crate) struct HintedMap<I, A, S, F> {
+    pub map: HashMap<I, A, S>,
+    lines_hint: F,
+}
+
+impl<I, A, S: Default, F> HintedMap<I, A, S, F> {
+    pub fn new(lines_hint: F) -> Self {
+        Self {
+            map: HashMap::default(),
+            lines_hint,
+        }
+    }
+}
+
+impl<I, A, S, F> Aggregate<I> for HintedMap<I, A, S, F>
+where
+    I: Hash + Eq + AsRef<str>,
+    A: Accumulate,
+    S: BuildHasher,
+    F: Fn(&str) -> usize,
+{
+    #[inline]
+    fn push(&mut self, id: I, count: usize) {
+        let hint = &self.lines_hint;
+        self.map
+            .entry(id)
+            .or_insert_with_key(|id| A::with_capacity(hint(id.as_ref())))
+            .push(count);
+    }
+}
+
+/// Map of identifiers to the aggregators created by `new` for their first line.
+pub(crate) struct AggregatorMap<I, A, F> {
+    pub map: HashMap<I, A>,
+    new: F,
+}
+
+impl<I, A, F> AggregatorMap<I, A, F> {
+    pub fn new(new: F) -> Self {
+        Self {
+            map: HashMap::new(),
+            new,
+        }
+    }
+}
+
+impl<I, A, F> Aggregate<I> for AggregatorMap<I, A, F>
+where
+    I: Hash + Eq + AsRef<str>,
+    A: Aggregator,
+    F: Fn(&str) -> A,
+{
+    #[inline]
+    fn push(&mut self, id: I, count: usize) {
+        let new = &self.new;
+        self.map
+            .entry(id)
+            .or_insert_with_key(|id| new(id.as_ref()))
+            .update(count);
+    }
+}
+
+/// Aggregate a stream of line counts, through a bounded buffer of the given capacity if any.
+pub(crate) async fn aggregate<I, T, G: Aggregate<I, T>>(
+    counts: impl Stream<Item = (I, T)>,
+    buffer_capacity: Option<usize>,
+    acc: G,
+) -> G {
+    match buffer_capacity {
+        Some(capacity) => aggregate_buffered(counts, capacity, acc).await,
+        None => aggregate_direct(counts, acc).await,
+    }
+}
+
+async fn aggregate_direct<I, T, G: Aggregate<I, T>>(
+    counts: impl Stream<Item = (I, T)>,
+    mut acc: G,
+) -> G {
+    let mut counts = pin!(counts);
+    while let Some((id, count)) = counts.next().await {
+        acc.push(id, count);
+        if acc.is_closed() {
+            break;
+        }
+    }
+    acc
+}
+
+/// Same as [`aggregate_direct`] but the counts are read ahead in a bounded buffer of size `capacity`.
+///
+/// The read and aggregation stages are polled concurrently, the read stage waits for free space
+/// in the buffer before polling the readers again.
+async fn aggregate_buffered<I, T, G: Aggregate<I, T>>(
+    counts: impl Stream<Item = (I, T)>,
+    capacity: usize,
+    acc: G,
+) -> G {
+    let (tx, mut rx) = mpsc::channel(capacity.max(1));
+    let read = async move {
+        let mut counts = pin!(counts);
+        while let Some(count) = counts.next().await {
+            if tx.send(count).await.is_err() {
+                break;
+            }
+        }
+    };
+    // The receiver is dropped with the stream once the aggregation stops, e.g. when closed,
+    // which ends the read stage waiting for free space.
+    let buffered = stream::poll_fn(move |cx| rx.poll_recv(cx));
+    let ((), acc) = future::join(read, aggregate_direct(buffered, acc)).await;
+    acc
+}
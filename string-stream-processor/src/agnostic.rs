@@ -0,0 +1,135 @@
+use std::{collections::HashMap, error::Error, future::Future, hash::RandomState, io};
+
+use futures_util::{io::AsyncBufRead, Stream, StreamExt, TryStreamExt};
+
+use crate::{
+    count_line_words_concurrent,
+    source::{count_line_words_retrying, NoReconnect},
+    ProcessorOptions,
+};
+
+/// Extension trait for stream over [`futures_util::io`] readers bound to a string identifier.
+///
+/// This is the runtime-agnostic counterpart of [`StringMultiStreamExt`](crate::StringMultiStreamExt),
+/// it can be used with async-std or smol readers. With the `runtime` feature, retries with a
+/// backoff delay and the [`blocking batches`](ProcessorOptions::with_blocking_batch) still need
+/// a Tokio runtime.
+pub trait FuturesMultiStreamExt<'a, R>: Stream<Item = (&'a str, R)> + Sized
+where
+    R: AsyncBufRead + Unpin,
+{
+    /// Count the number of words from a stream of async readers and associated identifiers.
+    /// Returns a map of the identifier to a vector of word counts for each line.
+    ///
+    /// The readers will be polled concurrently.
+    fn count_line_words_futures(
+        self,
+        options: ProcessorOptions,
+    ) -> impl Future<Output = HashMap<&'a str, Vec<usize>>> {
+        count_line_words_concurrent::<_, RandomState, _>(self, options, &NoReconnect)
+    }
+}
+
+impl<'a, R, S> FuturesMultiStreamExt<'a, R> for S
+where
+    R: AsyncBufRead + Unpin,
+    S: Stream<Item = (&'a str, R)>,
+{
+}
+
+/// Extension trait for stream over streams of byte chunks bound to a string identifier, e.g. the
+/// bodies of HTTP requests (`hyper` or `axum` bodies of `Bytes`) or the payloads of a message
+/// queue, without wrapping each of them into a reader.
+///
+/// The lines are re-split across the chunk boundaries. The streams of chunks which cannot fail
+/// can be mapped with `Ok::<_, Infallible>`.
+pub trait ChunkMultiStreamExt<'a, C, B, E>: Stream<Item = (&'a str, C)> + Sized
+where
+    C: Stream<Item = Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    /// Count the number of words from a stream of byte chunk streams and associated identifiers.
+    /// Returns a map of the identifier to a vector of word counts for each line.
+    ///
+    /// The chunk streams will be polled concurrently, an error ends the stream of its source.
+    fn count_chunk_line_words_concurrent(
+        self,
+        options: ProcessorOptions,
+    ) -> impl Future<Output = HashMap<&'a str, Vec<usize>>> {
+        let rds = self.map(|(id, chunks)| (id, chunks.map_err(io::Error::other).into_async_read()));
+        count_line_words_concurrent::<_, RandomState, _>(rds, options, &NoReconnect)
+    }
+}
+
+impl<'a, C, B, E, S> ChunkMultiStreamExt<'a, C, B, E> for S
+where
+    C: Stream<Item = Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+    E: Into<Box<dyn Error + Send + Sync>>,
+    S: Stream<Item = (&'a str, C)>,
+{
+}
+
+/// Returns a stream of the number of words for each line of a stream of byte chunks.
+///
+/// This is the entry point for sources which are not readers, e.g. a `web_sys::ReadableStream`
+/// converted with `wasm-streams`. An error ends the stream.
+pub fn count_chunk_line_words<'a, S, B>(
+    chunks: S,
+    options: ProcessorOptions,
+) -> impl Stream<Item = usize> + 'a
+where
+    S: Stream<Item = io::Result<B>> + Unpin + 'a,
+    B: AsRef<[u8]> + 'a,
+{
+    let rd = chunks.into_async_read();
+    count_line_words_retrying(("chunk stream", rd), options, &NoReconnect).map(|(_, count)| count)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+    use futures_util::{io::Cursor, stream};
+
+    use super::*;
+
+    #[test]
+    fn test_count_line_words_futures() {
+        // Polled without any Tokio runtime.
+        let srcs = [("a", Cursor::new("Hello world\n\nfoo bar baz"))];
+        let result =
+            block_on(stream::iter(srcs).count_line_words_futures(ProcessorOptions::default()));
+        assert_eq!(result["a"], [2, 0, 3]);
+    }
+
+    #[test]
+    fn test_count_chunk_line_words() {
+        let chunks = ["Hello wo", "rld\n", "\nfoo ", "bar baz"].map(io::Result::Ok);
+        let counts = count_chunk_line_words(stream::iter(chunks), ProcessorOptions::default());
+        assert_eq!(block_on(counts.collect::<Vec<_>>()), [2, 0, 3]);
+    }
+
+    #[test]
+    fn test_count_chunk_line_words_concurrent() {
+        let ok = |chunks: &'static [&'static str]| {
+            stream::iter(
+                chunks
+                    .iter()
+                    .map(|chunk| Ok::<_, io::Error>(chunk.as_bytes())),
+            )
+        };
+        let failing = stream::iter([Ok(&b"a b\nc"[..]), Err(io::ErrorKind::Other.into())]);
+        let srcs = [
+            ("a", ok(&["Hello wo", "rld\n", "\nfoo ", "bar baz"]).boxed()),
+            ("b", ok(&["a\r", "\nb"]).boxed()),
+            ("c", failing.boxed()),
+        ];
+        let result = block_on(
+            stream::iter(srcs).count_chunk_line_words_concurrent(ProcessorOptions::default()),
+        );
+        assert_eq!(result["a"], [2, 0, 3]);
+        assert_eq!(result["b"], [1, 1]);
+        assert_eq!(result["c"], [2]);
+    }
+}
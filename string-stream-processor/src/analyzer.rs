@@ -0,0 +1,106 @@
+use std::{io, sync::Arc};
+
+use crate::Tokenizer;
+
+/// Object-safe per-line computation, so that it can be selected at runtime (e.g. from a
+/// configuration file) as a `Box<dyn DynLineAnalyzer>`.
+///
+/// The value computed for each line is aggregated like the word counts. Lines may be analyzed on
+/// the blocking thread pool, so implementations must be thread-safe.
+pub trait DynLineAnalyzer: Send + Sync {
+    /// Analyze a line, without its trailing line ending.
+    fn analyze(&self, line: &[u8]) -> io::Result<usize>;
+}
+
+impl DynLineAnalyzer for Tokenizer {
+    fn analyze(&self, line: &[u8]) -> io::Result<usize> {
+        self.count_words(line)
+    }
+}
+
+impl<F> DynLineAnalyzer for F
+where
+    F: Fn(&[u8]) -> io::Result<usize> + Send + Sync,
+{
+    fn analyze(&self, line: &[u8]) -> io::Result<usize> {
+        self(line)
+    }
+}
+
+/// Returns the built-in analyzer with the given name:
+/// - `words`: word count with [`Tokenizer::Unicode`],
+/// - `ascii-words`: word count with [`Tokenizer::Ascii`],
+/// - `bytes`: length of the line in bytes,
+/// - `chars`: length of the line in Unicode characters, lines must be valid UTF-8,
+/// - `longest-word` and `shortest-word`: length in Unicode characters of the longest and of the
+///   shortest word of the line split on Unicode whitespace, zero without words, lines must be
+///   valid UTF-8.
+pub fn analyzer_from_name(name: &str) -> Option<Box<dyn DynLineAnalyzer>> {
+    fn utf8(line: &[u8]) -> io::Result<&str> {
+        std::str::from_utf8(line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+    fn chars(line: &[u8]) -> io::Result<usize> {
+        utf8(line).map(|line| line.chars().count())
+    }
+    fn word_lengths(line: &str) -> impl Iterator<Item = usize> + '_ {
+        line.split_whitespace().map(|word| word.chars().count())
+    }
+    fn longest_word(line: &[u8]) -> io::Result<usize> {
+        Ok(word_lengths(utf8(line)?).max().unwrap_or(0))
+    }
+    fn shortest_word(line: &[u8]) -> io::Result<usize> {
+        Ok(word_lengths(utf8(line)?).min().unwrap_or(0))
+    }
+    match name {
+        "words" => Some(Box::new(Tokenizer::Unicode)),
+        "ascii-words" => Some(Box::new(Tokenizer::Ascii)),
+        "bytes" => Some(Box::new(|line: &[u8]| Ok(line.len()))),
+        "chars" => Some(Box::new(chars)),
+        "longest-word" => Some(Box::new(longest_word)),
+        "shortest-word" => Some(Box::new(shortest_word)),
+        _ => None,
+    }
+}
+
+/// Per-line computation applied by the readers of the sources.
+#[derive(Clone)]
+pub(crate) enum Analyzer {
+    /// Word count, with the specialized paths of each tokenizer.
+    Tokenizer(Tokenizer),
+    Dyn(Arc<dyn DynLineAnalyzer>),
+}
+
+impl Analyzer {
+    /// Analyze a line read with its line ending, if any.
+    pub fn analyze(&self, line: &[u8]) -> io::Result<usize> {
+        match self {
+            // Line endings are whitespace, no need to strip them.
+            Analyzer::Tokenizer(tokenizer) => tokenizer.count_words(line),
+            Analyzer::Dyn(analyzer) => {
+                let line = line.strip_suffix(b"\n").unwrap_or(line);
+                analyzer.analyze(line.strip_suffix(b"\r").unwrap_or(line))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyzer_from_name() {
+        let analyze = |name, line| {
+            let analyzer = Analyzer::Dyn(analyzer_from_name(name).unwrap().into());
+            analyzer.analyze(line).unwrap()
+        };
+        assert_eq!(analyze("words", "héllo  world\r\n".as_bytes()), 2);
+        assert_eq!(analyze("ascii-words", b"a b c\n"), 3);
+        assert_eq!(analyze("bytes", "héllo\r\n".as_bytes()), 6);
+        assert_eq!(analyze("chars", "héllo\n".as_bytes()), 5);
+        assert_eq!(analyze("longest-word", "a héllo  bc\n".as_bytes()), 5);
+        assert_eq!(analyze("shortest-word", "a héllo  bc\n".as_bytes()), 1);
+        assert_eq!(analyze("longest-word", b"  \r\n"), 0);
+        assert!(analyzer_from_name("unknown").is_none());
+    }
+}
@@ -0,0 +1,212 @@
+use std::{collections::HashMap, io, path::Path};
+
+#[cfg(feature = "tar")]
+use futures_util::StreamExt;
+use tokio::fs::File;
+#[cfg(feature = "tar")]
+use tokio_tar::Archive;
+
+#[cfg(feature = "tar")]
+use crate::{compat::TokioCompat, Decompress};
+use crate::{source::try_count_line_words, ProcessorOptions};
+
+/// Count the number of words for each line of the regular files of a tar archive, which may be
+/// compressed (see [`Decompress::detect`]).
+///
+/// Each entry is identified by `{archive}::{path inside the archive}`. The entries are read one
+/// after the other, as a tar archive can only be read sequentially. Empty entries are skipped.
+/// A read error, even in the middle of an entry, is returned instead of the counts.
+#[cfg(feature = "tar")]
+pub async fn count_archive_line_words(
+    path: impl AsRef<Path>,
+    options: ProcessorOptions,
+) -> io::Result<HashMap<String, Vec<usize>>> {
+    let path = path.as_ref();
+    let file = File::open(path).await?;
+    let rd = Decompress::detect(path, options.buf_reader(file), options.read_buffer_size).await?;
+    let mut archive = Archive::new(rd);
+    let mut entries = archive.entries()?;
+    let mut result = HashMap::new();
+    while let Some(entry) = entries.next().await {
+        let entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let id = format!("{}::{}", path.display(), entry.path()?.display());
+        let rd = TokioCompat::new(options.buf_reader(entry));
+        let counts = try_count_line_words((id.as_str(), rd), options).await?;
+        if !counts.is_empty() {
+            result.insert(id, counts);
+        }
+    }
+    Ok(result)
+}
+
+/// Count the number of words for each line of the files of a zip archive, stored or deflated.
+///
+/// Each member is identified by `{archive}::{path inside the archive}`. The members are located
+/// with the central directory, so their sizes are known even when they were written with a data
+/// descriptor, and read one after the other. Empty members are skipped. A read error, even in
+/// the middle of a member, is returned instead of the counts.
+#[cfg(feature = "zip")]
+pub async fn count_zip_line_words(
+    path: impl AsRef<Path>,
+    options: ProcessorOptions,
+) -> io::Result<HashMap<String, Vec<usize>>> {
+    let path = path.as_ref();
+    let file = File::open(path).await?;
+    let mut archive =
+        async_zip::tokio::read::seek::ZipFileReader::with_tokio(options.buf_reader(file))
+            .await
+            .map_err(io::Error::other)?;
+    let mut result = HashMap::new();
+    for index in 0..archive.file().entries().len() {
+        let entry = &archive.file().entries()[index];
+        if entry.dir().map_err(io::Error::other)? {
+            continue;
+        }
+        let name = entry.filename().as_str().map_err(io::Error::other)?;
+        let id = format!("{}::{name}", path.display());
+        let rd = archive
+            .reader_without_entry(index)
+            .await
+            .map_err(io::Error::other)?;
+        let rd = futures_util::io::BufReader::with_capacity(options.read_buffer_size, rd);
+        let counts = try_count_line_words((id.as_str(), rd), options).await?;
+        if !counts.is_empty() {
+            result.insert(id, counts);
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "tar")]
+    #[tokio::test]
+    async fn test_count_archive_line_words() {
+        use async_compression::tokio::bufread::GzipEncoder;
+        use tokio::io::AsyncReadExt;
+        use tokio_tar::{Builder, Header};
+
+        let mut builder = Builder::new(Vec::new());
+        for (name, data) in [
+            ("logs/a.log", "a b\nc"),
+            ("b.log", "d e f\n"),
+            ("empty", ""),
+        ] {
+            let mut header = Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, data.as_bytes())
+                .await
+                .unwrap();
+        }
+        let tar = builder.into_inner().await.unwrap();
+        let mut gzip = Vec::new();
+        GzipEncoder::new(&tar[..])
+            .read_to_end(&mut gzip)
+            .await
+            .unwrap();
+        let file = tempfile::Builder::new()
+            .suffix(".tar.gz")
+            .tempfile()
+            .unwrap();
+        let path = file.path();
+        tokio::fs::write(path, gzip).await.unwrap();
+
+        let result = count_archive_line_words(path, ProcessorOptions::default())
+            .await
+            .unwrap();
+        let id = |name| format!("{}::{name}", path.display());
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[&id("logs/a.log")], [2, 1]);
+        assert_eq!(result[&id("b.log")], [3]);
+    }
+
+    #[cfg(feature = "tar")]
+    #[tokio::test]
+    async fn test_count_archive_line_words_truncated() {
+        use tokio_tar::{Builder, Header};
+
+        let data = "a b\n".repeat(1000);
+        let mut header = Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_cksum();
+        let mut builder = Builder::new(Vec::new());
+        builder
+            .append_data(&mut header, "a.log", data.as_bytes())
+            .await
+            .unwrap();
+        let mut tar = builder.into_inner().await.unwrap();
+        // Cut the archive in the middle of the data of the entry.
+        tar.truncate(512 + data.len() / 2);
+        let file = tempfile::Builder::new().suffix(".tar").tempfile().unwrap();
+        tokio::fs::write(file.path(), tar).await.unwrap();
+
+        let result = count_archive_line_words(file.path(), ProcessorOptions::default()).await;
+        assert!(result.is_err());
+    }
+
+    #[cfg(feature = "zip")]
+    #[tokio::test]
+    async fn test_count_zip_line_words() {
+        use async_zip::{base::write::ZipFileWriter, Compression, ZipEntryBuilder};
+
+        let mut writer = ZipFileWriter::new(Vec::new());
+        let members = [
+            ("logs/a.log", "a b\nc", Compression::Stored),
+            ("b.log", "d e f\n", Compression::Deflate),
+            ("empty", "", Compression::Deflate),
+        ];
+        for (name, data, compression) in members {
+            let entry = ZipEntryBuilder::new(name.to_string().into(), compression);
+            writer
+                .write_entry_whole(entry, data.as_bytes())
+                .await
+                .unwrap();
+        }
+        let zip = writer.close().await.unwrap();
+        let file = tempfile::Builder::new().suffix(".zip").tempfile().unwrap();
+        let path = file.path();
+        tokio::fs::write(path, zip).await.unwrap();
+
+        let result = count_zip_line_words(path, ProcessorOptions::default())
+            .await
+            .unwrap();
+        let id = |name| format!("{}::{name}", path.display());
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[&id("logs/a.log")], [2, 1]);
+        assert_eq!(result[&id("b.log")], [3]);
+    }
+
+    #[cfg(feature = "zip")]
+    #[tokio::test]
+    async fn test_count_zip_line_words_corrupted() {
+        use async_zip::{base::write::ZipFileWriter, Compression, ZipEntryBuilder};
+
+        // Deflate stream of a stored block of complete lines, followed by a block of the
+        // reserved type, invalid.
+        let lines = "a b\n".repeat(100);
+        let len = (lines.len() as u16).to_le_bytes();
+        let nlen = (!(lines.len() as u16)).to_le_bytes();
+        let deflated = [&[0][..], &len, &nlen, lines.as_bytes(), &[0x07]].concat();
+        let mut writer = ZipFileWriter::new(Vec::new());
+        let entry = ZipEntryBuilder::new("a.log".to_string().into(), Compression::Stored);
+        writer.write_entry_whole(entry, &deflated).await.unwrap();
+        let mut zip = writer.close().await.unwrap();
+        // Mark the member as deflated, in its local header and in the central directory.
+        let central = zip.windows(4).position(|sig| sig == b"PK\x01\x02").unwrap();
+        for method in [8, central + 10] {
+            zip[method..method + 2].copy_from_slice(&8u16.to_le_bytes());
+        }
+        let file = tempfile::Builder::new().suffix(".zip").tempfile().unwrap();
+        tokio::fs::write(file.path(), zip).await.unwrap();
+
+        let result = count_zip_line_words(file.path(), ProcessorOptions::default()).await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use std::{collections::HashMap, future::Future};
+
+use futures_util::{Stream, StreamExt};
+use tokio::io::{AsyncRead, BufReader};
+
+use crate::{ProcessorOptions, StringMultiStreamExt};
+
+/// Extension trait for stream over plain async readers bound to a string identifier, e.g. TCP
+/// sockets or HTTP bodies converted with `StreamReader`, without wrapping each of them into a
+/// [`BufReader`].
+///
+/// The readers are buffered with the [`read_buffer_size`](ProcessorOptions::read_buffer_size) of
+/// the options, then counted like with [`StringMultiStreamExt`].
+pub trait ReadMultiStreamExt<'a, R>: Stream<Item = (&'a str, R)> + Sized
+where
+    R: AsyncRead + Unpin,
+{
+    /// Wrap each reader into a [`BufReader`] of `capacity` bytes, to call the methods of
+    /// [`StringMultiStreamExt`] on them.
+    fn buffered_readers(self, capacity: usize) -> impl Stream<Item = (&'a str, BufReader<R>)> {
+        self.map(move |(id, rd)| (id, BufReader::with_capacity(capacity, rd)))
+    }
+
+    /// Count the number of words from a stream of plain async readers and associated
+    /// identifiers, see [`StringMultiStreamExt::count_line_words_concurrent_with`].
+    fn count_read_line_words_concurrent(
+        self,
+        options: ProcessorOptions,
+    ) -> impl Future<Output = HashMap<&'a str, Vec<usize>>> {
+        self.buffered_readers(options.read_buffer_size)
+            .count_line_words_concurrent_with(options)
+    }
+}
+
+impl<'a, R, S> ReadMultiStreamExt<'a, R> for S
+where
+    R: AsyncRead + Unpin,
+    S: Stream<Item = (&'a str, R)>,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use futures_util::stream;
+    use tokio::io::ReadBuf;
+
+    use super::*;
+
+    /// Reader of a byte at a time, without any buffer to fill.
+    struct Bytewise(&'static [u8]);
+
+    impl AsyncRead for Bytewise {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            if let Some((byte, rest)) = self.0.split_first() {
+                buf.put_slice(&[*byte]);
+                self.0 = rest;
+            }
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_count_read_line_words_concurrent() {
+        let srcs = [
+            ("a", Bytewise(b"Hello world\n\nfoo bar baz")),
+            ("b", Bytewise(b"c")),
+        ];
+        let options = ProcessorOptions::default().with_read_buffer_size(4);
+        let result = stream::iter(srcs)
+            .count_read_line_words_concurrent(options)
+            .await;
+        assert_eq!(result["a"], [2, 0, 3]);
+        assert_eq!(result["b"], [1]);
+    }
+}
@@ -0,0 +1,165 @@
+use std::{collections::HashMap, io, pin::Pin, sync::Arc};
+
+use futures_util::{stream, Stream, StreamExt, TryStreamExt};
+use object_store::{path::Path, ObjectStore, ObjectStoreExt};
+use tokio::io::AsyncBufRead;
+use tokio_util::io::StreamReader;
+
+use crate::{
+    provider::{split_uri, ESTIMATED_LINE_LEN},
+    ProcessorOptions, SourceProvider,
+};
+
+/// Reader of the objects of an [`ObjectStoreProvider`].
+#[cfg(not(feature = "compression"))]
+pub type ObjectReader = Pin<Box<dyn AsyncBufRead + Send>>;
+/// Reader of the objects of an [`ObjectStoreProvider`], decompressed according to their
+/// extension or their first bytes.
+#[cfg(feature = "compression")]
+pub type ObjectReader = crate::Decompress<Pin<Box<dyn AsyncBufRead + Send>>>;
+
+/// Provider of the objects of an [`ObjectStore`], identified by their URI, e.g.
+/// `gs://bucket/key` for Google Cloud Storage or `az://container/key` for Azure Blob Storage.
+#[derive(Debug)]
+pub struct ObjectStoreProvider {
+    store: Arc<dyn ObjectStore>,
+    /// URI and location of the objects.
+    objects: Vec<(String, Path)>,
+    options: ProcessorOptions,
+    /// Estimated number of lines of the objects, from their size.
+    lines_hints: HashMap<String, usize>,
+}
+
+impl ObjectStoreProvider {
+    /// Provide the objects of the store under `prefix`, sorted by location. Their URI is the
+    /// location prefixed by `base`, e.g. `gs://bucket`.
+    pub async fn list(
+        store: Arc<dyn ObjectStore>,
+        base: &str,
+        prefix: &str,
+        options: ProcessorOptions,
+    ) -> io::Result<Self> {
+        let prefix = (!prefix.is_empty()).then(|| Path::from(prefix));
+        let mut objects: Vec<_> = store
+            .list(prefix.as_ref())
+            .map_ok(|meta| (format!("{base}/{}", meta.location), meta))
+            .try_collect()
+            .await
+            .map_err(io::Error::other)?;
+        objects.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        let lines_hints = objects
+            .iter()
+            .map(|(uri, meta)| (uri.clone(), (meta.size / ESTIMATED_LINE_LEN) as usize))
+            .collect();
+        Ok(Self {
+            store,
+            objects: objects
+                .into_iter()
+                .map(|(uri, meta)| (uri, meta.location))
+                .collect(),
+            options,
+            lines_hints,
+        })
+    }
+
+    /// Provide the objects under a `gs://bucket/prefix` or `az://container/prefix` URI, with the
+    /// credentials of the environment (see the `from_env` constructors of the `object_store`
+    /// builders).
+    pub async fn from_uri(uri: &str, options: ProcessorOptions) -> io::Result<Self> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported object store URI {uri}"),
+            )
+        };
+        let scheme = uri.split_once("://").ok_or_else(invalid)?.0;
+        let (bucket, prefix) = split_uri(uri, scheme).ok_or_else(invalid)?;
+        let store: Arc<dyn ObjectStore> = match scheme {
+            #[cfg(feature = "gcs")]
+            "gs" => Arc::new(
+                object_store::gcp::GoogleCloudStorageBuilder::from_env()
+                    .with_bucket_name(bucket)
+                    .build()
+                    .map_err(io::Error::other)?,
+            ),
+            #[cfg(feature = "azure")]
+            "az" => Arc::new(
+                object_store::azure::MicrosoftAzureBuilder::from_env()
+                    .with_container_name(bucket)
+                    .build()
+                    .map_err(io::Error::other)?,
+            ),
+            _ => return Err(invalid()),
+        };
+        Self::list(store, &format!("{scheme}://{bucket}"), prefix, options).await
+    }
+
+    /// URIs of the provided objects.
+    pub fn uris(&self) -> impl Iterator<Item = &str> {
+        self.objects.iter().map(|(uri, _)| uri.as_str())
+    }
+
+    async fn open(&self, uri: &str, location: &Path) -> Option<ObjectReader> {
+        let object = self
+            .store
+            .get(location)
+            .await
+            .inspect_err(|e| log::warn!("Could not open {uri}, {e}, skipping it."))
+            .ok()?;
+        let body = object.into_stream().map_err(io::Error::other);
+        let rd: Pin<Box<dyn AsyncBufRead + Send>> =
+            Box::pin(self.options.buf_reader(StreamReader::new(body)));
+        #[cfg(feature = "compression")]
+        let rd = crate::Decompress::detect(uri, rd, self.options.read_buffer_size)
+            .await
+            .inspect_err(|e| log::warn!("Could not read {uri}, {e}, skipping it."))
+            .ok()?;
+        Some(rd)
+    }
+}
+
+impl SourceProvider for ObjectStoreProvider {
+    type Reader = ObjectReader;
+
+    fn sources(&self) -> impl Stream<Item = (&str, Self::Reader)> {
+        stream::iter(&self.objects).filter_map(move |(uri, location)| async move {
+            Some((uri.as_str(), self.open(uri, location).await?))
+        })
+    }
+
+    fn lines_hint(&self, id: &str) -> usize {
+        self.lines_hints.get(id).copied().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::memory::InMemory;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_object_store_provider() {
+        let store = Arc::new(InMemory::new());
+        for (key, data) in [
+            ("logs/a.log", "a b\nc"),
+            ("logs/b.log", "d e f"),
+            ("c.log", "g"),
+        ] {
+            store.put(&Path::from(key), data.into()).await.unwrap();
+        }
+        let provider = ObjectStoreProvider::list(store, "gs://bucket", "logs", Default::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            provider.uris().collect::<Vec<_>>(),
+            ["gs://bucket/logs/a.log", "gs://bucket/logs/b.log"]
+        );
+        let result = provider.count_line_words(ProcessorOptions::default()).await;
+        assert_eq!(result["gs://bucket/logs/a.log"], [2, 1]);
+        assert_eq!(result["gs://bucket/logs/b.log"], [3]);
+
+        let err = ObjectStoreProvider::from_uri("ftp://bucket", Default::default()).await;
+        assert_eq!(err.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+}
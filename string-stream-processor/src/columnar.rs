@@ -0,0 +1,252 @@
+use std::{io, sync::Arc};
+
+use arrow_array::{
+    builder::{ArrayBuilder, StringBuilder, UInt64Builder},
+    ArrayRef, RecordBatch,
+};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+#[cfg(feature = "parquet")]
+use parquet::arrow::ArrowWriter;
+
+/// Number of rows of the batches buffered by the writers before being written.
+const BATCH_ROWS: usize = 64 * 1024;
+
+/// Schema of the line counts: `id` (utf8), `line_no` (uint64, starting at 1) and `word_count`
+/// (uint64).
+pub fn line_words_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("line_no", DataType::UInt64, false),
+        Field::new("word_count", DataType::UInt64, false),
+    ]))
+}
+
+/// Builder of Arrow record batches of line counts, see [`line_words_schema`].
+#[derive(Debug)]
+pub struct LineWordsBatchBuilder {
+    schema: SchemaRef,
+    ids: StringBuilder,
+    line_nos: UInt64Builder,
+    counts: UInt64Builder,
+}
+
+impl LineWordsBatchBuilder {
+    /// Create a builder without rows.
+    pub fn new() -> Self {
+        Self {
+            schema: line_words_schema(),
+            ids: StringBuilder::new(),
+            line_nos: UInt64Builder::new(),
+            counts: UInt64Builder::new(),
+        }
+    }
+
+    /// Add the counts of the lines of a source, in line order.
+    pub fn append(&mut self, id: &str, counts: impl IntoIterator<Item = usize>) {
+        for (line, count) in counts.into_iter().enumerate() {
+            self.append_line(id, line, count);
+        }
+    }
+
+    /// Number of rows of the next batch.
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Returns true if no row was added since the last batch.
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Returns the batch of the added rows, and start a new one.
+    pub fn finish(&mut self) -> RecordBatch {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.ids.finish()),
+            Arc::new(self.line_nos.finish()),
+            Arc::new(self.counts.finish()),
+        ];
+        RecordBatch::try_new(self.schema.clone(), columns).expect("columns should match the schema")
+    }
+
+    /// Add the counts of a source, calling `write` with the batches reaching [`BATCH_ROWS`].
+    fn append_batched(
+        &mut self,
+        id: &str,
+        counts: impl IntoIterator<Item = usize>,
+        mut write: impl FnMut(RecordBatch) -> io::Result<()>,
+    ) -> io::Result<()> {
+        for (line, count) in counts.into_iter().enumerate() {
+            self.append_line(id, line, count);
+            if self.len() >= BATCH_ROWS {
+                write(self.finish())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn append_line(&mut self, id: &str, line: usize, count: usize) {
+        self.ids.append_value(id);
+        self.line_nos.append_value(line as u64 + 1);
+        self.counts.append_value(count as u64);
+    }
+}
+
+impl Default for LineWordsBatchBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Writer of line counts as an Arrow IPC file, see [`line_words_schema`].
+///
+/// The rows are buffered and written by batches, the file is complete once
+/// [`ArrowLineWordsWriter::finish`] is called.
+pub struct ArrowLineWordsWriter<W: io::Write> {
+    writer: FileWriter<W>,
+    batch: LineWordsBatchBuilder,
+}
+
+impl<W: io::Write> ArrowLineWordsWriter<W> {
+    /// Write an Arrow IPC file into `writer`.
+    pub fn new(writer: W) -> io::Result<Self> {
+        let writer = FileWriter::try_new(writer, &line_words_schema()).map_err(io::Error::other)?;
+        Ok(Self {
+            writer,
+            batch: LineWordsBatchBuilder::new(),
+        })
+    }
+
+    /// Add the counts of the lines of a source, in line order.
+    pub fn write(&mut self, id: &str, counts: impl IntoIterator<Item = usize>) -> io::Result<()> {
+        let writer = &mut self.writer;
+        self.batch.append_batched(id, counts, |batch| {
+            writer.write(&batch).map_err(io::Error::other)
+        })
+    }
+
+    /// Write the remaining rows and the footer of the file, returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.batch.is_empty() {
+            let batch = self.batch.finish();
+            self.writer.write(&batch).map_err(io::Error::other)?;
+        }
+        self.writer.finish().map_err(io::Error::other)?;
+        self.writer.into_inner().map_err(io::Error::other)
+    }
+}
+
+/// Writer of line counts as a Parquet file, see [`line_words_schema`].
+///
+/// The rows are buffered and written by batches, the file is complete once
+/// [`ParquetLineWordsWriter::finish`] is called.
+#[cfg(feature = "parquet")]
+#[derive(Debug)]
+pub struct ParquetLineWordsWriter<W: io::Write + Send> {
+    writer: ArrowWriter<W>,
+    batch: LineWordsBatchBuilder,
+}
+
+#[cfg(feature = "parquet")]
+impl<W: io::Write + Send> ParquetLineWordsWriter<W> {
+    /// Write a Parquet file into `writer`, with row groups of the size of the batches.
+    pub fn new(writer: W) -> io::Result<Self> {
+        let props = parquet::file::properties::WriterProperties::builder()
+            .set_max_row_group_row_count(Some(BATCH_ROWS))
+            .build();
+        let writer = ArrowWriter::try_new(writer, line_words_schema(), Some(props))
+            .map_err(io::Error::other)?;
+        Ok(Self {
+            writer,
+            batch: LineWordsBatchBuilder::new(),
+        })
+    }
+
+    /// Add the counts of the lines of a source, in line order.
+    pub fn write(&mut self, id: &str, counts: impl IntoIterator<Item = usize>) -> io::Result<()> {
+        let writer = &mut self.writer;
+        self.batch.append_batched(id, counts, |batch| {
+            writer.write(&batch).map_err(io::Error::other)
+        })
+    }
+
+    /// Write the remaining rows and the footer of the file, returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.batch.is_empty() {
+            let batch = self.batch.finish();
+            self.writer.write(&batch).map_err(io::Error::other)?;
+        }
+        self.writer.into_inner().map_err(io::Error::other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{cast::AsArray, types::UInt64Type};
+    use arrow_ipc::reader::FileReader;
+
+    use super::*;
+
+    fn assert_batch(batch: &RecordBatch) {
+        let ids: Vec<_> = batch
+            .column(0)
+            .as_string::<i32>()
+            .iter()
+            .flatten()
+            .collect();
+        assert_eq!(ids, ["a", "a", "b"]);
+        let line_nos = batch.column(1).as_primitive::<UInt64Type>().values();
+        assert_eq!(line_nos.as_ref(), [1, 2, 1]);
+        let counts = batch.column(2).as_primitive::<UInt64Type>().values();
+        assert_eq!(counts.as_ref(), [2, 0, 3]);
+    }
+
+    #[test]
+    fn test_line_words_batch_builder() {
+        let mut builder = LineWordsBatchBuilder::new();
+        builder.append("a", [2, 0]);
+        builder.append("b", [3]);
+        assert_eq!(builder.len(), 3);
+        assert_batch(&builder.finish());
+        assert!(builder.is_empty());
+    }
+
+    #[test]
+    fn test_arrow_line_words_writer() {
+        let mut writer = ArrowLineWordsWriter::new(Vec::new()).unwrap();
+        writer.write("a", [2, 0]).unwrap();
+        writer.write("b", [3]).unwrap();
+        let file = writer.finish().unwrap();
+
+        let batches: Vec<_> = FileReader::try_new(io::Cursor::new(file), None)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_batch(&batches[0]);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_parquet_line_words_writer() {
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let path = std::env::temp_dir().join("ssp_test_parquet_line_words.parquet");
+        let mut writer =
+            ParquetLineWordsWriter::new(std::fs::File::create(&path).unwrap()).unwrap();
+        writer.write("a", [2, 0]).unwrap();
+        writer.write("b", [3]).unwrap();
+        writer.finish().unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+
+        let batches: Vec<_> = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_batch(&batches[0]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
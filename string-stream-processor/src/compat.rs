@@ -0,0 +1,78 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use pin_project_lite::pin_project;
+use tokio::io::ReadBuf;
+
+pin_project! {
+    /// Adapter exposing a Tokio reader through the [`futures_util::io`] traits.
+    ///
+    /// The counting core is generic over the `futures` traits, readers given to the Tokio based
+    /// API are wrapped with this adapter.
+    #[derive(Debug)]
+    pub struct TokioCompat<R> {
+        #[pin]
+        inner: R,
+    }
+}
+
+impl<R> TokioCompat<R> {
+    /// Wrap a Tokio reader.
+    pub fn new(inner: R) -> Self {
+        Self { inner }
+    }
+
+    /// Returns a reference to the wrapped reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns the wrapped reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: tokio::io::AsyncRead> futures_util::io::AsyncRead for TokioCompat<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut buf = ReadBuf::new(buf);
+        ready!(self.project().inner.poll_read(cx, &mut buf))?;
+        Poll::Ready(Ok(buf.filled().len()))
+    }
+}
+
+impl<R: tokio::io::AsyncBufRead> futures_util::io::AsyncBufRead for TokioCompat<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        self.project().inner.poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.project().inner.consume(amt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::io::AsyncBufReadExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tokio_compat() {
+        let rd = tokio::io::BufReader::with_capacity(2, &b"foo\nbar"[..]);
+        let mut rd = TokioCompat::new(rd);
+        let mut line = String::new();
+        rd.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "foo\n");
+        line.clear();
+        rd.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "bar");
+    }
+}
@@ -0,0 +1,175 @@
+use std::{
+    io,
+    path::Path,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use async_compression::tokio::bufread::{BzDecoder, GzipDecoder, ZstdDecoder};
+use pin_project_lite::pin_project;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, BufReader, ReadBuf};
+
+/// Compression format of a source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl Compression {
+    /// Format given by the extension of the path, if it is a known one.
+    pub fn from_extension(path: impl AsRef<Path>) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            "gz" | "gzip" => Some(Compression::Gzip),
+            "zst" | "zstd" => Some(Compression::Zstd),
+            "bz2" => Some(Compression::Bzip2),
+            _ => None,
+        }
+    }
+
+    /// Format given by the magic bytes at the start of the data.
+    pub fn from_magic(data: &[u8]) -> Self {
+        if data.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else if data.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else {
+            Compression::None
+        }
+    }
+}
+
+pin_project! {
+    /// Reader decompressing its inner reader according to a [`Compression`] format.
+    #[project = DecompressProj]
+    pub enum Decompress<R> {
+        None { #[pin] rd: R },
+        Gzip { #[pin] rd: BufReader<GzipDecoder<R>> },
+        Zstd { #[pin] rd: BufReader<ZstdDecoder<R>> },
+        Bzip2 { #[pin] rd: BufReader<BzDecoder<R>> },
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> Decompress<R> {
+    /// Decompress the reader with the given format, `capacity` is the size of the buffer of
+    /// the decompressed data.
+    pub fn new(rd: R, compression: Compression, capacity: usize) -> Self {
+        match compression {
+            Compression::None => Decompress::None { rd },
+            Compression::Gzip => {
+                let mut decoder = GzipDecoder::new(rd);
+                // Concatenated gzip files, e.g. appended logs, are read as a single stream.
+                decoder.multiple_members(true);
+                Decompress::Gzip {
+                    rd: BufReader::with_capacity(capacity, decoder),
+                }
+            }
+            Compression::Zstd => {
+                let mut decoder = ZstdDecoder::new(rd);
+                decoder.multiple_members(true);
+                Decompress::Zstd {
+                    rd: BufReader::with_capacity(capacity, decoder),
+                }
+            }
+            Compression::Bzip2 => {
+                let mut decoder = BzDecoder::new(rd);
+                decoder.multiple_members(true);
+                Decompress::Bzip2 {
+                    rd: BufReader::with_capacity(capacity, decoder),
+                }
+            }
+        }
+    }
+
+    /// Detect the format from the extension of `path`, or else from the first bytes of the reader.
+    pub async fn detect(path: impl AsRef<Path>, mut rd: R, capacity: usize) -> io::Result<Self> {
+        let compression = match Compression::from_extension(path) {
+            Some(compression) => compression,
+            None => Compression::from_magic(rd.fill_buf().await?),
+        };
+        Ok(Self::new(rd, compression, capacity))
+    }
+}
+
+impl<R: AsyncBufRead> AsyncRead for Decompress<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.project() {
+            DecompressProj::None { rd } => rd.poll_read(cx, buf),
+            DecompressProj::Gzip { rd } => rd.poll_read(cx, buf),
+            DecompressProj::Zstd { rd } => rd.poll_read(cx, buf),
+            DecompressProj::Bzip2 { rd } => rd.poll_read(cx, buf),
+        }
+    }
+}
+
+impl<R: AsyncBufRead> AsyncBufRead for Decompress<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        match self.project() {
+            DecompressProj::None { rd } => rd.poll_fill_buf(cx),
+            DecompressProj::Gzip { rd } => rd.poll_fill_buf(cx),
+            DecompressProj::Zstd { rd } => rd.poll_fill_buf(cx),
+            DecompressProj::Bzip2 { rd } => rd.poll_fill_buf(cx),
+        }
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        match self.project() {
+            DecompressProj::None { rd } => rd.consume(amt),
+            DecompressProj::Gzip { rd } => rd.consume(amt),
+            DecompressProj::Zstd { rd } => rd.consume(amt),
+            DecompressProj::Bzip2 { rd } => rd.consume(amt),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_compression::tokio::bufread::{BzEncoder, GzipEncoder, ZstdEncoder};
+    use futures_util::StreamExt;
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+    use crate::count_line_words;
+
+    const DATA: &[u8] = b"Hello world\n\nfoo bar baz\n";
+
+    async fn read_all(mut rd: impl AsyncRead + Unpin) -> Vec<u8> {
+        let mut data = Vec::new();
+        rd.read_to_end(&mut data).await.unwrap();
+        data
+    }
+
+    #[tokio::test]
+    async fn test_decompress() {
+        let gzip = read_all(GzipEncoder::new(DATA)).await;
+        let zstd = read_all(ZstdEncoder::new(DATA)).await;
+        let bzip2 = read_all(BzEncoder::new(DATA)).await;
+        // Two gzip members.
+        let gzip2 = [gzip.clone(), gzip.clone()].concat();
+        let cases = [
+            ("a.txt", DATA, 3),
+            ("a.log", &gzip[..], 3),
+            ("a.gz", &gzip2[..], 6),
+            ("a.zst", &zstd[..], 3),
+            ("a", &bzip2[..], 3),
+        ];
+        for (path, data, lines) in cases {
+            let rd = Decompress::detect(path, data, 16).await.unwrap();
+            let counts: Vec<usize> = count_line_words(rd).collect().await;
+            assert_eq!(counts, [2, 0, 3].repeat(lines / 3), "{path}");
+        }
+        assert_eq!(
+            Compression::from_extension("a.tar.bz2"),
+            Some(Compression::Bzip2)
+        );
+        assert_eq!(Compression::from_magic(DATA), Compression::None);
+    }
+}
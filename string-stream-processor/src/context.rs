@@ -0,0 +1,90 @@
+use std::{
+    cell::RefCell,
+    collections::{hash_map::Entry, HashMap},
+    future::Future,
+};
+
+use futures_util::{Stream, StreamExt};
+use tokio::io::AsyncBufRead;
+
+use crate::{ProcessorOptions, StringMultiStreamExt};
+
+/// Counts of a source with the context it was given with, see [`ContextMultiStreamExt`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ContextResult<C> {
+    pub context: C,
+    pub counts: Vec<usize>,
+}
+
+/// Extension trait for stream over async readers bound to a string identifier and to a context
+/// of the caller, e.g. the tenant, the original URL or the tags of the source, carried through to
+/// its counts without a lookup table keyed by identifier.
+pub trait ContextMultiStreamExt<'a, R, C>: Stream<Item = (&'a str, R, C)> + Sized
+where
+    R: AsyncBufRead + Unpin,
+{
+    /// Count the number of words of the sources of the stream, see
+    /// [`StringMultiStreamExt::count_line_words_concurrent_with`], with the context of each
+    /// source. Every source has a result, even without any line.
+    ///
+    /// The counts of the sources with the same identifier are merged, with the context of the
+    /// last one.
+    fn count_line_words_with_context(
+        self,
+        options: ProcessorOptions,
+    ) -> impl Future<Output = HashMap<&'a str, ContextResult<C>>> {
+        async move {
+            let contexts = RefCell::new(Vec::new());
+            let rds = self.map(|(id, rd, context)| {
+                contexts.borrow_mut().push((id, context));
+                (id, rd)
+            });
+            let mut counts = rds.count_line_words_concurrent_with(options).await;
+            let mut results: HashMap<_, ContextResult<C>> = HashMap::new();
+            for (id, context) in contexts.into_inner() {
+                match results.entry(id) {
+                    Entry::Occupied(mut result) => result.get_mut().context = context,
+                    Entry::Vacant(result) => {
+                        let counts = counts.remove(id).unwrap_or_default();
+                        result.insert(ContextResult { context, counts });
+                    }
+                }
+            }
+            results
+        }
+    }
+}
+
+impl<'a, R, C, S> ContextMultiStreamExt<'a, R, C> for S
+where
+    R: AsyncBufRead + Unpin,
+    S: Stream<Item = (&'a str, R, C)>,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::stream;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_count_line_words_with_context() {
+        let srcs = [
+            ("a", "a b\nc".as_bytes(), "tenant-1"),
+            ("b", "".as_bytes(), "tenant-2"),
+            ("a", "d e f".as_bytes(), "tenant-3"),
+        ];
+        let result = stream::iter(srcs)
+            .count_line_words_with_context(ProcessorOptions::default())
+            .await;
+        assert_eq!(result.len(), 2);
+        assert_eq!(result["a"].context, "tenant-3");
+        let mut counts = result["a"].counts.clone();
+        counts.sort_unstable();
+        assert_eq!(counts, [1, 2, 3]);
+        assert_eq!(result["b"].context, "tenant-2");
+        assert!(result["b"].counts.is_empty());
+    }
+}
@@ -0,0 +1,33 @@
+/// Integer type used to store the word count of each line.
+///
+/// Storing `u16` or `u32` instead of `usize` divides the memory used by the results when the
+/// counts are known to be small. Counts larger than the maximum of the type saturate at it.
+pub trait LineCount: Copy {
+    /// Convert a count, saturating at the maximum of the type.
+    fn from_count(count: usize) -> Self;
+}
+
+macro_rules! impl_line_count {
+    ($($ty:ty),*) => {
+        $(impl LineCount for $ty {
+            #[inline]
+            fn from_count(count: usize) -> Self {
+                count.try_into().unwrap_or(<$ty>::MAX)
+            }
+        })*
+    };
+}
+impl_line_count!(u8, u16, u32, u64, usize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_count_saturate() {
+        assert_eq!(u8::from_count(12), 12);
+        assert_eq!(u8::from_count(300), u8::MAX);
+        assert_eq!(u16::from_count(70_000), u16::MAX);
+        assert_eq!(usize::from_count(70_000), 70_000);
+    }
+}
@@ -0,0 +1,79 @@
+use std::{cell::RefCell, collections::HashMap, future::Future};
+
+use futures_util::{future, Stream, StreamExt};
+use tokio::io::AsyncBufRead;
+
+use crate::{ProcessorOptions, StringMultiStreamExt};
+
+/// Word counts of each identifier of a fallible stream of sources, with the errors of the
+/// stream, see [`TryStringMultiStreamExt`].
+#[derive(Debug)]
+pub struct DiscoveredCounts<I, E> {
+    pub counts: HashMap<I, Vec<usize>>,
+    /// Errors yielded by the stream instead of a source, e.g. a failed listing of a bucket or
+    /// connection to a socket, in the order of the stream.
+    pub errors: Vec<E>,
+}
+
+/// Extension trait for fallible stream over async readers bound to a string identifier, when
+/// discovering the sources can fail, e.g. listing the objects of a bucket page by page.
+///
+/// The errors of the stream are recorded with the counts instead of being dropped, the stream
+/// is still polled until its end.
+pub trait TryStringMultiStreamExt<'a, R, E>:
+    Stream<Item = Result<(&'a str, R), E>> + Sized
+where
+    R: AsyncBufRead + Unpin,
+{
+    /// Count the number of words of the sources of the stream, see
+    /// [`StringMultiStreamExt::count_line_words_concurrent_with`], with the errors yielded
+    /// instead of the sources.
+    fn try_count_line_words_concurrent(
+        self,
+        options: ProcessorOptions,
+    ) -> impl Future<Output = DiscoveredCounts<&'a str, E>> {
+        async move {
+            let errors = RefCell::new(Vec::new());
+            let rds = self.filter_map(|src| {
+                future::ready(src.map_err(|error| errors.borrow_mut().push(error)).ok())
+            });
+            let counts = rds.count_line_words_concurrent_with(options).await;
+            DiscoveredCounts {
+                counts,
+                errors: errors.into_inner(),
+            }
+        }
+    }
+}
+
+impl<'a, R, E, S> TryStringMultiStreamExt<'a, R, E> for S
+where
+    R: AsyncBufRead + Unpin,
+    S: Stream<Item = Result<(&'a str, R), E>>,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use futures_util::stream;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_try_count_line_words_concurrent() {
+        let srcs = [
+            Ok(("a", "a b\nc".as_bytes())),
+            Err(io::Error::other("page 2 of the listing")),
+            Ok(("b", "d e f".as_bytes())),
+        ];
+        let result = stream::iter(srcs)
+            .try_count_line_words_concurrent(ProcessorOptions::default())
+            .await;
+        assert_eq!(result.counts["a"], [2, 1]);
+        assert_eq!(result.counts["b"], [3]);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].to_string(), "page 2 of the listing");
+    }
+}
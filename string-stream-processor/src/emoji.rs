@@ -0,0 +1,123 @@
+//! Emoji sequences of the [`Tokenizer::Emoji`](crate::Tokenizer::Emoji) words.
+
+/// Zero width joiner, joining two emoji into a single one, e.g. 👩‍💻.
+const ZWJ: char = '\u{200D}';
+/// Variation selector asking for the emoji presentation of the previous character, e.g. ©️.
+const EMOJI_PRESENTATION: char = '\u{FE0F}';
+const KEYCAP: char = '\u{20E3}';
+
+/// Whether a character is pictographic, the emoji of the Unicode blocks of symbols and
+/// pictographs. The characters with a text presentation by default, like `©`, are emoji when
+/// followed by the emoji presentation selector only.
+fn is_pictographic(c: char) -> bool {
+    matches!(
+        c,
+        '\u{231A}'..='\u{231B}'
+            | '\u{23E9}'..='\u{23F3}'
+            | '\u{23F8}'..='\u{23FA}'
+            | '\u{25FB}'..='\u{25FE}'
+            | '\u{2600}'..='\u{27BF}'
+            | '\u{2B05}'..='\u{2B07}'
+            | '\u{2B1B}'..='\u{2B1C}'
+            | '\u{2B50}'
+            | '\u{2B55}'
+            | '\u{1F000}'..='\u{1F1E5}'
+            | '\u{1F200}'..='\u{1F3FA}'
+            | '\u{1F400}'..='\u{1FAFF}'
+    )
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    matches!(c, '\u{1F1E6}'..='\u{1F1FF}')
+}
+
+/// Whether a character extends the emoji before it: variation selectors, skin tones, keycaps
+/// and the tags of the subdivision flags.
+fn is_extender(c: char) -> bool {
+    matches!(
+        c,
+        '\u{FE0E}'
+            | EMOJI_PRESENTATION
+            | KEYCAP
+            | '\u{1F3FB}'..='\u{1F3FF}'
+            | '\u{E0020}'..='\u{E007F}'
+    )
+}
+
+/// Length of the emoji sequence at the start of `s`, zero if it does not start with one.
+fn emoji_len(s: &str) -> usize {
+    let mut chars = s.char_indices().peekable();
+    let Some((_, first)) = chars.next() else {
+        return 0;
+    };
+    let next = chars.peek().map(|&(_, c)| c);
+    let emoji = is_pictographic(first)
+        || is_regional_indicator(first)
+        || matches!(next, Some(EMOJI_PRESENTATION | KEYCAP));
+    if !emoji {
+        return 0;
+    }
+    if is_regional_indicator(first) {
+        // A flag is a pair of regional indicators.
+        if let Some(&(i, c)) = chars.peek().filter(|(_, c)| is_regional_indicator(*c)) {
+            return i + c.len_utf8();
+        }
+    }
+    let mut len = first.len_utf8();
+    while let Some(&(i, c)) = chars.peek() {
+        if is_extender(c) {
+            chars.next();
+            len = i + c.len_utf8();
+        } else if c == ZWJ && s[i + c.len_utf8()..].starts_with(is_pictographic) {
+            chars.next();
+            let Some((i, joined)) = chars.next() else {
+                break;
+            };
+            len = i + joined.len_utf8();
+        } else {
+            break;
+        }
+    }
+    len
+}
+
+/// Split a word on its emoji sequences, each of them and each text between them being a word.
+pub(crate) fn split_emoji(word: &str) -> impl Iterator<Item = &str> {
+    let mut rest = word;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let len = match emoji_len(rest) {
+            0 => rest
+                .char_indices()
+                .skip(1)
+                .find(|&(i, _)| emoji_len(&rest[i..]) > 0)
+                .map_or(rest.len(), |(i, _)| i),
+            len => len,
+        };
+        let (token, after) = rest.split_at(len);
+        rest = after;
+        Some(token)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_emoji() {
+        let split = |word| split_emoji(word).collect::<Vec<_>>();
+        assert_eq!(split("hello"), ["hello"]);
+        assert_eq!(split("hi😀😀!"), ["hi", "😀", "😀", "!"]);
+        // Family and technologist ZWJ sequences, with a skin tone.
+        assert_eq!(split("👨‍👩‍👧👩🏽‍💻"), ["👨‍👩‍👧", "👩🏽‍💻"]);
+        // Flags of France and Japan, and of Scotland with tags.
+        assert_eq!(split("🇫🇷🇯🇵🏴󠁧󠁢󠁳󠁣󠁴󠁿"), ["🇫🇷", "🇯🇵", "🏴󠁧󠁢󠁳󠁣󠁴󠁿"]);
+        assert_eq!(split("1️⃣2"), ["1️⃣", "2"]);
+        assert_eq!(split("©2024"), ["©2024"]);
+        assert_eq!(split("❤️‍🔥x"), ["❤️‍🔥", "x"]);
+        assert_eq!(split(""), [] as [&str; 0]);
+    }
+}
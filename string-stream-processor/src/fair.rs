@@ -0,0 +1,79 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_util::Stream;
+use pin_project_lite::pin_project;
+
+pin_project! {
+    /// Stream of the counts of a source yielding to the other sources after `burst` consecutive
+    /// items, see [`ProcessorOptions::fairness`](crate::ProcessorOptions::fairness).
+    ///
+    /// Once the burst is reached the stream wakes itself and returns `Pending`, so that it is
+    /// queued behind the other ready sources of the concurrent poller.
+    pub(crate) struct Fair<S> {
+        #[pin]
+        inner: S,
+        burst: Option<usize>,
+        taken: usize,
+    }
+}
+
+impl<S> Fair<S> {
+    pub fn new(inner: S, burst: Option<usize>) -> Self {
+        Self {
+            inner,
+            burst,
+            taken: 0,
+        }
+    }
+}
+
+impl<S: Stream> Stream for Fair<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = self.project();
+        if this.burst.is_some_and(|burst| *this.taken >= burst) {
+            *this.taken = 0;
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        match this.inner.poll_next(cx) {
+            Poll::Ready(item) => {
+                *this.taken += 1;
+                Poll::Ready(item)
+            }
+            // The source yielded by itself.
+            Poll::Pending => {
+                *this.taken = 0;
+                Poll::Pending
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+    use futures_util::stream::{self, PollNext, StreamExt};
+
+    use super::*;
+
+    #[test]
+    fn test_fair() {
+        // A poller always preferring the first source, which is always ready.
+        let ids = |fairness| {
+            let [a, b] = ["a", "b"].map(|id| Fair::new(stream::repeat(id).take(3), fairness));
+            let srcs = stream::select_with_strategy(a, b, |_: &mut ()| PollNext::Left);
+            block_on(srcs.collect::<Vec<_>>()).concat()
+        };
+        assert_eq!(ids(None), "aaabbb");
+        assert_eq!(ids(Some(2)), "aababb");
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use pin_project_lite::pin_project;
+use tokio::{
+    io::{AsyncRead, ReadBuf},
+    time::Sleep,
+};
+
+pin_project! {
+    /// Reader polling its input again when it reaches its end, like `tail -f`.
+    ///
+    /// The end of the input is never returned: the reads wait for `interval` and try again, so
+    /// that the lines appended to a growing file are read as they are written. A partial last
+    /// line is only counted once its line ending is written.
+    #[derive(Debug)]
+    pub struct Follow<R> {
+        #[pin]
+        inner: R,
+        interval: Option<Duration>,
+        sleep: Option<Pin<Box<Sleep>>>,
+    }
+}
+
+impl<R> Follow<R> {
+    /// Follow a reader, polling it every `interval` at its end. With `None`, the end of the
+    /// input is returned as is.
+    pub fn new(inner: R, interval: Option<Duration>) -> Self {
+        Self {
+            inner,
+            interval,
+            sleep: None,
+        }
+    }
+
+    /// Returns a reference to the wrapped reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns the wrapped reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: AsyncRead> AsyncRead for Follow<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut this = self.project();
+        let Some(interval) = *this.interval else {
+            return this.inner.poll_read(cx, buf);
+        };
+        loop {
+            if let Some(sleep) = this.sleep {
+                ready!(sleep.as_mut().poll(cx));
+                *this.sleep = None;
+            }
+            let filled = buf.filled().len();
+            ready!(this.inner.as_mut().poll_read(cx, buf))?;
+            if buf.filled().len() > filled || buf.remaining() == 0 {
+                return Poll::Ready(Ok(()));
+            }
+            *this.sleep = Some(Box::pin(tokio::time::sleep(interval)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+
+    use super::*;
+    use crate::count_source_line_words;
+
+    #[tokio::test]
+    async fn test_follow() {
+        let path = std::env::temp_dir().join("ssp_test_follow.txt");
+        tokio::fs::write(&path, "a b\nc").await.unwrap();
+        let file = tokio::fs::File::open(&path).await.unwrap();
+        let rd = BufReader::new(Follow::new(file, Some(Duration::from_millis(5))));
+
+        let writer = path.clone();
+        tokio::spawn(async move {
+            let mut file = tokio::fs::OpenOptions::new()
+                .append(true)
+                .open(writer)
+                .await
+                .unwrap();
+            for data in [" d\n", "e f g\n"] {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                file.write_all(data.as_bytes()).await.unwrap();
+                file.flush().await.unwrap();
+            }
+        });
+        let counts: Vec<_> = count_source_line_words(("follow", rd), Default::default())
+            .take(3)
+            .map(|(_, count)| count)
+            .collect()
+            .await;
+        assert_eq!(counts, [2, 2, 3]);
+
+        let file = tokio::fs::File::open(&path).await.unwrap();
+        let mut content = String::new();
+        Follow::new(file, None)
+            .read_to_string(&mut content)
+            .await
+            .unwrap();
+        assert_eq!(content, "a b\nc d\ne f g\n");
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+}
@@ -0,0 +1,101 @@
+use std::{collections::HashMap, io};
+
+use futures_util::{stream, StreamExt, TryStreamExt};
+use reqwest::{Client, Response};
+use tokio::io::AsyncBufRead;
+use tokio_util::io::StreamReader;
+
+use crate::{compat::TokioCompat, source::try_count_line_words, ProcessorOptions};
+
+/// Count the number of words for each line of the bodies of HTTP(S) responses, identified by
+/// their URL.
+///
+/// Redirects are followed according to the policy of the client. A request which fails, a
+/// response with an error status or a body which cannot be read entirely is reported as an error
+/// for its URL. The requests are sent concurrently, up to
+/// [`max_concurrency`](ProcessorOptions::max_concurrency) at a time.
+pub async fn count_url_line_words<'a>(
+    client: &Client,
+    urls: &'a [String],
+    options: ProcessorOptions,
+) -> HashMap<&'a str, io::Result<Vec<usize>>> {
+    stream::iter(urls)
+        .flat_map_unordered(options.max_concurrency, |url| {
+            let counts = async move { (url.as_str(), count_url(client, url, options).await) };
+            stream::once(Box::pin(counts))
+        })
+        .collect()
+        .await
+}
+
+async fn count_url(
+    client: &Client,
+    url: &str,
+    options: ProcessorOptions,
+) -> io::Result<Vec<usize>> {
+    let rd = open_url(client, url, options).await?;
+    try_count_line_words((url, TokioCompat::new(rd)), options).await
+}
+
+/// Reader of the body of the response to a GET request of a URL, buffered with the
+/// [`read_buffer_size`](ProcessorOptions::read_buffer_size) of the options, see
+/// [`count_url_line_words`]. A request which fails or a response with an error status is an
+/// error, a body which cannot be read entirely is a read error.
+pub async fn open_url(
+    client: &Client,
+    url: &str,
+    options: ProcessorOptions,
+) -> io::Result<impl AsyncBufRead + Send + Unpin> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(Response::error_for_status)
+        .map_err(io::Error::other)?;
+    let body = Box::pin(response.bytes_stream().map_err(io::Error::other));
+    Ok(options.buf_reader(StreamReader::new(body)))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// Serve `/a`, a redirect to it from `/redirect` and a 404 for any other path.
+    async fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = [0; 1024];
+                let len = socket.read(&mut request).await.unwrap();
+                let request = String::from_utf8_lossy(&request[..len]);
+                let path = request.split(' ').nth(1).unwrap_or_default();
+                let response = match path {
+                    "/a" => "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\na b\nc",
+                    "/redirect" => {
+                        "HTTP/1.1 302 Found\r\nLocation: /a\r\nContent-Length: 0\r\n\r\n"
+                    }
+                    _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n",
+                };
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn test_count_url_line_words() {
+        let base = serve().await;
+        let urls = ["/a", "/redirect", "/missing"].map(|path| format!("{base}{path}"));
+        let result = count_url_line_words(&Client::new(), &urls, ProcessorOptions::default()).await;
+        assert_eq!(result[urls[0].as_str()].as_ref().unwrap(), &[2, 1]);
+        assert_eq!(result[urls[1].as_str()].as_ref().unwrap(), &[2, 1]);
+        assert!(result[urls[2].as_str()].is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use std::{fmt, io};
+
+use futures_util::{stream, Stream, StreamExt};
+use rdkafka::{
+    consumer::{Consumer, StreamConsumer},
+    ClientConfig, Message,
+};
+
+use crate::{message::message_line_words, ProcessorOptions};
+
+/// Identifier of a partition of a Kafka topic, displayed as `topic/partition`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct KafkaPartition<'a> {
+    pub topic: &'a str,
+    pub partition: i32,
+}
+
+impl fmt::Display for KafkaPartition<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.topic, self.partition)
+    }
+}
+
+/// Consume the topics and count the number of words for each line of the message payloads, as
+/// soon as they are received. The stream never ends.
+///
+/// `config` is the consumer configuration, it must at least set `bootstrap.servers` and
+/// `group.id`. Each payload is a line, and is split again on its line endings if it contains
+/// several lines. Messages without payload are ignored.
+pub fn consume_kafka_line_words<'a>(
+    config: &ClientConfig,
+    topics: &'a [String],
+    options: ProcessorOptions,
+) -> io::Result<impl Stream<Item = (KafkaPartition<'a>, usize)> + 'a> {
+    let consumer: StreamConsumer = config.create().map_err(io::Error::other)?;
+    let names: Vec<&str> = topics.iter().map(String::as_str).collect();
+    consumer.subscribe(&names).map_err(io::Error::other)?;
+    let messages = stream::unfold(consumer, move |consumer| async move {
+        let message = match consumer.recv().await {
+            Ok(message) => topics
+                .iter()
+                .find(|topic| *topic == message.topic())
+                .map(|topic| {
+                    let id = KafkaPartition {
+                        topic,
+                        partition: message.partition(),
+                    };
+                    (id, message.payload().map(<[u8]>::to_vec))
+                }),
+            Err(e) => {
+                log::warn!("Could not receive a Kafka message, {e}, skipping it.");
+                None
+            }
+        };
+        Some((message, consumer))
+    });
+    Ok(messages
+        .filter_map(move |message| async move {
+            let (id, payload) = message?;
+            Some(message_line_words(id, &payload?, options.tokenizer))
+        })
+        .flat_map(stream::iter))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kafka_partition() {
+        let id = KafkaPartition {
+            topic: "logs",
+            partition: 3,
+        };
+        assert_eq!(id.to_string(), "logs/3");
+    }
+}
@@ -1,12 +1,219 @@
-use std::{collections::HashMap, future::Future};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    future::Future,
+    hash::{BuildHasher, Hash, RandomState},
+    io,
+};
 
-use futures_util::{Stream, StreamExt};
-use tokio::io::{AsyncBufRead, AsyncBufReadExt};
-use tokio_stream::wrappers::LinesStream;
+use futures_util::{FutureExt, Stream, StreamExt};
+use tokio::io::AsyncBufRead;
+
+mod aggregate;
+mod agnostic;
+mod analyzer;
+#[cfg(any(feature = "tar", feature = "zip"))]
+mod archive;
+mod buffered;
+#[cfg(any(feature = "gcs", feature = "azure"))]
+mod cloud;
+#[cfg(feature = "arrow")]
+mod columnar;
+mod compat;
+#[cfg(feature = "compression")]
+mod compression;
+mod context;
+mod count;
+mod discovery;
+mod emoji;
+mod fair;
+#[cfg(feature = "runtime")]
+mod follow;
+#[cfg(feature = "http")]
+mod http;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod limit;
+mod memory;
+mod merge;
+#[cfg(any(
+    feature = "kafka",
+    feature = "redis",
+    feature = "nats",
+    feature = "mqtt",
+    feature = "syslog"
+))]
+mod message;
+mod meta;
+mod metrics;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+#[cfg(feature = "nats")]
+mod nats;
+#[cfg(feature = "net")]
+mod net;
+mod options;
+#[cfg(feature = "otel")]
+mod otel;
+#[cfg(feature = "runtime")]
+mod partition;
+mod pipeline;
+#[cfg(feature = "wasm-plugins")]
+mod plugin;
+mod pool;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod processor;
+mod progress;
+mod provider;
+mod records;
+#[cfg(feature = "redis")]
+mod redis_streams;
+mod retry;
+#[cfg(feature = "s3")]
+mod s3;
+#[cfg(feature = "sftp")]
+mod sftp;
+mod source;
+#[cfg(feature = "runtime")]
+mod spawn;
+mod spill;
+#[cfg(feature = "sse")]
+mod sse;
+mod stats;
+pub mod sync;
+#[cfg(feature = "syslog")]
+mod syslog;
+#[cfg(feature = "runtime")]
+mod throttle;
+#[cfg(feature = "tls")]
+mod tls;
+mod tokenizer;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod uring;
+mod warning;
+#[cfg(feature = "watch")]
+mod watch;
+#[cfg(feature = "runtime")]
+mod window;
+#[cfg(feature = "websocket")]
+mod ws;
+
+pub use aggregate::{Aggregator, ResultMap};
+pub use agnostic::{count_chunk_line_words, ChunkMultiStreamExt, FuturesMultiStreamExt};
+pub use analyzer::{analyzer_from_name, DynLineAnalyzer};
+#[cfg(feature = "tar")]
+pub use archive::count_archive_line_words;
+#[cfg(feature = "zip")]
+pub use archive::count_zip_line_words;
+pub use buffered::ReadMultiStreamExt;
+#[cfg(any(feature = "gcs", feature = "azure"))]
+pub use cloud::{ObjectReader, ObjectStoreProvider};
+#[cfg(feature = "parquet")]
+pub use columnar::ParquetLineWordsWriter;
+#[cfg(feature = "arrow")]
+pub use columnar::{line_words_schema, ArrowLineWordsWriter, LineWordsBatchBuilder};
+pub use compat::TokioCompat;
+#[cfg(feature = "compression")]
+pub use compression::{Compression, Decompress};
+pub use context::{ContextMultiStreamExt, ContextResult};
+pub use count::LineCount;
+pub use discovery::{DiscoveredCounts, TryStringMultiStreamExt};
+#[cfg(feature = "runtime")]
+pub use follow::Follow;
+#[cfg(feature = "http")]
+pub use http::{count_url_line_words, open_url};
+#[cfg(feature = "kafka")]
+pub use kafka::{consume_kafka_line_words, KafkaPartition};
+pub use limit::LineLimit;
+pub use memory::{BudgetedCounts, MemoryBudget, MemoryUsage, OnExceeded};
+#[cfg(feature = "serde")]
+pub use merge::deserialize_line_words;
+pub use merge::{merge_line_words, MergeMode};
+pub use meta::{Encoding, SourceMeta, SourceResult};
+pub use metrics::{Metric, MetricSet, MetricValue, MetricValues};
+#[cfg(feature = "mmap")]
+pub use mmap::MmapReader;
+#[cfg(feature = "mqtt")]
+pub use mqtt::subscribe_mqtt_line_words;
+#[cfg(feature = "nats")]
+pub use nats::subscribe_nats_line_words;
+#[cfg(feature = "tls")]
+pub use net::connect_tls_line_words;
+#[cfg(feature = "net")]
+pub use net::{
+    connect_line_words_with_reconnect, connect_tcp_line_words,
+    connect_tcp_line_words_with_reconnect, count_tcp_line_words,
+};
+pub use options::{ProcessorOptions, DEFAULT_READ_BUFFER_SIZE};
+#[cfg(feature = "runtime")]
+pub use partition::count_file_line_words_partitioned;
+pub use pipeline::Pipeline;
+#[cfg(feature = "wasm-plugins")]
+pub use plugin::WasmAnalyzer;
+#[cfg(feature = "postgres")]
+pub use postgres::write_postgres_line_words;
+pub use processor::{Processor, ProcessorBuilder};
+pub use progress::{Progress, ProgressReader, ProgressSnapshot};
+pub use provider::SourceProvider;
+#[cfg(feature = "runtime")]
+pub use provider::{FileProvider, FileReader};
+pub use records::{RecordSeparator, Records, DEFAULT_MAX_RECORD_LENGTH};
+#[cfg(feature = "redis")]
+pub use redis_streams::read_redis_line_words;
+pub use retry::RetryPolicy;
+#[cfg(feature = "s3")]
+pub use s3::{S3Provider, S3Reader};
+#[cfg(feature = "sftp")]
+pub use sftp::{SftpAuth, SftpProvider, SftpReader};
+pub use source::SourceError;
+#[cfg(feature = "runtime")]
+pub use spawn::OwnedMultiStreamExt;
+pub use spill::{SpillOptions, SpilledCounts, SpilledIter};
+#[cfg(feature = "sse")]
+pub use sse::connect_sse_line_words;
+pub use stats::LineStats;
+#[cfg(feature = "syslog")]
+pub use syslog::{count_tcp_syslog_line_words, count_udp_syslog_line_words};
+#[cfg(feature = "runtime")]
+pub use throttle::{RateLimit, SharedRateLimit, Throttle};
+#[cfg(feature = "tls")]
+pub use tls::TlsOptions;
+pub use tokenizer::Tokenizer;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub use uring::UringReader;
+pub use warning::{Warning, WarningKind};
+#[cfg(feature = "watch")]
+pub use watch::FileWatcher;
+#[cfg(feature = "runtime")]
+pub use window::{window_line_words, SlidingStats, SlidingWindow, Window};
+#[cfg(feature = "websocket")]
+pub use ws::connect_ws_line_words;
+#[cfg(all(feature = "websocket", feature = "tls"))]
+pub use ws::connect_ws_line_words_with_tls;
+
+/// Result map using the `ahash` hasher, see
+/// [`count_line_words_concurrent_with_hasher`](StringMultiStreamExt::count_line_words_concurrent_with_hasher).
+#[cfg(feature = "ahash")]
+pub type AHashMap<K, V> = HashMap<K, V, ahash::RandomState>;
+
+use aggregate::{aggregate, Accumulate, Aggregate, AggregatorMap, HintedMap};
+use analyzer::Analyzer;
+use fair::Fair;
+use memory::BudgetStore;
+use source::{
+    analyze_lines_retrying, count_line_words_fallible, count_line_words_retrying, NoReconnect,
+    Reconnect, ReconnectFn,
+};
+use spill::SpillStore;
+use warning::Warnings;
 
 /// Extension trait for stream over async readers bound to a string identifier.
 ///
-/// In practice, this can be a stream of file paths and associated readers or net identifiers and associated readers.
+/// In practice, this can be a stream of file paths and associated readers or net identifiers and associated readers
+/// (see `count_tcp_line_words` with the `net` feature for TCP connections).
 pub trait StringMultiStreamExt<'a, R>: Stream<Item = (&'a str, R)> + Sized
 where
     R: AsyncBufRead + Unpin,
@@ -16,7 +223,238 @@ where
     ///
     /// The readers will be polled concurrently.
     fn count_line_words_concurrent(self) -> impl Future<Output = HashMap<&'a str, Vec<usize>>> {
-        count_line_words_concurrent(self)
+        count_line_words_concurrent::<_, RandomState, _>(
+            compat(self),
+            ProcessorOptions::default(),
+            &NoReconnect,
+        )
+    }
+
+    /// Same as [`count_line_words_concurrent`](Self::count_line_words_concurrent) with the given options.
+    fn count_line_words_concurrent_with(
+        self,
+        options: ProcessorOptions,
+    ) -> impl Future<Output = HashMap<&'a str, Vec<usize>>> {
+        count_line_words_concurrent(compat(self), options, &NoReconnect)
+    }
+
+    /// Same as [`count_line_words_concurrent_with`](Self::count_line_words_concurrent_with) but
+    /// the count vector of each identifier is pre-allocated for `lines_hint(id)` lines.
+    ///
+    /// The hint is queried when the first line of an identifier is counted, so it can be
+    /// computed while opening the source (e.g. from the file size).
+    fn count_line_words_concurrent_with_hint(
+        self,
+        options: ProcessorOptions,
+        lines_hint: impl Fn(&str) -> usize,
+    ) -> impl Future<Output = HashMap<&'a str, Vec<usize>>> {
+        count_line_words_concurrent_hinted::<_, RandomState, _>(
+            compat(self),
+            options,
+            &NoReconnect,
+            lines_hint,
+        )
+    }
+
+    /// Same as [`count_line_words_concurrent_with`](Self::count_line_words_concurrent_with) but
+    /// the result map uses the hasher `S`.
+    ///
+    /// With a lot of identifiers, a faster hasher than the default SipHash (e.g. `ahash` with
+    /// the `ahash` feature, see [`AHashMap`]) speeds up the aggregation.
+    fn count_line_words_concurrent_with_hasher<S: BuildHasher + Default>(
+        self,
+        options: ProcessorOptions,
+    ) -> impl Future<Output = HashMap<&'a str, Vec<usize>, S>> {
+        count_line_words_concurrent(compat(self), options, &NoReconnect)
+    }
+
+    /// Same as [`count_line_words_concurrent_with`](Self::count_line_words_concurrent_with) but
+    /// the counts are stored as `C` (e.g. `u16`), saturating at its maximum.
+    fn count_line_words_concurrent_compact<C: LineCount>(
+        self,
+        options: ProcessorOptions,
+    ) -> impl Future<Output = HashMap<&'a str, Vec<C>>> {
+        count_line_words_concurrent::<_, RandomState, _>(compat(self), options, &NoReconnect)
+    }
+
+    /// Compute statistics of the word counts of each source, without storing the count of each line.
+    ///
+    /// The memory used is constant per identifier.
+    fn count_line_words_stats(
+        self,
+        options: ProcessorOptions,
+    ) -> impl Future<Output = HashMap<&'a str, LineStats>> {
+        count_line_words_concurrent::<_, RandomState, _>(compat(self), options, &NoReconnect)
+    }
+
+    /// Same as [`count_line_words_concurrent_with`](Self::count_line_words_concurrent_with) but
+    /// the counts of each identifier are given to the aggregator created by `new(id)` for its
+    /// first line, the result map holding the output of each aggregator.
+    fn count_line_words_aggregated<A: Aggregator>(
+        self,
+        options: ProcessorOptions,
+        new: impl Fn(&str) -> A,
+    ) -> impl Future<Output = HashMap<&'a str, A::Output>> {
+        async move {
+            let acc = AggregatorMap::new(new);
+            let acc = count_line_words_into(compat(self), options, &NoReconnect, acc).await;
+            acc.map
+                .into_iter()
+                .map(|(id, aggregator)| (id, aggregator.finish()))
+                .collect()
+        }
+    }
+
+    /// Same as [`count_line_words_concurrent_with`](Self::count_line_words_concurrent_with) but
+    /// the counts are aggregated per group of identifiers, `key_fn` mapping each identifier to
+    /// its group, see [`group_line_words`].
+    fn count_line_words_grouped<K: Hash + Eq>(
+        self,
+        options: ProcessorOptions,
+        key_fn: impl Fn(&'a str) -> K,
+    ) -> impl Future<Output = HashMap<K, Vec<usize>>> {
+        async move {
+            let result = count_line_words_concurrent::<_, RandomState, _>(
+                compat(self),
+                options,
+                &NoReconnect,
+            )
+            .await;
+            group_line_words(result, key_fn)
+        }
+    }
+
+    /// Same as [`count_line_words_concurrent_with`](Self::count_line_words_concurrent_with) but
+    /// a source ended by a read error maps to the error and the counts of the lines read before
+    /// it, instead of only these counts.
+    ///
+    /// A source failing before its first line is included, unlike a source without lines.
+    fn count_line_words_concurrent_checked(
+        self,
+        options: ProcessorOptions,
+    ) -> impl Future<Output = HashMap<&'a str, Result<Vec<usize>, SourceError>>> {
+        count_line_words_checked(compat(self), options)
+    }
+
+    /// Returns a stream of the word counts of each source, in the order of the input stream.
+    ///
+    /// The sources are still read concurrently, up to `options.max_concurrency` at a time, but
+    /// the counts of a source are held until all the sources before it are complete. Useful for a
+    /// deterministic output without sorting the results.
+    fn count_line_words_ordered(
+        self,
+        options: ProcessorOptions,
+    ) -> impl Stream<Item = (&'a str, Vec<usize>)>
+    where
+        R: 'a,
+    {
+        count_line_words_ordered(compat(self), options)
+    }
+
+    /// Same as [`count_line_words_concurrent_with`](Self::count_line_words_concurrent_with) but
+    /// the non-fatal anomalies of the sources (invalid UTF-8, truncated last line, binary data)
+    /// are reported to `on_warning` as they are read, e.g. to log data-quality issues.
+    ///
+    /// The callback is called from the polling task, it should not block. A channel can be used to
+    /// handle the warnings elsewhere.
+    fn count_line_words_with_warnings(
+        self,
+        options: ProcessorOptions,
+        on_warning: impl Fn(Warning<'a>),
+    ) -> impl Future<Output = HashMap<&'a str, Vec<usize>>> {
+        count_line_words_warning(self, options, on_warning)
+    }
+
+    /// Same as [`count_line_words_concurrent_with`](Self::count_line_words_concurrent_with) but
+    /// the counts are added to a map owned by the caller, e.g. pre-allocated or reused across
+    /// runs, instead of a new one.
+    fn count_line_words_into<M: ResultMap<&'a str>>(
+        self,
+        options: ProcessorOptions,
+        map: &mut M,
+    ) -> impl Future<Output = ()> {
+        async move {
+            count_line_words_into(compat(self), options, &NoReconnect, map).await;
+        }
+    }
+
+    /// Same as [`count_line_words_concurrent_with`](Self::count_line_words_concurrent_with) but
+    /// the count vectors are spilled to a temporary file when they exceed the memory budget.
+    ///
+    /// Fails if the counts could not be written to the spill file.
+    fn count_line_words_spilled(
+        self,
+        options: ProcessorOptions,
+        spill: SpillOptions,
+    ) -> impl Future<Output = io::Result<SpilledCounts<&'a str>>> {
+        async move {
+            let store = SpillStore::new(spill);
+            count_line_words_into(compat(self), options, &NoReconnect, store)
+                .await
+                .finish()
+        }
+    }
+
+    /// Same as [`count_line_words_concurrent_with`](Self::count_line_words_concurrent_with) but
+    /// the memory of the count vectors is accounted in the usage of the budget, and its
+    /// [`OnExceeded`] action is applied once they exceed its limit, e.g. to fail cleanly
+    /// instead of being killed by the system.
+    ///
+    /// Fails if the limit is exceeded with [`OnExceeded::Fail`], or if the counts could not be
+    /// written to the spill file.
+    fn count_line_words_budgeted(
+        self,
+        options: ProcessorOptions,
+        budget: &MemoryBudget,
+    ) -> impl Future<Output = io::Result<BudgetedCounts<&'a str>>> {
+        async move {
+            let store = BudgetStore::new(budget);
+            count_line_words_into(compat(self), options, &NoReconnect, store)
+                .await
+                .finish()
+        }
+    }
+
+    /// Same as [`count_line_words_concurrent_with`](Self::count_line_words_concurrent_with) but
+    /// each line is analyzed with `analyzer` instead of the tokenizer of the options.
+    fn analyze_lines_concurrent(
+        self,
+        options: ProcessorOptions,
+        analyzer: Box<dyn DynLineAnalyzer>,
+    ) -> impl Future<Output = HashMap<&'a str, Vec<usize>>> {
+        let acc = HintedMap::<_, _, RandomState, _>::new(|_: &str| 0);
+        let analyzer = Analyzer::Dyn(analyzer.into());
+        async move {
+            analyze_lines_into(compat(self), options, analyzer, &NoReconnect, acc)
+                .await
+                .map
+        }
+    }
+
+    /// Same as [`count_line_words_concurrent`](Self::count_line_words_concurrent) but transient
+    /// read errors are retried on the same reader according to the given policy.
+    fn count_line_words_concurrent_with_retry(
+        self,
+        policy: RetryPolicy,
+    ) -> impl Future<Output = HashMap<&'a str, Vec<usize>>> {
+        count_line_words_concurrent(compat(self), policy.into(), &NoReconnect)
+    }
+
+    /// Same as [`count_line_words_concurrent_with`](Self::count_line_words_concurrent_with)
+    /// but the reader is recreated with the `reconnect` hook before each retry.
+    ///
+    /// Counting resumes on the new reader under the same identifier.
+    fn count_line_words_concurrent_with_reconnect<F, Fut>(
+        self,
+        options: impl Into<ProcessorOptions>,
+        reconnect: F,
+    ) -> impl Future<Output = HashMap<&'a str, Vec<usize>>>
+    where
+        F: Fn(&str) -> Fut,
+        Fut: Future<Output = io::Result<R>>,
+    {
+        let options = options.into();
+        async move { count_line_words_concurrent(compat(self), options, &ReconnectFn(reconnect)).await }
     }
 }
 
@@ -27,69 +465,636 @@ where
 {
 }
 
+/// Returns a stream of the number of words for each line of a single reader.
+///
+/// A read error ends the stream.
+pub fn count_line_words<'a, R: AsyncBufRead + Unpin + 'a>(rd: R) -> impl Stream<Item = usize> + 'a {
+    count_line_words_with(rd, ProcessorOptions::default())
+}
+
+/// Same as [`count_line_words`] with the given options.
+pub fn count_line_words_with<'a, R: AsyncBufRead + Unpin + 'a>(
+    rd: R,
+    options: ProcessorOptions,
+) -> impl Stream<Item = usize> + 'a {
+    count_source_line_words(("reader", rd), options).map(|(_, count)| count)
+}
+
+/// Returns a stream of the number of words for each line of a source, tagged with its identifier.
+///
+/// This is the per-source building block of [`StringMultiStreamExt`], it can be merged with other
+/// sources and folded with [`fold_line_words`] to build custom pipelines. Transient read errors are
+/// retried according to the options, a read error ends the stream.
+pub fn count_source_line_words<'a, I, R>(
+    (id, rd): (I, R),
+    options: ProcessorOptions,
+) -> impl Stream<Item = (I, usize)> + 'a
+where
+    I: Copy + Display + 'a,
+    R: AsyncBufRead + Unpin + 'a,
+{
+    let counts = count_line_words_retrying((id, TokioCompat::new(rd)), options, &NoReconnect);
+    Fair::new(counts, options.fairness)
+}
+
+/// Same as [`count_source_line_words`] but each count is also tagged with the 1-based number of
+/// its line in the source, e.g. to report the lines of several merged sources without keeping a
+/// counter per identifier.
+pub fn count_source_line_words_numbered<'a, I, R>(
+    src: (I, R),
+    options: ProcessorOptions,
+) -> impl Stream<Item = (I, usize, usize)> + 'a
+where
+    I: Copy + Display + 'a,
+    R: AsyncBufRead + Unpin + 'a,
+{
+    count_source_line_words(src, options)
+        .enumerate()
+        .map(|(line, (id, count))| (id, line + 1, count))
+}
+
+/// Fold a stream of line counts tagged with their identifier into a map of the identifiers to the
+/// word counts of their lines, in the order of the stream.
+pub async fn fold_line_words<I: Hash + Eq>(
+    counts: impl Stream<Item = (I, usize)>,
+) -> HashMap<I, Vec<usize>> {
+    aggregate(counts, None, HashMap::new()).await
+}
+
+/// Group the word counts of identifiers by the key `key_fn` maps them to, e.g. their extension
+/// or their tenant. The counts of the identifiers of a group follow each other in the order of
+/// the identifiers, whatever the order of the map.
+pub fn group_line_words<I: Ord, K: Hash + Eq>(
+    results: HashMap<I, Vec<usize>>,
+    key_fn: impl Fn(I) -> K,
+) -> HashMap<K, Vec<usize>> {
+    let mut results: Vec<_> = results.into_iter().collect();
+    results.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+    let mut groups: HashMap<K, Vec<usize>> = HashMap::new();
+    for (id, counts) in results {
+        groups.entry(key_fn(id)).or_default().extend(counts);
+    }
+    groups
+}
+
+/// Wrap the Tokio readers of the stream into the `futures` traits used by the counting core.
+fn compat<'a, R>(
+    rds: impl Stream<Item = (&'a str, R)>,
+) -> impl Stream<Item = (&'a str, TokioCompat<R>)> {
+    rds.map(|(id, rd)| (id, TokioCompat::new(rd)))
+}
+
 /// Count the number of words from a stream of async readers and associated identifiers.
 ///
-/// Returns a map of identifiers to a vector of word counts for each line.
-async fn count_line_words_concurrent<'a, R: AsyncBufRead + Unpin>(
+/// Returns a map of identifiers to the accumulated word counts of their lines.
+async fn count_line_words_concurrent<'a, R, S, A>(
+    rds: impl Stream<Item = (&'a str, R)>,
+    options: ProcessorOptions,
+    reconnect: &impl Reconnect<&'a str, R>,
+) -> HashMap<&'a str, A, S>
+where
+    R: futures_util::io::AsyncBufRead + Unpin,
+    S: BuildHasher + Default,
+    A: Accumulate,
+{
+    count_line_words_concurrent_hinted(rds, options, reconnect, |_| 0).await
+}
+
+/// Same as [`count_line_words_concurrent`] with a hint of the number of lines of each identifier.
+async fn count_line_words_concurrent_hinted<'a, R, S, A>(
+    rds: impl Stream<Item = (&'a str, R)>,
+    options: ProcessorOptions,
+    reconnect: &impl Reconnect<&'a str, R>,
+    lines_hint: impl Fn(&str) -> usize,
+) -> HashMap<&'a str, A, S>
+where
+    R: futures_util::io::AsyncBufRead + Unpin,
+    S: BuildHasher + Default,
+    A: Accumulate,
+{
+    let acc = HintedMap::new(lines_hint);
+    count_line_words_into(rds, options, reconnect, acc)
+        .await
+        .map
+}
+
+/// Count the number of words from a stream of async readers and aggregate them into `acc`.
+async fn count_line_words_into<'a, R, G>(
     rds: impl Stream<Item = (&'a str, R)>,
+    options: ProcessorOptions,
+    reconnect: &impl Reconnect<&'a str, R>,
+    acc: G,
+) -> G
+where
+    R: futures_util::io::AsyncBufRead + Unpin,
+    G: Aggregate<&'a str>,
+{
+    let analyzer = Analyzer::Tokenizer(options.tokenizer);
+    analyze_lines_into(rds, options, analyzer, reconnect, acc).await
+}
+
+/// Count the words of the sources concurrently, yielding their counts in the input order.
+fn count_line_words_ordered<'a, R>(
+    rds: impl Stream<Item = (&'a str, R)>,
+    options: ProcessorOptions,
+) -> impl Stream<Item = (&'a str, Vec<usize>)>
+where
+    R: futures_util::io::AsyncBufRead + Unpin + 'a,
+{
+    rds.map(move |(id, rd)| {
+        count_line_words_retrying((id, rd), options, &NoReconnect)
+            .map(|(_, count)| count)
+            .collect::<Vec<_>>()
+            .map(move |counts| (id, counts))
+    })
+    .buffered(options.max_concurrency.unwrap_or(usize::MAX))
+}
+
+/// Same as [`count_line_words_concurrent`] but the anomalies of the sources are reported to
+/// `on_warning`.
+async fn count_line_words_warning<'a, R: AsyncBufRead + Unpin>(
+    rds: impl Stream<Item = (&'a str, R)>,
+    options: ProcessorOptions,
+    on_warning: impl Fn(Warning<'a>),
 ) -> HashMap<&'a str, Vec<usize>> {
-    let mut data: HashMap<&'a str, Vec<usize>> = HashMap::new();
-    rds.flat_map_unordered(None, count_line_words)
-        .fold(&mut data, |acc, (id, count)| {
-            acc.entry(id).or_default().push(count);
-            async move { acc }
-        })
-        .await;
+    let on_warning = &on_warning;
+    let rds = rds.map(|(id, rd)| (id, Warnings::new(rd, id, on_warning)));
+    count_line_words_concurrent::<_, RandomState, _>(compat(rds), options, &NoReconnect).await
+}
 
-    data
+/// Same as [`count_line_words_into`] but the read error which ended a source is kept with its
+/// counts.
+async fn count_line_words_checked<'a, R>(
+    rds: impl Stream<Item = (&'a str, R)>,
+    options: ProcessorOptions,
+) -> HashMap<&'a str, Result<Vec<usize>, SourceError>>
+where
+    R: futures_util::io::AsyncBufRead + Unpin,
+{
+    let counts = rds.flat_map_unordered(options.max_concurrency, |src| {
+        let counts = count_line_words_fallible(src, options, &NoReconnect);
+        Box::pin(Fair::new(counts, options.fairness))
+    });
+    aggregate(counts, options.buffer_capacity, HashMap::new()).await
 }
 
-/// Returns a stream of the number of words for each line of the input.
-///
-/// The input identifier will be included in the output.
-fn count_line_words<R: AsyncBufRead>((id, rd): (&str, R)) -> impl Stream<Item = (&str, usize)> {
-    LinesStream::new(rd.lines())
-        .map(|line| line.unwrap().split_whitespace().count())
-        .map(move |itm| (id, itm))
+/// Same as [`count_line_words_into`] with the given per-line computation.
+async fn analyze_lines_into<'a, R, G>(
+    rds: impl Stream<Item = (&'a str, R)>,
+    options: ProcessorOptions,
+    analyzer: Analyzer,
+    reconnect: &impl Reconnect<&'a str, R>,
+    acc: G,
+) -> G
+where
+    R: futures_util::io::AsyncBufRead + Unpin,
+    G: Aggregate<&'a str>,
+{
+    let counts = rds.flat_map_unordered(options.max_concurrency, |src| {
+        let counts = analyze_lines_retrying(src, options, analyzer.clone(), reconnect);
+        Box::pin(Fair::new(counts, options.fairness))
+    });
+    aggregate(counts, options.buffer_capacity, acc).await
 }
 
 #[cfg(test)]
 mod tests {
-    use std::io;
+    use std::{
+        io,
+        pin::Pin,
+        sync::atomic::{AtomicUsize, Ordering},
+        task::{Context, Poll},
+        time::Duration,
+    };
 
-    use futures_util::stream;
-    use tokio::io::BufReader;
+    use futures_util::{future, stream};
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader, ReadBuf};
 
     use super::*;
 
+    /// A reader that fails with the given error kind before yielding its data.
+    struct FlakyReader {
+        failures: usize,
+        kind: io::ErrorKind,
+        inner: io::Cursor<&'static str>,
+    }
+    impl AsyncRead for FlakyReader {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Poll::Ready(Err(self.kind.into()));
+            }
+            Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+    fn flaky(failures: usize, kind: io::ErrorKind, data: &'static str) -> BufReader<FlakyReader> {
+        BufReader::new(FlakyReader {
+            failures,
+            kind,
+            inner: io::Cursor::new(data),
+        })
+    }
+
     #[tokio::test]
     async fn test_count_line_words_concurrent() {
-        const FILE1: &str = include_str!("../../../tests/file1.txt");
-        const FILE2: &str = include_str!("../../../tests/file2.txt");
+        const FILE1: &str = include_str!("../../file1.txt");
+        const FILE2: &str = include_str!("../../file2.txt");
         let iter = [("file1.txt", FILE1), ("file2.txt", FILE2)];
         let stream =
             stream::iter(iter.map(|(path, buff)| (path, BufReader::new(io::Cursor::new(buff)))));
-        let result = count_line_words_concurrent(stream).await;
+        let result = stream.count_line_words_concurrent().await;
         assert_eq!(result.get("file1.txt").unwrap(), &[2, 3]);
         assert_eq!(result.get("file2.txt").unwrap(), &[2, 2]);
     }
 
     #[tokio::test]
     async fn test_count_line_words() {
-        const DATA: (&str, &str) = (
-            "file1.txt",
-            r#""Lorem ipsum dolor sit amet,
+        const DATA: &str = r#""Lorem ipsum dolor sit amet,
             consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna\n aliqua.
             Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris nisi ut aliquip ex ea\n commodo
             consequat. Duis aute irure dolor in reprehenderit in voluptate velit esse cillum dolore eu\n fugiat
             nulla pariatur. Excepteur sint occaecat cupidatat non proident\n,
-            sunt in culpa qui officia deserunt mollit anim id est laborum.""#,
+            sunt in culpa qui officia deserunt mollit anim id est laborum.""#;
+        let rd = BufReader::new(io::Cursor::new(DATA));
+        let counts: Vec<usize> = count_line_words(rd).collect().await;
+        assert_eq!(counts, [5, 14, 16, 15, 8, 11]);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_lines_concurrent() {
+        for options in [
+            ProcessorOptions::default(),
+            ProcessorOptions::default().with_blocking_batch(2),
+        ] {
+            let srcs = [("a", BufReader::new(io::Cursor::new("a b\r\n\nlast")))];
+            let analyzer = analyzer_from_name("bytes").unwrap();
+            let result = stream::iter(srcs)
+                .analyze_lines_concurrent(options, analyzer)
+                .await;
+            assert_eq!(result["a"], [3, 0, 4]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_analyze_lines_concurrent_invalid_line() {
+        // The counts of the lines before the invalid one are kept, with or without batches.
+        for options in [
+            ProcessorOptions::default(),
+            ProcessorOptions::default().with_blocking_batch(3),
+        ] {
+            let data: &[u8] = b"ab\nc\n\xff\nd e\n";
+            let srcs = [("a", BufReader::new(io::Cursor::new(data)))];
+            let analyzer = analyzer_from_name("chars").unwrap();
+            let result = stream::iter(srcs)
+                .analyze_lines_concurrent(options, analyzer)
+                .await;
+            assert_eq!(result["a"], [2, 1]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fold_line_words() {
+        let options = ProcessorOptions::default();
+        let a = count_source_line_words(("a", BufReader::new(io::Cursor::new("a b\nc"))), options);
+        let b = count_source_line_words(("b", BufReader::new(io::Cursor::new("d e f"))), options);
+        // Drop the empty lines of a custom pipeline.
+        let counts = stream::select(a, b).filter(|(_, count)| future::ready(*count > 0));
+        let result = fold_line_words(counts).await;
+        assert_eq!(result["a"], [2, 1]);
+        assert_eq!(result["b"], [3]);
+    }
+
+    #[tokio::test]
+    async fn test_count_line_words_grouped() {
+        let srcs = [
+            ("web-2", BufReader::new(io::Cursor::new("a b\nc"))),
+            ("db-1", BufReader::new(io::Cursor::new("d e f"))),
+            ("web-1", BufReader::new(io::Cursor::new("g\nh i j k"))),
+        ];
+        let result = stream::iter(srcs)
+            .count_line_words_grouped(ProcessorOptions::default(), |id| {
+                id.split_once('-').map_or(id, |(tenant, _)| tenant)
+            })
+            .await;
+        assert_eq!(result.len(), 2);
+        assert_eq!(result["web"], [1, 4, 2, 1]);
+        assert_eq!(result["db"], [3]);
+
+        let result = HashMap::from([(2, vec![1]), (1, vec![2]), (3, vec![3])]);
+        let result = group_line_words(result, |id| id % 2);
+        assert_eq!(result[&1], [2, 3]);
+        assert_eq!(result[&0], [1]);
+    }
+
+    #[tokio::test]
+    async fn test_count_source_line_words_numbered() {
+        let options = ProcessorOptions::default().with_fairness(1);
+        let a = ("a", BufReader::new(io::Cursor::new("a b\n\nc")));
+        let b = ("b", BufReader::new(io::Cursor::new("d e f\ng")));
+        let counts = stream::select(
+            count_source_line_words_numbered(a, options),
+            count_source_line_words_numbered(b, options),
         );
-        let rd = BufReader::new(io::Cursor::new(DATA.1));
-        let counts: Vec<usize> = count_line_words((DATA.0, rd))
-            .map(|(_, count)| count)
+        let mut counts: Vec<_> = counts.collect().await;
+        counts.sort();
+        assert_eq!(
+            counts,
+            [
+                ("a", 1, 2),
+                ("a", 2, 0),
+                ("a", 3, 1),
+                ("b", 1, 3),
+                ("b", 2, 1)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_count_line_words_concurrent_buffered() {
+        let srcs = (0..10).map(|i| (["a", "b"][i % 2], BufReader::new(io::Cursor::new("a b\nc"))));
+        let options = ProcessorOptions::default().with_buffer_capacity(1);
+        let result = stream::iter(srcs)
+            .count_line_words_concurrent_with(options)
+            .await;
+        assert_eq!(result.get("a").unwrap().len(), 10);
+        assert_eq!(result.get("b").unwrap().iter().sum::<usize>(), 15);
+    }
+
+    #[tokio::test]
+    async fn test_count_line_words_concurrent_ascii() {
+        let srcs = [
+            (
+                "a",
+                BufReader::new(io::Cursor::new("Hello world\n\nfoo\tbar baz")),
+            ),
+            ("b", BufReader::new(io::Cursor::new("a\u{a0}b c"))),
+        ];
+        let options = ProcessorOptions::default().with_tokenizer(Tokenizer::Ascii);
+        let result = stream::iter(srcs)
+            .count_line_words_concurrent_with(options)
+            .await;
+        assert_eq!(result.get("a").unwrap(), &[2, 0, 3]);
+        // Non-breaking spaces are not ASCII whitespace.
+        assert_eq!(result.get("b").unwrap(), &[2]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_count_line_words_concurrent_blocking() {
+        const DATA: &str = "a b\n\nc d e\nf";
+        for tokenizer in [Tokenizer::Unicode, Tokenizer::Ascii] {
+            let srcs = [("a", BufReader::new(io::Cursor::new(DATA)))];
+            let options = ProcessorOptions::default()
+                .with_tokenizer(tokenizer)
+                .with_blocking_batch(3);
+            let result = stream::iter(srcs)
+                .count_line_words_concurrent_with(options)
+                .await;
+            assert_eq!(result.get("a").unwrap(), &[2, 0, 3, 1]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_count_line_words_concurrent_with_hasher() {
+        use std::{collections::hash_map::DefaultHasher, hash::BuildHasherDefault};
+
+        let srcs = [("a", BufReader::new(io::Cursor::new("a b\nc")))];
+        let result: HashMap<_, _, BuildHasherDefault<DefaultHasher>> = stream::iter(srcs)
+            .count_line_words_concurrent_with_hasher(ProcessorOptions::default())
+            .await;
+        assert_eq!(result["a"], [2, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_count_line_words_concurrent_compact() {
+        let line = "a ".repeat(300);
+        let srcs = [("a", BufReader::new(io::Cursor::new(line + "\nb c")))];
+        let result = stream::iter(srcs)
+            .count_line_words_concurrent_compact::<u8>(ProcessorOptions::default())
+            .await;
+        assert_eq!(result["a"], [u8::MAX, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_count_line_words_spilled() {
+        let data = "a b\nc\n\nd e f\n".repeat(50);
+        let srcs = [
+            ("a", BufReader::new(io::Cursor::new(data.clone()))),
+            ("b", BufReader::new(io::Cursor::new(data))),
+        ];
+        let spill = SpillOptions::new(64);
+        let result = stream::iter(srcs)
+            .count_line_words_spilled(ProcessorOptions::default(), spill)
+            .await
+            .unwrap();
+        for id in ["a", "b"] {
+            let counts: Vec<usize> = result
+                .counts(&id)
+                .unwrap()
+                .unwrap()
+                .map(Result::unwrap)
+                .collect();
+            assert_eq!(counts, [2, 1, 0, 3].repeat(50));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_count_line_words_stats() {
+        let srcs = [("a", BufReader::new(io::Cursor::new("a b\nc\n\nd e f")))];
+        let options = ProcessorOptions::default().with_buffer_capacity(2);
+        let result = stream::iter(srcs).count_line_words_stats(options).await;
+        let stats = result["a"];
+        assert_eq!(
+            (stats.lines, stats.words, stats.min, stats.max),
+            (4, 6, 0, 3)
+        );
+        assert_eq!(stats.mean(), 1.5);
+    }
+
+    #[tokio::test]
+    async fn test_count_line_words_aggregated() {
+        /// The largest counts of a source and their line numbers.
+        struct TopLines {
+            top: usize,
+            line: usize,
+            lines: Vec<(usize, usize)>,
+        }
+        impl Aggregator for TopLines {
+            type Output = Vec<(usize, usize)>;
+            fn update(&mut self, count: usize) {
+                self.lines.push((count, self.line));
+                self.lines
+                    .sort_by_key(|&(count, _)| std::cmp::Reverse(count));
+                self.lines.truncate(self.top);
+                self.line += 1;
+            }
+            fn finish(self) -> Self::Output {
+                self.lines
+            }
+        }
+        let srcs = [
+            ("a", BufReader::new(io::Cursor::new("a\nb c d\n\ne f"))),
+            ("bb", BufReader::new(io::Cursor::new("a b\nc"))),
+        ];
+        let result = stream::iter(srcs)
+            .count_line_words_aggregated(ProcessorOptions::default(), |id| TopLines {
+                top: id.len(),
+                line: 0,
+                lines: Vec::new(),
+            })
+            .await;
+        assert_eq!(result["a"], [(3, 1)]);
+        assert_eq!(result["bb"], [(2, 0), (1, 1)]);
+
+        let srcs = [("a", BufReader::new(io::Cursor::new("a b\nc")))];
+        let result = stream::iter(srcs)
+            .count_line_words_aggregated(ProcessorOptions::default(), |_| LineStats::default())
+            .await;
+        assert_eq!(result["a"].words, 3);
+    }
+
+    #[tokio::test]
+    async fn test_count_line_words_concurrent_checked() {
+        // Fails after its data.
+        let failing = |data, failures| {
+            let tail = FlakyReader {
+                failures,
+                kind: io::ErrorKind::InvalidData,
+                inner: io::Cursor::new(""),
+            };
+            BufReader::new(io::Cursor::new(data).chain(tail))
+        };
+        let srcs = [
+            ("a", failing("a b\nc\n", 1)),
+            ("b", failing("", 1)),
+            ("c", failing("d e\nf", 0)),
+        ];
+        let result = stream::iter(srcs)
+            .count_line_words_concurrent_checked(ProcessorOptions::default())
+            .await;
+        let err = result["a"].as_ref().unwrap_err();
+        assert_eq!(err.counts, [2, 1]);
+        assert_eq!(err.error.kind(), io::ErrorKind::InvalidData);
+        assert!(result["b"].as_ref().unwrap_err().counts.is_empty());
+        assert_eq!(result["c"].as_ref().unwrap(), &[2, 1]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_count_line_words_ordered() {
+        // A source whose data is written after the given delay.
+        let delayed = |secs, data: &'static str| {
+            let (rd, mut wr) = tokio::io::duplex(64);
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_secs(secs)).await;
+                wr.write_all(data.as_bytes()).await.unwrap();
+            });
+            BufReader::new(rd)
+        };
+        let srcs = [
+            ("a", delayed(2, "a b\nc")),
+            ("b", delayed(0, "d")),
+            ("c", delayed(1, "e f g")),
+        ];
+        let start = tokio::time::Instant::now();
+        let options = ProcessorOptions::default().with_max_concurrency(3);
+        let result: Vec<_> = stream::iter(srcs)
+            .count_line_words_ordered(options)
             .collect()
             .await;
-        assert_eq!(counts, [5, 14, 16, 15, 8, 11]);
+        assert_eq!(result, [("a", vec![2, 1]), ("b", vec![1]), ("c", vec![3])]);
+        // The sources were read concurrently.
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn test_count_line_words_with_warnings() {
+        let srcs = [
+            ("a", BufReader::new(io::Cursor::new("a b\nc"))),
+            ("b", BufReader::new(io::Cursor::new("d\n"))),
+        ];
+        let warnings = std::cell::RefCell::new(Vec::new());
+        let result = stream::iter(srcs)
+            .count_line_words_with_warnings(ProcessorOptions::default(), |warning| {
+                warnings.borrow_mut().push(warning)
+            })
+            .await;
+        assert_eq!(result["a"], [2, 1]);
+        let warning = Warning {
+            id: "a",
+            line: 2,
+            kind: WarningKind::TruncatedLine,
+        };
+        assert_eq!(warnings.into_inner(), [warning]);
+    }
+
+    #[tokio::test]
+    async fn test_count_line_words_into() {
+        let srcs = || {
+            [
+                ("a", BufReader::new(io::Cursor::new("a b\nc"))),
+                ("b", BufReader::new(io::Cursor::new("d"))),
+            ]
+        };
+        let mut map = HashMap::<_, Vec<usize>>::with_capacity(2);
+        stream::iter(srcs())
+            .count_line_words_into(ProcessorOptions::default(), &mut map)
+            .await;
+        assert_eq!(map["a"], [2, 1]);
+        // The counts of another run are appended.
+        stream::iter(srcs())
+            .count_line_words_into(ProcessorOptions::default(), &mut map)
+            .await;
+        assert_eq!(map["b"], [1, 1]);
+
+        let mut stats = std::collections::BTreeMap::<_, LineStats>::new();
+        stream::iter(srcs())
+            .count_line_words_into(ProcessorOptions::default(), &mut stats)
+            .await;
+        assert_eq!(stats.keys().copied().collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(stats["a"].words, 3);
+    }
+
+    #[tokio::test]
+    async fn test_count_line_words_concurrent_with_hint() {
+        let srcs = [("a", BufReader::new(io::Cursor::new("a b\nc")))];
+        let result = stream::iter(srcs)
+            .count_line_words_concurrent_with_hint(ProcessorOptions::default(), |_| 100)
+            .await;
+        assert_eq!(result["a"], [2, 1]);
+        assert!(result["a"].capacity() >= 100);
+    }
+
+    #[tokio::test]
+    async fn test_retry_transient_errors() {
+        let policy = RetryPolicy::new(2, Duration::from_millis(1));
+        let srcs = [
+            ("a", flaky(2, io::ErrorKind::WouldBlock, "a b\nc")),
+            ("b", flaky(3, io::ErrorKind::WouldBlock, "a b\nc")),
+            ("c", flaky(1, io::ErrorKind::PermissionDenied, "a b\nc")),
+        ];
+        let result = stream::iter(srcs)
+            .count_line_words_concurrent_with_retry(policy)
+            .await;
+        assert_eq!(result.get("a").unwrap(), &[2, 1]);
+        assert_eq!(result.get("b"), None);
+        assert_eq!(result.get("c"), None);
+    }
+
+    #[tokio::test]
+    async fn test_retry_reconnect() {
+        let policy = RetryPolicy::new(2, Duration::from_millis(1));
+        let reconnects = AtomicUsize::new(0);
+        let src = ("a", flaky(1, io::ErrorKind::ConnectionReset, ""));
+        let result = stream::iter([src])
+            .count_line_words_concurrent_with_reconnect(policy, |_| {
+                reconnects.fetch_add(1, Ordering::Relaxed);
+                async { Ok(flaky(0, io::ErrorKind::ConnectionReset, "a b c\nd")) }
+            })
+            .await;
+        assert_eq!(result.get("a").unwrap(), &[3, 1]);
+        assert_eq!(reconnects.load(Ordering::Relaxed), 1);
     }
 }
@@ -0,0 +1,131 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use pin_project_lite::pin_project;
+use tokio::io::{AsyncBufRead, AsyncRead, ReadBuf};
+
+pin_project! {
+    /// Reader ending after the first lines of a reader, e.g. to sample enormous logs.
+    ///
+    /// The reader ends right after the line ending of the last line kept. Whether the inner
+    /// reader had more data is known once the end is read, see [`LineLimit::is_truncated`].
+    #[derive(Debug)]
+    pub struct LineLimit<R> {
+        #[pin]
+        inner: R,
+        // Number of lines left to find, `None` without a limit.
+        remaining: Option<usize>,
+        // Length of the start of the buffer of the inner reader already searched.
+        seen: usize,
+        // Length of the start of the buffer ending with the last line kept, once found.
+        end: Option<usize>,
+        truncated: bool,
+    }
+}
+
+impl<R> LineLimit<R> {
+    /// Read at most `max_lines` lines of `inner`, all of them with `None`.
+    pub fn new(inner: R, max_lines: Option<usize>) -> Self {
+        Self {
+            inner,
+            remaining: max_lines,
+            seen: 0,
+            end: (max_lines == Some(0)).then_some(0),
+            truncated: false,
+        }
+    }
+
+    /// Whether the inner reader had data after the last line kept.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Whether all the lines kept were consumed, the next read ends the reader.
+    pub fn is_exhausted(&self) -> bool {
+        self.end == Some(0)
+    }
+}
+
+impl<R: AsyncBufRead> AsyncBufRead for LineLimit<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.project();
+        let Some(remaining) = this.remaining else {
+            return this.inner.poll_fill_buf(cx);
+        };
+        if *this.end == Some(0) {
+            // The inner reader is not read anymore once it is known to have more data.
+            if !*this.truncated {
+                *this.truncated = !ready!(this.inner.poll_fill_buf(cx))?.is_empty();
+            }
+            return Poll::Ready(Ok(&[]));
+        }
+        let data = ready!(this.inner.poll_fill_buf(cx))?;
+        if this.end.is_none() {
+            for (pos, &byte) in data.iter().enumerate().skip(*this.seen) {
+                if byte == b'\n' {
+                    *remaining -= 1;
+                    if *remaining == 0 {
+                        *this.end = Some(pos + 1);
+                        break;
+                    }
+                }
+            }
+            *this.seen = data.len();
+        }
+        let len = this.end.unwrap_or(data.len());
+        Poll::Ready(Ok(&data[..len]))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.project();
+        *this.seen = this.seen.saturating_sub(amt);
+        if let Some(end) = this.end {
+            *end -= amt;
+        }
+        this.inner.consume(amt);
+    }
+}
+
+impl<R: AsyncBufRead> AsyncRead for LineLimit<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let data = ready!(self.as_mut().poll_fill_buf(cx))?;
+        let len = data.len().min(buf.remaining());
+        buf.put_slice(&data[..len]);
+        self.consume(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, BufReader};
+
+    use super::*;
+
+    async fn read(data: &'static [u8], max_lines: Option<usize>) -> (String, bool) {
+        // A small buffer splits the lines between reads.
+        let mut rd = LineLimit::new(BufReader::with_capacity(3, data), max_lines);
+        let mut read = String::new();
+        rd.read_to_string(&mut read).await.unwrap();
+        (read, rd.is_truncated())
+    }
+
+    #[tokio::test]
+    async fn test_line_limit() {
+        assert_eq!(
+            read(b"a b\nc\nd e f\n", Some(2)).await,
+            ("a b\nc\n".into(), true)
+        );
+        assert_eq!(read(b"a b\nc\n", Some(2)).await, ("a b\nc\n".into(), false));
+        assert_eq!(read(b"a b\nc", Some(2)).await, ("a b\nc".into(), false));
+        assert_eq!(read(b"a\nb", Some(0)).await, (String::new(), true));
+        assert_eq!(read(b"a\nb\nc", None).await, ("a\nb\nc".into(), false));
+    }
+}
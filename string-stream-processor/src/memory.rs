@@ -0,0 +1,321 @@
+use std::{
+    collections::{hash_map, HashMap},
+    hash::Hash,
+    io, mem,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use crate::{
+    aggregate::{Accumulate, Aggregate},
+    spill::SpillStore,
+    LineStats, SpillOptions, SpilledCounts,
+};
+
+/// Estimate of the memory used by the results of the runs sharing it, e.g. the count vectors
+/// of a [`MemoryBudget`] and the frequency maps of a [`MetricSet`](crate::MetricSet), read from
+/// another task to monitor a run.
+///
+/// Only the results are accounted, not the buffers of the readers.
+#[derive(Debug, Default)]
+pub struct MemoryUsage {
+    used: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl MemoryUsage {
+    /// Bytes currently used.
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Maximum of the bytes used so far.
+    pub fn peak(&self) -> usize {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    /// Account `bytes` more, e.g. of results stored by the caller.
+    pub fn add(&self, bytes: usize) {
+        let used = self.used.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.peak.fetch_max(used, Ordering::Relaxed);
+    }
+
+    /// Release `bytes` accounted with [`MemoryUsage::add`].
+    pub fn sub(&self, bytes: usize) {
+        self.used.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    /// Estimated bytes of an entry of a frequency map.
+    pub(crate) fn frequency_entry(word: &str) -> usize {
+        word.len() + mem::size_of::<(String, u64)>()
+    }
+}
+
+/// What to do once the count vectors exceed the limit of a [`MemoryBudget`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OnExceeded {
+    /// Spill the largest vectors to a temporary file in this directory, see
+    /// [`SpillOptions`].
+    Spill(PathBuf),
+    /// Replace the vectors by the [`LineStats`] of each source, which use a constant memory.
+    Aggregate,
+    /// Stop reading the sources and fail with an [`io::ErrorKind::OutOfMemory`] error.
+    Fail,
+}
+
+/// Limit of the memory of the count vectors of a run, see
+/// [`count_line_words_budgeted`](crate::StringMultiStreamExt::count_line_words_budgeted).
+#[derive(Debug, Clone)]
+pub struct MemoryBudget {
+    /// Maximum of the bytes of the count vectors, unlimited if `None`.
+    pub limit: Option<usize>,
+    pub on_exceeded: OnExceeded,
+    usage: Arc<MemoryUsage>,
+}
+
+impl MemoryBudget {
+    /// Apply `on_exceeded` once the count vectors use more than `limit` bytes.
+    pub fn new(limit: usize, on_exceeded: OnExceeded) -> Self {
+        Self {
+            limit: Some(limit),
+            on_exceeded,
+            usage: Arc::default(),
+        }
+    }
+
+    /// Only account the memory of the count vectors.
+    pub fn unlimited() -> Self {
+        Self {
+            limit: None,
+            on_exceeded: OnExceeded::Fail,
+            usage: Arc::default(),
+        }
+    }
+
+    /// Account the memory in `usage`, e.g. shared with the metrics of other runs.
+    pub fn with_usage(mut self, usage: Arc<MemoryUsage>) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Memory used by the count vectors, updated while the sources are read.
+    pub fn usage(&self) -> &Arc<MemoryUsage> {
+        &self.usage
+    }
+}
+
+/// Word counts of each identifier of a run with a [`MemoryBudget`], depending on whether and
+/// how the limit was exceeded.
+#[derive(Debug)]
+pub enum BudgetedCounts<I> {
+    /// The counts of all the lines kept in memory, within the limit.
+    Counts(HashMap<I, Vec<usize>>),
+    /// The counts partly spilled to disk, with [`OnExceeded::Spill`].
+    Spilled(SpilledCounts<I>),
+    /// The statistics of the lines, with [`OnExceeded::Aggregate`].
+    Stats(HashMap<I, LineStats>),
+}
+
+enum Store<I> {
+    Counts(HashMap<I, Vec<usize>>),
+    Spilled(SpillStore<I>),
+    Stats(HashMap<I, LineStats>),
+    Failed,
+}
+
+/// Aggregation storage of the counts accounting their memory, changed according to the budget
+/// once they exceed its limit.
+pub(crate) struct BudgetStore<'b, I> {
+    store: Store<I>,
+    budget: &'b MemoryBudget,
+    /// Bytes of the store accounted in the usage of the budget.
+    bytes: usize,
+}
+
+impl<'b, I: Hash + Eq> BudgetStore<'b, I> {
+    pub fn new(budget: &'b MemoryBudget) -> Self {
+        Self {
+            store: Store::Counts(HashMap::new()),
+            budget,
+            bytes: 0,
+        }
+    }
+
+    /// Estimated bytes of the store.
+    fn size(&self) -> usize {
+        match &self.store {
+            Store::Counts(map) => map
+                .values()
+                .map(|counts| counts.capacity() * mem::size_of::<usize>())
+                .sum::<usize>()
+                .saturating_add(map.len() * mem::size_of::<(I, Vec<usize>)>()),
+            Store::Spilled(store) => store.in_memory() * mem::size_of::<usize>(),
+            Store::Stats(map) => map.len() * mem::size_of::<(I, LineStats)>(),
+            Store::Failed => 0,
+        }
+    }
+
+    /// Account the new size of the store.
+    fn resize(&mut self, bytes: usize) {
+        let usage = &self.budget.usage;
+        match bytes.checked_sub(self.bytes) {
+            Some(more) => usage.add(more),
+            None => usage.sub(self.bytes - bytes),
+        }
+        self.bytes = bytes;
+    }
+
+    /// Change the store once the counts exceed the limit.
+    fn exceeded(&mut self, limit: usize) {
+        let Store::Counts(map) = mem::replace(&mut self.store, Store::Failed) else {
+            return;
+        };
+        self.store = match &self.budget.on_exceeded {
+            OnExceeded::Spill(dir) => {
+                log::info!("The counts exceed the memory budget of {limit} bytes, spilling them.");
+                let mut store = SpillStore::new(SpillOptions::new(limit).with_dir(dir));
+                for (id, counts) in map {
+                    store.insert(id, counts);
+                }
+                Store::Spilled(store)
+            }
+            OnExceeded::Aggregate => {
+                log::warn!(
+                    "The counts exceed the memory budget of {limit} bytes, keeping only their \
+                     statistics."
+                );
+                let stats = map
+                    .into_iter()
+                    .map(|(id, counts)| (id, counts.into_iter().collect()))
+                    .collect();
+                Store::Stats(stats)
+            }
+            OnExceeded::Fail => Store::Failed,
+        };
+        self.resize(self.size());
+    }
+
+    /// Returns the counts, the memory accounted is released as they are given to the caller.
+    pub fn finish(mut self) -> io::Result<BudgetedCounts<I>> {
+        self.resize(0);
+        match self.store {
+            Store::Counts(map) => Ok(BudgetedCounts::Counts(map)),
+            Store::Spilled(store) => store.finish().map(BudgetedCounts::Spilled),
+            Store::Stats(map) => Ok(BudgetedCounts::Stats(map)),
+            Store::Failed => Err(io::Error::new(
+                io::ErrorKind::OutOfMemory,
+                format!(
+                    "the counts exceed the memory budget of {} bytes",
+                    self.budget.limit.unwrap_or_default()
+                ),
+            )),
+        }
+    }
+}
+
+impl<I: Hash + Eq> Aggregate<I> for BudgetStore<'_, I> {
+    fn push(&mut self, id: I, count: usize) {
+        let bytes = match &mut self.store {
+            Store::Counts(map) => {
+                let mut bytes = self.bytes;
+                let counts = match map.entry(id) {
+                    hash_map::Entry::Occupied(counts) => counts.into_mut(),
+                    hash_map::Entry::Vacant(counts) => {
+                        bytes += mem::size_of::<(I, Vec<usize>)>();
+                        counts.insert(Vec::new())
+                    }
+                };
+                let capacity = counts.capacity();
+                counts.push(count);
+                bytes + (counts.capacity() - capacity) * mem::size_of::<usize>()
+            }
+            Store::Spilled(store) => {
+                store.push(id, count);
+                store.in_memory() * mem::size_of::<usize>()
+            }
+            Store::Stats(map) => {
+                let mut bytes = self.bytes;
+                let stats = map.entry(id).or_insert_with(|| {
+                    bytes += mem::size_of::<(I, LineStats)>();
+                    LineStats::default()
+                });
+                stats.push(count);
+                bytes
+            }
+            Store::Failed => return,
+        };
+        self.resize(bytes);
+        match self.budget.limit {
+            Some(limit) if bytes > limit && matches!(self.store, Store::Counts(_)) => {
+                self.exceeded(limit);
+            }
+            _ => {}
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        matches!(self.store, Store::Failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::stream;
+
+    use super::*;
+    use crate::{ProcessorOptions, StringMultiStreamExt};
+
+    #[tokio::test]
+    async fn test_count_line_words_budgeted() {
+        let srcs = || stream::iter([("a", "a b\nc\n".as_bytes()), ("b", "d e f\n".as_bytes())]);
+        let options = ProcessorOptions::default();
+        let budget = MemoryBudget::unlimited();
+        let result = srcs().count_line_words_budgeted(options, &budget).await;
+        let BudgetedCounts::Counts(counts) = result.unwrap() else {
+            panic!("the counts are within the budget");
+        };
+        assert_eq!(
+            (&counts["a"][..], &counts["b"][..]),
+            (&[2, 1][..], &[3][..])
+        );
+        assert!(budget.usage().peak() > 0);
+        assert_eq!(budget.usage().used(), 0);
+
+        let budget = MemoryBudget::new(8, OnExceeded::Aggregate);
+        let result = srcs().count_line_words_budgeted(options, &budget).await;
+        let BudgetedCounts::Stats(stats) = result.unwrap() else {
+            panic!("the counts exceed the budget");
+        };
+        assert_eq!((stats["a"].lines, stats["a"].words), (2, 3));
+        assert_eq!((stats["b"].lines, stats["b"].words), (1, 3));
+
+        let budget = MemoryBudget::new(8, OnExceeded::Spill(std::env::temp_dir()));
+        let result = srcs().count_line_words_budgeted(options, &budget).await;
+        let BudgetedCounts::Spilled(counts) = result.unwrap() else {
+            panic!("the counts exceed the budget");
+        };
+        assert_eq!(counts.len(), 2);
+
+        let budget = MemoryBudget::new(8, OnExceeded::Fail);
+        let error = srcs()
+            .count_line_words_budgeted(options, &budget)
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::OutOfMemory);
+    }
+
+    #[tokio::test]
+    async fn test_count_line_words_budgeted_buffered_fail() {
+        let data = "a b\n".repeat(1000);
+        let srcs = stream::iter([("a", data.as_bytes())]);
+        let options = ProcessorOptions::default().with_buffer_capacity(2);
+        let budget = MemoryBudget::new(64, OnExceeded::Fail);
+        let result = srcs.count_line_words_budgeted(options, &budget);
+        let result = tokio::time::timeout(std::time::Duration::from_secs(3), result).await;
+        let error = result.expect("the reading stops").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::OutOfMemory);
+    }
+}
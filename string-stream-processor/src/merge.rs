@@ -0,0 +1,107 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    hash::{BuildHasher, Hash},
+};
+
+/// How the counts of an identifier of both earlier and current results are merged by
+/// [`merge_line_words`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergeMode {
+    /// Keep the current counts, e.g. of a file read again since it changed.
+    #[default]
+    Replace,
+    /// Append the current counts to the earlier ones, e.g. of the lines appended to a log since
+    /// the earlier run.
+    Append,
+}
+
+/// Merge the `earlier` results of a run, e.g. saved by a previous incremental scan and loaded
+/// with [`deserialize_line_words`], into the `current` ones: the identifiers of the earlier
+/// results only are added, the other ones are merged according to `mode`.
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use string_stream_processor::{merge_line_words, MergeMode};
+///
+/// let mut current = HashMap::from([("b.txt", vec![3])]);
+/// let earlier = [("a.txt", vec![2, 1]), ("b.txt", vec![1])];
+/// merge_line_words(&mut current, earlier, MergeMode::Replace);
+/// assert_eq!((&current["a.txt"][..], &current["b.txt"][..]), (&[2, 1][..], &[3][..]));
+/// ```
+pub fn merge_line_words<I: Hash + Eq, S: BuildHasher>(
+    current: &mut HashMap<I, Vec<usize>, S>,
+    earlier: impl IntoIterator<Item = (I, Vec<usize>)>,
+    mode: MergeMode,
+) {
+    for (id, mut counts) in earlier {
+        match (current.entry(id), mode) {
+            (Entry::Vacant(entry), _) => {
+                entry.insert(counts);
+            }
+            (Entry::Occupied(_), MergeMode::Replace) => {}
+            (Entry::Occupied(mut entry), MergeMode::Append) => {
+                counts.append(entry.get_mut());
+                *entry.get_mut() = counts;
+            }
+        }
+    }
+}
+
+/// Counts of an identifier in saved results, with its metadata or not.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum SavedCounts {
+    Counts(Vec<usize>),
+    Meta { counts: Vec<usize> },
+}
+
+/// Deserialize saved results, a map of the identifiers to their counts or to an object of their
+/// `counts` and metadata, which is ignored. To be used with `#[serde(deserialize_with)]` on the
+/// results of a document, in any format supported by `serde`.
+#[cfg(feature = "serde")]
+pub fn deserialize_line_words<'de, D, M>(deserializer: D) -> Result<M, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    M: FromIterator<(String, Vec<usize>)>,
+{
+    let saved: HashMap<String, SavedCounts> = serde::Deserialize::deserialize(deserializer)?;
+    let counts = saved.into_iter().map(|(id, counts)| match counts {
+        SavedCounts::Counts(counts) | SavedCounts::Meta { counts } => (id, counts),
+    });
+    Ok(counts.collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_line_words() {
+        let earlier = || [("a", vec![1, 2]), ("b", vec![3])];
+        let mut current = HashMap::from([("a", vec![4])]);
+        merge_line_words(&mut current, earlier(), MergeMode::Append);
+        assert_eq!(
+            (&current["a"][..], &current["b"][..]),
+            (&[1, 2, 4][..], &[3][..])
+        );
+
+        let mut current = HashMap::from([("a", vec![4])]);
+        merge_line_words(&mut current, earlier(), MergeMode::Replace);
+        assert_eq!(current["a"], [4]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize_line_words() {
+        let json = r#"{"a": [2, 1], "b": {"counts": [3], "bytes": 6}}"#;
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        let counts: std::collections::BTreeMap<_, _> =
+            deserialize_line_words(&mut deserializer).unwrap();
+        assert_eq!(
+            counts.into_iter().collect::<Vec<_>>(),
+            [("a".to_string(), vec![2, 1]), ("b".to_string(), vec![3])]
+        );
+    }
+}
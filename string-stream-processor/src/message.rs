@@ -0,0 +1,37 @@
+use std::fmt::Display;
+
+use crate::Tokenizer;
+
+/// Count the number of words for each line of a message payload.
+pub(crate) fn message_line_words<I: Clone + Display>(
+    id: I,
+    payload: &[u8],
+    tokenizer: Tokenizer,
+) -> Vec<(I, usize)> {
+    let payload = payload.strip_suffix(b"\n").unwrap_or(payload);
+    payload
+        .split(|&b| b == b'\n')
+        .filter_map(|line| match tokenizer.count_words(line) {
+            Ok(count) => Some((id.clone(), count)),
+            Err(e) => {
+                log::warn!("Could not read a line of {id}, {e}, dropping it.");
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_line_words() {
+        let counts = message_line_words("id", b"a b\r\nc\n", Tokenizer::Unicode);
+        assert_eq!(counts, [("id", 2), ("id", 1)]);
+        assert_eq!(
+            message_line_words("id", b"", Tokenizer::Unicode),
+            [("id", 0)]
+        );
+    }
+}
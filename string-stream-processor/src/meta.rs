@@ -0,0 +1,244 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Context, Poll},
+    time::SystemTime,
+};
+
+use pin_project_lite::pin_project;
+use tokio::io::{AsyncBufRead, AsyncRead, ReadBuf};
+
+/// Encoding of a source, detected from its byte order mark or its content.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// Only ASCII bytes, the default of an empty source.
+    #[default]
+    Ascii,
+    /// Valid UTF-8, with or without a byte order mark.
+    Utf8,
+    /// UTF-16 little endian, from its byte order mark.
+    Utf16Le,
+    /// UTF-16 big endian, from its byte order mark.
+    Utf16Be,
+    /// Neither ASCII nor valid UTF-8, e.g. a binary file or a legacy 8-bit encoding.
+    Unknown,
+}
+
+impl Encoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Ascii => "ascii",
+            Encoding::Utf8 => "utf-8",
+            Encoding::Utf16Le => "utf-16le",
+            Encoding::Utf16Be => "utf-16be",
+            Encoding::Unknown => "unknown",
+        }
+    }
+}
+
+/// Metadata of a source collected while it is read, and its size and modification time when
+/// known by its provider, see [`SourceProvider::count_line_words_with_meta`].
+///
+/// [`SourceProvider::count_line_words_with_meta`]: crate::SourceProvider::count_line_words_with_meta
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMeta {
+    /// Number of bytes read, which may differ from the size for the compressed files.
+    pub bytes: u64,
+    pub encoding: Encoding,
+    /// Whether the last line has no line ending, e.g. a file still being written.
+    pub truncated_line: bool,
+    /// Size of the file when it was opened.
+    pub size: Option<u64>,
+    /// Last modification time of the file when it was opened.
+    pub modified: Option<SystemTime>,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for SourceMeta {
+    /// Serialize as a map, with the encoding name and the modification time in seconds since the
+    /// Unix epoch, without the unknown size and modification time.
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let modified = self.modified.and_then(|modified| {
+            let since_epoch = modified.duration_since(SystemTime::UNIX_EPOCH).ok()?;
+            Some(since_epoch.as_secs_f64())
+        });
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("bytes", &self.bytes)?;
+        map.serialize_entry("encoding", self.encoding.name())?;
+        map.serialize_entry("truncated_line", &self.truncated_line)?;
+        if let Some(size) = self.size {
+            map.serialize_entry("size", &size)?;
+        }
+        if let Some(modified) = modified {
+            map.serialize_entry("modified", &modified)?;
+        }
+        map.end()
+    }
+}
+
+/// Incremental detection of the encoding of a source.
+#[derive(Debug, Default)]
+struct EncodingDetector {
+    encoding: Encoding,
+    /// Start of a UTF-8 sequence split between two reads.
+    partial: Vec<u8>,
+    started: bool,
+}
+
+impl EncodingDetector {
+    fn update(&mut self, data: &[u8]) {
+        if !self.started {
+            self.started = true;
+            if data.starts_with(&[0xEF, 0xBB, 0xBF]) {
+                self.encoding = Encoding::Utf8;
+            } else if data.starts_with(&[0xFF, 0xFE]) {
+                self.encoding = Encoding::Utf16Le;
+            } else if data.starts_with(&[0xFE, 0xFF]) {
+                self.encoding = Encoding::Utf16Be;
+            }
+        }
+        if !matches!(self.encoding, Encoding::Ascii | Encoding::Utf8) {
+            return;
+        }
+        if self.partial.is_empty() && data.is_ascii() {
+            return;
+        }
+        self.partial.extend_from_slice(data);
+        match std::str::from_utf8(&self.partial) {
+            Ok(_) => {
+                self.encoding = Encoding::Utf8;
+                self.partial.clear();
+            }
+            // The end of the data is the start of a sequence, completed by the next read.
+            Err(e) if e.error_len().is_none() => {
+                if e.valid_up_to() > 0 {
+                    self.encoding = Encoding::Utf8;
+                }
+                self.partial.drain(..e.valid_up_to());
+            }
+            Err(_) => {
+                self.encoding = Encoding::Unknown;
+                self.partial.clear();
+            }
+        }
+    }
+
+    /// Encoding of the whole source once its end is read.
+    fn finish(&mut self) -> Encoding {
+        if !self.partial.is_empty() {
+            self.encoding = Encoding::Unknown;
+            self.partial.clear();
+        }
+        self.encoding
+    }
+}
+
+/// Word counts of the lines of a source and its metadata.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceResult {
+    pub counts: Vec<usize>,
+    pub meta: SourceMeta,
+}
+
+pin_project! {
+    /// Reader collecting the [`SourceMeta`] of the source it reads.
+    #[derive(Debug)]
+    pub(crate) struct Metered<R> {
+        #[pin]
+        inner: R,
+        meta: Arc<Mutex<SourceMeta>>,
+        detector: EncodingDetector,
+        // Length of the start of the buffer of the inner reader already collected.
+        seen: usize,
+        last: Option<u8>,
+    }
+}
+
+impl<R> Metered<R> {
+    pub fn new(inner: R, meta: Arc<Mutex<SourceMeta>>) -> Self {
+        Self {
+            inner,
+            meta,
+            detector: EncodingDetector::default(),
+            seen: 0,
+            last: None,
+        }
+    }
+}
+
+impl<R: AsyncBufRead> AsyncBufRead for Metered<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.project();
+        let data = ready!(this.inner.poll_fill_buf(cx))?;
+        if data.is_empty() {
+            let mut meta = this.meta.lock().unwrap();
+            meta.encoding = this.detector.finish();
+            meta.truncated_line = this.last.is_some_and(|last| last != b'\n');
+        }
+        if let Some(new) = data.get(*this.seen..).filter(|new| !new.is_empty()) {
+            this.meta.lock().unwrap().bytes += new.len() as u64;
+            this.detector.update(new);
+            *this.last = new.last().copied();
+            *this.seen = data.len();
+        }
+        Poll::Ready(Ok(data))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.project();
+        *this.seen = this.seen.saturating_sub(amt);
+        this.inner.consume(amt);
+    }
+}
+
+impl<R: AsyncBufRead> AsyncRead for Metered<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let data = ready!(self.as_mut().poll_fill_buf(cx))?;
+        let len = data.len().min(buf.remaining());
+        buf.put_slice(&data[..len]);
+        self.consume(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, BufReader};
+
+    use super::*;
+
+    async fn meta(data: &'static [u8]) -> SourceMeta {
+        let meta = Arc::default();
+        // A small buffer splits the UTF-8 sequences between reads.
+        let mut rd = Metered::new(BufReader::with_capacity(3, data), Arc::clone(&meta));
+        rd.read_to_end(&mut Vec::new()).await.unwrap();
+        drop(rd);
+        Arc::into_inner(meta).unwrap().into_inner().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_metered() {
+        let ascii = meta(b"a b\nc\n").await;
+        assert_eq!((ascii.bytes, ascii.encoding), (6, Encoding::Ascii));
+        assert!(!ascii.truncated_line);
+        let utf8 = meta("été\n€ uno".as_bytes()).await;
+        assert_eq!((utf8.encoding, utf8.truncated_line), (Encoding::Utf8, true));
+        assert_eq!(meta(b"\xEF\xBB\xBFa\n").await.encoding, Encoding::Utf8);
+        assert_eq!(meta(b"\xFF\xFEa\0").await.encoding, Encoding::Utf16Le);
+        assert_eq!(meta(b"\xFE\xFF\0a").await.encoding, Encoding::Utf16Be);
+        assert_eq!(meta(b"caf\xe9\n").await.encoding, Encoding::Unknown);
+        assert_eq!(meta(b"ab\xe2\x82").await.encoding, Encoding::Unknown);
+        let empty = meta(b"").await;
+        assert_eq!(
+            (empty.encoding, empty.truncated_line),
+            (Encoding::Ascii, false)
+        );
+    }
+}
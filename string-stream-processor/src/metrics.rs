@@ -0,0 +1,312 @@
+use std::{
+    collections::HashMap,
+    io,
+    sync::{Arc, Mutex},
+};
+
+use futures_util::{Stream, StreamExt};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+use crate::{MemoryUsage, ProcessorOptions, Tokenizer};
+
+/// Metric computed over the lines of each source by a [`MetricSet`].
+#[derive(Debug, Clone)]
+pub enum Metric {
+    /// Number of lines.
+    Lines,
+    /// Number of words split with the tokenizer.
+    Words(Tokenizer),
+    /// Number of bytes, line endings included.
+    Bytes,
+    /// Number of non-overlapping matches of the pattern in the lines, without their line endings.
+    #[cfg(feature = "regex")]
+    Matches(regex::bytes::Regex),
+    /// Number of occurrences of each word split with the tokenizer, lines must be valid UTF-8.
+    Frequency(Tokenizer),
+}
+
+/// Value of a [`Metric`] for a source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetricValue {
+    Count(u64),
+    Frequency(HashMap<String, u64>),
+}
+
+impl Metric {
+    fn init(&self) -> MetricValue {
+        match self {
+            Metric::Frequency(_) => MetricValue::Frequency(HashMap::new()),
+            _ => MetricValue::Count(0),
+        }
+    }
+
+    /// Whether the metric reads the lines as UTF-8.
+    fn is_utf8(&self) -> bool {
+        match self {
+            Metric::Words(tokenizer) => *tokenizer != Tokenizer::Ascii,
+            Metric::Frequency(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Add a line read with its line ending, if any, to the value of the metric, the new words
+    /// of the frequencies accounted in `memory`.
+    fn push(
+        &self,
+        value: &mut MetricValue,
+        line: &[u8],
+        memory: Option<&MemoryUsage>,
+    ) -> io::Result<()> {
+        match (self, value) {
+            (Metric::Lines, MetricValue::Count(count)) => *count += 1,
+            (Metric::Words(tokenizer), MetricValue::Count(count)) => {
+                *count += tokenizer.count_words(line)? as u64;
+            }
+            (Metric::Bytes, MetricValue::Count(count)) => *count += line.len() as u64,
+            #[cfg(feature = "regex")]
+            (Metric::Matches(pattern), MetricValue::Count(count)) => {
+                let line = line.strip_suffix(b"\n").unwrap_or(line);
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                *count += pattern.find_iter(line).count() as u64;
+            }
+            (Metric::Frequency(tokenizer), MetricValue::Frequency(counts)) => {
+                let line = std::str::from_utf8(line)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                for word in tokenizer.split_words(line) {
+                    match counts.get_mut(word) {
+                        Some(count) => *count += 1,
+                        None => {
+                            if let Some(memory) = memory {
+                                memory.add(MemoryUsage::frequency_entry(word));
+                            }
+                            counts.insert(word.to_owned(), 1);
+                        }
+                    }
+                }
+            }
+            _ => unreachable!("metric value initialized by another metric"),
+        }
+        Ok(())
+    }
+}
+
+/// Set of named metrics computed during a single read of each source, instead of reading the
+/// sources once per metric.
+///
+/// ```
+/// use futures_util::stream;
+/// use string_stream_processor::{Metric, MetricSet, ProcessorOptions, Tokenizer};
+///
+/// # futures_executor::block_on(async {
+/// let metrics = MetricSet::new()
+///     .with("words", Metric::Words(Tokenizer::Unicode))
+///     .with("bytes", Metric::Bytes);
+/// let srcs = [("a", "hello world\nbye\n".as_bytes())];
+/// let result = metrics
+///     .compute(stream::iter(srcs), ProcessorOptions::default())
+///     .await;
+/// let a = result["a"].as_ref().unwrap();
+/// assert_eq!(a.count("words"), Some(3));
+/// assert_eq!(a.count("bytes"), Some(16));
+/// # });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MetricSet {
+    names: Vec<String>,
+    metrics: Vec<Metric>,
+    memory: Option<Arc<MemoryUsage>>,
+}
+
+impl MetricSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a metric under the given name, replacing the metric of the same name if any.
+    pub fn with(mut self, name: impl Into<String>, metric: Metric) -> Self {
+        let name = name.into();
+        match self.names.iter().position(|n| *n == name) {
+            Some(i) => self.metrics[i] = metric,
+            None => {
+                self.names.push(name);
+                self.metrics.push(metric);
+            }
+        }
+        self
+    }
+
+    /// Account the memory of the frequency maps in `usage`, e.g. to monitor the runs over many
+    /// different words.
+    pub fn with_memory(mut self, usage: Arc<MemoryUsage>) -> Self {
+        self.memory = Some(usage);
+        self
+    }
+
+    /// Names of the metrics, in the order they were added.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(String::as_str)
+    }
+
+    /// Compute all the metrics of each source, reading at most `options.max_concurrency` sources
+    /// at a time. A read error or an invalid UTF-8 line ends the source with this error instead of
+    /// its values.
+    pub async fn compute<'a, R: AsyncBufRead + Unpin>(
+        &self,
+        rds: impl Stream<Item = (&'a str, R)>,
+        options: ProcessorOptions,
+    ) -> HashMap<&'a str, io::Result<MetricValues>> {
+        let names: Arc<[String]> = self.names.clone().into();
+        let merged = Mutex::new(HashMap::new());
+        rds.for_each_concurrent(options.max_concurrency, |(id, rd)| {
+            let (merged, names) = (&merged, names.clone());
+            async move {
+                let values = self.compute_source(rd).await;
+                let values = values.map(|values| MetricValues { names, values });
+                merged.lock().unwrap().insert(id, values);
+            }
+        })
+        .await;
+        merged.into_inner().unwrap()
+    }
+
+    async fn compute_source(
+        &self,
+        mut rd: impl AsyncBufRead + Unpin,
+    ) -> io::Result<Vec<MetricValue>> {
+        let mut values: Vec<_> = self.metrics.iter().map(Metric::init).collect();
+        // Checked before computing any metric, so that an invalid line counts for none of them.
+        let utf8 = self.metrics.iter().any(Metric::is_utf8);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let pushed = match rd.read_until(b'\n', &mut line).await {
+                Ok(0) => break,
+                Ok(_) if utf8 && std::str::from_utf8(&line).is_err() => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "stream did not contain valid UTF-8",
+                )),
+                Ok(_) => self
+                    .metrics
+                    .iter()
+                    .zip(&mut values)
+                    .try_for_each(|(metric, value)| {
+                        metric.push(value, &line, self.memory.as_deref())
+                    }),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = pushed {
+                self.release(&values);
+                return Err(e);
+            }
+        }
+        Ok(values)
+    }
+
+    /// Release the memory of the frequencies of the values of a source left out.
+    fn release(&self, values: &[MetricValue]) {
+        let Some(memory) = &self.memory else {
+            return;
+        };
+        for value in values {
+            if let MetricValue::Frequency(counts) = value {
+                let entries = counts.keys().map(|word| MemoryUsage::frequency_entry(word));
+                memory.sub(entries.sum());
+            }
+        }
+    }
+}
+
+/// Values of the metrics of a [`MetricSet`] for a source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetricValues {
+    names: Arc<[String]>,
+    values: Vec<MetricValue>,
+}
+
+impl MetricValues {
+    /// Value of the metric with the given name.
+    pub fn get(&self, name: &str) -> Option<&MetricValue> {
+        let i = self.names.iter().position(|n| n == name)?;
+        self.values.get(i)
+    }
+
+    /// Value of a counting metric, `None` for the frequencies.
+    pub fn count(&self, name: &str) -> Option<u64> {
+        match self.get(name)? {
+            MetricValue::Count(count) => Some(*count),
+            MetricValue::Frequency(_) => None,
+        }
+    }
+
+    /// Occurrences of each word of a [`Metric::Frequency`].
+    pub fn frequency(&self, name: &str) -> Option<&HashMap<String, u64>> {
+        match self.get(name)? {
+            MetricValue::Frequency(counts) => Some(counts),
+            MetricValue::Count(_) => None,
+        }
+    }
+
+    /// Names and values of the metrics, in the order of the [`MetricSet`].
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &MetricValue)> {
+        self.names.iter().map(String::as_str).zip(&self.values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::stream;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_metric_set() {
+        let metrics = MetricSet::new()
+            .with("lines", Metric::Lines)
+            .with("words", Metric::Words(Tokenizer::Ascii))
+            .with("bytes", Metric::Bytes)
+            .with("freq", Metric::Frequency(Tokenizer::Unicode))
+            .with("words", Metric::Words(Tokenizer::Unicode))
+            .with_memory(Arc::default());
+        assert_eq!(
+            metrics.names().collect::<Vec<_>>(),
+            ["lines", "words", "bytes", "freq"]
+        );
+        let srcs = [
+            ("a", "a b\u{a0}a\r\n\nb".as_bytes()),
+            ("b", "".as_bytes()),
+            ("c", b"x\n\xff\ny\n".as_slice()),
+        ];
+        let options = ProcessorOptions::default().with_max_concurrency(2);
+        let result = metrics.compute(stream::iter(srcs), options).await;
+        let a = result["a"].as_ref().unwrap();
+        assert_eq!(a.count("lines"), Some(3));
+        assert_eq!(a.count("words"), Some(4));
+        assert_eq!(a.count("bytes"), Some(10));
+        let freq = a.frequency("freq").unwrap();
+        assert_eq!((freq["a"], freq["b"]), (2, 2));
+        assert_eq!(a.count("freq"), None);
+        assert_eq!(a.get("unknown"), None);
+        let b = result["b"].as_ref().unwrap();
+        assert_eq!(b.count("lines"), Some(0));
+        let names: Vec<_> = b.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["lines", "words", "bytes", "freq"]);
+        // The invalid UTF-8 line ends the source with an error.
+        let c = result["c"].as_ref().unwrap_err();
+        assert_eq!(c.kind(), io::ErrorKind::InvalidData);
+        // The words a and b, the ones of the source in error are released.
+        let memory = metrics.memory.as_ref().unwrap();
+        assert_eq!(memory.used(), 2 * MemoryUsage::frequency_entry("a"));
+    }
+
+    #[cfg(feature = "regex")]
+    #[tokio::test]
+    async fn test_metric_set_matches() {
+        let pattern = regex::bytes::Regex::new(r"\d+$").unwrap();
+        let metrics = MetricSet::new().with("ends", Metric::Matches(pattern));
+        let srcs = [("a", "a 1\r\n22 b\n333\n".as_bytes())];
+        let result = metrics
+            .compute(stream::iter(srcs), ProcessorOptions::default())
+            .await;
+        assert_eq!(result["a"].as_ref().unwrap().count("ends"), Some(2));
+    }
+}
@@ -0,0 +1,86 @@
+use std::{
+    fs::File,
+    io,
+    path::Path,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use memmap2::Mmap;
+use tokio::io::{AsyncBufRead, AsyncRead, ReadBuf};
+
+/// Async reader over a memory-mapped local file.
+///
+/// The whole file is exposed as a single buffer, so no read syscall is issued and the
+/// [`Tokenizer::Ascii`](crate::Tokenizer::Ascii) path scans the mapped pages directly.
+/// Page faults block the current thread, this is best used with a warm page cache.
+pub struct MmapReader {
+    map: Mmap,
+    pos: usize,
+}
+
+impl MmapReader {
+    /// Map the file at the given path.
+    ///
+    /// # Safety
+    /// The file must not be modified nor truncated while the reader is alive,
+    /// see [`Mmap::map`].
+    pub unsafe fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let map = Mmap::map(&file)?;
+        Ok(Self { map, pos: 0 })
+    }
+
+    fn remaining(&self) -> &[u8] {
+        &self.map[self.pos..]
+    }
+}
+
+impl AsyncRead for MmapReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let len = self.remaining().len().min(buf.remaining());
+        buf.put_slice(&self.remaining()[..len]);
+        self.pos += len;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncBufRead for MmapReader {
+    fn poll_fill_buf(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        Poll::Ready(Ok(self.get_mut().remaining()))
+    }
+
+    fn consume(mut self: Pin<&mut Self>, amt: usize) {
+        self.pos = (self.pos + amt).min(self.map.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::stream;
+
+    use super::*;
+    use crate::{ProcessorOptions, StringMultiStreamExt, Tokenizer};
+
+    #[tokio::test]
+    async fn test_mmap_reader() {
+        let path = std::env::temp_dir().join("ssp_test_mmap_reader.txt");
+        tokio::fs::write(&path, "Hello world\n\nfoo bar baz")
+            .await
+            .unwrap();
+        for tokenizer in [Tokenizer::Unicode, Tokenizer::Ascii] {
+            // Safety: the file is not modified by the test.
+            let rd = unsafe { MmapReader::open(&path) }.unwrap();
+            let options = ProcessorOptions::default().with_tokenizer(tokenizer);
+            let result = stream::iter([("a", rd)])
+                .count_line_words_concurrent_with(options)
+                .await;
+            assert_eq!(result["a"], [2, 0, 3]);
+        }
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+}
@@ -0,0 +1,100 @@
+use std::io;
+
+use futures_util::{stream, Stream, StreamExt};
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+
+use crate::{message::message_line_words, ProcessorOptions};
+
+/// Capacity of the request channel of the MQTT client, only used for the subscriptions.
+const REQUESTS_CAPACITY: usize = 16;
+
+/// Connect to an MQTT broker, subscribe to the topic filters and count the number of words for
+/// each line of the published messages, as soon as they are received. Each message is
+/// identified by the first filter matching its topic, so a wildcard filter (e.g. `sensors/#`)
+/// counts the messages of all its matching topics under the same identifier.
+///
+/// Each payload is a line, and is split again on its line endings if it contains several lines.
+/// The subscriptions use the QoS 1 (at least once). The stream ends when the connection fails.
+pub async fn subscribe_mqtt_line_words<'a>(
+    mqtt_options: MqttOptions,
+    filters: &'a [String],
+    options: ProcessorOptions,
+) -> io::Result<impl Stream<Item = (&'a str, usize)> + 'a> {
+    let (client, mut events) = AsyncClient::new(mqtt_options, REQUESTS_CAPACITY);
+    // Wait for the connection, so that an unreachable broker is an error.
+    loop {
+        match events.poll().await.map_err(io::Error::other)? {
+            Event::Incoming(Packet::ConnAck(_)) => break,
+            _ => continue,
+        }
+    }
+    for filter in filters {
+        client
+            .subscribe(filter, QoS::AtLeastOnce)
+            .await
+            .map_err(io::Error::other)?;
+    }
+    let messages = stream::unfold((client, events), |(client, mut events)| async move {
+        loop {
+            match events.poll().await {
+                Ok(Event::Incoming(Packet::Publish(publish))) => {
+                    return Some((publish, (client, events)));
+                }
+                Ok(_) => continue,
+                Err(e) => {
+                    log::warn!("MQTT connection failed, {e}, ending it.");
+                    return None;
+                }
+            }
+        }
+    });
+    Ok(messages
+        .filter_map(move |publish| async move {
+            let filter = matching_filter(filters, &publish.topic);
+            if filter.is_none() {
+                log::debug!(
+                    "Skipping a message of {}, no filter matches it.",
+                    publish.topic
+                );
+            }
+            Some((filter?, publish.payload))
+        })
+        .flat_map(move |(filter, payload)| {
+            stream::iter(message_line_words(filter, &payload, options.tokenizer))
+        }))
+}
+
+/// First filter matching a topic, used as the identifier of its messages.
+fn matching_filter<'a>(filters: &'a [String], topic: &str) -> Option<&'a str> {
+    filters
+        .iter()
+        .find(|filter| rumqttc::matches(topic, filter))
+        .map(String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matching_filter() {
+        let filters = ["sensors/+/temp".to_string(), "sensors/#".to_string()];
+        assert_eq!(
+            matching_filter(&filters, "sensors/a/temp"),
+            Some("sensors/+/temp")
+        );
+        assert_eq!(
+            matching_filter(&filters, "sensors/a/hum"),
+            Some("sensors/#")
+        );
+        assert_eq!(matching_filter(&filters, "logs/a"), None);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_mqtt_line_words() {
+        let filters = ["sensors/#".to_string()];
+        let mqtt_options = MqttOptions::new("test", "127.0.0.1", 1);
+        let counts = subscribe_mqtt_line_words(mqtt_options, &filters, Default::default()).await;
+        assert!(counts.is_err());
+    }
+}
@@ -0,0 +1,50 @@
+use std::io;
+
+use async_nats::ToServerAddrs;
+use futures_util::{stream, Stream, StreamExt};
+
+use crate::{message::message_line_words, ProcessorOptions};
+
+/// Connect to a NATS server, subscribe to the subjects and count the number of words for each
+/// line of the published messages, as soon as they are received. Each message is identified by
+/// the subject it was received with, so a wildcard subject (e.g. `logs.>`) counts the messages
+/// of all its matching subjects under the same identifier.
+///
+/// Each payload is a line, and is split again on its line endings if it contains several lines.
+/// The stream ends when the connection is closed.
+pub async fn subscribe_nats_line_words<'a>(
+    addrs: impl ToServerAddrs,
+    subjects: &'a [String],
+    options: ProcessorOptions,
+) -> io::Result<impl Stream<Item = (&'a str, usize)> + 'a> {
+    let client = async_nats::connect(addrs).await.map_err(io::Error::other)?;
+    let mut subscribers = Vec::with_capacity(subjects.len());
+    for subject in subjects {
+        let subscriber = client
+            .subscribe(subject.clone())
+            .await
+            .map_err(io::Error::other)?;
+        subscribers.push(subscriber.map(move |message| (subject.as_str(), message)));
+    }
+    let messages = stream::select_all(subscribers);
+    Ok(messages.flat_map(move |(subject, message)| {
+        stream::iter(message_line_words(
+            subject,
+            &message.payload,
+            options.tokenizer,
+        ))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_subscribe_nats_line_words() {
+        let subjects = ["logs.>".to_string()];
+        let counts =
+            subscribe_nats_line_words("nats://127.0.0.1:1", &subjects, Default::default()).await;
+        assert!(counts.is_err());
+    }
+}
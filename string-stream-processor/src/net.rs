@@ -0,0 +1,206 @@
+use std::{fmt::Display, future::Future, io, net::SocketAddr, pin::Pin};
+
+use futures_util::{stream, Stream, StreamExt};
+use tokio::{
+    io::AsyncRead,
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+};
+
+use crate::{count_source_line_words, ProcessorOptions, RetryPolicy};
+
+/// Accept the connections of the listener and count the number of words for each line they
+/// send, as soon as it is received. Each connection is identified by the address of its peer.
+///
+/// The stream never ends, up to [`max_concurrency`](ProcessorOptions::max_concurrency)
+/// connections are read at a time and the next ones wait in the backlog of the listener.
+pub fn count_tcp_line_words(
+    listener: TcpListener,
+    options: ProcessorOptions,
+) -> impl Stream<Item = (SocketAddr, usize)> {
+    let connections = stream::unfold(listener, |listener| async move {
+        let connection = listener
+            .accept()
+            .await
+            .inspect_err(|e| log::warn!("Could not accept a connection, {e}, skipping it."));
+        Some((connection, listener))
+    });
+    connections
+        .filter_map(|connection| async move { connection.ok() })
+        .flat_map_unordered(options.max_concurrency, move |(socket, peer)| {
+            Box::pin(count_source_line_words(
+                (peer, options.buf_reader(socket)),
+                options,
+            ))
+        })
+}
+
+/// Connect to a TCP server and count the number of words for each line it sends, as soon as it
+/// is received, identified by the address of the server.
+pub async fn connect_tcp_line_words(
+    addr: impl ToSocketAddrs,
+    options: ProcessorOptions,
+) -> io::Result<impl Stream<Item = (SocketAddr, usize)>> {
+    let socket = TcpStream::connect(addr).await?;
+    let peer = socket.peer_addr()?;
+    Ok(count_source_line_words(
+        (peer, options.buf_reader(socket)),
+        options,
+    ))
+}
+
+/// Same as [`connect_tcp_line_words`] over a TLS connection, the certificate of the server
+/// being verified against `server_name`.
+#[cfg(feature = "tls")]
+pub async fn connect_tls_line_words(
+    addr: impl ToSocketAddrs,
+    server_name: &str,
+    tls: &crate::TlsOptions,
+    options: ProcessorOptions,
+) -> io::Result<impl Stream<Item = (SocketAddr, usize)>> {
+    let socket = TcpStream::connect(addr).await?;
+    let peer = socket.peer_addr()?;
+    let socket = tls.connect(server_name, socket).await?;
+    Ok(count_source_line_words(
+        (peer, options.buf_reader(socket)),
+        options,
+    ))
+}
+
+/// Same as [`connect_tcp_line_words`] but the server is connected again when the connection is
+/// closed or fails, see [`connect_line_words_with_reconnect`].
+pub fn connect_tcp_line_words_with_reconnect(
+    addr: SocketAddr,
+    options: ProcessorOptions,
+) -> impl Stream<Item = (SocketAddr, usize)> {
+    connect_line_words_with_reconnect(addr, move || TcpStream::connect(addr), options)
+}
+
+/// Count the number of words for each line of a long-lived connection opened by `connect`,
+/// identified by `id`. When the connection is closed or fails, a new one is opened and counting
+/// resumes under the same identifier, instead of ending the stream.
+///
+/// The connection attempts follow the [`retry`](ProcessorOptions::retry) policy of the options:
+/// the stream ends once `max_attempts` consecutive attempts failed or were closed without
+/// sending a line. A line received resets the attempt counter.
+pub fn connect_line_words_with_reconnect<'a, I, C, Fut, S>(
+    id: I,
+    connect: C,
+    options: ProcessorOptions,
+) -> impl Stream<Item = (I, usize)> + 'a
+where
+    I: Copy + Display + 'a,
+    C: FnMut() -> Fut + 'a,
+    Fut: Future<Output = io::Result<S>> + 'a,
+    S: AsyncRead + 'a,
+{
+    type Counts<'a, I> = Pin<Box<dyn Stream<Item = (I, usize)> + 'a>>;
+    let policy = options.retry;
+    // The read errors end the connection right away, it is retried by connecting again.
+    let options = options.with_retry(RetryPolicy::NONE);
+    let state = (connect, 0, None::<Counts<'a, I>>);
+    stream::unfold(
+        state,
+        move |(mut connect, mut attempt, mut counts)| async move {
+            loop {
+                if let Some(current) = &mut counts {
+                    if let Some(count) = current.next().await {
+                        return Some((count, (connect, 0, counts)));
+                    }
+                    counts = None;
+                    log::info!("Connection to {id} closed.");
+                } else {
+                    match connect().await {
+                        Ok(socket) => {
+                            let rd = Box::pin(options.buf_reader(socket));
+                            counts = Some(Box::pin(count_source_line_words((id, rd), options)));
+                            continue;
+                        }
+                        Err(e) => log::debug!("Could not connect to {id}, {e}."),
+                    }
+                }
+                if attempt >= policy.max_attempts {
+                    log::warn!("Could not reconnect to {id}, dropping it.");
+                    return None;
+                }
+                attempt += 1;
+                log::debug!("Reconnecting to {id}, attempt {attempt}.");
+                tokio::time::sleep(policy.delay(attempt)).await;
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tcp_line_words() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut counts = Box::pin(count_tcp_line_words(listener, Default::default()));
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(b"a b\n").await.unwrap();
+        let peer = client.local_addr().unwrap();
+        // Lines are counted as they are received.
+        assert_eq!(counts.next().await, Some((peer, 2)));
+        client.write_all(b"c d e").await.unwrap();
+        drop(client);
+        assert_eq!(counts.next().await, Some((peer, 3)));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            socket.write_all(b"f\ng h\n").await.unwrap();
+        });
+        let counts = connect_tcp_line_words(addr, Default::default())
+            .await
+            .unwrap();
+        assert_eq!(counts.collect::<Vec<_>>().await, [(addr, 1), (addr, 2)]);
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn test_tls_line_words() {
+        let pki = crate::tls::tests::TestPki::new();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let acceptor = pki.acceptor();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut tls = acceptor.accept(socket).await.unwrap();
+            tls.write_all(b"a b\nc").await.unwrap();
+            tls.shutdown().await.unwrap();
+        });
+        let tls = pki.client_options();
+        let counts = connect_tls_line_words(addr, "localhost", &tls, Default::default())
+            .await
+            .unwrap();
+        assert_eq!(counts.collect::<Vec<_>>().await, [(addr, 2), (addr, 1)]);
+    }
+
+    #[tokio::test]
+    async fn test_tcp_line_words_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        // The server closes each connection after a line.
+        tokio::spawn(async move {
+            for line in [&b"a b\n"[..], b"c\n", b"d e f\n"] {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                socket.write_all(line).await.unwrap();
+            }
+        });
+        let options = ProcessorOptions::default()
+            .with_retry(RetryPolicy::new(1, std::time::Duration::from_millis(1)));
+        let counts = connect_tcp_line_words_with_reconnect(addr, options);
+        // The listener is dropped after the last connection, the next attempt fails.
+        assert_eq!(
+            counts.collect::<Vec<_>>().await,
+            [(addr, 2), (addr, 1), (addr, 3)]
+        );
+    }
+}
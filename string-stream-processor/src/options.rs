@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, BufReader};
+
+use crate::{RetryPolicy, Tokenizer};
+
+/// Default capacity of the read buffers, same as [`BufReader::new`].
+pub const DEFAULT_READ_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Options for the concurrent processing of multiple sources.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessorOptions {
+    /// Retry policy applied to each source on transient read errors.
+    pub retry: RetryPolicy,
+    /// Capacity (in lines) of the buffer between the read and aggregation stages.
+    ///
+    /// When the buffer is full, readers are not polled anymore until the aggregation stage
+    /// catches up, so at most `buffer_capacity` counts are held in memory between the two stages.
+    /// With `None`, the aggregation stage directly pulls from the readers.
+    pub buffer_capacity: Option<usize>,
+    /// Capacity (in bytes) of the read buffers created with [`ProcessorOptions::buf_reader`].
+    ///
+    /// Large buffers (e.g. 1 MiB) reduce the number of reads on large sequential files.
+    pub read_buffer_size: usize,
+    /// Rules used to split lines into words.
+    pub tokenizer: Tokenizer,
+    /// Analyze the lines by batches of this size on the blocking thread pool.
+    ///
+    /// Reading stays asynchronous, this is useful when the per-line analysis is CPU-heavy
+    /// and would otherwise starve the async reactor. It must be used within a Tokio runtime.
+    pub blocking_batch: Option<usize>,
+    /// Maximum number of sources read at the same time, `None` for no limit.
+    ///
+    /// The next sources are pulled from the input stream as the current ones end.
+    pub max_concurrency: Option<usize>,
+    /// Poll the local files again after this delay when they reach their end, instead of
+    /// ending them, to count the lines appended to growing files (see [`crate::Follow`]).
+    ///
+    /// The followed sources never end, their counts are only available as a stream.
+    pub follow: Option<Duration>,
+    /// Maximum number of consecutive lines taken from a source before yielding to the other
+    /// sources, so that a source always ready (e.g. a large local file) does not starve the
+    /// others, even when merged by a biased poller in a custom pipeline (see
+    /// [`count_source_line_words`](crate::count_source_line_words)). `None` for no limit.
+    pub fairness: Option<usize>,
+}
+
+impl ProcessorOptions {
+    /// Set the retry policy applied to each source.
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Set the capacity of the buffer between the read and aggregation stages.
+    pub fn with_buffer_capacity(mut self, capacity: usize) -> Self {
+        self.buffer_capacity = Some(capacity);
+        self
+    }
+
+    /// Set the capacity of the read buffers created with [`ProcessorOptions::buf_reader`], at
+    /// least 1 byte: an empty buffer ends the reads at once.
+    pub fn with_read_buffer_size(mut self, size: usize) -> Self {
+        self.read_buffer_size = size.max(1);
+        self
+    }
+
+    /// Set the rules used to split lines into words.
+    pub fn with_tokenizer(mut self, tokenizer: Tokenizer) -> Self {
+        self.tokenizer = tokenizer;
+        self
+    }
+
+    /// Analyze the lines by batches of `size` lines on the blocking thread pool.
+    ///
+    /// Without the `runtime` feature, the batches are analyzed in place.
+    pub fn with_blocking_batch(mut self, size: usize) -> Self {
+        self.blocking_batch = Some(size);
+        self
+    }
+
+    /// Set the maximum number of sources read at the same time.
+    pub fn with_max_concurrency(mut self, max: usize) -> Self {
+        self.max_concurrency = Some(max.max(1));
+        self
+    }
+
+    /// Follow the local files as they grow, polling them every `interval` at their end.
+    pub fn with_follow(mut self, interval: Duration) -> Self {
+        self.follow = Some(interval);
+        self
+    }
+
+    /// Yield to the other sources after `burst` consecutive lines of a source.
+    pub fn with_fairness(mut self, burst: usize) -> Self {
+        self.fairness = Some(burst.max(1));
+        self
+    }
+
+    /// Wrap a reader in a [`BufReader`] with the configured capacity.
+    pub fn buf_reader<R: AsyncRead>(&self, rd: R) -> BufReader<R> {
+        BufReader::with_capacity(self.read_buffer_size.max(1), rd)
+    }
+}
+
+impl Default for ProcessorOptions {
+    /// No retry, no intermediate buffering, 8 KiB read buffers, Unicode word splitting, no
+    /// concurrency limit, no follow mode and no fairness limit.
+    fn default() -> Self {
+        Self {
+            retry: RetryPolicy::NONE,
+            buffer_capacity: None,
+            read_buffer_size: DEFAULT_READ_BUFFER_SIZE,
+            tokenizer: Tokenizer::Unicode,
+            blocking_batch: None,
+            max_concurrency: None,
+            follow: None,
+            fairness: None,
+        }
+    }
+}
+
+impl From<RetryPolicy> for ProcessorOptions {
+    fn from(retry: RetryPolicy) -> Self {
+        Self::default().with_retry(retry)
+    }
+}
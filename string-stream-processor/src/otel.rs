@@ -0,0 +1,227 @@
+use std::{
+    fmt::Display,
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Instant,
+};
+
+use futures_util::io::{AsyncBufRead, AsyncRead};
+use tracing::{field, Span};
+
+/// Reader counting the bytes consumed from a source.
+pub(crate) struct Metered<R> {
+    pub inner: R,
+    pub bytes: u64,
+}
+
+impl<R> From<R> for Metered<R> {
+    fn from(inner: R) -> Self {
+        Self { inner, bytes: 0 }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Metered<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let read = ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        this.bytes += read as u64;
+        Poll::Ready(Ok(read))
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncBufRead for Metered<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        Pin::new(&mut self.get_mut().inner).poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        this.bytes += amt as u64;
+        Pin::new(&mut this.inner).consume(amt);
+    }
+}
+
+/// Span of the read of a source, from the start of its counts until they are dropped.
+pub(crate) struct SourceSpan {
+    span: Span,
+    start: Instant,
+    lines: u64,
+    /// Bytes of the readers replaced after an error.
+    bytes: u64,
+}
+
+impl SourceSpan {
+    pub fn new(id: impl Display) -> Self {
+        let span = tracing::info_span!(
+            "source",
+            source.id = %id,
+            source.lines = field::Empty,
+            source.bytes = field::Empty,
+            error.message = field::Empty,
+            otel.status_code = field::Empty,
+        );
+        Self {
+            span,
+            start: Instant::now(),
+            lines: 0,
+            bytes: 0,
+        }
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub fn line(&mut self) {
+        self.lines += 1;
+    }
+
+    /// Add the bytes of a reader replaced by a new one.
+    pub fn replaced(&mut self, bytes: u64) {
+        self.bytes += bytes;
+    }
+
+    /// Record the error ending the source.
+    pub fn error(&self, error: &io::Error) {
+        self.span.record("error.message", field::display(error));
+        self.span.record("otel.status_code", "ERROR");
+        tracing::info!(parent: &self.span, monotonic_counter.ssp.source.errors = 1_u64);
+    }
+
+    /// End the span with the bytes of the current reader.
+    pub fn finish(&mut self, bytes: u64) {
+        let bytes = self.bytes + bytes;
+        self.span.record("source.lines", self.lines);
+        self.span.record("source.bytes", bytes);
+        tracing::info!(
+            parent: &self.span,
+            histogram.ssp.source.duration = self.start.elapsed().as_secs_f64() * 1e3,
+            monotonic_counter.ssp.source.bytes = bytes,
+        );
+    }
+}
+
+/// Span of the analysis of a batch of lines on the blocking thread pool, a child of the span
+/// of its source.
+pub(crate) struct BatchSpan {
+    span: Span,
+    start: Instant,
+    bytes: usize,
+}
+
+impl BatchSpan {
+    pub fn new(source: &Span, lines: usize, bytes: usize) -> Self {
+        let span = tracing::info_span!(
+            parent: source,
+            "batch",
+            batch.lines = lines,
+            batch.bytes = bytes,
+            otel.status_code = field::Empty,
+        );
+        Self {
+            span,
+            start: Instant::now(),
+            bytes,
+        }
+    }
+
+    pub fn finish(self, failed: bool) {
+        if failed {
+            self.span.record("otel.status_code", "ERROR");
+        }
+        tracing::info!(
+            parent: &self.span,
+            histogram.ssp.batch.duration = self.start.elapsed().as_secs_f64() * 1e3,
+            monotonic_counter.ssp.batch.bytes = self.bytes as u64,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    };
+
+    use futures_util::stream;
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+        Event, Metadata, Subscriber,
+    };
+
+    use crate::{ProcessorOptions, StringMultiStreamExt};
+
+    /// Subscriber recording the names of the spans and the numeric fields of the events.
+    #[derive(Default)]
+    struct Recorder {
+        next_id: AtomicU64,
+        spans: Mutex<Vec<&'static str>>,
+        values: Mutex<Vec<(&'static str, f64)>>,
+    }
+
+    impl Visit for &Recorder {
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            self.record_f64(field, value as f64);
+        }
+
+        fn record_f64(&mut self, field: &Field, value: f64) {
+            self.values.lock().unwrap().push((field.name(), value));
+        }
+
+        fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+    }
+
+    impl Subscriber for &'static Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            self.spans.lock().unwrap().push(span.metadata().name());
+            Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            event.record(&mut &**self);
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[tokio::test]
+    async fn test_otel_spans() {
+        let recorder: &'static Recorder = Box::leak(Box::default());
+        let _default = tracing::subscriber::set_default(recorder);
+        let srcs = [("a", "a b\nc\n".as_bytes()), ("b", "d\n".as_bytes())];
+        let options = ProcessorOptions::default().with_blocking_batch(8);
+        let result = stream::iter(srcs)
+            .count_line_words_concurrent_with(options)
+            .await;
+        assert_eq!(result["a"], [2, 1]);
+
+        let mut spans = recorder.spans.lock().unwrap().clone();
+        spans.sort_unstable();
+        assert_eq!(spans, ["batch", "batch", "source", "source"]);
+        let values = recorder.values.lock().unwrap();
+        let sum = |name: &str| -> f64 {
+            let values = values.iter().filter(|(field, _)| *field == name);
+            values.map(|(_, value)| value).sum()
+        };
+        assert_eq!(sum("monotonic_counter.ssp.source.bytes"), 8.0);
+        assert_eq!(sum("monotonic_counter.ssp.batch.bytes"), 8.0);
+        assert_eq!(sum("monotonic_counter.ssp.source.errors"), 0.0);
+    }
+}
@@ -0,0 +1,101 @@
+use std::{io, io::SeekFrom, path::Path, sync::Arc};
+
+use tokio::{
+    fs::File,
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt},
+    task::JoinSet,
+};
+
+use crate::{compat::TokioCompat, source::try_count_line_words, ProcessorOptions};
+
+/// Count the number of words for each line of a file, splitting it in `partitions` byte ranges
+/// processed in parallel.
+///
+/// Ranges are aligned on line boundaries and each one is read from its own file handle on its
+/// own task, the counts are then stitched back in the order of the file. The first error of a
+/// task, e.g. failing to read its range, is returned instead of the counts.
+/// This must be called from within a Tokio runtime.
+pub async fn count_file_line_words_partitioned(
+    path: impl AsRef<Path>,
+    partitions: usize,
+    options: ProcessorOptions,
+) -> io::Result<Vec<usize>> {
+    let path: Arc<Path> = path.as_ref().into();
+    let ranges = line_aligned_ranges(&path, partitions, &options).await?;
+
+    let mut tasks = JoinSet::new();
+    for (i, (start, end)) in ranges.into_iter().enumerate() {
+        let path = path.clone();
+        tasks.spawn(async move {
+            let mut file = File::open(&path).await?;
+            file.seek(SeekFrom::Start(start)).await?;
+            let rd = TokioCompat::new(options.buf_reader(file.take(end - start)));
+            let id = path.display();
+            let counts = try_count_line_words((&id, rd), options).await?;
+            io::Result::Ok((i, counts))
+        });
+    }
+
+    let mut parts = Vec::with_capacity(tasks.len());
+    while let Some(res) = tasks.join_next().await {
+        parts.push(res.map_err(io::Error::other)??);
+    }
+    parts.sort_unstable_by_key(|(i, _)| *i);
+    Ok(parts.into_iter().flat_map(|(_, counts)| counts).collect())
+}
+
+/// Split the file in at most `partitions` byte ranges of similar size, each ending after a newline
+/// (except the last one).
+async fn line_aligned_ranges(
+    path: &Path,
+    partitions: usize,
+    options: &ProcessorOptions,
+) -> io::Result<Vec<(u64, u64)>> {
+    let file = File::open(path).await?;
+    let len = file.metadata().await?.len();
+    let mut rd = options.buf_reader(file);
+    let partitions = partitions.max(1) as u64;
+
+    let mut bounds = vec![0];
+    let mut skipped = Vec::new();
+    for i in 1..partitions {
+        let target = len * i / partitions;
+        let last = *bounds.last().unwrap();
+        if target <= last {
+            continue;
+        }
+        // Move the bound right after the next newline, starting from the byte before the target
+        // so that a target already at the start of a line is kept.
+        rd.seek(SeekFrom::Start(target - 1)).await?;
+        skipped.clear();
+        let read = rd.read_until(b'\n', &mut skipped).await? as u64;
+        let bound = target - 1 + read;
+        if bound > last && bound < len {
+            bounds.push(bound);
+        }
+    }
+    bounds.push(len);
+    Ok(bounds.windows(2).map(|w| (w[0], w[1])).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_count_file_line_words_partitioned() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path();
+        let data: String = (0..500).map(|i| "word ".repeat(i % 7) + "\n").collect();
+        tokio::fs::write(path, data + "last line").await.unwrap();
+
+        let expected: Vec<usize> = (0..500).map(|i| i % 7).chain([2]).collect();
+        for partitions in [1, 3, 16, 10_000] {
+            let counts =
+                count_file_line_words_partitioned(path, partitions, ProcessorOptions::default())
+                    .await
+                    .unwrap();
+            assert_eq!(counts, expected, "{partitions} partitions");
+        }
+    }
+}
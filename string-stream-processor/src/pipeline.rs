@@ -0,0 +1,197 @@
+use std::{collections::HashMap, future::Future, sync::Arc};
+
+use futures_util::{future::Either, StreamExt};
+use tokio::io::AsyncBufRead;
+
+use crate::{
+    aggregate::AggregatorMap, analyze_lines_into, analyzer::Analyzer, source::NoReconnect,
+    Aggregator, DynLineAnalyzer, ProcessorOptions, RecordSeparator, Records, SourceProvider,
+    TokioCompat,
+};
+
+/// Processing of sources composed of stages, each one optional but the source:
+/// - [`source`](Pipeline::source): the provider discovering the sources,
+/// - [`decode`](Pipeline::decode): a wrapper of each reader, e.g. a
+///   [`Decompress`](crate::Decompress) or a [`LineLimit`](crate::LineLimit),
+/// - [`split`](Pipeline::split): records instead of lines, see [`Records`],
+/// - [`analyze`](Pipeline::analyze): the computation of each line instead of its word count,
+/// - [`aggregate`](Pipeline::aggregate): the result of each source instead of the values of
+///   its lines.
+///
+/// ```
+/// use string_stream_processor::{LineStats, Pipeline, RecordSeparator};
+/// # fn pipeline(provider: impl string_stream_processor::SourceProvider) {
+/// let pipeline = Pipeline::new()
+///     .source(provider)
+///     .split(RecordSeparator::Str(b"\n\n".to_vec()))
+///     .analyze(|line: &[u8]| Ok(line.len()))
+///     .aggregate(|_| LineStats::default());
+/// # }
+/// ```
+pub struct Pipeline<P = (), D = (), F = fn(&str) -> Vec<usize>> {
+    source: P,
+    decode: D,
+    split: Option<RecordSeparator>,
+    analyzer: Option<Arc<dyn DynLineAnalyzer>>,
+    aggregate: F,
+    options: ProcessorOptions,
+}
+
+impl Pipeline {
+    /// Start a pipeline counting the words of each line, without source yet.
+    pub fn new() -> Self {
+        Self {
+            source: (),
+            decode: (),
+            split: None,
+            analyzer: None,
+            aggregate: |_| Vec::new(),
+            options: ProcessorOptions::default(),
+        }
+    }
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P, D, F> Pipeline<P, D, F> {
+    /// Read the sources of `source`, their readers as is until [`Pipeline::decode`].
+    #[allow(clippy::type_complexity)]
+    pub fn source<S: SourceProvider>(
+        self,
+        source: S,
+    ) -> Pipeline<S, fn(&str, S::Reader) -> S::Reader, F> {
+        Pipeline {
+            source,
+            decode: |_, rd| rd,
+            split: self.split,
+            analyzer: self.analyzer,
+            aggregate: self.aggregate,
+            options: self.options,
+        }
+    }
+
+    /// Read the records ended by `separator` as lines.
+    pub fn split(mut self, separator: RecordSeparator) -> Self {
+        self.split = Some(separator);
+        self
+    }
+
+    /// Compute the value of each line, without its line ending, with `analyzer` instead of the
+    /// word count with the tokenizer of the options.
+    pub fn analyze(mut self, analyzer: impl DynLineAnalyzer + 'static) -> Self {
+        self.analyzer = Some(Arc::new(analyzer));
+        self
+    }
+
+    /// Aggregate the values of the lines of each source with the aggregator created by `new(id)`,
+    /// see [`count_line_words_aggregated`](crate::StringMultiStreamExt::count_line_words_aggregated).
+    pub fn aggregate<A: Aggregator, G: Fn(&str) -> A>(self, new: G) -> Pipeline<P, D, G> {
+        Pipeline {
+            source: self.source,
+            decode: self.decode,
+            split: self.split,
+            analyzer: self.analyzer,
+            aggregate: new,
+            options: self.options,
+        }
+    }
+
+    /// Options of the reads and of the concurrency.
+    pub fn options(mut self, options: ProcessorOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+impl<P: SourceProvider, D, F> Pipeline<P, D, F> {
+    /// Wrap the reader of each source with `decode(id, reader)`, replacing the previous wrapper.
+    pub fn decode<R, E>(self, decode: E) -> Pipeline<P, E, F>
+    where
+        E: Fn(&str, P::Reader) -> R,
+        R: AsyncBufRead + Unpin,
+    {
+        Pipeline {
+            source: self.source,
+            decode,
+            split: self.split,
+            analyzer: self.analyzer,
+            aggregate: self.aggregate,
+            options: self.options,
+        }
+    }
+
+    /// Process the sources through the stages, returns the result of each source.
+    pub fn run<R, A>(&self) -> impl Future<Output = HashMap<&str, A::Output>>
+    where
+        D: Fn(&str, P::Reader) -> R,
+        R: AsyncBufRead + Unpin,
+        F: Fn(&str) -> A,
+        A: Aggregator,
+    {
+        let analyzer = match &self.analyzer {
+            Some(analyzer) => Analyzer::Dyn(Arc::clone(analyzer)),
+            None => Analyzer::Tokenizer(self.options.tokenizer),
+        };
+        let rds = self.source.sources().map(|(id, rd)| {
+            let rd = (self.decode)(id, rd);
+            let rd = match &self.split {
+                Some(separator) => {
+                    Either::Right(TokioCompat::new(Records::new(rd, separator.clone())))
+                }
+                None => Either::Left(TokioCompat::new(rd)),
+            };
+            (id, rd)
+        });
+        let acc = AggregatorMap::new(&self.aggregate);
+        async move {
+            analyze_lines_into(rds, self.options, analyzer, &NoReconnect, acc)
+                .await
+                .map
+                .into_iter()
+                .map(|(id, aggregator)| (id, aggregator.finish()))
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::{stream, Stream};
+
+    use super::*;
+    use crate::{LineLimit, LineStats};
+
+    /// Sources held in memory.
+    struct Memory(Vec<(&'static str, &'static str)>);
+
+    impl SourceProvider for Memory {
+        type Reader = &'static [u8];
+
+        fn sources(&self) -> impl Stream<Item = (&str, Self::Reader)> {
+            stream::iter(&self.0).map(|(id, data)| (*id, data.as_bytes()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipeline() {
+        let memory = || Memory(vec![("a", "a b\nc\n\nd e f\n"), ("b", "g h\n")]);
+        let pipeline = Pipeline::new().source(memory());
+        let result = pipeline.run().await;
+        assert_eq!(result["a"], [2, 1, 0, 3]);
+        assert_eq!(result["b"], [2]);
+
+        let pipeline = Pipeline::new()
+            .source(memory())
+            .decode(|_, rd| LineLimit::new(rd, Some(3)))
+            .split(RecordSeparator::Str(b"\n\n".to_vec()))
+            .analyze(|line: &[u8]| Ok(line.len()))
+            .aggregate(|_| LineStats::default());
+        let result = pipeline.run().await;
+        assert_eq!((result["a"].lines, result["a"].words), (1, 5));
+        assert_eq!((result["b"].lines, result["b"].max), (1, 3));
+    }
+}
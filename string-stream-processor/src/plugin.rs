@@ -0,0 +1,137 @@
+use std::{
+    io,
+    sync::{Mutex, PoisonError},
+};
+
+use wasmtime::{Config, Engine, Instance, Memory, Module, Store, TypedFunc};
+
+use crate::DynLineAnalyzer;
+
+/// Per-line analyzer running a user provided WebAssembly module.
+///
+/// The module must export:
+/// - `memory`: its linear memory,
+/// - `alloc(len: i32) -> i32`: returns the address of a buffer of at least `len` bytes, the buffer
+///   is reused for the next lines as long as they fit,
+/// - `analyze(ptr: i32, len: i32) -> i64`: analyzes the line written at `ptr`, without its
+///   line ending. A negative result is reported as an error.
+///
+/// The module has no import, so it cannot access anything outside of its own memory.
+pub struct WasmAnalyzer {
+    instance: Mutex<Plugin>,
+}
+
+struct Plugin {
+    store: Store<()>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    analyze: TypedFunc<(i32, i32), i64>,
+    /// Address and capacity of the line buffer in the module memory.
+    buf: Option<(i32, usize)>,
+    fuel: u64,
+}
+
+impl WasmAnalyzer {
+    /// Compile and instantiate a module, in binary or text format.
+    pub fn new(wasm: impl AsRef<[u8]>) -> io::Result<Self> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(io::Error::other)?;
+        let module = Module::new(&engine, wasm).map_err(io::Error::other)?;
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[]).map_err(io::Error::other)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| io::Error::other("the plugin does not export its memory"))?;
+        let alloc = instance
+            .get_typed_func(&mut store, "alloc")
+            .map_err(io::Error::other)?;
+        let analyze = instance
+            .get_typed_func(&mut store, "analyze")
+            .map_err(io::Error::other)?;
+        Ok(Self {
+            instance: Mutex::new(Plugin {
+                store,
+                memory,
+                alloc,
+                analyze,
+                buf: None,
+                fuel: u64::MAX,
+            }),
+        })
+    }
+
+    /// Limit the fuel (roughly the number of instructions) of each call of the module, so that a
+    /// plugin stuck in a loop fails instead of blocking its source.
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        let plugin = self.instance.get_mut();
+        plugin.unwrap_or_else(PoisonError::into_inner).fuel = fuel;
+        self
+    }
+}
+
+impl Plugin {
+    fn analyze(&mut self, line: &[u8]) -> wasmtime::Result<i64> {
+        self.store.set_fuel(self.fuel)?;
+        let len = i32::try_from(line.len())?;
+        let ptr = match self.buf {
+            Some((ptr, capacity)) if capacity >= line.len() => ptr,
+            _ => {
+                let ptr = self.alloc.call(&mut self.store, len)?;
+                self.buf = Some((ptr, line.len()));
+                ptr
+            }
+        };
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, line)?;
+        self.analyze.call(&mut self.store, (ptr, len))
+    }
+}
+
+impl DynLineAnalyzer for WasmAnalyzer {
+    fn analyze(&self, line: &[u8]) -> io::Result<usize> {
+        let mut plugin = self.instance.lock().unwrap_or_else(PoisonError::into_inner);
+        let res = plugin.analyze(line).map_err(io::Error::other)?;
+        usize::try_from(res)
+            .map_err(|_| io::Error::other(format!("the plugin failed with code {res}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Count the comma separated fields of a line.
+    const FIELDS: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "alloc") (param $len i32) (result i32)
+                (i32.const 0))
+            (func (export "analyze") (param $ptr i32) (param $len i32) (result i64)
+                (local $fields i64)
+                (if (i32.eqz (local.get $len)) (then (return (i64.const 0))))
+                (local.set $fields (i64.const 1))
+                (block $end
+                    (loop $next
+                        (br_if $end (i32.eqz (local.get $len)))
+                        (if (i32.eq (i32.load8_u (local.get $ptr)) (i32.const 44))
+                            (then (local.set $fields (i64.add (local.get $fields) (i64.const 1)))))
+                        (local.set $ptr (i32.add (local.get $ptr) (i32.const 1)))
+                        (local.set $len (i32.sub (local.get $len) (i32.const 1)))
+                        (br $next)))
+                (local.get $fields)))
+    "#;
+
+    #[test]
+    fn test_wasm_analyzer() {
+        let analyzer = WasmAnalyzer::new(FIELDS).unwrap();
+        assert_eq!(analyzer.analyze(b"a,b,c").unwrap(), 3);
+        assert_eq!(analyzer.analyze(b"").unwrap(), 0);
+        assert_eq!(analyzer.analyze(&[b','; 1000]).unwrap(), 1001);
+
+        // Running out of fuel is an error.
+        let analyzer = WasmAnalyzer::new(FIELDS).unwrap().with_fuel(100);
+        assert!(analyzer.analyze(&[b','; 1000]).is_err());
+        assert!(WasmAnalyzer::new("(module)").is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::{Mutex, PoisonError};
+
+/// Maximum number of buffers kept in the pool.
+const MAX_POOLED: usize = 64;
+/// Buffers with a larger capacity are dropped instead of being pooled, so that a single huge
+/// line doesn't stay in memory for the whole run.
+const MAX_POOLED_CAPACITY: usize = 64 * 1024;
+
+/// Pool of byte buffers shared across all the sources of the process.
+///
+/// Processing a lot of small sources would otherwise allocate and free a line buffer per source.
+pub(crate) static POOL: BufferPool = BufferPool::new();
+
+pub(crate) struct BufferPool {
+    bufs: Mutex<Vec<Vec<u8>>>,
+}
+
+impl BufferPool {
+    pub const fn new() -> Self {
+        Self {
+            bufs: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Get an empty buffer from the pool, or a new one if the pool is empty.
+    pub fn get(&self) -> Vec<u8> {
+        let mut bufs = self.bufs.lock().unwrap_or_else(PoisonError::into_inner);
+        bufs.pop().unwrap_or_default()
+    }
+
+    /// Give a buffer back to the pool.
+    pub fn put(&self, mut buf: Vec<u8>) {
+        if buf.capacity() == 0 || buf.capacity() > MAX_POOLED_CAPACITY {
+            return;
+        }
+        buf.clear();
+        let mut bufs = self.bufs.lock().unwrap_or_else(PoisonError::into_inner);
+        if bufs.len() < MAX_POOLED {
+            bufs.push(buf);
+        }
+    }
+
+    /// Same as [`BufferPool::get`] for a string buffer.
+    pub fn get_string(&self) -> String {
+        // Buffers of the pool are always empty, so this never fails.
+        String::from_utf8(self.get()).unwrap_or_default()
+    }
+
+    /// Same as [`BufferPool::put`] for a string buffer.
+    pub fn put_string(&self, buf: String) {
+        self.put(buf.into_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffer_pool() {
+        let pool = BufferPool::new();
+        let mut buf = pool.get();
+        buf.extend_from_slice(b"hello");
+        let ptr = buf.as_ptr();
+        pool.put(buf);
+
+        let buf = pool.get_string();
+        assert!(buf.is_empty());
+        assert_eq!(buf.as_ptr(), ptr);
+
+        pool.put(Vec::with_capacity(MAX_POOLED_CAPACITY + 1));
+        assert_eq!(pool.get().capacity(), 0);
+    }
+}
@@ -0,0 +1,70 @@
+use std::{collections::HashMap, fmt::Display, io};
+
+use futures_util::{pin_mut, Stream, StreamExt};
+use tokio_postgres::{binary_copy::BinaryCopyInWriter, types::Type, Client};
+
+/// Write the line counts of a stream into a Postgres table with a binary `COPY`, as they are
+/// received, tagged with the identifier of the run. Returns the number of written rows.
+///
+/// The table must have the columns `run_id text, source text, line_no bigint, word_count bigint`,
+/// the line numbers of each source start at 1. `table` is written as is in the statement, it
+/// must be a trusted (and quoted if needed) name. The rows are only visible once the stream ends.
+pub async fn write_postgres_line_words<I: Display>(
+    client: &Client,
+    table: &str,
+    run_id: &str,
+    counts: impl Stream<Item = (I, usize)>,
+) -> io::Result<u64> {
+    let statement =
+        format!("COPY {table} (run_id, source, line_no, word_count) FROM STDIN (FORMAT binary)");
+    let sink = client
+        .copy_in(statement.as_str())
+        .await
+        .map_err(io::Error::other)?;
+    let writer = BinaryCopyInWriter::new(sink, &[Type::TEXT, Type::TEXT, Type::INT8, Type::INT8]);
+    pin_mut!(writer);
+    let rows = numbered_line_words(counts);
+    pin_mut!(rows);
+    while let Some((source, line_no, count)) = rows.next().await {
+        writer
+            .as_mut()
+            .write(&[&run_id, &source, &line_no, &count])
+            .await
+            .map_err(io::Error::other)?;
+    }
+    writer.finish().await.map_err(io::Error::other)
+}
+
+/// Number the lines of each source of a stream of line counts, from 1.
+fn numbered_line_words<I: Display>(
+    counts: impl Stream<Item = (I, usize)>,
+) -> impl Stream<Item = (String, i64, i64)> {
+    let mut lines = HashMap::<String, i64>::new();
+    counts.map(move |(id, count)| {
+        let id = id.to_string();
+        let line_no = lines.entry(id.clone()).or_default();
+        *line_no += 1;
+        (id, *line_no, count as i64)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::stream;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_numbered_line_words() {
+        let counts = stream::iter([("a", 2), ("b", 0), ("a", 3)]);
+        let rows: Vec<_> = numbered_line_words(counts).collect().await;
+        assert_eq!(
+            rows,
+            [
+                ("a".to_string(), 1, 2),
+                ("b".to_string(), 1, 0),
+                ("a".to_string(), 2, 3)
+            ]
+        );
+    }
+}
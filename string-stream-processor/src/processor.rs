@@ -0,0 +1,151 @@
+use std::{collections::HashMap, future::Future, time::Duration};
+
+use futures_util::Stream;
+use tokio::io::{AsyncBufRead, AsyncRead};
+
+use crate::{ProcessorOptions, ReadMultiStreamExt, RetryPolicy, StringMultiStreamExt, Tokenizer};
+
+/// Processor of multiple sources with a fixed configuration, see [`Processor::builder`].
+///
+/// ```
+/// use string_stream_processor::{Processor, Tokenizer};
+///
+/// let processor = Processor::builder()
+///     .max_concurrency(64)
+///     .tokenizer(Tokenizer::Unicode)
+///     .build();
+/// assert_eq!(processor.options().max_concurrency, Some(64));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Processor {
+    options: ProcessorOptions,
+}
+
+impl Processor {
+    /// Start the configuration of a processor from the default options.
+    pub fn builder() -> ProcessorBuilder {
+        ProcessorBuilder::default()
+    }
+
+    /// Options of the processor.
+    pub fn options(&self) -> ProcessorOptions {
+        self.options
+    }
+
+    /// Count the number of words from a stream of async readers and associated identifiers, see
+    /// [`StringMultiStreamExt::count_line_words_concurrent_with`].
+    pub fn count_line_words<'a, R: AsyncBufRead + Unpin>(
+        &self,
+        rds: impl Stream<Item = (&'a str, R)>,
+    ) -> impl Future<Output = HashMap<&'a str, Vec<usize>>> {
+        rds.count_line_words_concurrent_with(self.options)
+    }
+
+    /// Same as [`Processor::count_line_words`] with plain async readers, buffered with the
+    /// [`read_buffer_size`](ProcessorBuilder::read_buffer_size).
+    pub fn count_read_line_words<'a, R: AsyncRead + Unpin>(
+        &self,
+        rds: impl Stream<Item = (&'a str, R)>,
+    ) -> impl Future<Output = HashMap<&'a str, Vec<usize>>> {
+        rds.count_read_line_words_concurrent(self.options)
+    }
+}
+
+impl From<Processor> for ProcessorOptions {
+    fn from(processor: Processor) -> Self {
+        processor.options
+    }
+}
+
+/// Builder of a [`Processor`], each setter mirrors a field of [`ProcessorOptions`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessorBuilder {
+    options: ProcessorOptions,
+}
+
+impl ProcessorBuilder {
+    /// See [`ProcessorOptions::max_concurrency`].
+    pub fn max_concurrency(mut self, max: usize) -> Self {
+        self.options = self.options.with_max_concurrency(max);
+        self
+    }
+
+    /// See [`ProcessorOptions::tokenizer`].
+    pub fn tokenizer(mut self, tokenizer: Tokenizer) -> Self {
+        self.options = self.options.with_tokenizer(tokenizer);
+        self
+    }
+
+    /// See [`ProcessorOptions::read_buffer_size`].
+    pub fn read_buffer_size(mut self, size: usize) -> Self {
+        self.options = self.options.with_read_buffer_size(size);
+        self
+    }
+
+    /// See [`ProcessorOptions::buffer_capacity`].
+    pub fn buffer_capacity(mut self, capacity: usize) -> Self {
+        self.options = self.options.with_buffer_capacity(capacity);
+        self
+    }
+
+    /// See [`ProcessorOptions::retry`].
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.options = self.options.with_retry(retry);
+        self
+    }
+
+    /// See [`ProcessorOptions::blocking_batch`].
+    pub fn blocking_batch(mut self, size: usize) -> Self {
+        self.options = self.options.with_blocking_batch(size);
+        self
+    }
+
+    /// See [`ProcessorOptions::follow`].
+    pub fn follow(mut self, interval: Duration) -> Self {
+        self.options = self.options.with_follow(interval);
+        self
+    }
+
+    /// See [`ProcessorOptions::fairness`].
+    pub fn fairness(mut self, burst: usize) -> Self {
+        self.options = self.options.with_fairness(burst);
+        self
+    }
+
+    /// Returns the configured processor.
+    pub fn build(self) -> Processor {
+        Processor {
+            options: self.options,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use futures_util::stream;
+    use tokio::io::BufReader;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_processor_builder() {
+        let processor = Processor::builder()
+            .max_concurrency(1)
+            .tokenizer(Tokenizer::Ascii)
+            .buffer_capacity(4)
+            .fairness(8)
+            .build();
+        let options = processor.options();
+        assert_eq!(options.max_concurrency, Some(1));
+        assert_eq!(options.tokenizer, Tokenizer::Ascii);
+        assert_eq!(options.buffer_capacity, Some(4));
+        assert_eq!(options.fairness, Some(8));
+
+        let srcs = ["a", "b", "c"].map(|id| (id, BufReader::new(io::Cursor::new("a b\nc"))));
+        let result = processor.count_line_words(stream::iter(srcs)).await;
+        assert_eq!(result.len(), 3);
+        assert_eq!(result["b"], [2, 1]);
+    }
+}
@@ -0,0 +1,192 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{ready, Context, Poll},
+};
+
+use pin_project_lite::pin_project;
+use tokio::{
+    io::{AsyncBufRead, AsyncRead, ReadBuf},
+    sync::Notify,
+};
+
+/// Progress of the reading of sources, shared by the readers wrapped with
+/// [`Progress::reader`], e.g. to drive a progress bar.
+///
+/// ```
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// use string_stream_processor::{Progress, ProgressSnapshot};
+/// use tokio::io::AsyncReadExt;
+///
+/// let progress = Progress::default();
+/// let mut rd = progress.reader(&b"a b\nc\n"[..]);
+/// rd.read_to_end(&mut Vec::new()).await.unwrap();
+/// let snapshot = ProgressSnapshot { opened: 1, done: 1, bytes: 6 };
+/// assert_eq!(progress.snapshot(), snapshot);
+/// # });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    opened: AtomicUsize,
+    done: AtomicUsize,
+    bytes: AtomicU64,
+    changed: Notify,
+}
+
+/// Progress of the readers of a [`Progress`] at some point.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProgressSnapshot {
+    /// Number of readers created.
+    pub opened: usize,
+    /// Number of readers which reached their end, failed or were dropped before their end.
+    pub done: usize,
+    /// Number of bytes consumed from all the readers.
+    pub bytes: u64,
+}
+
+impl Progress {
+    /// Wrap a reader of a source, whose reading updates the progress.
+    pub fn reader<R>(&self, inner: R) -> ProgressReader<R> {
+        self.shared.opened.fetch_add(1, Ordering::Relaxed);
+        self.shared.changed.notify_one();
+        ProgressReader {
+            inner,
+            shared: Arc::clone(&self.shared),
+            done: false,
+        }
+    }
+
+    pub fn snapshot(&self) -> ProgressSnapshot {
+        ProgressSnapshot {
+            opened: self.shared.opened.load(Ordering::Relaxed),
+            done: self.shared.done.load(Ordering::Relaxed),
+            bytes: self.shared.bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Wait until the progress changes after the last call, or after the creation of the
+    /// progress on the first call. A single task is meant to wait for the changes.
+    pub async fn changed(&self) {
+        self.shared.changed.notified().await;
+    }
+
+    /// Snapshots of the progress each time it changes, at most one per `interval`, never
+    /// ending.
+    #[cfg(feature = "runtime")]
+    pub fn updates(
+        &self,
+        interval: std::time::Duration,
+    ) -> impl futures_util::Stream<Item = ProgressSnapshot> {
+        futures_util::stream::unfold(self.clone(), move |progress| async move {
+            progress.changed().await;
+            let snapshot = progress.snapshot();
+            tokio::time::sleep(interval).await;
+            Some((snapshot, progress))
+        })
+    }
+}
+
+pin_project! {
+    /// Reader updating a [`Progress`] with the bytes consumed from its inner reader, and once
+    /// done.
+    #[derive(Debug)]
+    pub struct ProgressReader<R> {
+        #[pin]
+        inner: R,
+        shared: Arc<Shared>,
+        done: bool,
+    }
+
+    impl<R> PinnedDrop for ProgressReader<R> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            finish(this.shared, this.done);
+        }
+    }
+}
+
+/// Count a reader as done once.
+fn finish(shared: &Shared, done: &mut bool) {
+    if !*done {
+        *done = true;
+        shared.done.fetch_add(1, Ordering::Relaxed);
+        shared.changed.notify_one();
+    }
+}
+
+impl<R: AsyncBufRead> AsyncBufRead for ProgressReader<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.project();
+        let data = ready!(this.inner.poll_fill_buf(cx));
+        if data.as_ref().map_or(true, |data| data.is_empty()) {
+            finish(this.shared, this.done);
+        }
+        Poll::Ready(data)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.project();
+        if amt > 0 {
+            this.shared.bytes.fetch_add(amt as u64, Ordering::Relaxed);
+            this.shared.changed.notify_one();
+        }
+        this.inner.consume(amt);
+    }
+}
+
+impl<R: AsyncBufRead> AsyncRead for ProgressReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let data = ready!(self.as_mut().poll_fill_buf(cx))?;
+        let len = data.len().min(buf.remaining());
+        buf.put_slice(&data[..len]);
+        self.consume(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_progress() {
+        let progress = Progress::default();
+        let mut a = progress.reader(BufReader::with_capacity(2, &b"a b\nc"[..]));
+        let b = progress.reader(&b"d\n"[..]);
+        progress.changed().await;
+        let mut line = String::new();
+        a.read_line(&mut line).await.unwrap();
+        let snapshot = ProgressSnapshot {
+            opened: 2,
+            done: 0,
+            bytes: 4,
+        };
+        assert_eq!(progress.snapshot(), snapshot);
+        drop(b);
+        assert_eq!(progress.snapshot().done, 1);
+        // The end of the last line is the end of the reader.
+        a.read_line(&mut line).await.unwrap();
+        drop(a);
+        let snapshot = ProgressSnapshot {
+            opened: 2,
+            done: 2,
+            bytes: 5,
+        };
+        assert_eq!(progress.snapshot(), snapshot);
+    }
+}
@@ -0,0 +1,343 @@
+#[cfg(feature = "runtime")]
+use std::{cell::Ref, io, path::Path};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+#[cfg(feature = "runtime")]
+use futures_util::stream;
+use futures_util::{Stream, StreamExt};
+use tokio::io::AsyncBufRead;
+#[cfg(feature = "runtime")]
+use tokio::{fs::File, io::BufReader};
+
+#[cfg(feature = "runtime")]
+use crate::Follow;
+use crate::{
+    meta::{Metered, SourceMeta, SourceResult},
+    ProcessorOptions, StringMultiStreamExt,
+};
+
+/// Source of the `(id, reader)` pairs to process, e.g. local files, objects of a bucket or
+/// messages of a queue.
+///
+/// The identifiers are borrowed from the provider, so that they are not cloned for each line.
+pub trait SourceProvider {
+    type Reader: AsyncBufRead + Unpin;
+
+    /// Returns the sources, those that cannot be opened should be skipped.
+    fn sources(&self) -> impl Stream<Item = (&str, Self::Reader)>;
+
+    /// Expected number of lines of a source yielded by [`SourceProvider::sources`],
+    /// see [`StringMultiStreamExt::count_line_words_concurrent_with_hint`].
+    fn lines_hint(&self, _id: &str) -> usize {
+        0
+    }
+
+    /// Metadata of a source yielded by [`SourceProvider::sources`] known by the provider, e.g.
+    /// the size and the modification time of a file, the rest is collected while reading it.
+    fn source_meta(&self, _id: &str) -> SourceMeta {
+        SourceMeta::default()
+    }
+
+    /// Count the number of words for each line of the sources.
+    fn count_line_words(
+        &self,
+        options: ProcessorOptions,
+    ) -> impl Future<Output = HashMap<&str, Vec<usize>>> {
+        self.sources()
+            .count_line_words_concurrent_with_hint(options, |id| self.lines_hint(id))
+    }
+
+    /// Same as [`SourceProvider::count_line_words`] with the metadata of each source collected
+    /// while reading it, without reading it again nor querying its file afterwards. Every
+    /// opened source has a result, even without any line.
+    fn count_line_words_with_meta(
+        &self,
+        options: ProcessorOptions,
+    ) -> impl Future<Output = HashMap<&str, SourceResult>> {
+        async move {
+            let metas = RefCell::new(Vec::new());
+            let sources = self.sources().map(|(id, rd)| {
+                let meta = Arc::new(Mutex::new(self.source_meta(id)));
+                metas.borrow_mut().push((id, Arc::clone(&meta)));
+                (id, Metered::new(rd, meta))
+            });
+            let mut counts = sources
+                .count_line_words_concurrent_with_hint(options, |id| self.lines_hint(id))
+                .await;
+            let metas = metas.into_inner().into_iter();
+            metas
+                .map(|(id, meta)| {
+                    let counts = counts.remove(id).unwrap_or_default();
+                    let meta = std::mem::take(&mut *meta.lock().unwrap());
+                    (id, SourceResult { counts, meta })
+                })
+                .collect()
+        }
+    }
+}
+
+/// Average line length used to estimate the number of lines of a file from its size.
+#[cfg(feature = "runtime")]
+pub(crate) const ESTIMATED_LINE_LEN: u64 = 64;
+
+/// Split a `{scheme}://bucket/prefix` URI of an object store, the prefix may be empty.
+#[cfg(any(feature = "s3", feature = "gcs", feature = "azure"))]
+pub(crate) fn split_uri<'a>(uri: &'a str, scheme: &str) -> Option<(&'a str, &'a str)> {
+    let path = uri.strip_prefix(scheme)?.strip_prefix("://")?;
+    let (bucket, prefix) = path.split_once('/').unwrap_or((path, ""));
+    (!bucket.is_empty()).then_some((bucket, prefix))
+}
+
+/// Reader of the files of a [`FileProvider`].
+#[cfg(all(feature = "runtime", not(feature = "compression")))]
+pub type FileReader = BufReader<Follow<File>>;
+/// Reader of the files of a [`FileProvider`], decompressed according to their extension or
+/// their first bytes.
+#[cfg(all(feature = "runtime", feature = "compression"))]
+pub type FileReader = crate::Decompress<BufReader<Follow<File>>>;
+
+/// Provider of local files, read with the read buffer size of the options and followed as they
+/// grow with [`ProcessorOptions::follow`].
+#[cfg(feature = "runtime")]
+#[derive(Debug)]
+pub struct FileProvider {
+    paths: Vec<String>,
+    options: ProcessorOptions,
+    keep_open: bool,
+    /// Estimated number of lines of the opened files, from their size.
+    lines_hints: RefCell<HashMap<String, usize>>,
+    /// Size and modification time of the opened files.
+    metas: RefCell<HashMap<String, SourceMeta>>,
+    errors: RefCell<Vec<(String, io::Error)>>,
+}
+
+#[cfg(feature = "runtime")]
+impl FileProvider {
+    /// Provide the files at the given paths.
+    pub fn new(paths: Vec<String>, options: ProcessorOptions) -> Self {
+        Self {
+            paths,
+            options,
+            keep_open: false,
+            lines_hints: RefCell::default(),
+            metas: RefCell::default(),
+            errors: RefCell::default(),
+        }
+    }
+
+    /// Keep reading the named pipes (FIFOs) when their writers close them, waiting for the next
+    /// writers instead of ending at the first end of file. Their sources then never end.
+    pub fn with_keep_open(mut self, keep_open: bool) -> Self {
+        self.keep_open = keep_open;
+        self
+    }
+
+    /// Provide the regular files of a directory, sorted by path (not recursive).
+    pub async fn from_dir(dir: impl AsRef<Path>, options: ProcessorOptions) -> io::Result<Self> {
+        let mut entries = tokio::fs::read_dir(dir).await?;
+        let mut paths = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            if !entry.file_type().await?.is_file() {
+                continue;
+            }
+            match entry.path().into_os_string().into_string() {
+                Ok(path) => paths.push(path),
+                Err(path) => log::warn!("Skipping non UTF-8 path {}.", path.to_string_lossy()),
+            }
+        }
+        paths.sort_unstable();
+        Ok(Self::new(paths, options))
+    }
+
+    /// Paths of the provided files.
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+
+    /// Paths and errors of the files skipped so far by [`SourceProvider::sources`], as they
+    /// could not be opened.
+    pub fn errors(&self) -> Ref<'_, [(String, io::Error)]> {
+        Ref::map(self.errors.borrow(), Vec::as_slice)
+    }
+
+    async fn open(&self, path: &str) -> Option<FileReader> {
+        let file = self
+            .open_file(path)
+            .await
+            .inspect_err(|e| log::warn!("Could not open {path}, {e}, skipping it."))
+            .map_err(|e| self.errors.borrow_mut().push((path.to_string(), e)))
+            .ok()?;
+        if let Ok(metadata) = file.metadata().await {
+            let lines = (metadata.len() / ESTIMATED_LINE_LEN) as usize;
+            self.lines_hints
+                .borrow_mut()
+                .insert(path.to_string(), lines);
+            let meta = SourceMeta {
+                size: Some(metadata.len()),
+                modified: metadata.modified().ok(),
+                ..SourceMeta::default()
+            };
+            self.metas.borrow_mut().insert(path.to_string(), meta);
+        }
+        let rd = self
+            .options
+            .buf_reader(Follow::new(file, self.options.follow));
+        #[cfg(feature = "compression")]
+        let rd = crate::Decompress::detect(path, rd, self.options.read_buffer_size)
+            .await
+            .inspect_err(|e| log::warn!("Could not read {path}, {e}, skipping it."))
+            .map_err(|e| self.errors.borrow_mut().push((path.to_string(), e)))
+            .ok()?;
+        Some(rd)
+    }
+
+    async fn open_file(&self, path: &str) -> io::Result<File> {
+        if self.keep_open && is_fifo(path).await {
+            // A pipe also opened for writing always has a writer, so its reads wait for the
+            // next writers instead of returning the end of file.
+            return tokio::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(path)
+                .await;
+        }
+        File::open(path).await
+    }
+}
+
+/// Whether the path is a named pipe (FIFO).
+#[cfg(feature = "runtime")]
+async fn is_fifo(path: &str) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        tokio::fs::metadata(path)
+            .await
+            .is_ok_and(|metadata| metadata.file_type().is_fifo())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+#[cfg(feature = "runtime")]
+impl SourceProvider for FileProvider {
+    type Reader = FileReader;
+
+    fn sources(&self) -> impl Stream<Item = (&str, Self::Reader)> {
+        stream::iter(&self.paths)
+            .filter_map(move |path| async move { Some((path.as_str(), self.open(path).await?)) })
+    }
+
+    fn lines_hint(&self, id: &str) -> usize {
+        self.lines_hints.borrow_mut().remove(id).unwrap_or(0)
+    }
+
+    fn source_meta(&self, id: &str) -> SourceMeta {
+        self.metas.borrow_mut().remove(id).unwrap_or_default()
+    }
+}
+
+#[cfg(all(test, feature = "runtime"))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_provider() {
+        let dir = std::env::temp_dir().join("ssp_test_file_provider");
+        tokio::fs::create_dir_all(dir.join("sub")).await.unwrap();
+        tokio::fs::write(dir.join("a.txt"), "a b\nc").await.unwrap();
+        tokio::fs::write(dir.join("b.txt"), "d e f").await.unwrap();
+
+        let provider = FileProvider::from_dir(&dir, ProcessorOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(provider.paths().len(), 2);
+        let result = provider.count_line_words(ProcessorOptions::default()).await;
+        let a = dir.join("a.txt");
+        assert_eq!(result[a.to_str().unwrap()], [2, 1]);
+        let result = provider
+            .count_line_words_with_meta(ProcessorOptions::default())
+            .await;
+        let a = &result[a.to_str().unwrap()];
+        assert_eq!(a.counts, [2, 1]);
+        assert_eq!((a.meta.bytes, a.meta.size), (5, Some(5)));
+        assert!(a.meta.truncated_line && a.meta.modified.is_some());
+
+        let provider =
+            FileProvider::new(vec!["missing.txt".to_string()], ProcessorOptions::default());
+        assert!(provider
+            .count_line_words(ProcessorOptions::default())
+            .await
+            .is_empty());
+        let errors: Vec<_> = provider
+            .errors()
+            .iter()
+            .map(|(path, e)| (path.clone(), e.kind()))
+            .collect();
+        assert_eq!(
+            errors,
+            [("missing.txt".to_string(), io::ErrorKind::NotFound)]
+        );
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_file_provider_keep_open() {
+        use tokio::io::AsyncWriteExt;
+
+        let dir = std::env::temp_dir().join("ssp_test_file_provider_keep_open");
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let fifo = dir.join("fifo");
+        let _ = tokio::fs::remove_file(&fifo).await;
+        let status = std::process::Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let path = fifo.to_str().unwrap().to_string();
+        assert!(is_fifo(&path).await);
+        assert!(!is_fifo(dir.to_str().unwrap()).await);
+
+        let options = ProcessorOptions::default();
+        let provider = FileProvider::new(vec![path.clone()], options).with_keep_open(true);
+        // The writers cannot open the pipe before it is opened for reading.
+        let writers = path.clone();
+        tokio::spawn(async move {
+            for line in ["a b\n", "c\n"] {
+                let mut writer = tokio::fs::OpenOptions::new()
+                    .write(true)
+                    .open(&writers)
+                    .await
+                    .unwrap();
+                writer.write_all(line.as_bytes()).await.unwrap();
+                writer.flush().await.unwrap();
+            }
+        });
+        let (id, rd) = Box::pin(provider.sources()).next().await.unwrap();
+        let counts: Vec<_> = crate::count_source_line_words((id, rd), options)
+            .take(2)
+            .map(|(_, count)| count)
+            .collect()
+            .await;
+        assert_eq!(counts, [2, 1]);
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[cfg(any(feature = "s3", feature = "gcs", feature = "azure"))]
+    #[test]
+    fn test_split_uri() {
+        assert_eq!(split_uri("s3://logs/2024/", "s3"), Some(("logs", "2024/")));
+        assert_eq!(split_uri("gs://logs", "gs"), Some(("logs", "")));
+        assert_eq!(split_uri("s3:///a", "s3"), None);
+        assert_eq!(split_uri("gs://logs", "s3"), None);
+    }
+}
@@ -0,0 +1,365 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use pin_project_lite::pin_project;
+use tokio::io::{AsyncBufRead, AsyncRead, ReadBuf};
+
+/// Default maximum length of the length-prefixed records of a [`Records`] reader.
+pub const DEFAULT_MAX_RECORD_LENGTH: usize = 64 << 20;
+
+/// Separator of the records of a [`Records`] reader.
+#[derive(Debug, Clone)]
+pub enum RecordSeparator {
+    /// The records end with this string, e.g. `"\n\n"` for the paragraphs.
+    Str(Vec<u8>),
+    /// The records are this number of bytes each, the last one possibly shorter, e.g. the
+    /// mainframe exports without line endings.
+    Fixed(usize),
+    /// Each record is prefixed by its length in bytes, as a big-endian `u32`, e.g. the frames of
+    /// a binary-safe protocol whose payloads may contain line endings. A truncated last record
+    /// is a read error.
+    LengthPrefixed,
+    /// A record starts at each line matching the pattern, without its line ending, e.g. the
+    /// log entries starting with a timestamp and continued on the lines after it.
+    #[cfg(feature = "regex")]
+    LineStart(regex::bytes::Regex),
+}
+
+pin_project! {
+    /// Reader of the records of a reader as lines, so that the computations on the lines apply
+    /// to the records.
+    ///
+    /// Each record is read as a line ended by a newline, the separator removed and its own line
+    /// endings replaced by spaces. A record is kept in memory until its end is read.
+    #[derive(Debug)]
+    pub struct Records<R> {
+        #[pin]
+        inner: R,
+        separator: RecordSeparator,
+        // Data read from the inner reader, the next records from `start`. The records read are
+        // removed from it at once when refilled or when they are most of it.
+        pending: Vec<u8>,
+        start: usize,
+        // Length of the start of `pending` already searched for the end of a record.
+        searched: usize,
+        // Current record written as a line, and the length of its start already read.
+        record: Vec<u8>,
+        pos: usize,
+        eof: bool,
+        max_length: usize,
+    }
+}
+
+impl<R> Records<R> {
+    pub fn new(inner: R, separator: RecordSeparator) -> Self {
+        Self {
+            inner,
+            separator,
+            pending: Vec::new(),
+            start: 0,
+            searched: 0,
+            record: Vec::new(),
+            pos: 0,
+            eof: false,
+            max_length: DEFAULT_MAX_RECORD_LENGTH,
+        }
+    }
+
+    /// Set the maximum length of the length-prefixed records, [`DEFAULT_MAX_RECORD_LENGTH`] by
+    /// default. A longer one is a read error instead of being buffered, e.g. for an input which
+    /// is not length-prefixed.
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    /// Returns the wrapped reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl RecordSeparator {
+    /// Start and end of the first record of `data` and the start of the next one, searched from
+    /// `from`, at its end for the last record of the input. The line starts are only known at
+    /// the end of a line. A length prefix above `max_length` is an error.
+    #[cfg_attr(not(feature = "regex"), allow(unused_variables))]
+    fn find(
+        &self,
+        data: &[u8],
+        from: usize,
+        eof: bool,
+        max_length: usize,
+    ) -> io::Result<Option<(usize, usize, usize)>> {
+        let found = match self {
+            RecordSeparator::Str(separator) if separator.is_empty() => None,
+            RecordSeparator::Fixed(0) => None,
+            RecordSeparator::Fixed(len) => (data.len() >= *len).then_some((0, *len, *len)),
+            RecordSeparator::LengthPrefixed => {
+                let Some(prefix) = data.first_chunk::<4>() else {
+                    return Ok(None);
+                };
+                let end = usize::try_from(u32::from_be_bytes(*prefix))
+                    .ok()
+                    .filter(|&len| len <= max_length)
+                    .and_then(|len| len.checked_add(4))
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "length-prefixed record longer than the maximum",
+                        )
+                    })?;
+                (data.len() >= end).then_some((4, end, end))
+            }
+            RecordSeparator::Str(separator) => {
+                let from = from.saturating_sub(separator.len() - 1);
+                data.get(from..).and_then(|data| {
+                    data.windows(separator.len())
+                        .position(|window| window == separator)
+                        .map(|i| (0, from + i, from + i + separator.len()))
+                })
+            }
+            #[cfg(feature = "regex")]
+            RecordSeparator::LineStart(pattern) => find_line_start(pattern, data, from, eof),
+        };
+        Ok(found)
+    }
+
+    /// Length of the start of `data` searched once no record end is found, the next search
+    /// starting from it.
+    fn searched(&self, data: &[u8]) -> usize {
+        match self {
+            RecordSeparator::Str(_) | RecordSeparator::Fixed(_) => data.len(),
+            RecordSeparator::LengthPrefixed => 0,
+            #[cfg(feature = "regex")]
+            RecordSeparator::LineStart(_) => {
+                data.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1)
+            }
+        }
+    }
+}
+
+/// Start of the first line after the first one of `data` matching the pattern, searched from
+/// `from`, see [`RecordSeparator::find`].
+#[cfg(feature = "regex")]
+fn find_line_start(
+    pattern: &regex::bytes::Regex,
+    data: &[u8],
+    from: usize,
+    eof: bool,
+) -> Option<(usize, usize, usize)> {
+    // The first line starts the first record whether it matches or not.
+    let mut start = match from {
+        0 => memchr_newline(data)? + 1,
+        from => from,
+    };
+    while start < data.len() {
+        let line = &data[start..];
+        let end = match memchr_newline(line) {
+            Some(end) => end,
+            None if eof && !line.is_empty() => line.len(),
+            None => return None,
+        };
+        let line = &line[..end];
+        if pattern.is_match(line.strip_suffix(b"\r").unwrap_or(line)) {
+            return Some((0, start, start));
+        }
+        start += end + 1;
+    }
+    None
+}
+
+#[cfg(feature = "regex")]
+fn memchr_newline(data: &[u8]) -> Option<usize> {
+    data.iter().position(|&b| b == b'\n')
+}
+
+/// Write a record as a line, its line endings replaced by spaces.
+fn write_record(line: &mut Vec<u8>, record: &[u8]) {
+    let record = record.strip_suffix(b"\n").unwrap_or(record);
+    line.clear();
+    line.extend(record.iter().map(|&b| if b == b'\n' { b' ' } else { b }));
+    line.push(b'\n');
+}
+
+impl<R: AsyncBufRead> AsyncBufRead for Records<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let mut this = self.project();
+        while *this.pos == this.record.len() {
+            let pending = &this.pending[*this.start..];
+            let end = this
+                .separator
+                .find(pending, *this.searched, *this.eof, *this.max_length);
+            let end = match end {
+                Ok(end) => end,
+                Err(e) => {
+                    this.pending.clear();
+                    *this.start = 0;
+                    return Poll::Ready(Err(e));
+                }
+            };
+            if let Some((start, end, next)) = end {
+                write_record(this.record, &pending[start..end]);
+                *this.start += next;
+                if *this.start > this.pending.len() / 2 {
+                    this.pending.drain(..*this.start);
+                    *this.start = 0;
+                }
+                *this.searched = 0;
+                *this.pos = 0;
+                break;
+            }
+            if *this.eof {
+                if matches!(this.separator, RecordSeparator::LengthPrefixed) && !pending.is_empty()
+                {
+                    this.pending.clear();
+                    *this.start = 0;
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "truncated length-prefixed record",
+                    )));
+                }
+                if !pending.is_empty() {
+                    write_record(this.record, pending);
+                    this.pending.clear();
+                    *this.start = 0;
+                    *this.searched = 0;
+                    *this.pos = 0;
+                }
+                break;
+            }
+            *this.searched = this.separator.searched(pending);
+            this.pending.drain(..*this.start);
+            *this.start = 0;
+            let data = ready!(this.inner.as_mut().poll_fill_buf(cx))?;
+            if data.is_empty() {
+                *this.eof = true;
+                continue;
+            }
+            let len = data.len();
+            this.pending.extend_from_slice(data);
+            this.inner.as_mut().consume(len);
+        }
+        let (record, pos) = (this.record, *this.pos);
+        Poll::Ready(Ok(&record[pos..]))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.project();
+        *this.pos = (*this.pos + amt).min(this.record.len());
+    }
+}
+
+impl<R: AsyncBufRead> AsyncRead for Records<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let data = ready!(self.as_mut().poll_fill_buf(cx))?;
+        let len = data.len().min(buf.remaining());
+        buf.put_slice(&data[..len]);
+        self.consume(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, BufReader};
+
+    use super::*;
+
+    async fn read(data: &'static str, separator: RecordSeparator) -> String {
+        // A small buffer makes the separators span several reads.
+        let rd = BufReader::with_capacity(3, data.as_bytes());
+        let mut records = String::new();
+        Records::new(rd, separator)
+            .read_to_string(&mut records)
+            .await
+            .unwrap();
+        records
+    }
+
+    #[tokio::test]
+    async fn test_records() {
+        let paragraphs = RecordSeparator::Str(b"\n\n".to_vec());
+        assert_eq!(
+            read("a b\nc\n\nd\n\n\ne f", paragraphs.clone()).await,
+            "a b c\nd\n e f\n"
+        );
+        assert_eq!(read("", paragraphs).await, "");
+        let semicolons = RecordSeparator::Str(b";".to_vec());
+        assert_eq!(read("a;b c;", semicolons).await, "a\nb c\n");
+        let fixed = RecordSeparator::Fixed(4);
+        assert_eq!(read("ab cdefg\nhij", fixed).await, "ab c\ndefg\n hij\n");
+
+        // The records of a large read are split from it in place.
+        let data = "a b;c;".repeat(1000);
+        let mut records = String::new();
+        Records::new(data.as_bytes(), RecordSeparator::Str(b";".to_vec()))
+            .read_to_string(&mut records)
+            .await
+            .unwrap();
+        assert_eq!(records, "a b\nc\n".repeat(1000));
+    }
+
+    #[tokio::test]
+    async fn test_length_prefixed_records() {
+        let frames = b"\0\0\0\x05a\nb c\0\0\0\0\0\0\0\x01d";
+        let rd = BufReader::with_capacity(3, &frames[..]);
+        let mut records = String::new();
+        Records::new(rd, RecordSeparator::LengthPrefixed)
+            .read_to_string(&mut records)
+            .await
+            .unwrap();
+        assert_eq!(records, "a b c\n\nd\n");
+
+        let truncated = b"\0\0\0\x05a b";
+        let mut records = Records::new(&truncated[..], RecordSeparator::LengthPrefixed);
+        let err = records.read_to_end(&mut Vec::new()).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let oversized = b"\xff\xff\xff\xffa b";
+        let mut records = Records::new(&oversized[..], RecordSeparator::LengthPrefixed);
+        let err = records.read_to_end(&mut Vec::new()).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let frames = b"\0\0\0\x02a\n\0\0\0\x03b c";
+        let mut records = Vec::new();
+        let err = Records::new(&frames[..], RecordSeparator::LengthPrefixed)
+            .with_max_length(2)
+            .read_to_end(&mut records)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            (err.kind(), &records[..]),
+            (io::ErrorKind::InvalidData, &b"a\n"[..])
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[tokio::test]
+    async fn test_line_start_records() {
+        let pattern = regex::bytes::Regex::new(r"^\d{4}-\d{2}-\d{2} ").unwrap();
+        let logs = "2024-01-01 start\n\
+                    Error: failed\n  at main\r\n\
+                    2024-01-02 ok\n\
+                    2024-01-03 end";
+        assert_eq!(
+            read(logs, RecordSeparator::LineStart(pattern.clone())).await,
+            "2024-01-01 start Error: failed   at main\r\n2024-01-02 ok\n2024-01-03 end\n"
+        );
+        let orphan = "continued\n2024-01-01 a\n";
+        assert_eq!(
+            read(orphan, RecordSeparator::LineStart(pattern.clone())).await,
+            "continued\n2024-01-01 a\n"
+        );
+        let unterminated = "2024-01-01 a\nfoo";
+        assert_eq!(
+            read(unterminated, RecordSeparator::LineStart(pattern)).await,
+            "2024-01-01 a foo\n"
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use futures_util::{stream, Stream, StreamExt};
+use redis::{
+    aio::MultiplexedConnection,
+    streams::{StreamReadOptions, StreamReadReply},
+    AsyncCommands,
+};
+
+use crate::{message::message_line_words, ProcessorOptions, Tokenizer};
+
+/// Maximum number of entries read from each stream by a `XREAD` command.
+const READ_COUNT: usize = 512;
+
+/// Read the entries of Redis Streams and count the number of words for each line of their
+/// `field`, as soon as they are added. Each entry is identified by the name of its stream.
+///
+/// The streams are read from their first entry, then the new entries are awaited with a
+/// blocking `XREAD`. Each value is a line, and is split again on its line endings if it
+/// contains several lines. Entries without the field are ignored. The stream ends on the first
+/// error of the connection.
+pub fn read_redis_line_words<'a>(
+    connection: MultiplexedConnection,
+    streams: &'a [String],
+    field: &'a str,
+    options: ProcessorOptions,
+) -> impl Stream<Item = (&'a str, usize)> + 'a {
+    let last_ids = vec!["0".to_string(); streams.len()];
+    let replies = stream::unfold(
+        (connection, last_ids),
+        move |(mut connection, mut last_ids)| async move {
+            let read = StreamReadOptions::default().count(READ_COUNT).block(0);
+            let reply: Option<StreamReadReply> = connection
+                .xread_options(streams, &last_ids, &read)
+                .await
+                .inspect_err(|e| log::warn!("Could not read the Redis streams, {e}."))
+                .ok()?;
+            let counts = reply.map_or_else(Vec::new, |reply| {
+                reply_line_words(reply, streams, &mut last_ids, field, options.tokenizer)
+            });
+            Some((counts, (connection, last_ids)))
+        },
+    );
+    replies.flat_map(stream::iter)
+}
+
+/// Count the words of the lines of the entries of a `XREAD` reply, and move the last read ids of
+/// the streams after them.
+fn reply_line_words<'a>(
+    reply: StreamReadReply,
+    streams: &'a [String],
+    last_ids: &mut [String],
+    field: &str,
+    tokenizer: Tokenizer,
+) -> Vec<(&'a str, usize)> {
+    let mut counts = Vec::new();
+    for key in reply.keys {
+        let Some(index) = streams.iter().position(|stream| *stream == key.key) else {
+            continue;
+        };
+        let stream = streams[index].as_str();
+        for entry in key.ids {
+            match entry.get::<Vec<u8>>(field) {
+                Some(value) => counts.extend(message_line_words(stream, &value, tokenizer)),
+                None => log::debug!("Entry {} of {stream} has no field {field}.", entry.id),
+            }
+            last_ids[index] = entry.id;
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use redis::{
+        streams::{StreamId, StreamKey},
+        Value,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_reply_line_words() {
+        let entry = |id: &str, field: &str, value: &str| StreamId {
+            id: id.to_string(),
+            map: HashMap::from([(
+                field.to_string(),
+                Value::BulkString(value.as_bytes().to_vec()),
+            )]),
+            ..Default::default()
+        };
+        let reply = StreamReadReply {
+            keys: vec![
+                StreamKey {
+                    key: "logs".to_string(),
+                    ids: vec![
+                        entry("1-0", "message", "a b\nc"),
+                        entry("2-0", "other", "d"),
+                    ],
+                },
+                StreamKey {
+                    key: "audit".to_string(),
+                    ids: vec![entry("1-1", "message", "e f g")],
+                },
+            ],
+        };
+        let streams = ["audit".to_string(), "logs".to_string()];
+        let mut last_ids = vec!["0".to_string(); 2];
+        let counts = reply_line_words(
+            reply,
+            &streams,
+            &mut last_ids,
+            "message",
+            Tokenizer::Unicode,
+        );
+        assert_eq!(counts, [("logs", 2), ("logs", 1), ("audit", 3)]);
+        assert_eq!(last_ids, ["1-1", "2-0"]);
+    }
+}
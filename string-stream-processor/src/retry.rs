@@ -0,0 +1,82 @@
+use std::{io, time::Duration};
+
+/// Retry policy applied to each source when a read fails with a transient error.
+///
+/// The delay between two attempts starts at `backoff` and doubles after each failed attempt,
+/// up to `max_backoff`. The attempt counter is reset as soon as a line is successfully read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of consecutive retries before the source is dropped.
+    pub max_attempts: u32,
+    /// Delay before the first retry.
+    pub backoff: Duration,
+    /// Upper bound of the delay between two retries.
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// A policy that never retries, the first read error drops the source.
+    pub const NONE: Self = Self {
+        max_attempts: 0,
+        backoff: Duration::ZERO,
+        max_backoff: Duration::ZERO,
+    };
+
+    /// Create a policy with `max_attempts` retries and an exponential backoff starting at `backoff`.
+    pub fn new(max_attempts: u32, backoff: Duration) -> Self {
+        Self {
+            max_attempts,
+            backoff,
+            max_backoff: backoff.saturating_mul(1 << 6),
+        }
+    }
+
+    /// Set the upper bound of the delay between two retries.
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Delay to wait before the given attempt (starting at 1).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+
+    /// Returns true if the error is worth retrying.
+    pub fn is_transient(err: &io::Error) -> bool {
+        use io::ErrorKind::*;
+        matches!(
+            err.kind(),
+            WouldBlock
+                | Interrupted
+                | TimedOut
+                | ConnectionReset
+                | ConnectionAborted
+                | BrokenPipe
+                | UnexpectedEof
+        )
+    }
+}
+
+impl Default for RetryPolicy {
+    /// Three retries starting at 100ms.
+    fn default() -> Self {
+        Self::new(3, Duration::from_millis(100))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_backoff() {
+        let policy = RetryPolicy::new(5, Duration::from_millis(10))
+            .with_max_backoff(Duration::from_millis(50));
+        let delays: Vec<_> = (1..=5).map(|i| policy.delay(i).as_millis()).collect();
+        assert_eq!(delays, [10, 20, 40, 50, 50]);
+    }
+}
@@ -0,0 +1,118 @@
+use std::{collections::HashMap, io, pin::Pin};
+
+use aws_sdk_s3::Client;
+use futures_util::{stream, Stream, StreamExt};
+use tokio::io::AsyncBufRead;
+
+use crate::{
+    provider::{split_uri, ESTIMATED_LINE_LEN},
+    ProcessorOptions, SourceProvider,
+};
+
+/// Reader of the objects of an [`S3Provider`].
+#[cfg(not(feature = "compression"))]
+pub type S3Reader = Pin<Box<dyn AsyncBufRead + Send>>;
+/// Reader of the objects of an [`S3Provider`], decompressed according to their extension or
+/// their first bytes.
+#[cfg(feature = "compression")]
+pub type S3Reader = crate::Decompress<Pin<Box<dyn AsyncBufRead + Send>>>;
+
+/// Provider of the objects of an S3 bucket, identified by their `s3://bucket/key` URI.
+#[derive(Debug)]
+pub struct S3Provider {
+    client: Client,
+    bucket: String,
+    uris: Vec<String>,
+    options: ProcessorOptions,
+    /// Estimated number of lines of the objects, from their size.
+    lines_hints: HashMap<String, usize>,
+}
+
+impl S3Provider {
+    /// Provide the objects of the bucket whose key starts with `prefix`, sorted by key.
+    /// Keys ending with a `/` are folder markers and are skipped.
+    pub async fn list(
+        client: Client,
+        bucket: impl Into<String>,
+        prefix: impl Into<String>,
+        options: ProcessorOptions,
+    ) -> io::Result<Self> {
+        let bucket = bucket.into();
+        let mut pages = client
+            .list_objects_v2()
+            .bucket(&bucket)
+            .prefix(prefix)
+            .into_paginator()
+            .send();
+        let mut uris = Vec::new();
+        let mut lines_hints = HashMap::new();
+        while let Some(page) = pages.next().await {
+            let page = page.map_err(io::Error::other)?;
+            for object in page.contents() {
+                let Some(key) = object.key().filter(|key| !key.ends_with('/')) else {
+                    continue;
+                };
+                let uri = format!("s3://{bucket}/{key}");
+                let size = object.size().unwrap_or(0).max(0) as u64;
+                lines_hints.insert(uri.clone(), (size / ESTIMATED_LINE_LEN) as usize);
+                uris.push(uri);
+            }
+        }
+        Ok(Self {
+            client,
+            bucket,
+            uris,
+            options,
+            lines_hints,
+        })
+    }
+
+    /// Provide the objects under a `s3://bucket/prefix` URI, with the credentials and region of
+    /// the environment.
+    pub async fn from_uri(uri: &str, options: ProcessorOptions) -> io::Result<Self> {
+        let (bucket, prefix) = split_uri(uri, "s3").ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("invalid S3 URI {uri}"))
+        })?;
+        let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+        Self::list(Client::new(&config), bucket, prefix, options).await
+    }
+
+    /// URIs of the provided objects.
+    pub fn uris(&self) -> &[String] {
+        &self.uris
+    }
+
+    async fn open(&self, uri: &str) -> Option<S3Reader> {
+        let key = &uri["s3://".len() + self.bucket.len() + 1..];
+        let object = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .inspect_err(|e| log::warn!("Could not open {uri}, {e}, skipping it."))
+            .ok()?;
+        let rd: Pin<Box<dyn AsyncBufRead + Send>> =
+            Box::pin(self.options.buf_reader(object.body.into_async_read()));
+        #[cfg(feature = "compression")]
+        let rd = crate::Decompress::detect(key, rd, self.options.read_buffer_size)
+            .await
+            .inspect_err(|e| log::warn!("Could not read {uri}, {e}, skipping it."))
+            .ok()?;
+        Some(rd)
+    }
+}
+
+impl SourceProvider for S3Provider {
+    type Reader = S3Reader;
+
+    fn sources(&self) -> impl Stream<Item = (&str, Self::Reader)> {
+        stream::iter(&self.uris)
+            .filter_map(move |uri| async move { Some((uri.as_str(), self.open(uri).await?)) })
+    }
+
+    fn lines_hint(&self, id: &str) -> usize {
+        self.lines_hints.get(id).copied().unwrap_or(0)
+    }
+}
@@ -0,0 +1,309 @@
+use std::{collections::HashMap, io, path::PathBuf, sync::Arc};
+
+use futures_util::{stream, Stream, StreamExt};
+use russh::{
+    client::{self, Handle},
+    keys::{self, PrivateKeyWithHashAlg, PublicKeyOrCertificate},
+};
+use russh_sftp::client::{fs::File, SftpSession};
+use tokio::io::BufReader;
+
+use crate::{provider::ESTIMATED_LINE_LEN, ProcessorOptions, SourceProvider};
+
+/// Reader of the files of a [`SftpProvider`].
+#[cfg(not(feature = "compression"))]
+pub type SftpReader = BufReader<File>;
+/// Reader of the files of a [`SftpProvider`], decompressed according to their extension or
+/// their first bytes.
+#[cfg(feature = "compression")]
+pub type SftpReader = crate::Decompress<BufReader<File>>;
+
+/// Authentication method of [`SftpProvider::connect`].
+#[derive(Debug, Clone)]
+pub enum SftpAuth {
+    Password(String),
+    /// OpenSSH private key file, with the passphrase to decipher it if any.
+    Key {
+        path: PathBuf,
+        passphrase: Option<String>,
+    },
+}
+
+/// Provider of the regular files of a directory of an SFTP server, identified by their
+/// `sftp://host/path` URI.
+pub struct SftpProvider {
+    sftp: SftpSession,
+    /// Keeps the SSH connection open, if it was opened by the provider.
+    _ssh: Option<Handle<KnownHosts>>,
+    /// URI and remote path of the files.
+    files: Vec<(String, String)>,
+    options: ProcessorOptions,
+    /// Estimated number of lines of the files, from their size.
+    lines_hints: HashMap<String, usize>,
+}
+
+impl SftpProvider {
+    /// Connect to an SFTP server and provide the regular files of `dir` (not recursive).
+    ///
+    /// The key of the server must be listed in the `~/.ssh/known_hosts` file of the user.
+    pub async fn connect(
+        host: &str,
+        port: u16,
+        user: &str,
+        auth: SftpAuth,
+        dir: &str,
+        options: ProcessorOptions,
+    ) -> io::Result<Self> {
+        let handler = KnownHosts {
+            host: host.to_string(),
+            port,
+        };
+        let config = Arc::new(client::Config::default());
+        let mut ssh = client::connect(config, (host, port), handler)
+            .await
+            .map_err(io::Error::other)?;
+        let authenticated = match auth {
+            SftpAuth::Password(password) => ssh.authenticate_password(user, password).await,
+            SftpAuth::Key { path, passphrase } => {
+                let key = keys::load_secret_key(path, passphrase.as_deref())
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+                let hash = ssh
+                    .best_supported_rsa_hash()
+                    .await
+                    .map_err(io::Error::other)?;
+                let key = PrivateKeyWithHashAlg::new(Arc::new(key), hash.flatten());
+                ssh.authenticate_publickey(user, key).await
+            }
+        }
+        .map_err(io::Error::other)?;
+        if !authenticated.success() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("authentication of {user} on {host} failed"),
+            ));
+        }
+        let channel = ssh.channel_open_session().await.map_err(io::Error::other)?;
+        channel
+            .request_subsystem(true, "sftp")
+            .await
+            .map_err(io::Error::other)?;
+        let sftp = SftpSession::new(channel.into_stream())
+            .await
+            .map_err(io::Error::other)?;
+        let mut provider = Self::list(sftp, &format!("sftp://{host}"), dir, options).await?;
+        provider._ssh = Some(ssh);
+        Ok(provider)
+    }
+
+    /// Provide the regular files of `dir` (not recursive) of an opened SFTP session, sorted by
+    /// path. Their URI is their path prefixed by `base`, e.g. `sftp://host`.
+    pub async fn list(
+        sftp: SftpSession,
+        base: &str,
+        dir: &str,
+        options: ProcessorOptions,
+    ) -> io::Result<Self> {
+        let mut files = Vec::new();
+        let mut lines_hints = HashMap::new();
+        for entry in sftp.read_dir(dir).await.map_err(io::Error::other)? {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let path = entry.path();
+            let uri = if path.starts_with('/') {
+                format!("{base}{path}")
+            } else {
+                format!("{base}/{path}")
+            };
+            let lines = (entry.metadata().len() / ESTIMATED_LINE_LEN) as usize;
+            lines_hints.insert(uri.clone(), lines);
+            files.push((uri, path));
+        }
+        files.sort_unstable();
+        Ok(Self {
+            sftp,
+            _ssh: None,
+            files,
+            options,
+            lines_hints,
+        })
+    }
+
+    /// URIs of the provided files.
+    pub fn uris(&self) -> impl Iterator<Item = &str> {
+        self.files.iter().map(|(uri, _)| uri.as_str())
+    }
+
+    async fn open(&self, uri: &str, path: &str) -> Option<SftpReader> {
+        let file = self
+            .sftp
+            .open(path)
+            .await
+            .inspect_err(|e| log::warn!("Could not open {uri}, {e}, skipping it."))
+            .ok()?;
+        let rd = self.options.buf_reader(file);
+        #[cfg(feature = "compression")]
+        let rd = crate::Decompress::detect(path, rd, self.options.read_buffer_size)
+            .await
+            .inspect_err(|e| log::warn!("Could not read {uri}, {e}, skipping it."))
+            .ok()?;
+        Some(rd)
+    }
+}
+
+impl SourceProvider for SftpProvider {
+    type Reader = SftpReader;
+
+    fn sources(&self) -> impl Stream<Item = (&str, Self::Reader)> {
+        stream::iter(&self.files).filter_map(move |(uri, path)| async move {
+            Some((uri.as_str(), self.open(uri, path).await?))
+        })
+    }
+
+    fn lines_hint(&self, id: &str) -> usize {
+        self.lines_hints.get(id).copied().unwrap_or(0)
+    }
+}
+
+/// Accepts the server keys listed in the `known_hosts` file of the user.
+struct KnownHosts {
+    host: String,
+    port: u16,
+}
+
+impl client::Handler for KnownHosts {
+    type Error = russh::Error;
+
+    async fn check_server_key(
+        &mut self,
+        key: &PublicKeyOrCertificate,
+    ) -> Result<bool, Self::Error> {
+        match key {
+            PublicKeyOrCertificate::PublicKey { key, .. } => {
+                Ok(keys::check_known_hosts(&self.host, self.port, key)?)
+            }
+            PublicKeyOrCertificate::Certificate(_) => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use russh_sftp::{
+        protocol::{Data, File, FileAttributes, Handle, Name, OpenFlags, Status, StatusCode},
+        server,
+    };
+
+    use super::*;
+
+    /// In memory SFTP server with a `/logs` directory.
+    struct Server {
+        files: HashMap<&'static str, &'static [u8]>,
+        dir_read: bool,
+    }
+
+    impl server::Handler for Server {
+        type Error = StatusCode;
+
+        fn unimplemented(&self) -> Self::Error {
+            StatusCode::OpUnsupported
+        }
+
+        async fn opendir(&mut self, id: u32, handle: String) -> Result<Handle, Self::Error> {
+            Ok(Handle { id, handle })
+        }
+
+        async fn readdir(&mut self, id: u32, _handle: String) -> Result<Name, Self::Error> {
+            if std::mem::replace(&mut self.dir_read, true) {
+                return Err(StatusCode::Eof);
+            }
+            let file = |name: &str, size, permissions| {
+                let attrs = FileAttributes {
+                    size: Some(size),
+                    permissions: Some(permissions),
+                    ..Default::default()
+                };
+                File::new(name, attrs)
+            };
+            let files = vec![
+                file("b.log", 5, 0o100644),
+                file("a.log", 5, 0o100644),
+                file("archive", 0, 0o040755),
+            ];
+            Ok(Name { id, files })
+        }
+
+        async fn open(
+            &mut self,
+            id: u32,
+            filename: String,
+            _pflags: OpenFlags,
+            _attrs: FileAttributes,
+        ) -> Result<Handle, Self::Error> {
+            if !self.files.contains_key(filename.as_str()) {
+                return Err(StatusCode::NoSuchFile);
+            }
+            Ok(Handle {
+                id,
+                handle: filename,
+            })
+        }
+
+        async fn read(
+            &mut self,
+            id: u32,
+            handle: String,
+            offset: u64,
+            len: u32,
+        ) -> Result<Data, Self::Error> {
+            let data = self.files[handle.as_str()];
+            let start = offset as usize;
+            if start >= data.len() {
+                return Err(StatusCode::Eof);
+            }
+            let end = (start + len as usize).min(data.len());
+            Ok(Data {
+                id,
+                data: data[start..end].to_vec(),
+            })
+        }
+
+        async fn close(&mut self, id: u32, _handle: String) -> Result<Status, Self::Error> {
+            Ok(Status {
+                id,
+                status_code: StatusCode::Ok,
+                error_message: "Ok".to_string(),
+                language_tag: "en-US".to_string(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sftp_provider() {
+        let (client, server) = tokio::io::duplex(4096);
+        let files = HashMap::from([
+            ("/logs/a.log", &b"a b\nc"[..]),
+            ("/logs/b.log", &b"d e f"[..]),
+        ]);
+        server::run(
+            server,
+            Server {
+                files,
+                dir_read: false,
+            },
+        )
+        .await;
+        let sftp = SftpSession::new(client).await.unwrap();
+
+        let provider = SftpProvider::list(sftp, "sftp://host", "/logs", Default::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            provider.uris().collect::<Vec<_>>(),
+            ["sftp://host/logs/a.log", "sftp://host/logs/b.log"]
+        );
+        let result = provider.count_line_words(ProcessorOptions::default()).await;
+        assert_eq!(result["sftp://host/logs/a.log"], [2, 1]);
+        assert_eq!(result["sftp://host/logs/b.log"], [3]);
+    }
+}
@@ -0,0 +1,421 @@
+use std::{collections::VecDeque, error::Error, fmt, fmt::Display, future::Future, io, mem};
+
+use futures_util::{
+    future,
+    io::{AsyncBufRead, AsyncBufReadExt},
+    stream, Stream, StreamExt,
+};
+
+use crate::{
+    analyzer::Analyzer,
+    compat::TokioCompat,
+    pool::POOL,
+    tokenizer::{ByteWordCounter, Tokenizer},
+    ProcessorOptions, RetryPolicy,
+};
+
+/// Hook used to recreate a reader after a transient read error.
+pub(crate) trait Reconnect<I, R> {
+    /// Returns `None` if the source cannot be recreated and should be read again as is.
+    fn reconnect(&self, id: I) -> impl Future<Output = Option<io::Result<R>>>;
+}
+
+pub(crate) struct NoReconnect;
+impl<I, R> Reconnect<I, R> for NoReconnect {
+    async fn reconnect(&self, _id: I) -> Option<io::Result<R>> {
+        None
+    }
+}
+
+/// Reconnect hook of the Tokio based API, the new readers are wrapped in [`TokioCompat`].
+pub(crate) struct ReconnectFn<F>(pub F);
+impl<R, F, Fut> Reconnect<&str, TokioCompat<R>> for ReconnectFn<F>
+where
+    F: Fn(&str) -> Fut,
+    Fut: Future<Output = io::Result<R>>,
+{
+    async fn reconnect(&self, id: &str) -> Option<io::Result<TokioCompat<R>>> {
+        Some((self.0)(id).await.map(TokioCompat::new))
+    }
+}
+
+/// Read error which ended a source, with the word counts of the lines read before it, see
+/// [`count_line_words_concurrent_checked`](crate::StringMultiStreamExt::count_line_words_concurrent_checked).
+#[derive(Debug)]
+pub struct SourceError {
+    pub counts: Vec<usize>,
+    pub error: io::Error,
+}
+
+impl Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} after {} lines", self.error, self.counts.len())
+    }
+}
+
+impl Error for SourceError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Reader of a source, counting its bytes for its span with the `otel` feature.
+#[cfg(feature = "otel")]
+type SourceReader<R> = crate::otel::Metered<R>;
+#[cfg(not(feature = "otel"))]
+type SourceReader<R> = R;
+
+/// Reading state of a single source.
+struct SourceState<'r, R, H> {
+    rd: SourceReader<R>,
+    analyzer: Analyzer,
+    /// Line buffer reused across reads, for the tokenizers other than [`Tokenizer::Ascii`].
+    line: String,
+    /// Raw line buffer reused across reads, for the [`Analyzer::Dyn`] path.
+    bytes: Vec<u8>,
+    /// Word counter of the current line, for the [`Tokenizer::Ascii`] path.
+    counter: ByteWordCounter,
+    /// Lines waiting to be analyzed on the blocking pool, if enabled.
+    batch: Option<Batch>,
+    attempt: u32,
+    policy: RetryPolicy,
+    reconnect: &'r H,
+    #[cfg(feature = "otel")]
+    span: crate::otel::SourceSpan,
+}
+
+/// Returns a stream of the number of words for each line of the input.
+///
+/// Transient read errors are retried according to the policy, recreating the reader with the
+/// `reconnect` hook if provided. Once the attempts are exhausted the stream ends.
+/// The backoff delays and the blocking batches rely on the Tokio runtime.
+pub(crate) fn count_line_words_retrying<'r, I, R, H>(
+    src: (I, R),
+    options: ProcessorOptions,
+    reconnect: &'r H,
+) -> impl Stream<Item = (I, usize)> + 'r
+where
+    I: Copy + Display + 'r,
+    R: AsyncBufRead + Unpin + 'r,
+    H: Reconnect<I, R>,
+{
+    analyze_lines_retrying(
+        src,
+        options,
+        Analyzer::Tokenizer(options.tokenizer),
+        reconnect,
+    )
+}
+
+/// Same as [`count_line_words_retrying`] with the given per-line computation instead of the
+/// tokenizer of the options.
+pub(crate) fn analyze_lines_retrying<'r, I, R, H>(
+    src: (I, R),
+    options: ProcessorOptions,
+    analyzer: Analyzer,
+    reconnect: &'r H,
+) -> impl Stream<Item = (I, usize)> + 'r
+where
+    I: Copy + Display + 'r,
+    R: AsyncBufRead + Unpin + 'r,
+    H: Reconnect<I, R>,
+{
+    analyze_lines_fallible(src, options, analyzer, reconnect)
+        .filter_map(|(id, count)| future::ready(count.ok().map(|count| (id, count))))
+}
+
+/// Same as [`count_line_words_retrying`] but the error which ended the source, if any, is
+/// yielded as its last item.
+pub(crate) fn count_line_words_fallible<'r, I, R, H>(
+    src: (I, R),
+    options: ProcessorOptions,
+    reconnect: &'r H,
+) -> impl Stream<Item = (I, io::Result<usize>)> + 'r
+where
+    I: Copy + Display + 'r,
+    R: AsyncBufRead + Unpin + 'r,
+    H: Reconnect<I, R>,
+{
+    let analyzer = Analyzer::Tokenizer(options.tokenizer);
+    analyze_lines_fallible(src, options, analyzer, reconnect)
+}
+
+/// Counts of each line of a source read until its end, or the error which ended it, e.g. a
+/// partition of a file or a member of an archive.
+#[cfg(any(feature = "runtime", feature = "http"))]
+pub(crate) async fn try_count_line_words<I, R>(
+    src: (I, R),
+    options: ProcessorOptions,
+) -> io::Result<Vec<usize>>
+where
+    I: Copy + Display,
+    R: AsyncBufRead + Unpin,
+{
+    let mut lines = std::pin::pin!(count_line_words_fallible(src, options, &NoReconnect));
+    let mut counts = Vec::new();
+    while let Some((_, count)) = lines.next().await {
+        counts.push(count?);
+    }
+    Ok(counts)
+}
+
+/// Same as [`analyze_lines_retrying`] but the error which ended the source, if any, is yielded
+/// as its last item.
+#[cfg_attr(not(feature = "otel"), allow(clippy::useless_conversion))]
+fn analyze_lines_fallible<'r, I, R, H>(
+    (id, rd): (I, R),
+    options: ProcessorOptions,
+    analyzer: Analyzer,
+    reconnect: &'r H,
+) -> impl Stream<Item = (I, io::Result<usize>)> + 'r
+where
+    I: Copy + Display + 'r,
+    R: AsyncBufRead + Unpin + 'r,
+    H: Reconnect<I, R>,
+{
+    let inline = options.blocking_batch.is_none();
+    let unicode_lines = inline
+        && matches!(analyzer, Analyzer::Tokenizer(tokenizer) if tokenizer != Tokenizer::Ascii);
+    let dyn_lines = inline && matches!(analyzer, Analyzer::Dyn(_));
+    #[cfg_attr(not(feature = "otel"), allow(unused_mut))]
+    let mut state = SourceState {
+        rd: rd.into(),
+        analyzer,
+        line: if unicode_lines {
+            POOL.get_string()
+        } else {
+            String::new()
+        },
+        bytes: if dyn_lines { POOL.get() } else { Vec::new() },
+        counter: ByteWordCounter::default(),
+        batch: options.blocking_batch.map(Batch::new),
+        attempt: 0,
+        policy: options.retry,
+        reconnect,
+        #[cfg(feature = "otel")]
+        span: crate::otel::SourceSpan::new(id),
+    };
+    #[cfg(feature = "otel")]
+    if let Some(batch) = &mut state.batch {
+        batch.source = state.span.span().clone();
+    }
+    // The state is dropped once the source failed, after yielding its error.
+    stream::unfold(Some(state), move |state| async move {
+        let mut state = state?;
+        loop {
+            let err = match state.read_line_words().await {
+                Ok(None) => return None,
+                Ok(Some(count)) => {
+                    #[cfg(feature = "otel")]
+                    state.span.line();
+                    state.attempt = 0;
+                    return Some(((id, Ok(count)), Some(state)));
+                }
+                Err(e) => e,
+            };
+            if let Err(err) = state.retry(id, err).await {
+                #[cfg(feature = "otel")]
+                state.span.error(&err);
+                return Some(((id, Err(err)), None));
+            }
+        }
+    })
+}
+
+impl<R: AsyncBufRead + Unpin, H> SourceState<'_, R, H> {
+    /// Count the words of the next line, returns `None` at the end of the input.
+    ///
+    /// On error, the partially read line is kept and completed by the next read.
+    async fn read_line_words(&mut self) -> io::Result<Option<usize>> {
+        if let Some(batch) = &mut self.batch {
+            return batch.read_line_words(&mut self.rd, &self.analyzer).await;
+        }
+        match &self.analyzer {
+            Analyzer::Tokenizer(Tokenizer::Ascii) => self.counter.read_line(&mut self.rd).await,
+            Analyzer::Tokenizer(tokenizer) => {
+                if self.rd.read_line(&mut self.line).await? == 0 {
+                    return Ok(None);
+                }
+                let count = tokenizer.count_str_words(&self.line);
+                self.line.clear();
+                Ok(Some(count))
+            }
+            Analyzer::Dyn(_) => {
+                if self.rd.read_until(b'\n', &mut self.bytes).await? == 0 {
+                    return Ok(None);
+                }
+                let count = self.analyzer.analyze(&self.bytes);
+                self.bytes.clear();
+                count.map(Some)
+            }
+        }
+    }
+
+    /// Wait for the backoff delay and recreate the reader if possible.
+    /// Returns the last error if the source should be dropped.
+    #[cfg_attr(not(feature = "otel"), allow(clippy::useless_conversion))]
+    async fn retry<I>(&mut self, id: I, mut err: io::Error) -> io::Result<()>
+    where
+        I: Copy + Display,
+        H: Reconnect<I, R>,
+    {
+        loop {
+            if self.attempt >= self.policy.max_attempts || !RetryPolicy::is_transient(&err) {
+                log::warn!("Could not read {id}, {err}, dropping it.");
+                return Err(err);
+            }
+            self.attempt += 1;
+            log::debug!(
+                "Could not read {id}, {err}, retry attempt {}.",
+                self.attempt
+            );
+            // Without a runtime there is no timer, the source is retried right away.
+            #[cfg(feature = "runtime")]
+            tokio::time::sleep(self.policy.delay(self.attempt)).await;
+
+            match self.reconnect.reconnect(id).await {
+                None => return Ok(()),
+                Some(Ok(rd)) => {
+                    #[cfg(feature = "otel")]
+                    self.span.replaced(self.rd.bytes);
+                    self.rd = rd.into();
+                    self.line.clear();
+                    self.bytes.clear();
+                    self.counter = ByteWordCounter::default();
+                    if let Some(batch) = &mut self.batch {
+                        batch.reset();
+                    }
+                    return Ok(());
+                }
+                Some(Err(e)) => err = e,
+            }
+        }
+    }
+}
+
+impl<R, H> Drop for SourceState<'_, R, H> {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        self.span.finish(self.rd.bytes);
+        POOL.put_string(mem::take(&mut self.line));
+        POOL.put(mem::take(&mut self.bytes));
+    }
+}
+
+/// Batch of lines analyzed on the blocking thread pool.
+///
+/// Lines are read asynchronously into a single buffer, then their words are counted
+/// with `tokio::task::spawn_blocking` so that CPU-heavy analysis doesn't starve the reactor.
+struct Batch {
+    size: usize,
+    /// Content of the lines of the batch.
+    data: Vec<u8>,
+    /// End offset of each line in `data`.
+    ends: Vec<usize>,
+    /// Counts of the last analyzed batch, not yielded yet.
+    counts: VecDeque<usize>,
+    /// Error of the line of the last analyzed batch which failed, yielded after the counts of
+    /// the lines before it.
+    error: Option<io::Error>,
+    /// Span of the source, parent of the spans of the batches.
+    #[cfg(feature = "otel")]
+    source: tracing::Span,
+}
+
+impl Batch {
+    fn new(size: usize) -> Self {
+        Self {
+            size: size.max(1),
+            data: POOL.get(),
+            ends: Vec::new(),
+            counts: VecDeque::new(),
+            error: None,
+            #[cfg(feature = "otel")]
+            source: tracing::Span::none(),
+        }
+    }
+
+    /// Drop the lines read so far, the analyzed counts are kept.
+    fn reset(&mut self) {
+        self.data.clear();
+        self.ends.clear();
+    }
+
+    async fn read_line_words<R: AsyncBufRead + Unpin>(
+        &mut self,
+        rd: &mut R,
+        analyzer: &Analyzer,
+    ) -> io::Result<Option<usize>> {
+        if let Some(count) = self.next_count()? {
+            return Ok(Some(count));
+        }
+        // On error, the partially read line stays after the last end offset and is completed
+        // by the next read.
+        while self.ends.len() < self.size {
+            if rd.read_until(b'\n', &mut self.data).await? == 0 {
+                if self.ends.last().copied().unwrap_or(0) < self.data.len() {
+                    self.ends.push(self.data.len());
+                }
+                break;
+            }
+            self.ends.push(self.data.len());
+        }
+        if self.ends.is_empty() {
+            return Ok(None);
+        }
+
+        let (data, ends) = (mem::take(&mut self.data), mem::take(&mut self.ends));
+        #[cfg(feature = "otel")]
+        let span = crate::otel::BatchSpan::new(&self.source, ends.len(), data.len());
+        let analyzer = analyzer.clone();
+        let analyze = move || {
+            let (mut start, mut counts) = (0, VecDeque::with_capacity(ends.len()));
+            let mut error = None;
+            for &end in &ends {
+                let line = &data[mem::replace(&mut start, end)..end];
+                match analyzer.analyze(line) {
+                    Ok(count) => counts.push_back(count),
+                    Err(e) => {
+                        error = Some(e);
+                        break;
+                    }
+                }
+            }
+            (counts, error, data, ends)
+        };
+        #[cfg(feature = "runtime")]
+        let (counts, error, mut data, mut ends) = tokio::task::spawn_blocking(analyze)
+            .await
+            .map_err(io::Error::other)?;
+        // Without a runtime the batch is analyzed in place.
+        #[cfg(not(feature = "runtime"))]
+        let (counts, error, mut data, mut ends) = analyze();
+
+        // Keep the allocations for the next batch.
+        data.clear();
+        ends.clear();
+        (self.data, self.ends) = (data, ends);
+        #[cfg(feature = "otel")]
+        span.finish(error.is_some());
+        (self.counts, self.error) = (counts, error);
+        self.next_count()
+    }
+
+    /// Next count of the last analyzed batch, then its error once they are all yielded.
+    fn next_count(&mut self) -> io::Result<Option<usize>> {
+        match (self.counts.pop_front(), self.error.take()) {
+            (Some(count), error) => {
+                self.error = error;
+                Ok(Some(count))
+            }
+            (None, Some(e)) => Err(e),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+impl Drop for Batch {
+    fn drop(&mut self) {
+        POOL.put(mem::take(&mut self.data));
+    }
+}
@@ -0,0 +1,166 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    future::Future,
+    hash::{BuildHasher, Hash, RandomState},
+    io, panic,
+};
+
+use futures_util::{Stream, StreamExt};
+use tokio::{
+    io::AsyncBufRead,
+    task::{JoinError, JoinSet},
+};
+
+use crate::{
+    compat::TokioCompat,
+    source::{count_line_words_retrying, NoReconnect},
+    ProcessorOptions,
+};
+
+/// Extension trait for stream over async readers bound to an owned identifier.
+///
+/// Contrary to [`StringMultiStreamExt`](crate::StringMultiStreamExt), the sources can be moved
+/// to other tasks so that they are processed in parallel on a multi-threaded runtime.
+pub trait OwnedMultiStreamExt<K, R>: Stream<Item = (K, R)> + Sized
+where
+    K: Hash + Eq + Display + Send + Sync + 'static,
+    R: AsyncBufRead + Unpin + Send + 'static,
+{
+    /// Count the number of words from a stream of async readers and associated identifiers.
+    /// Returns a map of the identifier to a vector of word counts for each line.
+    ///
+    /// Each reader is spawned on its own task with [`tokio::spawn`] so this must be called
+    /// from within a Tokio runtime. The panic of a task is resumed, and a task cancelled before
+    /// its end is an error rather than missing counts.
+    fn count_line_words_spawned(
+        self,
+        options: ProcessorOptions,
+    ) -> impl Future<Output = io::Result<HashMap<K, Vec<usize>>>> {
+        count_line_words_spawned::<_, _, RandomState>(self, options)
+    }
+
+    /// Same as [`count_line_words_spawned`](Self::count_line_words_spawned) but the result map
+    /// uses the hasher `S`.
+    fn count_line_words_spawned_with_hasher<S: BuildHasher + Default>(
+        self,
+        options: ProcessorOptions,
+    ) -> impl Future<Output = io::Result<HashMap<K, Vec<usize>, S>>> {
+        count_line_words_spawned(self, options)
+    }
+}
+
+impl<K, R, S> OwnedMultiStreamExt<K, R> for S
+where
+    K: Hash + Eq + Display + Send + Sync + 'static,
+    R: AsyncBufRead + Unpin + Send + 'static,
+    S: Stream<Item = (K, R)>,
+{
+}
+
+/// Spawn a task per source and join their results.
+///
+/// Each task aggregates its own counts, so that identifiers are not cloned for each line.
+async fn count_line_words_spawned<K, R, S>(
+    rds: impl Stream<Item = (K, R)>,
+    options: ProcessorOptions,
+) -> io::Result<HashMap<K, Vec<usize>, S>>
+where
+    K: Hash + Eq + Display + Send + Sync + 'static,
+    R: AsyncBufRead + Unpin + Send + 'static,
+    S: BuildHasher + Default,
+{
+    let mut tasks = JoinSet::new();
+    let mut data: HashMap<K, Vec<usize>, S> = HashMap::default();
+    let mut rds = std::pin::pin!(rds);
+    while let Some((id, rd)) = rds.next().await {
+        if options
+            .max_concurrency
+            .is_some_and(|max| tasks.len() >= max)
+        {
+            if let Some(res) = tasks.join_next().await {
+                merge(&mut data, res)?;
+            }
+        }
+        tasks.spawn(async move {
+            let src = (&id, TokioCompat::new(rd));
+            let counts: Vec<usize> = count_line_words_retrying(src, options, &NoReconnect)
+                .map(|(_, count)| count)
+                .collect()
+                .await;
+            (id, counts)
+        });
+    }
+
+    while let Some(res) = tasks.join_next().await {
+        merge(&mut data, res)?;
+    }
+    Ok(data)
+}
+
+fn merge<K: Hash + Eq, S: BuildHasher>(
+    data: &mut HashMap<K, Vec<usize>, S>,
+    res: Result<(K, Vec<usize>), JoinError>,
+) -> io::Result<()> {
+    match res {
+        // Like the other modes, sources without any line are not part of the result.
+        Ok((_, counts)) if counts.is_empty() => (),
+        Ok((id, counts)) => data.entry(id).or_default().extend(counts),
+        Err(e) if e.is_panic() => panic::resume_unwind(e.into_panic()),
+        Err(e) => return Err(io::Error::other(e)),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use futures_util::stream;
+    use tokio::io::{AsyncRead, BufReader, ReadBuf};
+
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_count_line_words_spawned() {
+        let srcs = (0..20).map(|i| {
+            let data = "a b\nc d e\n".repeat(i);
+            (
+                format!("src{}", i % 10),
+                BufReader::new(io::Cursor::new(data)),
+            )
+        });
+        let result = stream::iter(srcs)
+            .count_line_words_spawned(ProcessorOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 10);
+        assert_eq!(result["src1"].len(), 2 * (1 + 11));
+        assert_eq!(result["src1"].iter().sum::<usize>(), 5 * (1 + 11));
+    }
+
+    /// Reader panicking when read.
+    struct Panicking;
+
+    impl AsyncRead for Panicking {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            _: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            panic!("the source panicked")
+        }
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "the source panicked")]
+    async fn test_count_line_words_spawned_panic() {
+        let srcs = [("a", BufReader::new(Panicking))];
+        let _ = stream::iter(srcs)
+            .count_line_words_spawned(ProcessorOptions::default())
+            .await;
+    }
+}
@@ -0,0 +1,377 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    hash::Hash,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    mem,
+    path::{Path, PathBuf},
+    slice,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::aggregate::Aggregate;
+
+/// Options of the spill-to-disk mode, see
+/// [`count_line_words_spilled`](crate::StringMultiStreamExt::count_line_words_spilled).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpillOptions {
+    /// Maximum size (in bytes) of the count vectors kept in memory.
+    pub memory_budget: usize,
+    /// Directory of the temporary spill file.
+    pub dir: PathBuf,
+}
+
+impl SpillOptions {
+    /// Spill to the system temporary directory once `memory_budget` bytes of counts are in memory.
+    pub fn new(memory_budget: usize) -> Self {
+        Self {
+            memory_budget,
+            dir: std::env::temp_dir(),
+        }
+    }
+
+    /// Set the directory of the temporary spill file.
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = dir.into();
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+struct Entry {
+    /// Offset and number of counts of each spilled segment, in order.
+    segments: Vec<(u64, usize)>,
+    /// Counts not spilled yet, after the spilled segments.
+    counts: Vec<usize>,
+}
+
+impl Entry {
+    fn len(&self) -> usize {
+        self.segments.iter().map(|(_, len)| len).sum::<usize>() + self.counts.len()
+    }
+}
+
+/// Spill file shared by all the identifiers, removed when dropped.
+#[derive(Debug)]
+struct SpillFile {
+    path: PathBuf,
+    writer: Option<BufWriter<File>>,
+    offset: u64,
+}
+
+impl SpillFile {
+    fn create(dir: &Path) -> io::Result<Self> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("ssp-{}-{id}.spill", std::process::id()));
+        let writer = BufWriter::new(File::create_new(&path)?);
+        Ok(Self {
+            path,
+            writer: Some(writer),
+            offset: 0,
+        })
+    }
+
+    /// Append the counts, returns their segment.
+    fn write(&mut self, counts: &[usize]) -> io::Result<(u64, usize)> {
+        let writer = self.writer.as_mut().expect("spill file is not finished");
+        for &count in counts {
+            writer.write_all(&(count as u64).to_le_bytes())?;
+        }
+        let segment = (self.offset, counts.len());
+        self.offset += (counts.len() * mem::size_of::<u64>()) as u64;
+        Ok(segment)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match self.writer.take() {
+            Some(writer) => writer.into_inner().map_err(|e| e.into_error())?.sync_data(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        self.writer = None;
+        if let Err(e) = fs::remove_file(&self.path) {
+            log::warn!("Could not remove spill file {}, {e}.", self.path.display());
+        }
+    }
+}
+
+/// Aggregation storage spilling the largest count vectors to disk above the memory budget.
+pub(crate) struct SpillStore<I> {
+    entries: HashMap<I, Entry>,
+    options: SpillOptions,
+    /// Number of counts currently in memory.
+    in_memory: usize,
+    file: Option<SpillFile>,
+    error: Option<io::Error>,
+}
+
+impl<I: Hash + Eq> SpillStore<I> {
+    pub fn new(options: SpillOptions) -> Self {
+        Self {
+            entries: HashMap::new(),
+            options,
+            in_memory: 0,
+            file: None,
+            error: None,
+        }
+    }
+
+    fn budget(&self) -> usize {
+        self.options.memory_budget / mem::size_of::<usize>()
+    }
+
+    /// Spill the largest vectors until half of the budget is free.
+    fn spill(&mut self) -> io::Result<()> {
+        let target = self.budget() / 2;
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => self.file.insert(SpillFile::create(&self.options.dir)?),
+        };
+        let mut entries: Vec<_> = self
+            .entries
+            .values_mut()
+            .filter(|e| !e.counts.is_empty())
+            .collect();
+        entries.sort_unstable_by_key(|e| std::cmp::Reverse(e.counts.len()));
+        for entry in entries {
+            if self.in_memory <= target {
+                break;
+            }
+            entry.segments.push(file.write(&entry.counts)?);
+            self.in_memory -= entry.counts.len();
+            // Release the memory of the vector, not only its content.
+            entry.counts = Vec::new();
+        }
+        log::debug!("Spilled counts to {}.", file.path.display());
+        Ok(())
+    }
+
+    /// Number of counts currently in memory.
+    pub fn in_memory(&self) -> usize {
+        self.in_memory
+    }
+
+    /// Add the counts of an identifier without any yet, spilling them if needed.
+    pub fn insert(&mut self, id: I, counts: Vec<usize>) {
+        self.in_memory += counts.len();
+        self.entries.entry(id).or_default().counts = counts;
+        self.check_budget();
+    }
+
+    /// Spill once the counts in memory exceed the budget.
+    fn check_budget(&mut self) {
+        // After an error, counts are kept in memory and the error is reported when finishing.
+        if self.in_memory > self.budget() && self.error.is_none() {
+            if let Err(e) = self.spill() {
+                log::error!("Could not spill counts to disk, {e}.");
+                self.error = Some(e);
+            }
+        }
+    }
+
+    /// Flush the spill file, returns the first error that occurred while spilling.
+    pub fn finish(mut self) -> io::Result<SpilledCounts<I>> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        if let Some(file) = &mut self.file {
+            file.finish()?;
+        }
+        Ok(SpilledCounts {
+            entries: self.entries,
+            file: self.file,
+        })
+    }
+}
+
+impl<I: Hash + Eq> Aggregate<I> for SpillStore<I> {
+    fn push(&mut self, id: I, count: usize) {
+        self.entries.entry(id).or_default().counts.push(count);
+        self.in_memory += 1;
+        self.check_budget();
+    }
+}
+
+/// Word counts of each identifier, partly stored in a temporary file.
+///
+/// The spilled counts are read back from the file when iterating, the file is removed when
+/// this is dropped.
+#[derive(Debug)]
+pub struct SpilledCounts<I> {
+    entries: HashMap<I, Entry>,
+    file: Option<SpillFile>,
+}
+
+impl<I: Hash + Eq> SpilledCounts<I> {
+    /// Number of identifiers.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if there is no identifier.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate over the identifiers.
+    pub fn ids(&self) -> impl Iterator<Item = &I> {
+        self.entries.keys()
+    }
+
+    /// Number of lines of an identifier.
+    pub fn lines(&self, id: &I) -> Option<usize> {
+        self.entries.get(id).map(Entry::len)
+    }
+
+    /// Iterate over the counts of an identifier, in line order.
+    pub fn counts(&self, id: &I) -> Option<io::Result<SpilledIter<'_>>> {
+        self.entries.get(id).map(|entry| self.iter_entry(entry))
+    }
+
+    /// Iterate over the identifiers and their counts.
+    pub fn iter(&self) -> impl Iterator<Item = (&I, io::Result<SpilledIter<'_>>)> {
+        self.entries
+            .iter()
+            .map(|(id, entry)| (id, self.iter_entry(entry)))
+    }
+
+    fn iter_entry<'s>(&'s self, entry: &'s Entry) -> io::Result<SpilledIter<'s>> {
+        let reader = match &self.file {
+            Some(file) if !entry.segments.is_empty() => {
+                Some(BufReader::new(File::open(&file.path)?))
+            }
+            _ => None,
+        };
+        Ok(SpilledIter {
+            reader,
+            segments: entry.segments.iter(),
+            remaining: 0,
+            counts: entry.counts.iter(),
+        })
+    }
+}
+
+/// Iterator over the counts of an identifier, see [`SpilledCounts::counts`].
+pub struct SpilledIter<'s> {
+    reader: Option<BufReader<File>>,
+    segments: slice::Iter<'s, (u64, usize)>,
+    /// Remaining counts to read in the current segment.
+    remaining: usize,
+    counts: slice::Iter<'s, usize>,
+}
+
+impl SpilledIter<'_> {
+    fn read_spilled(&mut self) -> io::Result<Option<usize>> {
+        let Some(reader) = &mut self.reader else {
+            return Ok(None);
+        };
+        while self.remaining == 0 {
+            let Some(&(offset, len)) = self.segments.next() else {
+                return Ok(None);
+            };
+            reader.seek(SeekFrom::Start(offset))?;
+            self.remaining = len;
+        }
+        let mut buf = [0; mem::size_of::<u64>()];
+        reader.read_exact(&mut buf)?;
+        self.remaining -= 1;
+        Ok(Some(u64::from_le_bytes(buf) as usize))
+    }
+}
+
+impl Iterator for SpilledIter<'_> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_spilled() {
+            Ok(Some(count)) => Some(Ok(count)),
+            Ok(None) => self.counts.next().copied().map(Ok),
+            Err(e) => {
+                // Don't yield anything after an error.
+                self.reader = None;
+                self.counts = [].iter();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<I: Hash + Eq + serde::Serialize> serde::Serialize for SpilledCounts<I> {
+    /// Serialize as a map of identifiers to counts, reading the spilled counts on the fly.
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::{Error, SerializeMap};
+
+        struct Counts<'s, I>(&'s SpilledCounts<I>, &'s Entry);
+        impl<I: Hash + Eq> serde::Serialize for Counts<'_, I> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                use serde::ser::SerializeSeq;
+                let iter = self.0.iter_entry(self.1).map_err(S::Error::custom)?;
+                let mut seq = serializer.serialize_seq(Some(self.1.len()))?;
+                for count in iter {
+                    seq.serialize_element(&count.map_err(S::Error::custom)?)?;
+                }
+                seq.end()
+            }
+        }
+
+        let mut map = serializer.serialize_map(Some(self.entries.len()))?;
+        for (id, entry) in &self.entries {
+            map.serialize_entry(id, &Counts(self, entry))?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spill_store() {
+        // Budget of 4 counts.
+        let options = SpillOptions::new(4 * mem::size_of::<usize>());
+        let mut store = SpillStore::new(options);
+        for i in 0..100 {
+            store.push(["a", "b", "c"][i % 3], i);
+        }
+        assert!(store.in_memory <= 4);
+        assert!(store.file.is_some());
+
+        let counts = store.finish().unwrap();
+        let path = counts.file.as_ref().unwrap().path.clone();
+        assert_eq!(counts.len(), 3);
+        for (i, id) in ["a", "b", "c"].iter().enumerate() {
+            let expected: Vec<usize> = (0..100).filter(|n| n % 3 == i).collect();
+            let actual: Vec<usize> = counts
+                .counts(id)
+                .unwrap()
+                .unwrap()
+                .map(Result::unwrap)
+                .collect();
+            assert_eq!(actual, expected);
+            assert_eq!(counts.lines(id), Some(expected.len()));
+        }
+        drop(counts);
+        assert!(!path.exists());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_spilled_counts_serialize() {
+        let mut store = SpillStore::new(SpillOptions::new(2 * mem::size_of::<usize>()));
+        for count in [1, 2, 3, 4, 5] {
+            store.push("a", count);
+        }
+        let counts = store.finish().unwrap();
+        assert_eq!(
+            serde_json::to_string(&counts).unwrap(),
+            r#"{"a":[1,2,3,4,5]}"#
+        );
+    }
+}
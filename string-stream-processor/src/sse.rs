@@ -0,0 +1,120 @@
+use std::io;
+
+use futures_util::{future, io::AsyncBufReadExt, Stream, TryStreamExt};
+use reqwest::{header, Client, Response};
+
+use crate::{
+    source::{count_line_words_retrying, NoReconnect},
+    ProcessorOptions,
+};
+
+/// Subscribe to a Server-Sent Events endpoint and count the number of words for each event, as
+/// soon as it is received, identified by the URL.
+///
+/// The data of each event is a line, split again on its line endings if it has several `data:`
+/// fields. The comments, the other fields and the events without data are ignored. The stream
+/// ends when the server closes the response, a request which fails or a response with an error
+/// status is an error.
+pub async fn connect_sse_line_words<'a>(
+    client: &Client,
+    url: &'a str,
+    options: ProcessorOptions,
+) -> io::Result<impl Stream<Item = (&'a str, usize)>> {
+    let response = client
+        .get(url)
+        .header(header::ACCEPT, "text/event-stream")
+        .send()
+        .await
+        .and_then(Response::error_for_status)
+        .map_err(io::Error::other)?;
+    let body = response.bytes_stream().map_err(io::Error::other);
+    let mut events = EventParser::default();
+    let lines = Box::pin(body)
+        .into_async_read()
+        .lines()
+        .try_filter_map(move |line| future::ok(events.push(&line)))
+        .into_async_read();
+    Ok(count_line_words_retrying(
+        (url, lines),
+        options,
+        &NoReconnect,
+    ))
+}
+
+/// Parser of the lines of an event stream.
+#[derive(Debug, Default)]
+struct EventParser {
+    /// Data of the current event, each `data:` field ended by a newline.
+    data: Vec<u8>,
+    /// Whether the current event has a `data:` field, possibly empty.
+    has_data: bool,
+}
+
+impl EventParser {
+    /// Parse a line without its line ending, returns the data of the event it ends, if any.
+    fn push(&mut self, line: &str) -> Option<Vec<u8>> {
+        if line.is_empty() {
+            let data = std::mem::take(&mut self.data);
+            return std::mem::take(&mut self.has_data).then_some(data);
+        }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        if field == "data" {
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            self.data.extend_from_slice(value.as_bytes());
+            self.data.push(b'\n');
+            self.has_data = true;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_event_parser() {
+        let mut events = EventParser::default();
+        let lines = [
+            ": comment",
+            "event: update",
+            "data: a b",
+            "data:c",
+            "",
+            "id: 1",
+            "",
+            "data",
+            "",
+        ];
+        let events: Vec<_> = lines.iter().filter_map(|line| events.push(line)).collect();
+        assert_eq!(events, [&b"a b\nc\n"[..], b"\n"]);
+    }
+
+    #[tokio::test]
+    async fn test_connect_sse_line_words() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/events", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let len = socket.read(&mut request).await.unwrap();
+            assert!(String::from_utf8_lossy(&request[..len]).contains("text/event-stream"));
+            let response = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\n\
+                            : ping\r\ndata: a b\r\n\r\ndata: c d e\r\ndata: f\r\n\r\ndata: g";
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let counts = connect_sse_line_words(&Client::new(), &url, Default::default())
+            .await
+            .unwrap();
+        let counts: Vec<_> = counts.map(|(_, count)| count).collect().await;
+        // The last event is not complete.
+        assert_eq!(counts, [2, 3, 1]);
+    }
+}
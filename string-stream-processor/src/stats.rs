@@ -0,0 +1,94 @@
+use crate::{aggregate::Accumulate, Aggregator};
+
+/// Statistics of the word counts of the lines of a source, computed in constant memory.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LineStats {
+    /// Number of lines.
+    pub lines: u64,
+    /// Total number of words.
+    pub words: u64,
+    /// Minimum number of words in a line, 0 if there is no line.
+    pub min: usize,
+    /// Maximum number of words in a line.
+    pub max: usize,
+    mean: f64,
+    /// Sum of squares of differences from the mean (Welford's algorithm).
+    m2: f64,
+}
+
+impl LineStats {
+    /// Mean number of words per line, 0 if there is no line.
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Population variance of the number of words per line.
+    pub fn variance(&self) -> f64 {
+        match self.lines {
+            0 => 0.0,
+            n => self.m2 / n as f64,
+        }
+    }
+
+    /// Population standard deviation of the number of words per line.
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+}
+
+impl Accumulate for LineStats {
+    fn push(&mut self, count: usize) {
+        self.min = if self.lines == 0 {
+            count
+        } else {
+            self.min.min(count)
+        };
+        self.max = self.max.max(count);
+        self.lines += 1;
+        self.words += count as u64;
+        let delta = count as f64 - self.mean;
+        self.mean += delta / self.lines as f64;
+        self.m2 += delta * (count as f64 - self.mean);
+    }
+}
+
+impl Aggregator for LineStats {
+    type Output = Self;
+
+    fn update(&mut self, count: usize) {
+        self.push(count);
+    }
+
+    fn finish(self) -> Self {
+        self
+    }
+}
+
+impl FromIterator<usize> for LineStats {
+    fn from_iter<T: IntoIterator<Item = usize>>(counts: T) -> Self {
+        let mut stats = Self::default();
+        for count in counts {
+            stats.push(count);
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_stats() {
+        let mut stats = LineStats::default();
+        for count in [2, 4, 4, 4, 5, 5, 7, 9] {
+            stats.push(count);
+        }
+        assert_eq!(
+            (stats.lines, stats.words, stats.min, stats.max),
+            (8, 40, 2, 9)
+        );
+        assert_eq!(stats.mean(), 5.0);
+        assert_eq!(stats.std_dev(), 2.0);
+    }
+}
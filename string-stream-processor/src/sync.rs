@@ -0,0 +1,170 @@
+//! Blocking API over [`std::io::BufRead`], for consumers without a Tokio runtime.
+//!
+//! Lines are split into words with the same [`Tokenizer`] rules as the async API.
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    hash::Hash,
+    io::{self, BufRead},
+    mem,
+    sync::{Mutex, PoisonError},
+    thread,
+};
+
+use crate::{pool::POOL, tokenizer::ByteWordCounter, ProcessorOptions, RetryPolicy, Tokenizer};
+
+/// Iterator over the number of words for each line of a blocking reader,
+/// see [`count_line_words`].
+pub struct LineWords<R> {
+    rd: R,
+    tokenizer: Tokenizer,
+    /// Line buffer reused across reads, for the tokenizers other than [`Tokenizer::Ascii`].
+    line: String,
+    /// Word counter of the current line, for the [`Tokenizer::Ascii`] path.
+    counter: ByteWordCounter,
+}
+
+/// Returns an iterator of the number of words for each line of the input.
+///
+/// On error, the partially read line is kept and completed by the next call to `next`.
+pub fn count_line_words<R: BufRead>(rd: R, tokenizer: Tokenizer) -> LineWords<R> {
+    LineWords {
+        rd,
+        tokenizer,
+        line: match tokenizer {
+            Tokenizer::Ascii => String::new(),
+            _ => POOL.get_string(),
+        },
+        counter: ByteWordCounter::default(),
+    }
+}
+
+impl<R: BufRead> LineWords<R> {
+    fn read_line_words(&mut self) -> io::Result<Option<usize>> {
+        match self.tokenizer {
+            Tokenizer::Ascii => self.counter.read_line_blocking(&mut self.rd),
+            _ => {
+                if self.rd.read_line(&mut self.line)? == 0 {
+                    return Ok(None);
+                }
+                let count = self.tokenizer.count_str_words(&self.line);
+                self.line.clear();
+                Ok(Some(count))
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for LineWords<R> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_line_words().transpose()
+    }
+}
+
+impl<R> Drop for LineWords<R> {
+    fn drop(&mut self) {
+        POOL.put_string(mem::take(&mut self.line));
+    }
+}
+
+/// Count the number of words for each line of each source on `threads` threads.
+///
+/// With a single thread, the sources are read on the current thread. Transient read errors are
+/// retried on the same reader according to the retry policy of the options, other errors drop the
+/// rest of the source.
+/// Returns a map of identifiers to the word counts of their lines.
+pub fn count_line_words_concurrent<I, R>(
+    srcs: impl IntoIterator<Item = (I, R), IntoIter: Send>,
+    options: ProcessorOptions,
+    threads: usize,
+) -> HashMap<I, Vec<usize>>
+where
+    I: Hash + Eq + Display + Send,
+    R: BufRead + Send,
+{
+    let srcs = Mutex::new(srcs.into_iter());
+    let next_src = || srcs.lock().unwrap_or_else(PoisonError::into_inner).next();
+    let work = || {
+        let mut result = HashMap::new();
+        while let Some((id, rd)) = next_src() {
+            let counts = count_source(&id, rd, &options);
+            if !counts.is_empty() {
+                result.insert(id, counts);
+            }
+        }
+        result
+    };
+    if threads <= 1 {
+        return work();
+    }
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads).map(|_| scope.spawn(work)).collect();
+        let mut result = HashMap::new();
+        for worker in workers {
+            // Panics of a worker are propagated.
+            result.extend(
+                worker
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e)),
+            );
+        }
+        result
+    })
+}
+
+/// Count the words of each line of a source, retrying transient errors.
+fn count_source<I: Display>(id: &I, rd: impl BufRead, options: &ProcessorOptions) -> Vec<usize> {
+    let lines = count_line_words(rd, options.tokenizer);
+    let mut counts = Vec::new();
+    let mut attempt = 0;
+    for res in lines {
+        match res {
+            Ok(count) => {
+                attempt = 0;
+                counts.push(count);
+            }
+            Err(err) if attempt < options.retry.max_attempts && RetryPolicy::is_transient(&err) => {
+                attempt += 1;
+                log::debug!("Could not read {id}, {err}, retry attempt {attempt}.");
+                thread::sleep(options.retry.delay(attempt));
+            }
+            Err(err) => {
+                log::warn!("Could not read {id}, {err}, dropping it.");
+                break;
+            }
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_count_line_words() {
+        for tokenizer in [Tokenizer::Unicode, Tokenizer::Ascii] {
+            let counts: Vec<usize> =
+                count_line_words(Cursor::new("Hello world\n\nfoo bar baz"), tokenizer)
+                    .map(Result::unwrap)
+                    .collect();
+            assert_eq!(counts, [2, 0, 3]);
+        }
+    }
+
+    #[test]
+    fn test_count_line_words_concurrent() {
+        let srcs = (0..20).map(|i| (i, Cursor::new("a b\nc\n".repeat(i))));
+        for threads in [1, 4] {
+            let result =
+                count_line_words_concurrent(srcs.clone(), ProcessorOptions::default(), threads);
+            assert_eq!(result.len(), 19);
+            assert_eq!(result[&3], [2, 1].repeat(3));
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use std::io;
+
+use futures_util::{stream, Stream, StreamExt};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt},
+    net::{TcpListener, UdpSocket},
+};
+
+use crate::{message::message_line_words, ProcessorOptions};
+
+/// Maximum size of a syslog datagram.
+const MAX_DATAGRAM_SIZE: usize = 64 * 1024;
+
+/// Receive syslog messages (RFC 3164 or RFC 5424) on a UDP socket and count the number of
+/// words for each line of their text, as soon as they are received. Each message is identified
+/// by `host/program`, `-` standing for a missing field. The stream never ends.
+pub fn count_udp_syslog_line_words(
+    socket: UdpSocket,
+    options: ProcessorOptions,
+) -> impl Stream<Item = (String, usize)> {
+    let datagrams = stream::unfold(
+        (socket, vec![0; MAX_DATAGRAM_SIZE]),
+        move |(socket, mut buf)| async move {
+            let counts = match socket.recv(&mut buf).await {
+                Ok(len) => syslog_line_words(&buf[..len], options),
+                Err(e) => {
+                    log::warn!("Could not receive a syslog message, {e}, skipping it.");
+                    Vec::new()
+                }
+            };
+            Some((counts, (socket, buf)))
+        },
+    );
+    datagrams.flat_map(stream::iter)
+}
+
+/// Accept syslog connections on a TCP listener, with newline delimited or octet counted
+/// (RFC 6587) messages, and count the number of words for each line of their text as soon as
+/// they are received. See [`count_udp_syslog_line_words`] for the identifiers.
+pub fn count_tcp_syslog_line_words(
+    listener: TcpListener,
+    options: ProcessorOptions,
+) -> impl Stream<Item = (String, usize)> {
+    let connections = stream::unfold(listener, |listener| async move {
+        let connection = listener
+            .accept()
+            .await
+            .inspect_err(|e| log::warn!("Could not accept a connection, {e}, skipping it."));
+        Some((connection, listener))
+    });
+    connections
+        .filter_map(|connection| async move { connection.ok() })
+        .flat_map_unordered(options.max_concurrency, move |(socket, peer)| {
+            let messages = stream::unfold(options.buf_reader(socket), move |mut rd| async move {
+                match read_frame(&mut rd).await {
+                    Ok(Some(frame)) => Some((syslog_line_words(&frame, options), rd)),
+                    Ok(None) => None,
+                    Err(e) => {
+                        log::warn!("Could not read {peer}, {e}, dropping it.");
+                        None
+                    }
+                }
+            });
+            Box::pin(messages.flat_map(stream::iter))
+        })
+}
+
+/// Read the next message of a syslog connection, `None` at the end of the connection.
+async fn read_frame(rd: &mut (impl AsyncBufRead + Unpin)) -> io::Result<Option<Vec<u8>>> {
+    let octet_counted = match rd.fill_buf().await?.first() {
+        None => return Ok(None),
+        Some(b) => b.is_ascii_digit(),
+    };
+    let mut frame = Vec::new();
+    if octet_counted {
+        rd.read_until(b' ', &mut frame).await?;
+        let len = std::str::from_utf8(&frame)
+            .ok()
+            .and_then(|len| len.trim_end().parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid frame length"))?;
+        frame.clear();
+        rd.take(len).read_to_end(&mut frame).await?;
+    } else {
+        rd.read_until(b'\n', &mut frame).await?;
+    }
+    Ok(Some(frame))
+}
+
+/// Count the words of the lines of the text of a syslog message, identified by its host and
+/// program.
+fn syslog_line_words(message: &[u8], options: ProcessorOptions) -> Vec<(String, usize)> {
+    match parse_syslog(message) {
+        Some((host, program, text)) => {
+            let id = format!("{}/{}", host.unwrap_or("-"), program.unwrap_or("-"));
+            message_line_words(id, text, options.tokenizer)
+        }
+        None => {
+            log::warn!("Could not parse a syslog message, dropping it.");
+            Vec::new()
+        }
+    }
+}
+
+/// Split a RFC 5424 or RFC 3164 message into its host, program and text.
+fn parse_syslog(message: &[u8]) -> Option<(Option<&str>, Option<&str>, &[u8])> {
+    let message = message.strip_prefix(b"<")?;
+    let end = message.iter().position(|&b| b == b'>')?;
+    if end == 0 || !message[..end].iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let message = &message[end + 1..];
+    match message.strip_prefix(b"1 ") {
+        Some(message) => parse_rfc5424(message),
+        None => Some(parse_rfc3164(message)),
+    }
+}
+
+/// `TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA [MSG]`, after the version.
+fn parse_rfc5424(message: &[u8]) -> Option<(Option<&str>, Option<&str>, &[u8])> {
+    let mut fields = message.splitn(6, |&b| b == b' ');
+    let _timestamp = fields.next()?;
+    let host = nil_field(fields.next()?);
+    let program = nil_field(fields.next()?);
+    let _procid = fields.next()?;
+    let _msgid = fields.next()?;
+    let mut rest = fields.next().unwrap_or_default();
+    if let Some(after) = rest.strip_prefix(b"-") {
+        rest = after;
+    } else {
+        // Structured data elements, `]` is escaped as `\]` in their values.
+        while rest.first() == Some(&b'[') {
+            let mut escaped = false;
+            let end = rest.iter().position(|&b| {
+                let end = b == b']' && !escaped;
+                escaped = b == b'\\' && !escaped;
+                end
+            })?;
+            rest = &rest[end + 1..];
+        }
+    }
+    let text = rest.strip_prefix(b" ").unwrap_or(rest);
+    Some((
+        host,
+        program,
+        text.strip_prefix(b"\xef\xbb\xbf").unwrap_or(text),
+    ))
+}
+
+/// `Mmm dd hh:mm:ss HOSTNAME TAG[PID]: MSG`, the header is optional.
+fn parse_rfc3164(message: &[u8]) -> (Option<&str>, Option<&str>, &[u8]) {
+    let is_timestamp = message.len() > 16 && message[3] == b' ' && message[15] == b' ';
+    if !is_timestamp {
+        return (None, None, message);
+    }
+    let rest = &message[16..];
+    let Some(space) = rest.iter().position(|&b| b == b' ') else {
+        return (None, None, message);
+    };
+    let host = std::str::from_utf8(&rest[..space]).ok();
+    let rest = &rest[space + 1..];
+    match rest.iter().position(|&b| b == b':') {
+        Some(colon) if !rest[..colon].contains(&b' ') => {
+            let tag = &rest[..colon];
+            let program = &tag[..tag.iter().position(|&b| b == b'[').unwrap_or(tag.len())];
+            let text = &rest[colon + 1..];
+            let program = std::str::from_utf8(program).ok();
+            (host, program, text.strip_prefix(b" ").unwrap_or(text))
+        }
+        _ => (host, None, rest),
+    }
+}
+
+fn nil_field(field: &[u8]) -> Option<&str> {
+    match field {
+        b"-" => None,
+        field => std::str::from_utf8(field).ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{io::AsyncWriteExt, net::TcpStream};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_syslog() {
+        let message = b"<34>Oct 11 22:14:15 mymachine su[42]: 'su root' failed";
+        assert_eq!(
+            parse_syslog(message),
+            Some((Some("mymachine"), Some("su"), &b"'su root' failed"[..]))
+        );
+        let message = b"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 \
+            [exampleSDID@32473 iut=\"3\" eventID=\"1011\\]\"] An application event";
+        assert_eq!(
+            parse_syslog(message),
+            Some((
+                Some("mymachine.example.com"),
+                Some("evntslog"),
+                &b"An application event"[..]
+            ))
+        );
+        let message = b"<13>1 - - - - - - hello";
+        assert_eq!(parse_syslog(message), Some((None, None, &b"hello"[..])));
+        assert_eq!(
+            parse_syslog(b"<13>hello"),
+            Some((None, None, &b"hello"[..]))
+        );
+        assert_eq!(parse_syslog(b"hello"), None);
+    }
+
+    #[tokio::test]
+    async fn test_syslog_line_words() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let mut counts = Box::pin(count_udp_syslog_line_words(socket, Default::default()));
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client
+            .send_to(b"<34>Oct 11 22:14:15 host app: a b c", addr)
+            .await
+            .unwrap();
+        assert_eq!(counts.next().await, Some(("host/app".to_string(), 3)));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut counts = Box::pin(count_tcp_syslog_line_words(listener, Default::default()));
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client
+            .write_all(b"<13>1 - host app - - - a b\n22 <13>1 - host - - - - c")
+            .await
+            .unwrap();
+        drop(client);
+        assert_eq!(counts.next().await, Some(("host/app".to_string(), 2)));
+        assert_eq!(counts.next().await, Some(("host/-".to_string(), 1)));
+    }
+}
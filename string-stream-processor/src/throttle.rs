@@ -0,0 +1,241 @@
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use pin_project_lite::pin_project;
+use tokio::{
+    io::{AsyncBufRead, AsyncRead, ReadBuf},
+    time::{Instant, Sleep},
+};
+
+/// Maximum rate at which a [`Throttle`] reader is read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimit {
+    BytesPerSec(u64),
+    LinesPerSec(u64),
+}
+
+impl RateLimit {
+    fn rate(self) -> f64 {
+        match self {
+            RateLimit::BytesPerSec(rate) | RateLimit::LinesPerSec(rate) => rate.max(1) as f64,
+        }
+    }
+
+    /// Budget waited for once it is exhausted: a line, or a tenth of second of bytes rather than
+    /// reading them one at a time.
+    fn min_grant(self) -> f64 {
+        match self {
+            RateLimit::BytesPerSec(_) => (self.rate() / 10.0).max(1.0),
+            RateLimit::LinesPerSec(_) => 1.0,
+        }
+    }
+}
+
+/// Token bucket allowing bursts of one second of data.
+#[derive(Debug)]
+struct Bucket {
+    /// Bytes or lines which can be read right away, refilled over time.
+    budget: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit) -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(Self {
+            budget: limit.rate(),
+            refilled: Instant::now(),
+        }))
+    }
+
+    /// Take up to `wanted` tokens once at least the minimum grant of the limit is available,
+    /// otherwise returns the delay until it is.
+    fn take(&mut self, limit: RateLimit, wanted: usize) -> Result<usize, Duration> {
+        let (rate, min_grant) = (limit.rate(), limit.min_grant());
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled).as_secs_f64();
+        self.budget = (self.budget + elapsed * rate).min(rate);
+        self.refilled = now;
+        if self.budget < min_grant {
+            return Err(Duration::from_secs_f64((min_grant - self.budget) / rate));
+        }
+        let taken = wanted.min(self.budget as usize);
+        self.budget -= taken as f64;
+        Ok(taken)
+    }
+}
+
+/// Rate limit shared by several [`Throttle`] readers, e.g. a cap on the total throughput of the
+/// concurrent sources so that they do not starve the other processes using the same disks.
+#[derive(Debug, Clone)]
+pub struct SharedRateLimit {
+    limit: RateLimit,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+impl SharedRateLimit {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            bucket: Bucket::new(limit),
+        }
+    }
+}
+
+pin_project! {
+    /// Reader limiting the rate at which a source is read, e.g. to not saturate a shared link or
+    /// overload the server of a network source.
+    ///
+    /// The limit is a token bucket allowing bursts of one second of data. In lines mode, the
+    /// lines are handed out one at a time and a token is taken for each of them. The tokens are
+    /// taken when the data is handed out, so that the readers sharing a limit do not exceed it.
+    #[derive(Debug)]
+    pub struct Throttle<R> {
+        #[pin]
+        inner: R,
+        limit: RateLimit,
+        bucket: Arc<Mutex<Bucket>>,
+        sleep: Pin<Box<Sleep>>,
+        // Length of the data handed out and not consumed yet.
+        granted: usize,
+    }
+}
+
+impl<R> Throttle<R> {
+    pub fn new(inner: R, limit: RateLimit) -> Self {
+        Self::shared(inner, &SharedRateLimit::new(limit))
+    }
+
+    /// Reader taking its tokens from a limit shared with other readers.
+    pub fn shared(inner: R, limit: &SharedRateLimit) -> Self {
+        Self {
+            inner,
+            limit: limit.limit,
+            bucket: limit.bucket.clone(),
+            sleep: Box::pin(tokio::time::sleep_until(Instant::now())),
+            granted: 0,
+        }
+    }
+
+    /// Returns the wrapped reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: AsyncBufRead> AsyncBufRead for Throttle<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let mut this = self.project();
+        // The end of the input is not delayed.
+        if ready!(this.inner.as_mut().poll_fill_buf(cx))?.is_empty() {
+            return Poll::Ready(Ok(&[]));
+        }
+        // The rest of the last grant is handed out right away.
+        while *this.granted == 0 {
+            let data = ready!(this.inner.as_mut().poll_fill_buf(cx))?;
+            let taken = match this.limit {
+                RateLimit::BytesPerSec(_) => {
+                    this.bucket.lock().unwrap().take(*this.limit, data.len())
+                }
+                RateLimit::LinesPerSec(_) => match data.iter().position(|&b| b == b'\n') {
+                    Some(end) => this
+                        .bucket
+                        .lock()
+                        .unwrap()
+                        .take(*this.limit, 1)
+                        .map(|_| end + 1),
+                    // The start of a line is free, its token is taken with its end.
+                    None => Ok(data.len()),
+                },
+            };
+            match taken {
+                Ok(granted) => *this.granted = granted,
+                Err(wait) => {
+                    this.sleep.as_mut().reset(Instant::now() + wait);
+                    ready!(this.sleep.as_mut().poll(cx));
+                }
+            }
+        }
+        let data = ready!(this.inner.poll_fill_buf(cx))?;
+        Poll::Ready(Ok(&data[..*this.granted]))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.project();
+        let amt = amt.min(*this.granted);
+        *this.granted -= amt;
+        this.inner.consume(amt);
+    }
+}
+
+impl<R: AsyncBufRead> AsyncRead for Throttle<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let data = ready!(self.as_mut().poll_fill_buf(cx))?;
+        let len = data.len().min(buf.remaining());
+        buf.put_slice(&data[..len]);
+        self.consume(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncBufReadExt;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_throttle() {
+        let data = "a\nb\nc\nd\ne\n".as_bytes();
+        let start = Instant::now();
+        let mut lines = Throttle::new(data, RateLimit::LinesPerSec(2)).lines();
+        let mut read = Vec::new();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            read.push((line, start.elapsed().as_millis()));
+        }
+        // A burst of 2 lines, then a line every 500 ms.
+        let read: Vec<_> = read.iter().map(|(line, ms)| (line.as_str(), *ms)).collect();
+        assert_eq!(
+            read,
+            [("a", 0), ("b", 0), ("c", 500), ("d", 1000), ("e", 1500)]
+        );
+
+        let start = Instant::now();
+        let mut rd = Throttle::new(&[0; 1000][..], RateLimit::BytesPerSec(400));
+        let mut data = Vec::new();
+        tokio::io::AsyncReadExt::read_to_end(&mut rd, &mut data)
+            .await
+            .unwrap();
+        assert_eq!(data.len(), 1000);
+        assert_eq!(start.elapsed().as_millis(), 1500);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shared_throttle() {
+        let start = Instant::now();
+        let limit = SharedRateLimit::new(RateLimit::BytesPerSec(400));
+        let read = |len| {
+            let mut rd = Throttle::shared(io::Cursor::new(vec![0; len]), &limit);
+            async move {
+                let mut data = Vec::new();
+                tokio::io::AsyncReadExt::read_to_end(&mut rd, &mut data)
+                    .await
+                    .unwrap();
+                data.len()
+            }
+        };
+        let lens = tokio::join!(read(600), read(600));
+        assert_eq!(lens, (600, 600));
+        // 1200 bytes at 400 bytes per second after a burst of 400 bytes.
+        assert_eq!(start.elapsed().as_millis(), 2000);
+    }
+}
@@ -0,0 +1,202 @@
+use std::{io, sync::Arc};
+
+use rustls::{
+    crypto::ring,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, ServerName},
+    ClientConfig, RootCertStore,
+};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::{client::TlsStream, TlsConnector};
+
+/// TLS configuration of the connections to the remote sources, e.g. log forwarders only
+/// accepting encrypted connections.
+///
+/// The server certificates are verified with the Mozilla root certificates by default, or with
+/// the given root certificates only.
+#[derive(Debug, Default)]
+pub struct TlsOptions {
+    root_certs: Vec<CertificateDer<'static>>,
+    client_auth: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+}
+
+impl TlsOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trust the root certificates of a PEM file content instead of the Mozilla ones, e.g. the
+    /// certificate of a private authority.
+    pub fn with_root_certs(mut self, pem: &[u8]) -> io::Result<Self> {
+        for cert in CertificateDer::pem_slice_iter(pem) {
+            self.root_certs.push(cert.map_err(invalid_pem)?);
+        }
+        if self.root_certs.is_empty() {
+            return Err(invalid_pem("no certificate found"));
+        }
+        Ok(self)
+    }
+
+    /// Authenticate to the servers with the certificate chain and the private key of PEM file
+    /// contents.
+    pub fn with_client_auth(mut self, cert_chain: &[u8], key: &[u8]) -> io::Result<Self> {
+        let certs = CertificateDer::pem_slice_iter(cert_chain)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(invalid_pem)?;
+        let key = PrivateKeyDer::from_pem_slice(key).map_err(invalid_pem)?;
+        self.client_auth = Some((certs, key));
+        Ok(self)
+    }
+
+    /// Configuration of the client connections.
+    pub(crate) fn client_config(&self) -> io::Result<Arc<ClientConfig>> {
+        let mut roots = RootCertStore::empty();
+        if self.root_certs.is_empty() {
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        } else {
+            for cert in &self.root_certs {
+                roots.add(cert.clone()).map_err(io::Error::other)?;
+            }
+        }
+        let builder = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(io::Error::other)?
+            .with_root_certificates(roots);
+        let config = match &self.client_auth {
+            Some((certs, key)) => builder
+                .with_client_auth_cert(certs.clone(), key.clone_key())
+                .map_err(io::Error::other)?,
+            None => builder.with_no_client_auth(),
+        };
+        Ok(Arc::new(config))
+    }
+
+    /// Open a TLS connection to `server_name` over a stream, e.g. a TCP connection, the name
+    /// being verified against the certificate of the server.
+    pub async fn connect<S>(&self, server_name: &str, stream: S) -> io::Result<TlsStream<S>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let server_name = ServerName::try_from(server_name.to_owned())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        TlsConnector::from(self.client_config()?)
+            .connect(server_name, stream)
+            .await
+    }
+}
+
+fn invalid_pem(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+    use rustls::{server::WebPkiClientVerifier, ServerConfig};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+    use tokio_rustls::TlsAcceptor;
+
+    use super::*;
+
+    /// PEM certificates and keys of an authority, a `localhost` server and a client signed by it.
+    pub(crate) struct TestPki {
+        pub ca: String,
+        pub server: (String, String),
+        pub client: (String, String),
+    }
+
+    impl TestPki {
+        pub fn new() -> Self {
+            let ca_key = KeyPair::generate().unwrap();
+            let mut params = CertificateParams::new(Vec::new()).unwrap();
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            let ca = params.self_signed(&ca_key).unwrap();
+            let signed = |name: &str| {
+                let key = KeyPair::generate().unwrap();
+                let params = CertificateParams::new(vec![name.to_string()]).unwrap();
+                let cert = params.signed_by(&key, &ca, &ca_key).unwrap();
+                (cert.pem(), key.serialize_pem())
+            };
+            Self {
+                server: signed("localhost"),
+                client: signed("client"),
+                ca: ca.pem(),
+            }
+        }
+
+        /// Acceptor of the server, requiring a client certificate signed by the authority.
+        pub fn acceptor(&self) -> TlsAcceptor {
+            let provider = Arc::new(ring::default_provider());
+            let mut roots = RootCertStore::empty();
+            roots
+                .add(CertificateDer::from_pem_slice(self.ca.as_bytes()).unwrap())
+                .unwrap();
+            let verifier =
+                WebPkiClientVerifier::builder_with_provider(roots.into(), provider.clone())
+                    .build()
+                    .unwrap();
+            let certs = CertificateDer::pem_slice_iter(self.server.0.as_bytes())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            let key = PrivateKeyDer::from_pem_slice(self.server.1.as_bytes()).unwrap();
+            let config = ServerConfig::builder_with_provider(provider)
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_client_cert_verifier(verifier)
+                .with_single_cert(certs, key)
+                .unwrap();
+            TlsAcceptor::from(Arc::new(config))
+        }
+
+        pub fn client_options(&self) -> TlsOptions {
+            TlsOptions::new()
+                .with_root_certs(self.ca.as_bytes())
+                .unwrap()
+                .with_client_auth(self.client.0.as_bytes(), self.client.1.as_bytes())
+                .unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tls_connect() {
+        let pki = TestPki::new();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let acceptor = pki.acceptor();
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                if let Ok(mut tls) = acceptor.accept(socket).await {
+                    tls.write_all(b"a b\n").await.unwrap();
+                    tls.shutdown().await.unwrap();
+                }
+            }
+        });
+
+        let socket = TcpStream::connect(addr).await.unwrap();
+        let mut tls = pki
+            .client_options()
+            .connect("localhost", socket)
+            .await
+            .unwrap();
+        let mut data = String::new();
+        tls.read_to_string(&mut data).await.unwrap();
+        assert_eq!(data, "a b\n");
+
+        // Without the client certificate nor the authority.
+        let without_auth = TlsOptions::new()
+            .with_root_certs(pki.ca.as_bytes())
+            .unwrap();
+        for options in [without_auth, TlsOptions::new()] {
+            let socket = TcpStream::connect(addr).await.unwrap();
+            let connected = async {
+                let mut tls = options.connect("localhost", socket).await?;
+                tls.read_to_string(&mut String::new()).await
+            };
+            assert!(connected.await.is_err());
+        }
+        assert!(TlsOptions::new().with_root_certs(b"").is_err());
+    }
+}
@@ -0,0 +1,320 @@
+use std::io;
+
+use futures_util::io::{AsyncBufRead, AsyncBufReadExt};
+
+use crate::emoji::split_emoji;
+
+/// Rules used to split lines into words.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Tokenizer {
+    /// Words are separated by Unicode whitespace. Lines must be valid UTF-8.
+    #[default]
+    Unicode,
+    /// Words are separated by ASCII whitespace. Lines are scanned as raw bytes in the reader
+    /// buffer, without UTF-8 validation nor copy.
+    ///
+    /// This is the fast path, it gives the same counts as [`Tokenizer::Unicode`] as long as
+    /// the input doesn't contain non-ASCII whitespace.
+    Ascii,
+    /// Words are separated by the given characters, and by the line endings. Lines must be
+    /// valid UTF-8. See [`Tokenizer::custom`].
+    Custom(&'static [char]),
+    /// Words are separated by Unicode whitespace, and each emoji sequence is a word of its own,
+    /// the ZWJ sequences, flags, keycaps and skin tones included: `hi👋🏽!` is `hi`, `👋🏽` and
+    /// `!`. Lines must be valid UTF-8.
+    Emoji,
+    /// Words are the word-like segments of the ICU word break rules, with the dictionaries and
+    /// models of the languages written without spaces like Thai, Lao, Khmer or Japanese. The
+    /// punctuation and the symbols are not words. Lines must be valid UTF-8.
+    #[cfg(feature = "icu")]
+    Icu,
+}
+
+/// Word segmenter of [`Tokenizer::Icu`], with the compiled data of all the languages.
+#[cfg(feature = "icu")]
+static SEGMENTER: std::sync::LazyLock<icu_segmenter::WordSegmenterBorrowed<'static>> =
+    std::sync::LazyLock::new(|| icu_segmenter::WordSegmenter::new_auto(Default::default()));
+
+impl Tokenizer {
+    /// Tokenizer splitting the words on the given characters, e.g. the ASCII whitespace and the
+    /// no-break space `U+00A0` but not the other Unicode spaces.
+    ///
+    /// The characters are leaked so that the tokenizer, and the options holding it, stay
+    /// [`Copy`]: it is meant to be created once for a run.
+    pub fn custom(whitespace: impl IntoIterator<Item = char>) -> Self {
+        let mut whitespace: Vec<char> = whitespace.into_iter().collect();
+        whitespace.sort_unstable();
+        whitespace.dedup();
+        Tokenizer::Custom(whitespace.leak())
+    }
+
+    /// Whether a character separates the words. The [`Tokenizer::Emoji`] words are also
+    /// separated by the emoji, the [`Tokenizer::Icu`] ones by the punctuation.
+    pub fn is_whitespace(&self, c: char) -> bool {
+        match self {
+            Tokenizer::Unicode | Tokenizer::Emoji => c.is_whitespace(),
+            #[cfg(feature = "icu")]
+            Tokenizer::Icu => c.is_whitespace(),
+            Tokenizer::Ascii => c.is_ascii() && is_ascii_space(c as u8),
+            Tokenizer::Custom(whitespace) => whitespace.binary_search(&c).is_ok(),
+        }
+    }
+
+    /// Words of a line, with or without its line ending.
+    pub fn split_words(self, line: &str) -> Box<dyn Iterator<Item = &str> + '_> {
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match self {
+            Tokenizer::Emoji => Box::new(line.split_whitespace().flat_map(split_emoji)),
+            #[cfg(feature = "icu")]
+            Tokenizer::Icu => {
+                let mut start = 0;
+                let segments = SEGMENTER.segment_str(line).iter_with_word_type();
+                Box::new(segments.filter_map(move |(end, word_type)| {
+                    let word = &line[std::mem::replace(&mut start, end)..end];
+                    word_type.is_word_like().then_some(word)
+                }))
+            }
+            _ => Box::new(
+                line.split(move |c| self.is_whitespace(c))
+                    .filter(|word| !word.is_empty()),
+            ),
+        }
+    }
+
+    /// Count the words of a line read as text, with or without its line ending.
+    pub(crate) fn count_str_words(&self, line: &str) -> usize {
+        match self {
+            Tokenizer::Unicode => line.split_whitespace().count(),
+            #[cfg(feature = "icu")]
+            Tokenizer::Icu => SEGMENTER
+                .segment_str(line)
+                .iter_with_word_type()
+                .filter(|(_, word_type)| word_type.is_word_like())
+                .count(),
+            _ => self.split_words(line).count(),
+        }
+    }
+
+    /// Count the words of a line.
+    pub(crate) fn count_words(&self, line: &[u8]) -> io::Result<usize> {
+        match self {
+            Tokenizer::Ascii => Ok(count_word_starts(false, line)),
+            _ => std::str::from_utf8(line)
+                .map(|line| self.count_str_words(line))
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        }
+    }
+}
+
+/// Returns true for the ASCII characters considered as whitespace by [`char::is_whitespace`].
+#[inline]
+pub(crate) fn is_ascii_space(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\n' | b'\x0B' | b'\x0C' | b'\r')
+}
+
+/// Position of the first newline in the buffer.
+#[inline]
+fn find_newline(buf: &[u8]) -> Option<usize> {
+    #[cfg(feature = "simd")]
+    return memchr::memchr(b'\n', buf);
+    #[cfg(not(feature = "simd"))]
+    return buf.iter().position(|&b| b == b'\n');
+}
+
+/// Number of words starting in `bytes`, `in_word` tells if the previous byte was part of a word.
+#[cfg(not(feature = "simd"))]
+fn count_word_starts(mut in_word: bool, bytes: &[u8]) -> usize {
+    let mut words = 0;
+    for &b in bytes {
+        if is_ascii_space(b) {
+            in_word = false;
+        } else if !in_word {
+            words += 1;
+            in_word = true;
+        }
+    }
+    words
+}
+
+/// Number of words starting in `bytes`, `in_word` tells if the previous byte was part of a word.
+///
+/// A word starts at each non-whitespace byte preceded by a whitespace byte. The transitions are
+/// counted branchless in blocks of 255 bytes accumulated in a `u8`, so that the loop is vectorized.
+#[cfg(feature = "simd")]
+fn count_word_starts(in_word: bool, bytes: &[u8]) -> usize {
+    let Some(&first) = bytes.first() else {
+        return 0;
+    };
+    let mut words = (!in_word && !is_ascii_space(first)) as usize;
+    let (prev, next) = (&bytes[..bytes.len() - 1], &bytes[1..]);
+    for (prev, next) in prev.chunks(255).zip(next.chunks(255)) {
+        let starts = prev
+            .iter()
+            .zip(next)
+            .map(|(&p, &n)| (is_ascii_space(p) & !is_ascii_space(n)) as u8)
+            .fold(0u8, u8::wrapping_add);
+        words += starts as usize;
+    }
+    words
+}
+
+/// Incremental word counter over the raw bytes of a line.
+///
+/// The line may be fed in several chunks, words spanning two chunks are counted once.
+#[derive(Debug, Default)]
+pub(crate) struct ByteWordCounter {
+    words: usize,
+    in_word: bool,
+    /// Some bytes of the current line have been fed.
+    pending: bool,
+}
+
+impl ByteWordCounter {
+    /// Feed some bytes of the current line.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        let Some(&last) = bytes.last() else {
+            return;
+        };
+        self.words += count_word_starts(self.in_word, bytes);
+        self.in_word = !is_ascii_space(last);
+        self.pending = true;
+    }
+
+    /// Returns the word count of the current line and start a new one.
+    pub fn finish(&mut self) -> usize {
+        std::mem::take(self).words
+    }
+
+    /// Feed `buf` up to the next newline.
+    ///
+    /// Returns the number of bytes to consume and the word count if the line is complete.
+    fn feed_line(&mut self, buf: &[u8]) -> (usize, Option<usize>) {
+        match find_newline(buf) {
+            Some(pos) => {
+                self.feed(&buf[..pos]);
+                (pos + 1, Some(self.finish()))
+            }
+            None => {
+                self.feed(buf);
+                (buf.len(), None)
+            }
+        }
+    }
+
+    /// Count the words of the next line directly from the reader buffer.
+    ///
+    /// Returns `None` at the end of the input. On error, the state of the current line is kept
+    /// so that it can be resumed by the next call.
+    pub async fn read_line<R: AsyncBufRead + Unpin>(
+        &mut self,
+        rd: &mut R,
+    ) -> io::Result<Option<usize>> {
+        loop {
+            let buf = rd.fill_buf().await?;
+            if buf.is_empty() {
+                return Ok(self.pending.then(|| self.finish()));
+            }
+            let (consumed, words) = self.feed_line(buf);
+            rd.consume_unpin(consumed);
+            if words.is_some() {
+                return Ok(words);
+            }
+        }
+    }
+
+    /// Same as [`ByteWordCounter::read_line`] for a blocking reader.
+    pub fn read_line_blocking<R: io::BufRead>(&mut self, rd: &mut R) -> io::Result<Option<usize>> {
+        loop {
+            let buf = rd.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(self.pending.then(|| self.finish()));
+            }
+            let (consumed, words) = self.feed_line(buf);
+            rd.consume(consumed);
+            if words.is_some() {
+                return Ok(words);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::io::BufReader;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_byte_word_counter() {
+        const DATA: &[u8] = b"Hello  world\n\n\tfoo bar\r\nbaz\xFF qux";
+        // A small buffer makes words span several chunks.
+        let mut rd = BufReader::with_capacity(3, DATA);
+        let mut counter = ByteWordCounter::default();
+        let mut counts = Vec::new();
+        while let Some(count) = counter.read_line(&mut rd).await.unwrap() {
+            counts.push(count);
+        }
+        assert_eq!(counts, [2, 0, 2, 2]);
+    }
+
+    #[test]
+    fn test_custom() {
+        let tokenizer = Tokenizer::custom([' ', '\u{a0}', ' ']);
+        assert_eq!(tokenizer, Tokenizer::Custom(&[' ', '\u{a0}']));
+        let line = "a\u{a0}b\u{2003}c\td \r\n";
+        assert_eq!(tokenizer.count_words(line.as_bytes()).unwrap(), 2);
+        assert_eq!(
+            tokenizer.split_words(line).collect::<Vec<_>>(),
+            ["a", "b\u{2003}c\td"]
+        );
+        assert_eq!(Tokenizer::Unicode.count_str_words(line), 4);
+        assert_eq!(Tokenizer::Ascii.split_words(line).count(), 2);
+        assert!(Tokenizer::custom([]).count_words(b"\xff").is_err());
+    }
+
+    #[test]
+    fn test_emoji() {
+        let line = "so cool😎🇫🇷 👍🏽👍🏽\n";
+        assert_eq!(Tokenizer::Emoji.count_words(line.as_bytes()).unwrap(), 6);
+        assert_eq!(Tokenizer::Unicode.count_words(line.as_bytes()).unwrap(), 3);
+        assert_eq!(
+            Tokenizer::Emoji.split_words("👩‍💻coding").collect::<Vec<_>>(),
+            ["👩‍💻", "coding"]
+        );
+    }
+
+    #[cfg(feature = "icu")]
+    #[test]
+    fn test_icu() {
+        let tokenizer = Tokenizer::Icu;
+        assert_eq!(
+            tokenizer.count_words("Hello, world!\n".as_bytes()).unwrap(),
+            2
+        );
+        assert_eq!(tokenizer.count_words("ทุกสองสัปดาห์".as_bytes()).unwrap(), 3);
+        assert_eq!(
+            tokenizer
+                .split_words("こんにちは世界\r\n")
+                .collect::<Vec<_>>(),
+            ["こんにちは", "世界"]
+        );
+        assert_eq!(
+            Tokenizer::Unicode
+                .count_words("ทุกสองสัปดาห์".as_bytes())
+                .unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_count_word_starts() {
+        let data: Vec<u8> = (0..2000u32)
+            .map(|i| b" a\tbc\r"[(i * i % 7) as usize % 6])
+            .collect();
+        let expected = String::from_utf8_lossy(&data).split_whitespace().count();
+        assert_eq!(count_word_starts(false, &data), expected);
+        assert_eq!(count_word_starts(true, b"a b"), 1);
+        assert_eq!(count_word_starts(false, b""), 0);
+    }
+}
@@ -0,0 +1,127 @@
+use std::{
+    future::Future,
+    io, mem,
+    path::Path,
+    pin::Pin,
+    rc::Rc,
+    task::{ready, Context, Poll},
+};
+
+use tokio::io::{AsyncBufRead, AsyncRead, ReadBuf};
+use tokio_uring::fs::File;
+
+use crate::DEFAULT_READ_BUFFER_SIZE;
+
+type ReadFuture = Pin<Box<dyn Future<Output = (io::Result<usize>, Vec<u8>)>>>;
+
+/// Buffered async reader over a local file, backed by `io_uring`.
+///
+/// It can be used anywhere a Tokio [`AsyncBufRead`] is expected, but it must be polled from
+/// within the `tokio-uring` runtime (see [`tokio_uring::start`]).
+pub struct UringReader {
+    file: Rc<File>,
+    /// File offset of the next read.
+    offset: u64,
+    buf: Vec<u8>,
+    /// Consumed bytes of `buf`.
+    pos: usize,
+    capacity: usize,
+    eof: bool,
+    read: Option<ReadFuture>,
+}
+
+impl UringReader {
+    /// Open the file at the given path with the default buffer capacity.
+    pub async fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::with_capacity(
+            DEFAULT_READ_BUFFER_SIZE,
+            File::open(path).await?,
+        ))
+    }
+
+    /// Create a reader with the given buffer capacity.
+    pub fn with_capacity(capacity: usize, file: File) -> Self {
+        Self {
+            file: Rc::new(file),
+            offset: 0,
+            buf: Vec::new(),
+            pos: 0,
+            capacity: capacity.max(1),
+            eof: false,
+            read: None,
+        }
+    }
+}
+
+impl AsyncBufRead for UringReader {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        if this.pos < this.buf.len() || this.eof {
+            return Poll::Ready(Ok(&this.buf[this.pos..]));
+        }
+
+        let read = match &mut this.read {
+            Some(read) => read,
+            None => {
+                let file = this.file.clone();
+                let offset = this.offset;
+                let mut buf = mem::take(&mut this.buf);
+                buf.clear();
+                buf.reserve(this.capacity);
+                this.read
+                    .insert(Box::pin(async move { file.read_at(buf, offset).await }))
+            }
+        };
+        let (res, buf) = ready!(read.as_mut().poll(cx));
+        this.read = None;
+        this.buf = buf;
+        this.pos = 0;
+        let read = res?;
+        this.eof = read == 0;
+        this.offset += read as u64;
+        Poll::Ready(Ok(&this.buf))
+    }
+
+    fn consume(mut self: Pin<&mut Self>, amt: usize) {
+        self.pos = (self.pos + amt).min(self.buf.len());
+    }
+}
+
+impl AsyncRead for UringReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let data = ready!(self.as_mut().poll_fill_buf(cx))?;
+        let len = data.len().min(buf.remaining());
+        buf.put_slice(&data[..len]);
+        self.consume(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::stream;
+
+    use super::*;
+    use crate::{ProcessorOptions, StringMultiStreamExt, Tokenizer};
+
+    #[test]
+    fn test_uring_reader() {
+        let path = std::env::temp_dir().join("ssp_test_uring_reader.txt");
+        std::fs::write(&path, "Hello world\n\nfoo bar baz\n".repeat(100)).unwrap();
+        tokio_uring::start(async {
+            let file = File::open(&path).await.unwrap();
+            // A small buffer makes lines span several reads.
+            let rd = UringReader::with_capacity(7, file);
+            let options = ProcessorOptions::default().with_tokenizer(Tokenizer::Ascii);
+            let result = stream::iter([("a", rd)])
+                .count_line_words_concurrent_with(options)
+                .await;
+            assert_eq!(result["a"], [2, 0, 3].repeat(100));
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+}
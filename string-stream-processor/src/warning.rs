@@ -0,0 +1,215 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use pin_project_lite::pin_project;
+use tokio::io::{AsyncBufRead, AsyncRead, ReadBuf};
+
+/// Kind of a non-fatal anomaly of the data of a source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarningKind {
+    /// The line is not valid UTF-8, which ends the source with the Unicode tokenizers.
+    InvalidUtf8,
+    /// The last line has no line ending, e.g. a file still being written.
+    TruncatedLine,
+    /// The line contains a NUL byte, the source is likely binary. Reported once per source.
+    Binary,
+}
+
+/// Non-fatal anomaly of a source, reported while it is read, see
+/// [`count_line_words_with_warnings`](crate::StringMultiStreamExt::count_line_words_with_warnings).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Warning<'a> {
+    pub id: &'a str,
+    /// Number of the line, starting at 1.
+    pub line: usize,
+    pub kind: WarningKind,
+}
+
+/// Incremental detection of the anomalies of the lines of a source.
+#[derive(Debug)]
+struct LineScanner {
+    line: usize,
+    /// Whether the current line has content not followed by a line ending yet.
+    started: bool,
+    invalid: bool,
+    /// Start of a UTF-8 sequence split between two reads.
+    partial: Vec<u8>,
+    binary: bool,
+    finished: bool,
+}
+
+impl LineScanner {
+    fn new() -> Self {
+        Self {
+            line: 1,
+            started: false,
+            invalid: false,
+            partial: Vec::new(),
+            binary: false,
+            finished: false,
+        }
+    }
+
+    fn update(&mut self, data: &[u8], mut emit: impl FnMut(usize, WarningKind)) {
+        for segment in data.split_inclusive(|&b| b == b'\n') {
+            let (content, ended) = match segment.strip_suffix(b"\n") {
+                Some(content) => (content, true),
+                None => (segment, false),
+            };
+            self.started = true;
+            if !self.binary && content.contains(&0) {
+                self.binary = true;
+                emit(self.line, WarningKind::Binary);
+            }
+            if !self.invalid {
+                self.check_utf8(content);
+            }
+            if ended {
+                self.end_line(&mut emit);
+            }
+        }
+    }
+
+    fn check_utf8(&mut self, content: &[u8]) {
+        if self.partial.is_empty() && content.is_ascii() {
+            return;
+        }
+        self.partial.extend_from_slice(content);
+        match std::str::from_utf8(&self.partial) {
+            Ok(_) => self.partial.clear(),
+            // The end of the content is the start of a sequence, completed by the next read.
+            Err(e) if e.error_len().is_none() => {
+                self.partial.drain(..e.valid_up_to());
+            }
+            Err(_) => {
+                self.invalid = true;
+                self.partial.clear();
+            }
+        }
+    }
+
+    fn end_line(&mut self, emit: &mut impl FnMut(usize, WarningKind)) {
+        if self.invalid || !self.partial.is_empty() {
+            emit(self.line, WarningKind::InvalidUtf8);
+        }
+        self.started = false;
+        self.invalid = false;
+        self.partial.clear();
+        self.line += 1;
+    }
+
+    /// Report the anomalies of the last line once the end of the source is read.
+    fn finish(&mut self, mut emit: impl FnMut(usize, WarningKind)) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        if self.started {
+            emit(self.line, WarningKind::TruncatedLine);
+            self.end_line(&mut emit);
+        }
+    }
+}
+
+pin_project! {
+    /// Reader reporting the [`Warning`]s of the source it reads to a callback.
+    pub(crate) struct Warnings<'a, 'h, R, F> {
+        #[pin]
+        inner: R,
+        id: &'a str,
+        on_warning: &'h F,
+        scanner: LineScanner,
+        // Length of the start of the buffer of the inner reader already scanned.
+        seen: usize,
+    }
+}
+
+impl<'a, 'h, R, F> Warnings<'a, 'h, R, F> {
+    pub fn new(inner: R, id: &'a str, on_warning: &'h F) -> Self {
+        Self {
+            inner,
+            id,
+            on_warning,
+            scanner: LineScanner::new(),
+            seen: 0,
+        }
+    }
+}
+
+impl<'a, R: AsyncBufRead, F: Fn(Warning<'a>)> AsyncBufRead for Warnings<'a, '_, R, F> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.project();
+        let data = ready!(this.inner.poll_fill_buf(cx))?;
+        let (id, on_warning) = (*this.id, *this.on_warning);
+        let emit = |line, kind| on_warning(Warning { id, line, kind });
+        if data.is_empty() {
+            this.scanner.finish(emit);
+        } else if let Some(new) = data.get(*this.seen..).filter(|new| !new.is_empty()) {
+            this.scanner.update(new, emit);
+            *this.seen = data.len();
+        }
+        Poll::Ready(Ok(data))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.project();
+        *this.seen = this.seen.saturating_sub(amt);
+        this.inner.consume(amt);
+    }
+}
+
+impl<'a, R: AsyncBufRead, F: Fn(Warning<'a>)> AsyncRead for Warnings<'a, '_, R, F> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let data = ready!(self.as_mut().poll_fill_buf(cx))?;
+        let len = data.len().min(buf.remaining());
+        buf.put_slice(&data[..len]);
+        self.consume(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use tokio::io::{AsyncReadExt, BufReader};
+
+    use super::*;
+
+    async fn warnings(data: &'static [u8]) -> Vec<(usize, WarningKind)> {
+        let warnings = RefCell::new(Vec::new());
+        let on_warning =
+            |warning: Warning| warnings.borrow_mut().push((warning.line, warning.kind));
+        // A small buffer splits the lines and the UTF-8 sequences between reads.
+        let mut rd = Warnings::new(BufReader::with_capacity(3, data), "a", &on_warning);
+        rd.read_to_end(&mut Vec::new()).await.unwrap();
+        drop(rd);
+        warnings.into_inner()
+    }
+
+    #[tokio::test]
+    async fn test_warnings() {
+        assert_eq!(warnings("a b\nété\n".as_bytes()).await, []);
+        assert_eq!(warnings(b"").await, []);
+        assert_eq!(
+            warnings(b"ok\ncaf\xe9\na\0b\n\0\nlast").await,
+            [
+                (2, WarningKind::InvalidUtf8),
+                (3, WarningKind::Binary),
+                (5, WarningKind::TruncatedLine)
+            ]
+        );
+        // A sequence cut by the end of the line.
+        assert_eq!(
+            warnings(b"ab\xe2\x82\n").await,
+            [(1, WarningKind::InvalidUtf8)]
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use std::{
+    collections::HashMap,
+    io::{self, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use futures_util::StreamExt;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+    sync::mpsc,
+};
+
+use crate::{count_source_line_words, ProcessorOptions};
+
+/// Word counts of local files, updated as the files change.
+///
+/// Each file is read once at creation, then only the region appended since the last read is
+/// processed on each change and merged into the counts of the file. A partial last line is only
+/// counted once its line ending is written. A file truncated below its read offset (e.g.
+/// rotated) is processed again from its start.
+#[derive(Debug)]
+pub struct FileWatcher {
+    files: Vec<WatchedFile>,
+    results: HashMap<String, Vec<usize>>,
+    options: ProcessorOptions,
+    events: mpsc::UnboundedReceiver<notify::Result<Event>>,
+    _watcher: notify::RecommendedWatcher,
+}
+
+#[derive(Debug)]
+struct WatchedFile {
+    path: String,
+    /// Canonical path, to match the paths of the events.
+    canonical: Option<PathBuf>,
+    /// Offset after the last counted line.
+    offset: u64,
+}
+
+impl FileWatcher {
+    /// Count the lines of the files and watch them. Files that cannot be opened are still
+    /// watched, and counted once created.
+    pub async fn new(paths: Vec<String>, options: ProcessorOptions) -> io::Result<Self> {
+        let (tx, events) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+        })
+        .map_err(io::Error::other)?;
+        let mut files = Vec::with_capacity(paths.len());
+        for path in paths {
+            // The parent directory is watched, so that files which are created or replaced
+            // are still followed.
+            let parent = match Path::new(&path).parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            watcher
+                .watch(parent, RecursiveMode::NonRecursive)
+                .map_err(io::Error::other)?;
+            let canonical = tokio::fs::canonicalize(&path).await.ok();
+            files.push(WatchedFile {
+                path,
+                canonical,
+                offset: 0,
+            });
+        }
+
+        let mut watcher = Self {
+            files,
+            results: HashMap::new(),
+            options,
+            events,
+            _watcher: watcher,
+        };
+        for index in 0..watcher.files.len() {
+            watcher.update(index).await;
+        }
+        Ok(watcher)
+    }
+
+    /// Word counts of the lines of each file, by path.
+    pub fn results(&self) -> &HashMap<String, Vec<usize>> {
+        &self.results
+    }
+
+    /// Returns the results.
+    pub fn into_results(self) -> HashMap<String, Vec<usize>> {
+        self.results
+    }
+
+    /// Wait for the next change of a watched file and update its counts, returns its path.
+    /// Returns `None` if the watcher stopped.
+    pub async fn changed(&mut self) -> Option<&str> {
+        loop {
+            let event = match self.events.recv().await? {
+                Ok(event) => event,
+                Err(e) => {
+                    log::warn!("Could not watch the files, {e}.");
+                    continue;
+                }
+            };
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                continue;
+            }
+            let Some(index) = self
+                .files
+                .iter()
+                .position(|file| event.paths.iter().any(|path| file.matches(path)))
+            else {
+                continue;
+            };
+            if self.update(index).await {
+                return Some(&self.files[index].path);
+            }
+        }
+    }
+
+    /// Count the lines appended to a file since its last read, returns true if there were any.
+    async fn update(&mut self, index: usize) -> bool {
+        let file = &mut self.files[index];
+        let appended = match read_from(&file.path, file.offset).await {
+            Ok(Some(appended)) => appended,
+            Ok(None) => {
+                log::info!("{} was truncated, processing it again.", file.path);
+                file.offset = 0;
+                self.results.remove(&file.path);
+                match read_from(&file.path, 0).await {
+                    Ok(appended) => appended.unwrap_or_default(),
+                    Err(e) => {
+                        log::warn!("Could not read {}, {e}, skipping it.", file.path);
+                        return false;
+                    }
+                }
+            }
+            Err(e) => {
+                log::warn!("Could not read {}, {e}, skipping it.", file.path);
+                return false;
+            }
+        };
+        if file.canonical.is_none() {
+            file.canonical = tokio::fs::canonicalize(&file.path).await.ok();
+        }
+        let Some(end) = appended.iter().rposition(|&b| b == b'\n') else {
+            return false;
+        };
+        let lines = &appended[..=end];
+        file.offset += lines.len() as u64;
+        let path = file.path.as_str();
+        let counts = count_source_line_words((path, lines), self.options);
+        let counts: Vec<_> = counts.map(|(_, count)| count).collect().await;
+        self.results
+            .entry(file.path.clone())
+            .or_default()
+            .extend(counts);
+        true
+    }
+}
+
+impl WatchedFile {
+    fn matches(&self, path: &Path) -> bool {
+        path == Path::new(&self.path) || self.canonical.as_deref() == Some(path)
+    }
+}
+
+/// Read a file from the offset, `None` if it is shorter than the offset.
+async fn read_from(path: &str, offset: u64) -> io::Result<Option<Vec<u8>>> {
+    let mut file = File::open(path).await?;
+    let len = file.metadata().await?.len();
+    if len < offset {
+        return Ok(None);
+    }
+    file.seek(SeekFrom::Start(offset)).await?;
+    let mut appended = Vec::with_capacity((len - offset) as usize);
+    file.read_to_end(&mut appended).await?;
+    Ok(Some(appended))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_watcher() {
+        let dir = std::env::temp_dir().join("ssp_test_file_watcher");
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("a.txt");
+        tokio::fs::write(&path, "a b\nc").await.unwrap();
+        let path = path.to_str().unwrap().to_string();
+
+        let mut watcher = FileWatcher::new(vec![path.clone()], Default::default())
+            .await
+            .unwrap();
+        assert_eq!(watcher.results()[&path], [2]);
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .await
+            .unwrap();
+        file.write_all(b" d\ne f g\nh").await.unwrap();
+        file.flush().await.unwrap();
+        let changed = tokio::time::timeout(Duration::from_secs(5), watcher.changed()).await;
+        assert_eq!(changed.unwrap(), Some(path.as_str()));
+        assert_eq!(watcher.results()[&path], [2, 2, 3]);
+
+        tokio::fs::write(&path, "i\n").await.unwrap();
+        let changed = tokio::time::timeout(Duration::from_secs(5), watcher.changed()).await;
+        assert_eq!(changed.unwrap(), Some(path.as_str()));
+        assert_eq!(watcher.results()[&path], [1]);
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
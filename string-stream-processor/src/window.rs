@@ -0,0 +1,260 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    mem,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures_util::Stream;
+use pin_project_lite::pin_project;
+use tokio::time::{Instant, Interval, MissedTickBehavior};
+
+use crate::LineStats;
+
+/// Counts of the lines received during a window of a [`window_line_words`] stream.
+#[derive(Debug, Clone)]
+pub struct Window<I> {
+    pub start: Instant,
+    pub end: Instant,
+    /// Word counts of the lines of each identifier received during the window, in the order
+    /// they were received.
+    pub counts: HashMap<I, Vec<usize>>,
+}
+
+pin_project! {
+    struct Windows<S, I> {
+        #[pin]
+        counts: S,
+        interval: Interval,
+        window: Window<I>,
+        done: bool,
+    }
+}
+
+/// Aggregate a stream of line counts tagged with their identifier in consecutive windows of
+/// `period`, e.g. the counts of each connection of a TCP listener per minute, for the sources
+/// which never end.
+///
+/// A window is yielded at the end of each period, even without any line. When the counts end,
+/// the last window is yielded up to that time, unless it is empty.
+///
+/// # Panics
+///
+/// Panics if `period` is zero.
+pub fn window_line_words<I, S>(counts: S, period: Duration) -> impl Stream<Item = Window<I>>
+where
+    I: Hash + Eq,
+    S: Stream<Item = (I, usize)>,
+{
+    let start = Instant::now();
+    let mut interval = tokio::time::interval_at(start + period, period);
+    // A late window ends at its scheduled time, the next ones stay aligned on the first one.
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    Windows {
+        counts,
+        interval,
+        window: Window {
+            start,
+            end: start,
+            counts: HashMap::new(),
+        },
+        done: false,
+    }
+}
+
+impl<I, S> Stream for Windows<S, I>
+where
+    I: Hash + Eq,
+    S: Stream<Item = (I, usize)>,
+{
+    type Item = Window<I>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        if *this.done {
+            return Poll::Ready(None);
+        }
+        loop {
+            // The end of the window is checked before each count, as the counts may always be
+            // ready.
+            if let Poll::Ready(end) = this.interval.poll_tick(cx) {
+                return Poll::Ready(Some(next_window(this.window, end)));
+            }
+            match this.counts.as_mut().poll_next(cx) {
+                Poll::Ready(Some((id, count))) => {
+                    this.window.counts.entry(id).or_default().push(count);
+                }
+                Poll::Ready(None) => {
+                    *this.done = true;
+                    if this.window.counts.is_empty() {
+                        return Poll::Ready(None);
+                    }
+                    return Poll::Ready(Some(next_window(this.window, Instant::now())));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Ends the window at `end`, returns it and starts the next one.
+fn next_window<I>(window: &mut Window<I>, end: Instant) -> Window<I> {
+    let next = Window {
+        start: end,
+        end,
+        counts: HashMap::new(),
+    };
+    Window {
+        end,
+        ..mem::replace(window, next)
+    }
+}
+
+/// Extent of the lines of each identifier kept by [`SlidingStats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlidingWindow {
+    /// The lines received during the last period.
+    Duration(Duration),
+    /// The last lines received.
+    Lines(usize),
+}
+
+/// Statistics of the word counts of the last lines of each identifier of a live stream, e.g. to
+/// alert on a sudden rise of the verbosity of a log.
+///
+/// ```
+/// use string_stream_processor::{SlidingStats, SlidingWindow};
+///
+/// let mut stats = SlidingStats::new(SlidingWindow::Lines(2));
+/// for count in [1, 2, 10] {
+///     stats.push("app.log", count);
+/// }
+/// assert_eq!(stats.stats(&"app.log").unwrap().mean(), 6.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SlidingStats<I> {
+    window: SlidingWindow,
+    /// Word counts of the lines in the window of each identifier, with the time they were
+    /// received, the oldest first.
+    lines: HashMap<I, VecDeque<(Instant, usize)>>,
+}
+
+impl<I: Hash + Eq> SlidingStats<I> {
+    pub fn new(window: SlidingWindow) -> Self {
+        Self {
+            window,
+            lines: HashMap::new(),
+        }
+    }
+
+    /// Add the word count of a line of an identifier received now.
+    pub fn push(&mut self, id: I, count: usize) {
+        let now = Instant::now();
+        let lines = self.lines.entry(id).or_default();
+        lines.push_back((now, count));
+        if let SlidingWindow::Lines(max) = self.window {
+            let excess = lines.len().saturating_sub(max);
+            lines.drain(..excess);
+        }
+        evict(self.window, lines, now);
+    }
+
+    /// Statistics of the lines of an identifier in the window, `None` without any.
+    pub fn stats(&mut self, id: &I) -> Option<LineStats> {
+        let lines = self.lines.get_mut(id)?;
+        evict(self.window, lines, Instant::now());
+        if lines.is_empty() {
+            self.lines.remove(id);
+            return None;
+        }
+        Some(lines.iter().map(|(_, count)| *count).collect())
+    }
+
+    /// Statistics of the identifiers with lines in the window, in any order. The other ones are
+    /// forgotten.
+    pub fn iter(&mut self) -> impl Iterator<Item = (&I, LineStats)> {
+        let now = Instant::now();
+        self.lines.retain(|_, lines| {
+            evict(self.window, lines, now);
+            !lines.is_empty()
+        });
+        self.lines
+            .iter()
+            .map(|(id, lines)| (id, lines.iter().map(|(_, count)| *count).collect()))
+    }
+}
+
+/// Remove the lines received before the period of the window.
+fn evict(window: SlidingWindow, lines: &mut VecDeque<(Instant, usize)>, now: Instant) {
+    if let SlidingWindow::Duration(period) = window {
+        while lines
+            .front()
+            .is_some_and(|(received, _)| now.duration_since(*received) > period)
+        {
+            lines.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::{stream, StreamExt};
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_window_line_words() {
+        let start = Instant::now();
+        // Lines at 0, 0.5, 1.5 and 3.2 seconds.
+        let counts = stream::iter([(0, "a", 2), (500, "b", 1), (1500, "a", 3), (3200, "a", 4)])
+            .then(|(at, id, count)| async move {
+                tokio::time::sleep_until(start + Duration::from_millis(at)).await;
+                (id, count)
+            });
+        let windows: Vec<_> = window_line_words(counts, Duration::from_secs(1))
+            .map(|window| {
+                let mut counts: Vec<_> = window.counts.into_iter().collect();
+                counts.sort();
+                let elapsed = |at: Instant| at.duration_since(start).as_millis();
+                (elapsed(window.start), elapsed(window.end), counts)
+            })
+            .collect()
+            .await;
+        assert_eq!(
+            windows,
+            [
+                (0, 1000, vec![("a", vec![2]), ("b", vec![1])]),
+                (1000, 2000, vec![("a", vec![3])]),
+                (2000, 3000, vec![]),
+                (3000, 3200, vec![("a", vec![4])]),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sliding_stats() {
+        let mut stats = SlidingStats::new(SlidingWindow::Duration(Duration::from_secs(10)));
+        stats.push("a", 2);
+        tokio::time::sleep(Duration::from_secs(6)).await;
+        stats.push("a", 4);
+        stats.push("b", 1);
+        assert_eq!(stats.stats(&"a").unwrap().mean(), 3.0);
+        tokio::time::sleep(Duration::from_secs(6)).await;
+        let a = stats.stats(&"a").unwrap();
+        assert_eq!((a.lines, a.max), (1, 4));
+        assert_eq!(stats.iter().count(), 2);
+        tokio::time::sleep(Duration::from_secs(6)).await;
+        assert!(stats.stats(&"a").is_none());
+        assert_eq!(stats.iter().count(), 0);
+
+        let mut stats = SlidingStats::new(SlidingWindow::Lines(3));
+        for count in [10, 1, 2, 3] {
+            stats.push("a", count);
+        }
+        let a = stats.stats(&"a").unwrap();
+        assert_eq!((a.lines, a.words, a.max), (3, 6, 3));
+        assert!(stats.stats(&"b").is_none());
+    }
+}
@@ -0,0 +1,126 @@
+use std::io;
+
+use futures_util::{future, Stream, TryStreamExt};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
+
+use crate::{
+    source::{count_line_words_retrying, NoReconnect},
+    ProcessorOptions,
+};
+
+/// Connect to a WebSocket server and count the number of words for each line of the text
+/// messages it sends, as soon as they are received, identified by the URL.
+///
+/// Each text message is a line, and is split again on its line endings if it contains several
+/// lines. Binary and control messages are ignored. The stream ends when the connection is closed.
+pub async fn connect_ws_line_words(
+    url: &str,
+    options: ProcessorOptions,
+) -> io::Result<impl Stream<Item = (&str, usize)>> {
+    let (messages, _) = tokio_tungstenite::connect_async(url)
+        .await
+        .map_err(io::Error::other)?;
+    Ok(count_ws_line_words(url, messages, options))
+}
+
+/// Same as [`connect_ws_line_words`] with the TLS configuration of a `wss://` URL, e.g. the root
+/// certificates of a private authority or a client certificate.
+#[cfg(feature = "tls")]
+pub async fn connect_ws_line_words_with_tls<'a>(
+    url: &'a str,
+    tls: &crate::TlsOptions,
+    options: ProcessorOptions,
+) -> io::Result<impl Stream<Item = (&'a str, usize)>> {
+    let connector = tokio_tungstenite::Connector::Rustls(tls.client_config()?);
+    let (messages, _) =
+        tokio_tungstenite::connect_async_tls_with_config(url, None, false, Some(connector))
+            .await
+            .map_err(io::Error::other)?;
+    Ok(count_ws_line_words(url, messages, options))
+}
+
+fn count_ws_line_words<'a, S: AsyncRead + AsyncWrite + Unpin + 'a>(
+    url: &'a str,
+    messages: WebSocketStream<S>,
+    options: ProcessorOptions,
+) -> impl Stream<Item = (&'a str, usize)> + 'a {
+    let lines = messages
+        .map_err(io::Error::other)
+        .try_filter_map(|message| {
+            future::ok(match message {
+                Message::Text(text) => {
+                    let mut line = Vec::with_capacity(text.len() + 1);
+                    line.extend_from_slice(text.as_bytes());
+                    if !line.ends_with(b"\n") {
+                        line.push(b'\n');
+                    }
+                    Some(line)
+                }
+                _ => None,
+            })
+        })
+        .into_async_read();
+    count_line_words_retrying((url, lines), options, &NoReconnect)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_connect_ws_line_words() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+            for message in [
+                Message::text("a b"),
+                Message::binary(&b"c d e"[..]),
+                Message::text("f\ng h\n"),
+                Message::text(""),
+            ] {
+                ws.send(message).await.unwrap();
+            }
+            ws.close(None).await.unwrap();
+        });
+
+        let counts = connect_ws_line_words(&url, Default::default())
+            .await
+            .unwrap();
+        let counts: Vec<_> = counts.map(|(_, count)| count).collect().await;
+        assert_eq!(counts, [2, 1, 2, 0]);
+        assert!(
+            connect_ws_line_words("ws://127.0.0.1:1", Default::default())
+                .await
+                .is_err()
+        );
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn test_connect_ws_line_words_with_tls() {
+        let pki = crate::tls::tests::TestPki::new();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("wss://localhost:{}", listener.local_addr().unwrap().port());
+        let acceptor = pki.acceptor();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let tls = acceptor.accept(socket).await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(tls).await.unwrap();
+            ws.send(Message::text("a b")).await.unwrap();
+            ws.close(None).await.unwrap();
+        });
+
+        let tls = pki.client_options();
+        let counts = connect_ws_line_words_with_tls(&url, &tls, Default::default())
+            .await
+            .unwrap();
+        let counts: Vec<_> = counts.map(|(_, count)| count).collect().await;
+        assert_eq!(counts, [2]);
+    }
+}